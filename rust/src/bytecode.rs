@@ -0,0 +1,462 @@
+//! Bytecode for rate expressions: a register VM evaluated across compartments.
+//!
+//! A `Formula` is first built into a DAG with hash-consing, so repeated
+//! subexpressions (`S` in `div(S, add(km, S))`) share one node. Operations
+//! whose arguments are all constant are folded, `if` with a constant condition
+//! keeps only the taken branch, and operands of commutative operations are put
+//! in a canonical order so `mul(a, b)` and `mul(b, a)` are the same node. N-ary
+//! operations are split into binary ones. The DAG is then emitted as a linear
+//! instruction stream over registers, reusing a register once its last reader
+//! has run.
+//!
+//! Every register holds one value per lane. `Program::eval_sites` runs each
+//! instruction over all lanes before moving to the next, where lane `i` reads
+//! the concentrations of compartment `sites[i]` from the flat `WorldState`
+//! buffer.
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::rate::{Formula, Op};
+use crate::reaction::MoleculeId;
+use crate::tree::CompartmentId;
+
+pub type Register = u32;
+
+/// One VM instruction; every operand is a register.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Instr {
+    /// `dst = value`.
+    Const { dst: Register, value: f64 },
+    /// `dst = conc[molecule]` in the lane's compartment.
+    Load { dst: Register, molecule: u32 },
+    /// `dst = op(a)`.
+    Unary { op: Op, dst: Register, a: Register },
+    /// `dst = op(a, b)`.
+    Binary {
+        op: Op,
+        dst: Register,
+        a: Register,
+        b: Register,
+    },
+    /// `dst = cond != 0 ? a : b`; both sides are evaluated.
+    Select {
+        dst: Register,
+        cond: Register,
+        a: Register,
+        b: Register,
+    },
+}
+
+impl fmt::Display for Instr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Instr::Const { dst, value } => write!(f, "r{dst} = {value:?}"),
+            Instr::Load { dst, molecule } => write!(f, "r{dst} = load {molecule}"),
+            Instr::Unary { op, dst, a } => write!(f, "r{dst} = {} r{a}", op.name()),
+            Instr::Binary { op, dst, a, b } => write!(f, "r{dst} = {} r{a}, r{b}", op.name()),
+            Instr::Select { dst, cond, a, b } => {
+                write!(f, "r{dst} = select r{cond}, r{a}, r{b}")
+            }
+        }
+    }
+}
+
+/// DAG node; operands are node indices.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Node {
+    /// Constant, by bit pattern so nodes can be hashed.
+    Const(u64),
+    Load(MoleculeId),
+    Unary(Op, usize),
+    Binary(Op, usize, usize),
+    Select(usize, usize, usize),
+}
+
+/// Hash-consed expression DAG.
+#[derive(Default)]
+struct Dag {
+    nodes: Vec<Node>,
+    index: HashMap<Node, usize>,
+}
+
+impl Dag {
+    fn intern(&mut self, node: Node) -> usize {
+        if let Some(&id) = self.index.get(&node) {
+            return id;
+        }
+        let id = self.nodes.len();
+        self.nodes.push(node.clone());
+        self.index.insert(node, id);
+        id
+    }
+
+    fn constant(&self, id: usize) -> Option<f64> {
+        match self.nodes[id] {
+            Node::Const(bits) => Some(f64::from_bits(bits)),
+            _ => None,
+        }
+    }
+
+    fn constant_node(&mut self, value: f64) -> usize {
+        self.intern(Node::Const(value.to_bits()))
+    }
+
+    fn unary(&mut self, op: Op, a: usize) -> usize {
+        match self.constant(a) {
+            Some(v) => self.constant_node(op.apply(&[v])),
+            None => self.intern(Node::Unary(op, a)),
+        }
+    }
+
+    fn binary(&mut self, op: Op, a: usize, b: usize) -> usize {
+        if let (Some(x), Some(y)) = (self.constant(a), self.constant(b)) {
+            return self.constant_node(op.apply(&[x, y]));
+        }
+        let commutative = matches!(
+            op,
+            Op::Add | Op::Mul | Op::Min | Op::Max | Op::And | Op::Or | Op::Eq
+        );
+        let (a, b) = if commutative && b < a { (b, a) } else { (a, b) };
+        self.intern(Node::Binary(op, a, b))
+    }
+
+    fn build(&mut self, formula: &Formula, params: &[f64]) -> usize {
+        match formula {
+            Formula::Const(v) => self.constant_node(*v),
+            Formula::Param(p) => self.constant_node(params[*p]),
+            Formula::Var(m) => self.intern(Node::Load(*m)),
+            Formula::Apply(Op::If, args) => {
+                let cond = self.build(&args[0], params);
+                match self.constant(cond) {
+                    Some(c) if c != 0.0 => self.build(&args[1], params),
+                    Some(_) => self.build(&args[2], params),
+                    None => {
+                        let a = self.build(&args[1], params);
+                        let b = self.build(&args[2], params);
+                        if a == b {
+                            a
+                        } else {
+                            self.intern(Node::Select(cond, a, b))
+                        }
+                    }
+                }
+            }
+            Formula::Apply(op, args) => {
+                let mut ids: Vec<usize> = args.iter().map(|a| self.build(a, params)).collect();
+                match op.arity() {
+                    (_, None) => {
+                        ids.sort_unstable();
+                        let first = ids[0];
+                        if ids.len() == 1 {
+                            // A single `and`/`or` operand still becomes 0 or 1.
+                            return match op {
+                                Op::And | Op::Or => {
+                                    let not = self.unary(Op::Not, first);
+                                    self.unary(Op::Not, not)
+                                }
+                                _ => first,
+                            };
+                        }
+                        ids[1..]
+                            .iter()
+                            .fold(first, |acc, &id| self.binary(*op, acc, id))
+                    }
+                    (1, Some(1)) => self.unary(*op, ids[0]),
+                    _ => self.binary(*op, ids[0], ids[1]),
+                }
+            }
+        }
+    }
+}
+
+/// A compiled instruction stream for one rate expression.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Program {
+    code: Vec<Instr>,
+    num_registers: usize,
+    output: Register,
+}
+
+impl Program {
+    /// Compile `formula` with parameters fixed at `params`.
+    pub fn compile(formula: &Formula, params: &[f64]) -> Self {
+        let mut dag = Dag::default();
+        let root = dag.build(formula, params);
+
+        // Depth-first post-order from the root: nodes left behind by folding
+        // are dropped, and each operand is computed close to its reader.
+        // Later-built (usually deeper) operands go first, which keeps fewer
+        // values live across a chain such as `add(x, add(y, add(z, w)))`.
+        let mut order = Vec::new();
+        let mut visited = vec![false; dag.nodes.len()];
+        let mut stack = vec![(root, false)];
+        while let Some((id, expanded)) = stack.pop() {
+            if expanded {
+                order.push(id);
+            } else if !visited[id] {
+                visited[id] = true;
+                stack.push((id, true));
+                let mut next = operands(&dag.nodes[id]);
+                next.sort_unstable();
+                stack.extend(next.into_iter().map(|operand| (operand, false)));
+            }
+        }
+        // Position of the last instruction reading each node.
+        let mut last_use = vec![usize::MAX; dag.nodes.len()];
+        for (pos, &id) in order.iter().enumerate() {
+            for operand in operands(&dag.nodes[id]) {
+                last_use[operand] = pos;
+            }
+        }
+
+        let mut register = vec![0; dag.nodes.len()];
+        let mut free: Vec<Register> = Vec::new();
+        let mut num_registers = 0;
+        let mut code = Vec::new();
+        for (pos, &id) in order.iter().enumerate() {
+            let node = &dag.nodes[id];
+            // Operands read for the last time free their registers first,
+            // since every instruction reads a lane before writing it.
+            for operand in operands(node) {
+                if last_use[operand] == pos && !free.contains(&register[operand]) {
+                    free.push(register[operand]);
+                }
+            }
+            let dst = match free.iter().enumerate().min_by_key(|&(_, r)| *r) {
+                Some((i, _)) => free.swap_remove(i),
+                None => {
+                    num_registers += 1;
+                    (num_registers - 1) as Register
+                }
+            };
+            let r = |operand: usize| register[operand];
+            code.push(match *node {
+                Node::Const(bits) => Instr::Const {
+                    dst,
+                    value: f64::from_bits(bits),
+                },
+                Node::Load(m) => Instr::Load {
+                    dst,
+                    molecule: m as u32,
+                },
+                Node::Unary(op, a) => Instr::Unary { op, dst, a: r(a) },
+                Node::Binary(op, a, b) => Instr::Binary {
+                    op,
+                    dst,
+                    a: r(a),
+                    b: r(b),
+                },
+                Node::Select(c, a, b) => Instr::Select {
+                    dst,
+                    cond: r(c),
+                    a: r(a),
+                    b: r(b),
+                },
+            });
+            register[id] = dst;
+        }
+        Self {
+            output: register[root],
+            num_registers: num_registers.max(1) as usize,
+            code,
+        }
+    }
+
+    pub fn code(&self) -> &[Instr] {
+        &self.code
+    }
+
+    pub fn num_registers(&self) -> usize {
+        self.num_registers
+    }
+
+    /// Evaluate on one compartment's concentrations.
+    pub fn eval(&self, conc: &[f64]) -> f64 {
+        let mut out = [0.0];
+        let mut registers = Vec::new();
+        self.eval_sites(conc, conc.len(), &[0], &mut out, &mut registers);
+        out[0]
+    }
+
+    /// Evaluate at every compartment of `sites` in one pass.
+    ///
+    /// `conc` is the flat buffer with `num_molecules` values per compartment;
+    /// `out[i]` receives the rate in `sites[i]`. `registers` is scratch space,
+    /// resized as needed so it can be reused across calls.
+    pub fn eval_sites(
+        &self,
+        conc: &[f64],
+        num_molecules: usize,
+        sites: &[CompartmentId],
+        out: &mut [f64],
+        registers: &mut Vec<f64>,
+    ) {
+        let lanes = sites.len();
+        registers.resize(self.num_registers * lanes, 0.0);
+        let lane = |r: Register| r as usize * lanes..(r as usize + 1) * lanes;
+        for instr in &self.code {
+            match *instr {
+                Instr::Const { dst, value } => registers[lane(dst)].fill(value),
+                Instr::Load { dst, molecule } => {
+                    for (value, &comp) in registers[lane(dst)].iter_mut().zip(sites) {
+                        *value = conc[comp * num_molecules + molecule as usize];
+                    }
+                }
+                Instr::Unary { op, dst, a } => {
+                    let (a, dst) = (a as usize * lanes, dst as usize * lanes);
+                    for i in 0..lanes {
+                        registers[dst + i] = op.apply(&[registers[a + i]]);
+                    }
+                }
+                Instr::Binary { op, dst, a, b } => {
+                    let (a, b, dst) =
+                        (a as usize * lanes, b as usize * lanes, dst as usize * lanes);
+                    for i in 0..lanes {
+                        registers[dst + i] = binary(op, registers[a + i], registers[b + i]);
+                    }
+                }
+                Instr::Select { dst, cond, a, b } => {
+                    let (c, a, b, dst) = (
+                        cond as usize * lanes,
+                        a as usize * lanes,
+                        b as usize * lanes,
+                        dst as usize * lanes,
+                    );
+                    for i in 0..lanes {
+                        registers[dst + i] = if registers[c + i] != 0.0 {
+                            registers[a + i]
+                        } else {
+                            registers[b + i]
+                        };
+                    }
+                }
+            }
+        }
+        out[..lanes].copy_from_slice(&registers[lane(self.output)]);
+    }
+}
+
+/// Binary operations without the slice round trip of `Op::apply`.
+#[inline]
+fn binary(op: Op, a: f64, b: f64) -> f64 {
+    match op {
+        Op::Add => a + b,
+        Op::Sub => a - b,
+        Op::Mul => a * b,
+        Op::Div => a / b,
+        _ => op.apply(&[a, b]),
+    }
+}
+
+fn operands(node: &Node) -> Vec<usize> {
+    match *node {
+        Node::Const(_) | Node::Load(_) => Vec::new(),
+        Node::Unary(_, a) => vec![a],
+        Node::Binary(_, a, b) => vec![a, b],
+        Node::Select(c, a, b) => vec![c, a, b],
+    }
+}
+
+impl fmt::Display for Program {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for instr in &self.code {
+            writeln!(f, "{instr}")?;
+        }
+        write!(f, "return r{}", self.output)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chemistry::MoleculeIndex;
+    use crate::expr::Arg;
+    use crate::rate::Symbols;
+
+    fn formula(source: &str, params: &[(String, f64)]) -> Formula {
+        let molecules = MoleculeIndex::new(vec!["S".into(), "I".into(), "P".into()]);
+        Formula::lower(
+            &Arg::parse(source).unwrap(),
+            &Symbols::new(&molecules, params),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn shared_subexpressions_are_computed_once() {
+        // S appears three times and add(km, S) twice; km folds into the add.
+        let params = [("km".to_string(), 2.0)];
+        let f = formula(
+            "add(div(S, add(km, S)), mul(add(S, km), add(S, km)))",
+            &params,
+        );
+        let program = Program::compile(&f, &[2.0]);
+        let loads = program
+            .code()
+            .iter()
+            .filter(|i| matches!(i, Instr::Load { .. }))
+            .count();
+        assert_eq!(loads, 1, "{program}");
+        assert_eq!(program.code().len(), 6, "{program}");
+        assert_eq!(program.eval(&[2.0, 0.0, 0.0]), 0.5 + 16.0);
+    }
+
+    #[test]
+    fn constants_fold() {
+        let params = [("k".to_string(), 3.0)];
+        let f = formula("mul(power(k, 2), exp(0), if(gt(k, 1), S, I))", &params);
+        let program = Program::compile(&f, &[3.0]);
+        // 9.0 * S: the untaken branch and all parameter arithmetic are gone.
+        assert_eq!(
+            program.to_string(),
+            "r0 = load 0\nr1 = 9.0\nr0 = mul r1, r0\nreturn r0"
+        );
+        assert_eq!(program.eval(&[2.0, 5.0, 0.0]), 18.0);
+
+        let constant = Program::compile(&formula("add(1, 2, 3)", &[]), &[]);
+        assert_eq!(constant.code().len(), 1);
+        assert_eq!(constant.eval(&[0.0; 3]), 6.0);
+    }
+
+    #[test]
+    fn matches_tree_evaluation_across_sites() {
+        let sources = [
+            "div(mul(10, S), add(5, S, mul(5, div(I, 0.5))))",
+            "if(gt(S, I), sub(S, I), neg(log(add(I, 1))))",
+            "and(S, I)",
+            "or(0, S)",
+            "min(S, I, P, 1.5)",
+            "max(S)",
+            "not(eq(S, I))",
+        ];
+        let conc = [2.0, 1.0, 0.0, 0.5, 3.0, 4.0, 0.0, 0.0, 1.0];
+        let sites = [2, 0, 1];
+        let mut registers = Vec::new();
+        for source in sources {
+            let f = formula(source, &[]);
+            let program = Program::compile(&f, &[]);
+            let mut out = [0.0; 3];
+            program.eval_sites(&conc, 3, &sites, &mut out, &mut registers);
+            for (i, &comp) in sites.iter().enumerate() {
+                let expected = f.eval(&conc[comp * 3..comp * 3 + 3], &[]);
+                assert_eq!(out[i], expected, "{source} in {comp}\n{program}");
+            }
+        }
+    }
+
+    #[test]
+    fn registers_are_reused() {
+        // Six products of three loads need only a few live registers.
+        let f = formula(
+            "add(mul(S, I), mul(S, P), mul(I, P), mul(S, S), mul(I, I), mul(P, P))",
+            &[],
+        );
+        let program = Program::compile(&f, &[]);
+        assert!(program.num_registers() <= 5, "{program}");
+        assert_eq!(
+            program.eval(&[1.0, 2.0, 3.0]),
+            2.0 + 3.0 + 6.0 + 1.0 + 4.0 + 9.0
+        );
+    }
+}
@@ -0,0 +1,167 @@
+//! Molecule index: name → ID mapping for a Python `ChemistryImpl`.
+//!
+//! Molecule IDs follow the order of `chemistry.molecules`, the same convention
+//! as `WorldSimulatorImpl.from_chemistry`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use crate::error::{SimError, SimResult};
+use crate::expr::Arg;
+use crate::kinetics::Binding;
+use crate::rate::RateExpr;
+use crate::reaction::{MoleculeId, RateLaw, Reaction};
+use crate::script::{self, ScriptRate};
+
+/// Molecule names of a chemistry and their integer IDs.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MoleculeIndex {
+    names: Vec<String>,
+    ids: HashMap<String, MoleculeId>,
+}
+
+impl MoleculeIndex {
+    pub fn new(names: Vec<String>) -> Self {
+        let ids = names
+            .iter()
+            .enumerate()
+            .map(|(i, name)| (name.clone(), i))
+            .collect();
+        Self { names, ids }
+    }
+
+    /// Index the keys of `chemistry.molecules`.
+    pub fn from_chemistry(chemistry: &PyAny) -> PyResult<Self> {
+        let names = chemistry
+            .getattr("molecules")?
+            .call_method0("keys")?
+            .iter()?
+            .map(|key| key?.extract())
+            .collect::<PyResult<Vec<String>>>()?;
+        Ok(Self::new(names))
+    }
+
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    pub fn get(&self, name: &str) -> Option<MoleculeId> {
+        self.ids.get(name).copied()
+    }
+
+    /// Look up a molecule, failing with a `KeyError`-style error if unknown.
+    pub fn id(&self, name: &str) -> SimResult<MoleculeId> {
+        self.get(name)
+            .ok_or_else(|| SimError::Key(format!("Unknown molecule: {name:?}")))
+    }
+
+    /// Read one side of a `ReactionImpl` (`{MoleculeImpl: coefficient}`).
+    ///
+    /// Unknown molecules are an error when `strict`, otherwise skipped.
+    pub fn side(&self, side: &PyAny, strict: bool) -> PyResult<Vec<(MoleculeId, f64)>> {
+        let mut result = Vec::new();
+        for item in side.call_method0("items")?.iter()? {
+            let (molecule, coefficient): (&PyAny, f64) = item?.extract()?;
+            let name: String = molecule.getattr("name")?.extract()?;
+            match self.get(&name) {
+                Some(id) => result.push((id, coefficient)),
+                None if strict => {
+                    return Err(SimError::Key(format!("Unknown molecule: {name:?}")).into())
+                }
+                None => {}
+            }
+        }
+        Ok(result)
+    }
+
+    /// Convert every `ReactionImpl` in `chemistry.reactions`.
+    ///
+    /// Constant rates become the reaction's rate constant; Expr rates (an
+    /// `Expr`, an Expr string or the structured dict form, possibly naming a
+    /// kinetic template) are compiled into a `RateLaw::Expr` and `rhai:`
+    /// strings into a `RateLaw::Script`; callable rates are returned
+    /// alongside so the caller can decide how to evaluate them.
+    /// `ReactionImpl.effectors`, when present, become the reaction's effectors.
+    pub fn reactions<'py>(
+        &self,
+        chemistry: &'py PyAny,
+        law: RateLaw,
+        strict: bool,
+    ) -> PyResult<Vec<(Reaction, Option<&'py PyAny>)>> {
+        let mut result = Vec::new();
+        for item in chemistry
+            .getattr("reactions")?
+            .call_method0("items")?
+            .iter()?
+        {
+            let (name, reaction): (String, &PyAny) = item?.extract()?;
+            let rate = reaction.getattr("rate")?;
+            let (rate_constant, rate_fn) = if rate.is_callable() {
+                (1.0, Some(reaction))
+            } else {
+                (rate.extract().unwrap_or(1.0), None)
+            };
+            let mut native = Reaction::new(
+                name,
+                self.side(reaction.getattr("reactants")?, strict)?,
+                self.side(reaction.getattr("products")?, strict)?,
+                rate_constant,
+            )
+            .with_law(law);
+            if let Ok(effectors) = reaction.getattr("effectors") {
+                native = native.with_effectors(self.side(effectors, strict)?);
+            }
+            let script = rate
+                .extract::<&str>()
+                .ok()
+                .filter(|s| script::strip(s).is_some());
+            if let Some(source) = script {
+                let script = ScriptRate::new(source, self, &[])
+                    .map_err(|e| SimError::Value(format!("Reaction {}: {e}", native.name)))?;
+                native = native.with_script(Arc::new(script));
+            } else if rate_fn.is_none() && !rate.is_none() && rate.extract::<f64>().is_err() {
+                let expr = RateExpr::for_reaction(
+                    &Arg::from_py(rate, true)?,
+                    self,
+                    Vec::new(),
+                    &Binding::of(&native),
+                )?;
+                native = native.with_expr(Arc::new(expr));
+            }
+            result.push((native, rate_fn));
+        }
+        Ok(result)
+    }
+
+    /// `reactions`, for an engine (named by `engine` in errors) that cannot
+    /// call back into Python for a callable rate.
+    pub fn native_reactions(
+        &self,
+        chemistry: &PyAny,
+        law: RateLaw,
+        strict: bool,
+        engine: &str,
+    ) -> PyResult<Vec<Reaction>> {
+        self.reactions(chemistry, law, strict)?
+            .into_iter()
+            .map(|(reaction, rate_fn)| match rate_fn {
+                Some(_) => Err(SimError::Value(format!(
+                    "Reaction {:?} has a callable rate; {engine} needs constant rate constants",
+                    reaction.name
+                ))
+                .into()),
+                None => Ok(reaction),
+            })
+            .collect()
+    }
+}
@@ -0,0 +1,912 @@
+//! Code generation: rate laws and whole chemistries as source code.
+//!
+//! `emit` prints a lowered `Formula` in Python, Lua, Rhai or Rust with its
+//! parameters inlined, following each language's precedence rules so that
+//! only necessary parentheses appear. `to_code` wraps the expression the way
+//! the Interpreter spec shows it:
+//!
+//! ```text
+//! michaelis_menten(vmax=10, km=5)
+//!   python  10.0 * S / (5.0 + S)
+//!   lua     return 10.0 * S / (5.0 + S)
+//!   rhai    10.0 * S / (5.0 + S)
+//!   rust    |s: f64| 10.0 * s / (5.0 + s)
+//! ```
+//!
+//! `rust_module` writes a self-contained module with the rates, right-hand
+//! side and dense Jacobian of a chemistry's mass-action ODE system, every
+//! rate law inlined, for building specialized simulators. Booleans are `1.0` /
+//! `0.0` as in the rate compiler, and any non-zero value counts as true.
+
+use std::fmt::Write;
+
+use pyo3::prelude::*;
+
+use crate::chemistry::MoleculeIndex;
+use crate::derivative::Wrt;
+use crate::error::{SimError, SimResult};
+use crate::expr::{Arg, Expr};
+use crate::kinetics::{self, TemplateRegistry};
+use crate::rate::{extract_params, Formula, Op, RateExpr};
+use crate::reaction::{MoleculeId, RateLaw, Reaction};
+
+/// Target language of generated code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Language {
+    Python,
+    Lua,
+    Rhai,
+    Rust,
+}
+
+impl Language {
+    pub const ALL: [Language; 4] = [
+        Language::Python,
+        Language::Lua,
+        Language::Rhai,
+        Language::Rust,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Language::Python => "python",
+            Language::Lua => "lua",
+            Language::Rhai => "rhai",
+            Language::Rust => "rust",
+        }
+    }
+
+    pub fn parse(name: &str) -> SimResult<Self> {
+        Language::ALL
+            .into_iter()
+            .find(|l| l.name() == name)
+            .ok_or_else(|| {
+                SimError::Value(format!(
+                    "Unknown language {name:?}; expected one of python, lua, rhai, rust"
+                ))
+            })
+    }
+}
+
+// Binding strength, loosest first.
+const IF: u8 = 0;
+const OR: u8 = 1;
+const AND: u8 = 2;
+const NOT: u8 = 3;
+const CMP: u8 = 4;
+const ADD: u8 = 5;
+const MUL: u8 = 6;
+const NEG: u8 = 7;
+const POW: u8 = 8;
+const ATOM: u8 = 9;
+
+/// Source text with the binding strength of its outermost operator.
+type Code = (String, u8);
+
+struct Emitter<'a> {
+    language: Language,
+    params: &'a [f64],
+    var: &'a dyn Fn(MoleculeId) -> String,
+}
+
+impl Emitter<'_> {
+    fn at_least((code, prec): Code, min: u8) -> String {
+        if prec >= min {
+            code
+        } else {
+            format!("({code})")
+        }
+    }
+
+    fn literal(&self, v: f64) -> Code {
+        use Language::*;
+        let code = match (self.language, v) {
+            (Python, v) if v.is_nan() => "math.nan".to_string(),
+            (Lua, v) if v.is_nan() => "(0 / 0)".to_string(),
+            (Rhai, v) if v.is_nan() => "(0.0 / 0.0)".to_string(),
+            (Rust, v) if v.is_nan() => "f64::NAN".to_string(),
+            (lang, v) if v.is_infinite() => {
+                let inf = match lang {
+                    Python => "math.inf",
+                    Lua => "math.huge",
+                    Rhai => "(1.0 / 0.0)",
+                    Rust => "f64::INFINITY",
+                };
+                if v > 0.0 {
+                    inf.to_string()
+                } else {
+                    format!("-{inf}")
+                }
+            }
+            (_, v) => {
+                // Keep a decimal point so every language reads a float.
+                let text = format!("{v:?}");
+                match text.find('e') {
+                    Some(e) if !text[..e].contains('.') => {
+                        format!("{}.0{}", &text[..e], &text[e..])
+                    }
+                    _ => text,
+                }
+            }
+        };
+        let prec = if code.starts_with('-') { NEG } else { ATOM };
+        (code, prec)
+    }
+
+    /// `f` in a numeric position.
+    fn num(&self, f: &Formula) -> Code {
+        use Language::*;
+        match f {
+            Formula::Const(v) => self.literal(*v),
+            Formula::Param(p) => self.literal(self.params[*p]),
+            Formula::Var(m) => ((self.var)(*m), ATOM),
+            Formula::Apply(op, args) => match op {
+                Op::Add => self.infix(args, " + ", ADD),
+                Op::Mul => self.infix(args, " * ", MUL),
+                Op::Sub => self.infix(args, " - ", ADD),
+                Op::Div => self.infix(args, " / ", MUL),
+                // Rhai's unary minus binds tighter than `**`: -S ** 2 is (-S)².
+                Op::Neg => {
+                    let operand = if self.language == Rhai { ATOM } else { POW };
+                    (
+                        format!("-{}", Self::at_least(self.num(&args[0]), operand)),
+                        NEG,
+                    )
+                }
+                Op::Power => match self.language {
+                    Rust => match args[1] {
+                        Formula::Const(n) if n.fract() == 0.0 && n.abs() <= 64.0 => (
+                            format!("{}.powi({})", self.receiver(&args[0]), n as i32),
+                            ATOM,
+                        ),
+                        _ => {
+                            let exponent = self.num(&args[1]).0;
+                            (
+                                format!("{}.powf({exponent})", self.receiver(&args[0])),
+                                ATOM,
+                            )
+                        }
+                    },
+                    // Right-associative and tighter than unary minus.
+                    lang => {
+                        let op = if lang == Lua { " ^ " } else { " ** " };
+                        let base = Self::at_least(self.num(&args[0]), ATOM);
+                        let exponent = Self::at_least(self.num(&args[1]), POW);
+                        (format!("{base}{op}{exponent}"), POW)
+                    }
+                },
+                Op::Exp | Op::Log => {
+                    let name = match (self.language, op) {
+                        (Python, Op::Exp) => "math.exp",
+                        (Python, _) => "math.log",
+                        (Lua, Op::Exp) => "math.exp",
+                        (Lua, _) => "math.log",
+                        (Rhai, Op::Exp) => "exp",
+                        (Rhai, _) => "ln",
+                        (Rust, Op::Exp) => "exp",
+                        (Rust, _) => "ln",
+                    };
+                    if self.language == Rust {
+                        (format!("{}.{name}()", self.receiver(&args[0])), ATOM)
+                    } else {
+                        (format!("{name}({})", self.num(&args[0]).0), ATOM)
+                    }
+                }
+                Op::Min | Op::Max => {
+                    let name = if *op == Op::Min { "min" } else { "max" };
+                    let values: Vec<String> = args.iter().map(|a| self.num(a).0).collect();
+                    match self.language {
+                        _ if args.len() == 1 => self.num(&args[0]),
+                        Python => (format!("{name}({})", values.join(", ")), ATOM),
+                        Lua => (format!("math.{name}({})", values.join(", ")), ATOM),
+                        // Binary only: fold left.
+                        Rhai => {
+                            let mut code = values[0].clone();
+                            for value in &values[1..] {
+                                code = format!("{name}({code}, {value})");
+                            }
+                            (code, ATOM)
+                        }
+                        Rust => {
+                            let mut code = self.receiver(&args[0]);
+                            for value in &values[1..] {
+                                write!(code, ".{name}({value})").unwrap();
+                            }
+                            (code, ATOM)
+                        }
+                    }
+                }
+                Op::If => {
+                    let cond = self.cond(&args[0]);
+                    let (a, b) = (self.num(&args[1]), self.num(&args[2]));
+                    self.select(cond, a, b)
+                }
+                Op::Gt | Op::Lt | Op::Ge | Op::Le | Op::Eq | Op::And | Op::Or | Op::Not => {
+                    let cond = self.cond(f);
+                    if self.language == Python {
+                        return (format!("float({})", cond.0), ATOM);
+                    }
+                    let (one, zero) = (self.literal(1.0), self.literal(0.0));
+                    self.select(cond, one, zero)
+                }
+            },
+        }
+    }
+
+    /// `f` in a boolean position (an `if` condition or logic operand).
+    fn cond(&self, f: &Formula) -> Code {
+        use Language::*;
+        let Formula::Apply(op, args) = f else {
+            return self.nonzero(f);
+        };
+        match op {
+            Op::Gt | Op::Lt | Op::Ge | Op::Le | Op::Eq => {
+                let symbol = match op {
+                    Op::Gt => " > ",
+                    Op::Lt => " < ",
+                    Op::Ge => " >= ",
+                    Op::Le => " <= ",
+                    _ => " == ",
+                };
+                let a = Self::at_least(self.num(&args[0]), ADD);
+                let b = Self::at_least(self.num(&args[1]), ADD);
+                (format!("{a}{symbol}{b}"), CMP)
+            }
+            Op::And | Op::Or => {
+                let (word, prec) = match (self.language, op) {
+                    (Python | Lua, Op::And) => (" and ", AND),
+                    (Python | Lua, _) => (" or ", OR),
+                    (_, Op::And) => (" && ", AND),
+                    _ => (" || ", OR),
+                };
+                let parts: Vec<String> = args
+                    .iter()
+                    .map(|a| Self::at_least(self.cond(a), prec + 1))
+                    .collect();
+                (parts.join(word), prec)
+            }
+            Op::Not => match self.language {
+                Python => (
+                    format!("not {}", Self::at_least(self.cond(&args[0]), NOT)),
+                    NOT,
+                ),
+                Lua => (
+                    format!("not {}", Self::at_least(self.cond(&args[0]), ATOM)),
+                    NEG,
+                ),
+                Rhai | Rust => (
+                    format!("!{}", Self::at_least(self.cond(&args[0]), ATOM)),
+                    NEG,
+                ),
+            },
+            _ => self.nonzero(f),
+        }
+    }
+
+    fn nonzero(&self, f: &Formula) -> Code {
+        let ne = if self.language == Language::Lua {
+            " ~= "
+        } else {
+            " != "
+        };
+        let a = Self::at_least(self.num(f), ADD);
+        (format!("{a}{ne}{}", self.literal(0.0).0), CMP)
+    }
+
+    /// `cond ? a : b` as an expression.
+    fn select(&self, cond: Code, a: Code, b: Code) -> Code {
+        match self.language {
+            Language::Python => (
+                format!(
+                    "{} if {} else {}",
+                    Self::at_least(a, OR),
+                    Self::at_least(cond, OR),
+                    Self::at_least(b, IF)
+                ),
+                IF,
+            ),
+            // Numbers are always truthy in Lua, so and/or selects correctly.
+            Language::Lua => (
+                format!(
+                    "{} and {} or {}",
+                    Self::at_least(cond, AND + 1),
+                    Self::at_least(a, AND + 1),
+                    Self::at_least(b, AND)
+                ),
+                OR,
+            ),
+            Language::Rhai | Language::Rust => {
+                let otherwise = match b {
+                    (chain, IF) if chain.starts_with("if ") => chain,
+                    (code, _) => format!("{{ {code} }}"),
+                };
+                (format!("if {} {{ {} }} else {otherwise}", cond.0, a.0), IF)
+            }
+        }
+    }
+
+    /// Left-associative infix chain.
+    fn infix(&self, args: &[Formula], op: &str, prec: u8) -> Code {
+        let parts: Vec<String> = args
+            .iter()
+            .enumerate()
+            .map(|(i, a)| Self::at_least(self.num(a), if i == 0 { prec } else { prec + 1 }))
+            .collect();
+        (parts.join(op), prec)
+    }
+
+    /// Receiver of a Rust method call: literals need a type suffix.
+    fn receiver(&self, f: &Formula) -> String {
+        let value = match f {
+            Formula::Const(v) => Some(*v),
+            Formula::Param(p) => Some(self.params[*p]),
+            _ => None,
+        };
+        match value {
+            Some(v) if v.is_finite() => {
+                let (code, prec) = self.literal(v);
+                Self::at_least((format!("{code}_f64"), prec), ATOM)
+            }
+            _ => Self::at_least(self.num(f), ATOM),
+        }
+    }
+}
+
+/// `formula` as an expression in `language`, with parameter `p` inlined as
+/// `params[p]` and molecule `m` spelled `var(m)`.
+pub fn emit(
+    formula: &Formula,
+    params: &[f64],
+    language: Language,
+    var: &dyn Fn(MoleculeId) -> String,
+) -> String {
+    let emitter = Emitter {
+        language,
+        params,
+        var,
+    };
+    emitter.num(formula).0
+}
+
+/// A rate as a standalone snippet: a Python expression, a Lua chunk, a Rhai
+/// expression or a Rust closure taking each variable as an `f64` argument.
+pub fn to_code(rate: &RateExpr, molecules: &MoleculeIndex, language: Language) -> String {
+    let molecule = |m: MoleculeId| {
+        molecules
+            .names()
+            .get(m)
+            .cloned()
+            .unwrap_or_else(|| format!("m{m}"))
+    };
+    // Distinct Rust arguments, in variable order: `S` and `s` become `s` and `s_2`.
+    let mut idents: Vec<(MoleculeId, String)> = Vec::new();
+    if language == Language::Rust {
+        for &m in rate.variables() {
+            let base = rust_ident(&molecule(m));
+            let mut ident = base.clone();
+            let mut suffix = 2;
+            while idents.iter().any(|(_, taken)| *taken == ident) {
+                ident = format!("{base}_{suffix}");
+                suffix += 1;
+            }
+            idents.push((m, ident));
+        }
+    }
+    let name = |m: MoleculeId| match idents.iter().find(|(id, _)| *id == m) {
+        Some((_, ident)) => ident.clone(),
+        None => molecule(m),
+    };
+    let values: Vec<f64> = rate.params().iter().map(|(_, v)| *v).collect();
+    let body = emit(rate.formula(), &values, language, &name);
+    match language {
+        Language::Python | Language::Rhai => body,
+        Language::Lua => format!("return {body}"),
+        Language::Rust => {
+            let args: Vec<String> = rate
+                .variables()
+                .iter()
+                .map(|&m| format!("{}: f64", name(m)))
+                .collect();
+            format!("|{}| {body}", args.join(", "))
+        }
+    }
+}
+
+/// A snake-case Rust identifier for a molecule name.
+fn rust_ident(name: &str) -> String {
+    let mut ident: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    if ident.is_empty() || ident.starts_with(|c: char| c.is_ascii_digit()) {
+        ident.insert(0, '_');
+    }
+    // Strict and reserved keywords; `self`, `super` and `crate` cannot be raw
+    // identifiers, so every keyword takes a trailing underscore instead.
+    const KEYWORDS: [&str; 50] = [
+        "abstract", "as", "async", "await", "become", "box", "break", "const", "continue", "crate",
+        "do", "dyn", "else", "enum", "extern", "false", "final", "fn", "for", "gen", "if", "impl",
+        "in", "let", "loop", "macro", "match", "mod", "move", "mut", "override", "priv", "pub",
+        "ref", "return", "self", "static", "struct", "super", "trait", "true", "try", "type",
+        "typeof", "unsafe", "unsized", "use", "virtual", "where", "while",
+    ];
+    if KEYWORDS.contains(&ident.as_str()) || ident == "_" {
+        ident.push('_');
+    }
+    ident
+}
+
+/// Bind every unbound template role to a variable named after its symbol,
+/// so `michaelis_menten(vmax=10, km=5)` reads `S` without a reaction.
+fn bind_roles(arg: &Arg, templates: &TemplateRegistry) -> Arg {
+    let Arg::Expr(expr) = arg else {
+        return arg.clone();
+    };
+    let mut out = Expr::new(
+        expr.head.clone(),
+        expr.args.iter().map(|a| bind_roles(a, templates)).collect(),
+    );
+    for (k, v) in &expr.kwargs {
+        out.kwargs.push((k.clone(), bind_roles(v, templates)));
+    }
+    if let Some(template) = templates.get(&expr.head) {
+        for (symbol, _) in &template.roles {
+            if expr.kwarg(symbol).is_none() {
+                out.kwargs.push((symbol.clone(), Arg::Str(symbol.clone())));
+            }
+        }
+    }
+    Arg::Expr(out)
+}
+
+/// Names read as variables by `arg`, in order of first appearance.
+fn free_names(arg: &Arg, templates: &TemplateRegistry, out: &mut Vec<String>) {
+    let mut push = |name: &str| {
+        if !out.iter().any(|n| n == name) {
+            out.push(name.to_string());
+        }
+    };
+    match arg {
+        Arg::Str(name) => push(name),
+        Arg::Expr(expr) => {
+            let bare = expr.args.is_empty() && expr.kwargs.is_empty();
+            match expr.head.as_str() {
+                "var" => {
+                    if let [Arg::Str(name)] = expr.args.as_slice() {
+                        push(name);
+                    }
+                }
+                "const" => {}
+                head if bare && Op::from_head(head).is_none() && templates.get(head).is_none() => {
+                    push(head)
+                }
+                _ => {
+                    for a in expr.args.iter().chain(expr.kwargs.iter().map(|(_, v)| v)) {
+                        free_names(a, templates, out);
+                    }
+                }
+            }
+        }
+        Arg::Number(_) | Arg::Bool(_) | Arg::None => {}
+    }
+}
+
+/// The rate of `reaction` as a formula with its parameter values.
+fn rate_formula(reaction: &Reaction) -> SimResult<(Formula, Vec<f64>)> {
+    match reaction.law {
+        RateLaw::MassAction => {
+            let mut factors = Vec::new();
+            if reaction.rate_constant != 1.0 {
+                factors.push(Formula::Const(reaction.rate_constant));
+            }
+            for &(m, s) in reaction.reactants.iter().chain(&reaction.effectors) {
+                factors.push(if s == 1.0 {
+                    Formula::Var(m)
+                } else {
+                    Formula::Apply(Op::Power, vec![Formula::Var(m), Formula::Const(s)])
+                });
+            }
+            let formula = match factors.len() {
+                0 => Formula::Const(1.0),
+                1 => factors.pop().unwrap(),
+                _ => Formula::Apply(Op::Mul, factors),
+            };
+            Ok((formula, Vec::new()))
+        }
+        RateLaw::Constant => Ok((Formula::Const(reaction.rate_constant), Vec::new())),
+        RateLaw::Expr => {
+            let expr = reaction.expr.as_ref().ok_or_else(|| {
+                SimError::Value(format!(
+                    "Reaction {}: expression rate law without an expression",
+                    reaction.name
+                ))
+            })?;
+            let values = expr.params().iter().map(|(_, v)| *v).collect();
+            Ok((expr.formula().clone(), values))
+        }
+        RateLaw::Script => Err(SimError::Value(format!(
+            "Reaction {}: rhai script rates cannot be translated to Rust",
+            reaction.name
+        ))),
+    }
+}
+
+/// `Σ coef · term` as Rust source, or `0.0`.
+fn linear_combination(terms: &[(f64, String)]) -> String {
+    let mut code = String::new();
+    for (coef, term) in terms {
+        let (sign, magnitude) = if *coef < 0.0 {
+            ("-", -coef)
+        } else {
+            ("+", *coef)
+        };
+        match (code.is_empty(), sign) {
+            (true, "-") => code.push('-'),
+            (true, _) => {}
+            (false, sign) => write!(code, " {sign} ").unwrap(),
+        }
+        if magnitude == 1.0 {
+            code.push_str(term);
+        } else {
+            write!(code, "{magnitude:?} * {term}").unwrap();
+        }
+    }
+    if code.is_empty() {
+        code.push_str("0.0");
+    }
+    code
+}
+
+/// A self-contained Rust module for the mass-action ODE system of
+/// `reactions` over one compartment (the semantics of `WorldModel`).
+///
+/// It defines `NUM_MOLECULES`, `MOLECULES`, `REACTIONS` and
+/// `rates(c, r)`, `rhs(c, dcdt)` and `jacobian(c, jac)` over fixed-size
+/// arrays in molecule-ID order, with every rate law and its symbolic
+/// derivatives inlined.
+pub fn rust_module(
+    name: &str,
+    molecules: &MoleculeIndex,
+    reactions: &[Reaction],
+) -> SimResult<String> {
+    let n = molecules.len();
+    for reaction in reactions {
+        reaction.validate(n, 1)?;
+        if reaction.compartments.is_some() {
+            return Err(SimError::Value(format!(
+                "Reaction {}: compartment-restricted reactions cannot be exported",
+                reaction.name
+            )));
+        }
+    }
+    let var = |m: MoleculeId| format!("c[{m}]");
+    let quote = |s: &str| format!("{s:?}");
+    let list = |names: Vec<String>| names.join(", ");
+
+    let mut rates = String::new();
+    let mut dcdt: Vec<Vec<(f64, String)>> = vec![Vec::new(); n];
+    let mut jac: Vec<Vec<Vec<(f64, String)>>> = vec![vec![Vec::new(); n]; n];
+    let mut partials = String::new();
+    for (r, reaction) in reactions.iter().enumerate() {
+        let (formula, params) = rate_formula(reaction)?;
+        let code = emit(&formula, &params, Language::Rust, &var);
+        writeln!(rates, "    r[{r}] = {code}; // {}", reaction.name).unwrap();
+        let stoich = || {
+            let consumed = reaction.reactants.iter().map(|&(m, s)| (m, -s));
+            consumed.chain(reaction.products.iter().copied())
+        };
+        for (m, s) in stoich() {
+            dcdt[m].push((s, format!("r[{r}]")));
+        }
+        for col in formula.variables() {
+            let d = formula.derivative(Wrt::Var(col));
+            if d == Formula::Const(0.0) {
+                continue;
+            }
+            let code = emit(&d, &params, Language::Rust, &var);
+            writeln!(partials, "    let d{r}_{col} = {code};").unwrap();
+            for (row, s) in stoich() {
+                jac[row][col].push((s, format!("d{r}_{col}")));
+            }
+        }
+    }
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "//! Generated by alienbio_sim from {name:?}; do not edit."
+    )
+    .unwrap();
+    writeln!(out, "//!").unwrap();
+    writeln!(
+        out,
+        "//! Mass-action ODE right-hand side over one compartment's concentrations,"
+    )
+    .unwrap();
+    writeln!(out, "//! in molecule-ID order (see `MOLECULES`).").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "#![allow(clippy::all, unused_parens)]").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "pub const NUM_MOLECULES: usize = {n};").unwrap();
+    writeln!(out, "pub const NUM_REACTIONS: usize = {};", reactions.len()).unwrap();
+    writeln!(
+        out,
+        "pub const MOLECULES: [&str; NUM_MOLECULES] = [{}];",
+        list(molecules.names().iter().map(|s| quote(s)).collect())
+    )
+    .unwrap();
+    writeln!(
+        out,
+        "pub const REACTIONS: [&str; NUM_REACTIONS] = [{}];",
+        list(reactions.iter().map(|r| quote(&r.name)).collect())
+    )
+    .unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// Reaction rates at concentrations `c`.").unwrap();
+    writeln!(out, "#[inline]").unwrap();
+    writeln!(
+        out,
+        "pub fn rates(c: &[f64; NUM_MOLECULES], r: &mut [f64; NUM_REACTIONS]) {{"
+    )
+    .unwrap();
+    out.push_str(&rates);
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "/// `dc/dt` at concentrations `c`.").unwrap();
+    writeln!(out, "#[inline]").unwrap();
+    writeln!(
+        out,
+        "pub fn rhs(c: &[f64; NUM_MOLECULES], dcdt: &mut [f64; NUM_MOLECULES]) {{"
+    )
+    .unwrap();
+    writeln!(out, "    let mut r = [0.0; NUM_REACTIONS];").unwrap();
+    writeln!(out, "    rates(c, &mut r);").unwrap();
+    for (m, terms) in dcdt.iter().enumerate() {
+        writeln!(out, "    dcdt[{m}] = {};", linear_combination(terms)).unwrap();
+    }
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(
+        out,
+        "/// Dense Jacobian `jac[i][j] = ∂(dc_i/dt)/∂c_j` at concentrations `c`."
+    )
+    .unwrap();
+    writeln!(out, "#[inline]").unwrap();
+    writeln!(
+        out,
+        "pub fn jacobian(c: &[f64; NUM_MOLECULES], jac: &mut [[f64; NUM_MOLECULES]; NUM_MOLECULES]) {{"
+    )
+    .unwrap();
+    writeln!(out, "    *jac = [[0.0; NUM_MOLECULES]; NUM_MOLECULES];").unwrap();
+    out.push_str(&partials);
+    for (row, cols) in jac.iter().enumerate() {
+        for (col, terms) in cols.iter().enumerate() {
+            if !terms.is_empty() {
+                writeln!(
+                    out,
+                    "    jac[{row}][{col}] = {};",
+                    linear_combination(terms)
+                )
+                .unwrap();
+            }
+        }
+    }
+    writeln!(out, "}}").unwrap();
+    Ok(out)
+}
+
+/// Generate source code for a rate expression.
+///
+/// Args:
+///     expr: Expr, Expr string, structured {head, args, kwargs} dict, or number
+///     language: "python", "lua", "rhai" or "rust"
+///     molecules: Molecule names, in concentration-vector order; by default
+///         the names the expression reads, in order of appearance
+///     params: Optional {name: value} parameters, inlined as literals
+///
+/// Kinetic templates are expanded; roles not bound by keyword read a
+/// variable named after the role (`S`, `I`, ...).
+#[pyfunction]
+#[pyo3(name = "to_code", signature = (expr, language="python", molecules=None, params=None))]
+pub fn py_to_code(
+    expr: &PyAny,
+    language: &str,
+    molecules: Option<Vec<String>>,
+    params: Option<&PyAny>,
+) -> PyResult<String> {
+    let language = Language::parse(language)?;
+    let params = extract_params(params)?;
+    let templates = kinetics::builtins();
+    let source = bind_roles(&Arg::from_py(expr, true)?, templates);
+    let molecules = match molecules {
+        Some(names) => names,
+        None => {
+            let mut names = Vec::new();
+            free_names(&source, templates, &mut names);
+            names.retain(|name| !params.iter().any(|(p, _)| p == name));
+            names
+        }
+    };
+    let molecules = MoleculeIndex::new(molecules);
+    let rate = RateExpr::new(&source, &molecules, params)?;
+    Ok(to_code(&rate, &molecules, language))
+}
+
+/// Generate a Rust module for a chemistry's mass-action right-hand side.
+///
+/// Args:
+///     chemistry: ChemistryImpl whose rates are constants or Exprs
+///
+/// Returns the module source: `rates`, `rhs` and `jacobian` functions over
+/// `[f64; NUM_MOLECULES]` arrays in `chemistry.molecules` order.
+#[pyfunction]
+pub fn chemistry_to_rust(chemistry: &PyAny) -> PyResult<String> {
+    let molecules = MoleculeIndex::from_chemistry(chemistry)?;
+    let mut reactions = Vec::new();
+    for (reaction, rate_fn) in molecules.reactions(chemistry, RateLaw::MassAction, true)? {
+        if rate_fn.is_some() {
+            return Err(SimError::Value(format!(
+                "Reaction {:?} has a callable rate, which cannot be exported",
+                reaction.name
+            ))
+            .into());
+        }
+        reactions.push(reaction);
+    }
+    let name = chemistry
+        .getattr("local_name")
+        .and_then(|n| n.extract::<String>())
+        .unwrap_or_else(|_| "chemistry".to_string());
+    Ok(rust_module(&name, &molecules, &reactions)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(source: &str, names: &[&str], language: Language) -> String {
+        let molecules = MoleculeIndex::new(names.iter().map(|s| s.to_string()).collect());
+        let source = bind_roles(&Arg::parse(source).unwrap(), kinetics::builtins());
+        let rate = RateExpr::new(&source, &molecules, vec![]).unwrap();
+        to_code(&rate, &molecules, language)
+    }
+
+    #[test]
+    fn interpreter_spec_examples() {
+        let mm = "michaelis_menten(vmax=10, km=5)";
+        assert_eq!(code(mm, &["S"], Language::Python), "10.0 * S / (5.0 + S)");
+        assert_eq!(
+            code(mm, &["S"], Language::Lua),
+            "return 10.0 * S / (5.0 + S)"
+        );
+        assert_eq!(code(mm, &["S"], Language::Rhai), "10.0 * S / (5.0 + S)");
+        assert_eq!(
+            code(mm, &["S"], Language::Rust),
+            "|s: f64| 10.0 * s / (5.0 + s)"
+        );
+    }
+
+    #[test]
+    fn precedence_and_associativity() {
+        let cases = [
+            ("sub(S, sub(I, 1))", "S - (I - 1.0)", "s - (i - 1.0)"),
+            ("div(S, mul(I, 2))", "S / (I * 2.0)", "s / (i * 2.0)"),
+            ("neg(neg(S))", "-(-S)", "-(-s)"),
+            ("power(neg(S), 2)", "(-S) ** 2.0", "(-s).powi(2)"),
+            (
+                "power(S, power(I, 2))",
+                "S ** I ** 2.0",
+                "s.powf(i.powi(2))",
+            ),
+            ("mul(-2, S)", "-2.0 * S", "-2.0 * s"),
+            (
+                "exp(add(S, 1e-7))",
+                "math.exp(S + 1.0e-7)",
+                "(s + 1.0e-7).exp()",
+            ),
+            ("min(S, I, 3)", "min(S, I, 3.0)", "s.min(i).min(3.0)"),
+            (
+                "if(and(gt(S, 1), not(I)), S, 0)",
+                "S if S > 1.0 and not I != 0.0 else 0.0",
+                "if s > 1.0 && !(i != 0.0) { s } else { 0.0 }",
+            ),
+            (
+                "add(1, lt(S, I))",
+                "1.0 + float(S < I)",
+                "1.0 + (if s < i { 1.0 } else { 0.0 })",
+            ),
+            ("log(2)", "math.log(2.0)", "2.0_f64.ln()"),
+        ];
+        for (source, python, rust) in cases {
+            assert_eq!(
+                code(source, &["S", "I"], Language::Python),
+                python,
+                "{source}"
+            );
+            let closure = code(source, &["S", "I"], Language::Rust);
+            assert_eq!(closure.split_once("| ").unwrap().1, rust, "{source}");
+        }
+        assert_eq!(
+            code("if(gt(S, 1), S, 0)", &["S"], Language::Lua),
+            "return S > 1.0 and S or 0.0"
+        );
+        let closure = |source: &str, names: &[&str]| code(source, names, Language::Rust);
+        assert_eq!(
+            closure("add(S, s)", &["S", "s"]),
+            "|s: f64, s_2: f64| s + s_2"
+        );
+        assert_eq!(
+            closure("mul(k, self, S)", &["k", "self", "S"]),
+            "|k: f64, self_: f64, s: f64| k * self_ * s"
+        );
+        assert_eq!(
+            closure("mul(k, while)", &["k", "while"]),
+            "|k: f64, while_: f64| k * while_"
+        );
+        let negated_power = "neg(power(S, I))";
+        assert_eq!(
+            code(negated_power, &["S", "I"], Language::Python),
+            "-S ** I"
+        );
+        assert_eq!(
+            code(negated_power, &["S", "I"], Language::Rhai),
+            "-(S ** I)"
+        );
+        let closure = code(negated_power, &["S", "I"], Language::Rust);
+        assert_eq!(closure.split_once("| ").unwrap().1, "-s.powf(i)");
+        assert_eq!(
+            code("max(S, 1, I)", &["S", "I"], Language::Rhai),
+            "max(max(S, 1.0), I)"
+        );
+    }
+
+    #[test]
+    fn free_names_follow_templates_and_params() {
+        let templates = kinetics::builtins();
+        let source = bind_roles(
+            &Arg::parse("add(competitive_inhibition(vmax=v, km=1, ki=2), var(P), k)").unwrap(),
+            templates,
+        );
+        let mut names = Vec::new();
+        free_names(&source, templates, &mut names);
+        assert_eq!(names, ["v", "S", "I", "P", "k"]);
+    }
+
+    #[test]
+    fn module_inlines_rates_and_jacobian() {
+        let molecules = MoleculeIndex::new(vec!["S".into(), "P".into(), "E".into()]);
+        let mm = RateExpr::new(
+            &Arg::parse("div(mul(vmax, S), add(km, S))").unwrap(),
+            &molecules,
+            vec![("vmax".into(), 10.0), ("km".into(), 5.0)],
+        )
+        .unwrap();
+        let reactions = vec![
+            Reaction::new("mm", vec![(0, 1.0)], vec![(1, 1.0)], 0.0).with_expr(mm.into()),
+            Reaction::new("dimer", vec![(1, 2.0)], vec![(0, 1.0)], 0.5)
+                .with_effectors(vec![(2, 1.0)]),
+        ];
+        let module = rust_module("toy", &molecules, &reactions).unwrap();
+        for line in [
+            "pub const MOLECULES: [&str; NUM_MOLECULES] = [\"S\", \"P\", \"E\"];",
+            "    r[0] = 10.0 * c[0] / (5.0 + c[0]); // mm",
+            "    r[1] = 0.5 * c[1].powi(2) * c[2]; // dimer",
+            "    dcdt[0] = -r[0] + r[1];",
+            "    dcdt[1] = r[0] - 2.0 * r[1];",
+            "    dcdt[2] = 0.0;",
+            "    jac[0][0] = -d0_0;",
+            "    jac[0][2] = d1_2;",
+            "    jac[1][1] = -2.0 * d1_1;",
+        ] {
+            assert!(module.contains(line), "missing {line:?} in\n{module}");
+        }
+        let restricted = vec![reactions[1].clone().in_compartments(vec![0])];
+        assert!(rust_module("toy", &molecules, &restricted).is_err());
+    }
+}
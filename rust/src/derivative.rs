@@ -0,0 +1,368 @@
+//! Symbolic derivatives of rate formulas.
+//!
+//! `Formula::derivative` differentiates a lowered rate law with respect to a
+//! molecule concentration or a parameter, simplifying as it goes so that
+//! mass-action-like terms stay small. The result is an ordinary `Formula`,
+//! compiled like any other rate (closure and bytecode, see `rate`); this is
+//! what implicit solvers, sensitivities and fitting use in place of finite
+//! differences.
+//!
+//! Piecewise operators differentiate piecewise: `if(c, a, b)` becomes
+//! `if(c, a', b')`, `min`/`max` follow the selected argument, and comparisons
+//! and logic are locally constant.
+
+use crate::rate::{Formula, Op, RateExpr};
+use crate::reaction::MoleculeId;
+
+/// What to differentiate with respect to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wrt {
+    /// Concentration of a molecule in the current compartment.
+    Var(MoleculeId),
+    /// Parameter, by index into the parameter list.
+    Param(usize),
+}
+
+impl Formula {
+    /// `∂self/∂wrt`, simplified.
+    pub fn derivative(&self, wrt: Wrt) -> Formula {
+        match self {
+            Formula::Const(_) => zero(),
+            Formula::Var(i) => Formula::Const(if wrt == Wrt::Var(*i) { 1.0 } else { 0.0 }),
+            Formula::Param(p) => Formula::Const(if wrt == Wrt::Param(*p) { 1.0 } else { 0.0 }),
+            Formula::Apply(op, args) => {
+                let d = |a: &Formula| a.derivative(wrt);
+                match op {
+                    Op::Add => add(args.iter().map(d).collect()),
+                    Op::Sub => sub(d(&args[0]), d(&args[1])),
+                    Op::Neg => neg(d(&args[0])),
+                    // Product rule: Σ_i a_i' · ∏_{j≠i} a_j.
+                    Op::Mul => add((0..args.len())
+                        .map(|i| {
+                            let mut factors = vec![d(&args[i])];
+                            factors.extend(
+                                args.iter()
+                                    .enumerate()
+                                    .filter(|&(j, _)| j != i)
+                                    .map(|(_, a)| a.clone()),
+                            );
+                            mul(factors)
+                        })
+                        .collect()),
+                    Op::Div => {
+                        let (a, b) = (&args[0], &args[1]);
+                        let (da, db) = (d(a), d(b));
+                        if is_zero(&db) {
+                            return div(da, b.clone());
+                        }
+                        div(
+                            sub(mul(vec![da, b.clone()]), mul(vec![a.clone(), db])),
+                            mul(vec![b.clone(), b.clone()]),
+                        )
+                    }
+                    Op::Power => {
+                        let (a, b) = (&args[0], &args[1]);
+                        let (da, db) = (d(a), d(b));
+                        if is_zero(&db) {
+                            // b · a^(b-1) · a'
+                            let lowered = power(a.clone(), sub(b.clone(), Formula::Const(1.0)));
+                            return mul(vec![b.clone(), lowered, da]);
+                        }
+                        // a^b · (b' · ln a + b · a' / a)
+                        mul(vec![
+                            self.clone(),
+                            add(vec![
+                                mul(vec![db, apply(Op::Log, vec![a.clone()])]),
+                                div(mul(vec![b.clone(), da]), a.clone()),
+                            ]),
+                        ])
+                    }
+                    Op::Exp => mul(vec![self.clone(), d(&args[0])]),
+                    Op::Log => div(d(&args[0]), args[0].clone()),
+                    Op::Min | Op::Max => {
+                        // min(a, rest...) = if(a <= min(rest), a, min(rest)).
+                        let Some((first, rest)) = args.split_first() else {
+                            return zero();
+                        };
+                        if rest.is_empty() {
+                            return d(first);
+                        }
+                        let rest = match rest {
+                            [only] => only.clone(),
+                            _ => Formula::Apply(*op, rest.to_vec()),
+                        };
+                        let pick = if *op == Op::Min { Op::Le } else { Op::Ge };
+                        select(
+                            apply(pick, vec![first.clone(), rest.clone()]),
+                            d(first),
+                            d(&rest),
+                        )
+                    }
+                    Op::If => select(args[0].clone(), d(&args[1]), d(&args[2])),
+                    Op::Gt | Op::Lt | Op::Ge | Op::Le | Op::Eq | Op::And | Op::Or | Op::Not => {
+                        zero()
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Compiled first derivatives of a rate law (see `RateExpr::derivatives`).
+#[derive(Debug)]
+pub struct Derivatives {
+    /// `∂rate/∂conc[m]` for each molecule the rate reads, in `variables` order.
+    pub conc: Vec<(MoleculeId, RateExpr)>,
+    /// `∂rate/∂p` for each parameter, in parameter order.
+    pub params: Vec<RateExpr>,
+}
+
+impl Derivatives {
+    pub fn of(rate: &RateExpr) -> Self {
+        Self {
+            conc: rate
+                .variables()
+                .iter()
+                .map(|&m| (m, rate.derivative(Wrt::Var(m))))
+                .collect(),
+            params: (0..rate.params().len())
+                .map(|p| rate.derivative(Wrt::Param(p)))
+                .collect(),
+        }
+    }
+}
+
+fn zero() -> Formula {
+    Formula::Const(0.0)
+}
+
+fn is_zero(f: &Formula) -> bool {
+    *f == Formula::Const(0.0)
+}
+
+fn is_one(f: &Formula) -> bool {
+    *f == Formula::Const(1.0)
+}
+
+fn constant(f: &Formula) -> Option<f64> {
+    match f {
+        Formula::Const(v) => Some(*v),
+        _ => None,
+    }
+}
+
+/// `op(args)`, folded when every argument is constant.
+fn apply(op: Op, args: Vec<Formula>) -> Formula {
+    match args.iter().map(constant).collect::<Option<Vec<f64>>>() {
+        Some(values) => Formula::Const(op.apply(&values)),
+        None => Formula::Apply(op, args),
+    }
+}
+
+fn add(terms: Vec<Formula>) -> Formula {
+    let mut sum = 0.0;
+    let mut rest = Vec::new();
+    for term in terms {
+        match term {
+            Formula::Const(v) => sum += v,
+            other => rest.push(other),
+        }
+    }
+    if sum != 0.0 || rest.is_empty() {
+        rest.push(Formula::Const(sum));
+    }
+    match rest.len() {
+        1 => rest.pop().unwrap(),
+        _ => Formula::Apply(Op::Add, rest),
+    }
+}
+
+fn mul(factors: Vec<Formula>) -> Formula {
+    let mut product = 1.0;
+    let mut rest = Vec::new();
+    for factor in factors {
+        match factor {
+            Formula::Const(v) => product *= v,
+            other => rest.push(other),
+        }
+    }
+    if product == 0.0 {
+        return zero();
+    }
+    if product != 1.0 || rest.is_empty() {
+        rest.insert(0, Formula::Const(product));
+    }
+    match rest.len() {
+        1 => rest.pop().unwrap(),
+        _ => Formula::Apply(Op::Mul, rest),
+    }
+}
+
+fn neg(a: Formula) -> Formula {
+    match a {
+        Formula::Const(v) => Formula::Const(-v),
+        Formula::Apply(Op::Neg, mut args) => args.pop().unwrap(),
+        other => Formula::Apply(Op::Neg, vec![other]),
+    }
+}
+
+fn sub(a: Formula, b: Formula) -> Formula {
+    if is_zero(&b) {
+        a
+    } else if is_zero(&a) {
+        neg(b)
+    } else {
+        apply(Op::Sub, vec![a, b])
+    }
+}
+
+fn div(a: Formula, b: Formula) -> Formula {
+    if is_zero(&a) {
+        zero()
+    } else if is_one(&b) {
+        a
+    } else {
+        apply(Op::Div, vec![a, b])
+    }
+}
+
+fn power(a: Formula, b: Formula) -> Formula {
+    if is_zero(&b) {
+        Formula::Const(1.0)
+    } else if is_one(&b) {
+        a
+    } else {
+        apply(Op::Power, vec![a, b])
+    }
+}
+
+fn select(cond: Formula, a: Formula, b: Formula) -> Formula {
+    if a == b {
+        return a;
+    }
+    match constant(&cond) {
+        Some(c) if c != 0.0 => a,
+        Some(_) => b,
+        None => Formula::Apply(Op::If, vec![cond, a, b]),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chemistry::MoleculeIndex;
+    use crate::expr::Arg;
+    use crate::reaction::Reaction;
+    use std::sync::Arc;
+
+    fn compile(source: &str, params: &[(&str, f64)]) -> RateExpr {
+        let molecules = MoleculeIndex::new(vec!["S".into(), "I".into(), "E".into()]);
+        let params = params.iter().map(|(n, v)| (n.to_string(), *v)).collect();
+        RateExpr::new(&Arg::parse(source).unwrap(), &molecules, params).unwrap()
+    }
+
+    /// Central difference of `f` at `x` along coordinate `i`.
+    fn central(f: impl Fn(&[f64]) -> f64, x: &[f64], i: usize) -> f64 {
+        let h = f64::EPSILON.cbrt() * x[i].abs().max(1.0);
+        let (mut up, mut down) = (x.to_vec(), x.to_vec());
+        up[i] += h;
+        down[i] -= h;
+        (f(&up) - f(&down)) / (2.0 * h)
+    }
+
+    fn assert_close(exact: f64, approx: f64, what: &str) {
+        let tol = 1e-6 * exact.abs().max(1.0);
+        assert!((exact - approx).abs() < tol, "{what}: {exact} vs {approx}");
+    }
+
+    #[test]
+    fn matches_finite_differences() {
+        let params = [("vmax", 10.0), ("km", 5.0), ("ki", 0.7), ("n", 2.5)];
+        let conc = [3.0, 0.4, 1.7];
+        for source in [
+            "div(mul(vmax, S), add(km, S))",
+            "div(mul(vmax, S), add(mul(km, add(1, div(I, ki))), S))",
+            "mul(vmax, div(power(S, n), add(power(km, n), power(S, n))))",
+            "michaelis_menten(vmax=vmax, km=km, S=S)",
+            "hill_repression(vmax=vmax, k=km, n=n, R=I)",
+            "mul(E, exp(neg(div(S, km))), log(add(1, I)))",
+            "sub(mul(S, S, E), div(I, sub(S, ki)))",
+            "power(S, I)",
+            "min(S, mul(km, I), E)",
+            "max(mul(2, I), E)",
+            "if(gt(S, km), mul(S, vmax), mul(I, I))",
+            "if(lt(S, km), mul(S, vmax), mul(I, I))",
+            "add(gt(S, 1), and(S, I), not(E))",
+        ] {
+            let rate = compile(source, &params);
+            let values: Vec<f64> = params.iter().map(|(_, v)| *v).collect();
+            let derivatives = rate.derivatives();
+            for (m, d) in &derivatives.conc {
+                let approx = central(|c| rate.eval(c), &conc, *m);
+                assert_close(d.eval(&conc), approx, &format!("d/d{m} {source}"));
+            }
+            for (p, d) in derivatives.params.iter().enumerate() {
+                let approx = central(|v| rate.formula().eval(&conc, v), &values, p);
+                assert_close(
+                    d.eval(&conc),
+                    approx,
+                    &format!("d/d{} {source}", params[p].0),
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn simplifies_polynomials() {
+        let rate = compile("mul(k, S, S, I)", &[("k", 2.0)]);
+        assert_eq!(
+            rate.formula().derivative(Wrt::Var(1)).to_string(),
+            "mul(param(0), var(0), var(0))"
+        );
+        assert_eq!(rate.formula().derivative(Wrt::Var(2)).to_string(), "0.0");
+        let d = rate.derivative(Wrt::Var(0));
+        assert_eq!(d.variables(), &[0, 1]);
+        assert_eq!(d.eval(&[3.0, 5.0, 0.0]), 2.0 * 2.0 * 3.0 * 5.0);
+    }
+
+    #[test]
+    fn second_derivatives() {
+        let rate = compile("power(S, 3)", &[]);
+        let d2 = &rate.derivatives().conc[0].1.derivatives().conc[0].1;
+        assert_eq!(d2.eval(&[2.0, 0.0, 0.0]), 12.0);
+    }
+
+    #[test]
+    fn reaction_partials_match_finite_differences() {
+        let conc = [1.3, 0.6, 2.2];
+        let mass_action =
+            Reaction::new("ma", vec![(0, 2.0)], vec![(1, 1.0)], 0.3).with_effectors(vec![(2, 1.5)]);
+        let mm =
+            Reaction::new("mm", vec![(0, 1.0)], vec![(1, 1.0)], 0.0).with_expr(Arc::new(compile(
+                "competitive_inhibition(vmax=vmax, km=km, ki=ki, S=S, I=I)",
+                &[("vmax", 4.0), ("km", 0.5), ("ki", 0.2)],
+            )));
+        let mut partials = Vec::new();
+        for reaction in [&mass_action, &mm] {
+            reaction.rate_partials(&conc, &mut partials);
+            assert_eq!(
+                partials.iter().map(|&(m, _)| m).collect::<Vec<_>>(),
+                reaction.rate_inputs()
+            );
+            for &(m, d) in &partials {
+                let approx = central(|c| reaction.rate(c), &conc, m);
+                assert_close(d, approx, &format!("{} d/d{m}", reaction.name));
+            }
+        }
+
+        let d_k = mass_action.param_partials(&conc);
+        assert_eq!(d_k[0].0, "rate_constant");
+        assert_close(d_k[0].1, mass_action.rate(&conc) / 0.3, "d/dk");
+        let names: Vec<_> = mm
+            .param_partials(&conc)
+            .into_iter()
+            .map(|(n, _)| n)
+            .collect();
+        assert_eq!(names, ["vmax", "km", "ki"]);
+    }
+}
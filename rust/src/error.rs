@@ -0,0 +1,39 @@
+//! Error type shared by the native simulator modules.
+
+use std::fmt;
+
+use pyo3::exceptions::{PyIndexError, PyKeyError, PyValueError};
+use pyo3::PyErr;
+
+/// Errors raised by the pure-Rust core; converted to Python exceptions at the boundary.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SimError {
+    /// Invalid argument or inconsistent data (Python `ValueError`).
+    Value(String),
+    /// Compartment or molecule index out of range (Python `IndexError`).
+    Index(String),
+    /// Unknown name lookup (Python `KeyError`).
+    Key(String),
+}
+
+pub type SimResult<T> = Result<T, SimError>;
+
+impl fmt::Display for SimError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SimError::Value(msg) | SimError::Index(msg) | SimError::Key(msg) => f.write_str(msg),
+        }
+    }
+}
+
+impl std::error::Error for SimError {}
+
+impl From<SimError> for PyErr {
+    fn from(err: SimError) -> PyErr {
+        match err {
+            SimError::Value(msg) => PyValueError::new_err(msg),
+            SimError::Index(msg) => PyIndexError::new_err(msg),
+            SimError::Key(msg) => PyKeyError::new_err(msg),
+        }
+    }
+}
@@ -0,0 +1,309 @@
+//! Events: interventions scheduled at exact times during `WorldSimulator.run`.
+//!
+//! The run loop cuts the step that crosses an event's time in two, so the
+//! integrator stops exactly there, applies the event and carries on. Boluses
+//! add an amount of a molecule to a compartment, clamps hold a concentration
+//! fixed until released, knockdowns multiply a reaction's rate, knockouts
+//! silence it, and removals kill a compartment for the rest of the run (see
+//! `remodel::Remodel::Die`). Events at the end of a run apply to its final
+//! state.
+//!
+//! Compartments are resolved when the event fires, against the tree current
+//! at that time; molecules and reactions are resolved when it is scheduled.
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::flow;
+use crate::reaction::{MoleculeId, Reaction};
+use crate::tree::{CompartmentId, Topology};
+
+/// A compartment named by an event, by ID or by name.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Site {
+    Id(CompartmentId),
+    Name(String),
+}
+
+impl Site {
+    pub fn resolve(&self, topology: &Topology) -> SimResult<CompartmentId> {
+        match self {
+            Site::Id(id) => {
+                topology.check(*id)?;
+                Ok(*id)
+            }
+            Site::Name(name) => topology
+                .names()
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| SimError::Key(format!("Unknown compartment: {name:?}"))),
+        }
+    }
+
+    fn from_py(key: &PyAny) -> PyResult<Self> {
+        match key.extract::<CompartmentId>() {
+            Ok(id) => Ok(Site::Id(id)),
+            Err(_) => Ok(Site::Name(key.extract()?)),
+        }
+    }
+}
+
+/// What an event does.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// Add `amount` of a molecule to a compartment, spread over its instances.
+    Bolus {
+        compartment: Site,
+        molecule: MoleculeId,
+        amount: f64,
+    },
+    /// Hold a concentration at `value` (default: its value when clamped).
+    Clamp {
+        compartment: Site,
+        molecule: MoleculeId,
+        value: Option<f64>,
+    },
+    /// Let a clamped concentration evolve again.
+    Release {
+        compartment: Site,
+        molecule: MoleculeId,
+    },
+    /// Multiply a reaction's rate by `factor`.
+    Knockdown { reaction: usize, factor: f64 },
+    /// Stop a reaction.
+    Knockout { reaction: usize },
+    /// Kill a compartment and its subtree, releasing contents to the parent.
+    Remove { compartment: Site },
+}
+
+/// An action at a time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Event {
+    pub time: f64,
+    pub action: Action,
+}
+
+impl Event {
+    pub fn new(time: f64, action: Action) -> Self {
+        Self { time, action }
+    }
+
+    /// Read `{"time": t, "type": kind, ...}` from Python, where kind is
+    /// bolus, clamp, release, knockdown, knockout or remove. Molecules and
+    /// reactions are given by ID or name.
+    pub fn from_py(
+        spec: &PyDict,
+        molecules: &MoleculeIndex,
+        reactions: &[Reaction],
+    ) -> PyResult<Event> {
+        let field = |key: &str| -> PyResult<&PyAny> {
+            spec.get_item(key)?
+                .ok_or_else(|| SimError::Key(format!("Event is missing {key:?}")).into())
+        };
+        let compartment = || Site::from_py(field("compartment")?);
+        let molecule = || flow::molecule_key(field("molecule")?, molecules);
+        let reaction = || -> PyResult<usize> {
+            let key = field("reaction")?;
+            match key.extract::<usize>() {
+                Ok(i) if i < reactions.len() => Ok(i),
+                Ok(i) => Err(SimError::Key(format!("Unknown reaction: {i}")).into()),
+                Err(_) => {
+                    let name = key.extract::<&str>()?;
+                    Ok(reactions
+                        .iter()
+                        .position(|r| r.name == name)
+                        .ok_or_else(|| SimError::Key(format!("Unknown reaction: {name:?}")))?)
+                }
+            }
+        };
+        let action = match field("type")?.extract::<&str>()? {
+            "bolus" => Action::Bolus {
+                compartment: compartment()?,
+                molecule: molecule()?,
+                amount: field("amount")?.extract()?,
+            },
+            "clamp" => Action::Clamp {
+                compartment: compartment()?,
+                molecule: molecule()?,
+                value: match spec.get_item("value")? {
+                    Some(value) => value.extract()?,
+                    None => None,
+                },
+            },
+            "release" => Action::Release {
+                compartment: compartment()?,
+                molecule: molecule()?,
+            },
+            "knockdown" => Action::Knockdown {
+                reaction: reaction()?,
+                factor: field("factor")?.extract()?,
+            },
+            "knockout" => Action::Knockout {
+                reaction: reaction()?,
+            },
+            "remove" => Action::Remove {
+                compartment: compartment()?,
+            },
+            other => {
+                return Err(SimError::Value(format!(
+                    "Unknown event type {other:?} (expected bolus, clamp, release, \
+                     knockdown, knockout or remove)"
+                ))
+                .into())
+            }
+        };
+        let event = Event::new(field("time")?.extract()?, action);
+        event.validate()?;
+        Ok(event)
+    }
+
+    /// Check the time and the action's numbers.
+    pub fn validate(&self) -> SimResult<()> {
+        if !(self.time.is_finite() && self.time >= 0.0) {
+            return Err(SimError::Value(format!(
+                "Event time must be a non-negative number, got {}",
+                self.time
+            )));
+        }
+        let bad = match &self.action {
+            Action::Bolus { amount, .. } => (!amount.is_finite()).then_some(("amount", *amount)),
+            Action::Clamp {
+                value: Some(value), ..
+            } => (!(value.is_finite() && *value >= 0.0)).then_some(("value", *value)),
+            Action::Knockdown { factor, .. } => {
+                (!(factor.is_finite() && *factor >= 0.0)).then_some(("factor", *factor))
+            }
+            _ => None,
+        };
+        match bad {
+            Some((what, value)) => Err(SimError::Value(format!("Invalid event {what}: {value}"))),
+            None => Ok(()),
+        }
+    }
+
+    /// One-line description for the event log, e.g. `"bolus 5 glucose into cell"`.
+    pub fn describe(
+        &self,
+        topology: &Topology,
+        molecules: &MoleculeIndex,
+        reactions: &[Reaction],
+    ) -> String {
+        let place = |site: &Site| match site.resolve(topology) {
+            Ok(id) => topology.name(id).to_string(),
+            Err(_) => format!("{site:?}"),
+        };
+        let molecule = |m: &MoleculeId| molecules.names()[*m].as_str();
+        let reaction = |r: &usize| reactions[*r].name.as_str();
+        match &self.action {
+            Action::Bolus {
+                compartment,
+                molecule: m,
+                amount,
+            } => format!("bolus {amount} {} into {}", molecule(m), place(compartment)),
+            Action::Clamp {
+                compartment,
+                molecule: m,
+                value,
+            } => match value {
+                Some(value) => {
+                    format!("clamp {} in {} at {value}", molecule(m), place(compartment))
+                }
+                None => format!("clamp {} in {}", molecule(m), place(compartment)),
+            },
+            Action::Release {
+                compartment,
+                molecule: m,
+            } => format!("release {} in {}", molecule(m), place(compartment)),
+            Action::Knockdown {
+                reaction: r,
+                factor,
+            } => {
+                format!("knockdown {} x{factor}", reaction(r))
+            }
+            Action::Knockout { reaction: r } => format!("knockout {}", reaction(r)),
+            Action::Remove { compartment } => format!("remove {}", place(compartment)),
+        }
+    }
+}
+
+/// Events in firing order; events at the same time fire in the order given.
+#[derive(Debug, Clone, Default)]
+pub struct Schedule {
+    events: Vec<Event>,
+    next: usize,
+}
+
+impl Schedule {
+    pub fn new(mut events: Vec<Event>) -> SimResult<Self> {
+        for event in &events {
+            event.validate()?;
+        }
+        events.sort_by(|a, b| a.time.total_cmp(&b.time));
+        Ok(Self { events, next: 0 })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty()
+    }
+
+    /// Time of the next event still to fire.
+    pub fn next_time(&self) -> Option<f64> {
+        self.events.get(self.next).map(|e| e.time)
+    }
+
+    /// Time of the last event.
+    pub fn last_time(&self) -> Option<f64> {
+        self.events.last().map(|e| e.time)
+    }
+
+    /// The next event, if it is due by time `t`.
+    pub fn pop_due(&mut self, t: f64) -> Option<Event> {
+        let event = self.events.get(self.next).filter(|e| e.time <= t)?.clone();
+        self.next += 1;
+        Some(event)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn schedule_fires_in_time_order() {
+        let knockout = |time, reaction| Event::new(time, Action::Knockout { reaction });
+        let mut schedule =
+            Schedule::new(vec![knockout(2.0, 0), knockout(0.5, 1), knockout(2.0, 2)]).unwrap();
+        assert_eq!(schedule.next_time(), Some(0.5));
+        assert_eq!(schedule.last_time(), Some(2.0));
+        assert!(schedule.pop_due(0.4).is_none());
+        assert_eq!(schedule.pop_due(1.0), Some(knockout(0.5, 1)));
+        assert!(schedule.pop_due(1.0).is_none());
+        // Ties keep the order given.
+        assert_eq!(schedule.pop_due(2.0), Some(knockout(2.0, 0)));
+        assert_eq!(schedule.pop_due(2.0), Some(knockout(2.0, 2)));
+        assert_eq!(schedule.next_time(), None);
+
+        assert!(Schedule::new(vec![knockout(-1.0, 0)]).is_err());
+        let boost = Event::new(
+            1.0,
+            Action::Knockdown {
+                reaction: 0,
+                factor: f64::NAN,
+            },
+        );
+        assert!(Schedule::new(vec![boost]).is_err());
+    }
+
+    #[test]
+    fn sites_resolve_against_the_current_tree() {
+        let mut t = Topology::new();
+        let root = t.add_root("organism").unwrap();
+        t.add_child(root, "cell").unwrap();
+        assert_eq!(Site::Name("cell".into()).resolve(&t).unwrap(), 1);
+        assert_eq!(Site::Id(0).resolve(&t).unwrap(), 0);
+        assert!(Site::Id(2).resolve(&t).is_err());
+        assert!(Site::Name("nucleus".into()).resolve(&t).is_err());
+    }
+}
@@ -0,0 +1,776 @@
+//! Expr: functional expression trees, as in docs/architecture/classes/infra/Expr.md.
+//!
+//! An `Expr` is a `head` with positional `args` and keyword `kwargs`. It is
+//! written as a Python-style call, `div(var(S), add(var(S), 0.5))`, or in
+//! YAML as the structured form
+//!
+//! ```yaml
+//! head: michaelis_menten
+//! kwargs: {vmax: 10.0, km: 5.0}
+//! ```
+//!
+//! This module only handles the data: parsing, printing and conversion.
+//! `rate` compiles rate-law Exprs into native functions.
+
+use std::fmt;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyTuple, PyType};
+use serde_yaml::Value;
+
+use crate::error::{SimError, SimResult};
+use crate::rate::PyCompiledRate;
+
+/// Deepest call nesting accepted when reading an Expr, so hostile input
+/// cannot exhaust the stack.
+pub const MAX_DEPTH: usize = 256;
+
+/// An Expr argument: a nested Expr or a literal.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Expr(Expr),
+    Number(f64),
+    Str(String),
+    Bool(bool),
+    None,
+}
+
+/// Functional expression tree node.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Expr {
+    pub head: String,
+    pub args: Vec<Arg>,
+    /// Keyword arguments, in declaration order.
+    pub kwargs: Vec<(String, Arg)>,
+}
+
+impl Expr {
+    pub fn new(head: impl Into<String>, args: Vec<Arg>) -> Self {
+        Self {
+            head: head.into(),
+            args,
+            kwargs: Vec::new(),
+        }
+    }
+
+    /// Add a keyword argument.
+    pub fn with_kwarg(mut self, name: impl Into<String>, value: Arg) -> Self {
+        self.kwargs.push((name.into(), value));
+        self
+    }
+
+    pub fn kwarg(&self, name: &str) -> Option<&Arg> {
+        self.kwargs.iter().find(|(k, _)| k == name).map(|(_, v)| v)
+    }
+
+    /// Parse a Python-style call (or bare identifier) into an Expr.
+    pub fn parse(s: &str) -> SimResult<Self> {
+        match Arg::parse(s)? {
+            Arg::Expr(expr) => Ok(expr),
+            Arg::Str(name) if is_identifier(&name) && !s.trim_start().starts_with(['\'', '"']) => {
+                Ok(Expr::new(name, Vec::new()))
+            }
+            _ => Err(SimError::Value(format!(
+                "Expr must be a function call or identifier, got {s:?}"
+            ))),
+        }
+    }
+
+    /// Calls on the deepest path through the tree, counting this one.
+    pub fn depth(&self) -> usize {
+        let nested = self.args.iter().chain(self.kwargs.iter().map(|(_, v)| v));
+        1 + nested
+            .map(|arg| match arg {
+                Arg::Expr(expr) => expr.depth(),
+                _ => 0,
+            })
+            .max()
+            .unwrap_or(0)
+    }
+
+    /// Format as a Python-style function call.
+    pub fn print(&self) -> String {
+        self.to_string()
+    }
+
+    /// Structured `{head, args, kwargs}` form; empty args and kwargs are omitted.
+    pub fn to_yaml(&self) -> Value {
+        let mut map = serde_yaml::Mapping::new();
+        map.insert("head".into(), self.head.clone().into());
+        if !self.args.is_empty() {
+            map.insert(
+                "args".into(),
+                Value::Sequence(self.args.iter().map(Arg::to_yaml).collect()),
+            );
+        }
+        if !self.kwargs.is_empty() {
+            let kwargs = self
+                .kwargs
+                .iter()
+                .map(|(k, v)| (Value::from(k.clone()), v.to_yaml()))
+                .collect();
+            map.insert("kwargs".into(), Value::Mapping(kwargs));
+        }
+        Value::Mapping(map)
+    }
+
+    /// Read the structured `{head, args, kwargs}` form.
+    pub fn from_yaml(value: &Value) -> SimResult<Self> {
+        let Value::Mapping(map) = value else {
+            return Err(SimError::Value(format!(
+                "Structured Expr must be a mapping with a 'head', got {value:?}"
+            )));
+        };
+        let mut head = None;
+        let mut args = Vec::new();
+        let mut kwargs = Vec::new();
+        for (key, value) in map {
+            match key.as_str() {
+                Some("head") => {
+                    head = Some(
+                        value
+                            .as_str()
+                            .ok_or_else(|| SimError::Value("Expr head must be a string".into()))?
+                            .to_string(),
+                    )
+                }
+                Some("args") => {
+                    let Value::Sequence(items) = value else {
+                        return Err(SimError::Value("Expr args must be a list".into()));
+                    };
+                    args = items.iter().map(Arg::from_yaml).collect::<SimResult<_>>()?;
+                }
+                Some("kwargs") => {
+                    let Value::Mapping(items) = value else {
+                        return Err(SimError::Value("Expr kwargs must be a mapping".into()));
+                    };
+                    for (k, v) in items {
+                        let name = k.as_str().ok_or_else(|| {
+                            SimError::Value("Expr kwarg names must be strings".into())
+                        })?;
+                        kwargs.push((name.to_string(), Arg::from_yaml(v)?));
+                    }
+                }
+                _ => {
+                    return Err(SimError::Value(format!(
+                        "Unexpected key {key:?} in structured Expr (expected head, args, kwargs)"
+                    )))
+                }
+            }
+        }
+        let head = head.ok_or_else(|| SimError::Value("Structured Expr has no 'head'".into()))?;
+        Ok(Self { head, args, kwargs })
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}(", self.head)?;
+        let mut first = true;
+        for arg in &self.args {
+            if !first {
+                f.write_str(", ")?;
+            }
+            write!(f, "{arg}")?;
+            first = false;
+        }
+        for (name, arg) in &self.kwargs {
+            if !first {
+                f.write_str(", ")?;
+            }
+            write!(f, "{name}={arg}")?;
+            first = false;
+        }
+        f.write_str(")")
+    }
+}
+
+impl Arg {
+    /// Parse one argument: a call, identifier, number, string or keyword literal.
+    ///
+    /// Bare identifiers are strings, as in `measure(glucose)`.
+    pub fn parse(s: &str) -> SimResult<Self> {
+        Arg::parse_at(s, 0)
+    }
+
+    /// `parse` for a string `depth` calls deep, sharing the `MAX_DEPTH` budget.
+    fn parse_at(s: &str, depth: usize) -> SimResult<Self> {
+        let mut parser = Parser::new(s);
+        parser.depth = depth;
+        let arg = parser.arg()?;
+        parser.skip_whitespace();
+        if parser.pos < parser.chars.len() {
+            return Err(parser.error("unexpected trailing input"));
+        }
+        Ok(arg)
+    }
+
+    /// Read a YAML value: numbers, booleans and null are literals, strings are
+    /// parsed, and mappings use the structured form.
+    pub fn from_yaml_rate(value: &Value) -> SimResult<Self> {
+        match value {
+            Value::String(s) => Arg::parse(s),
+            other => Arg::from_yaml(other),
+        }
+    }
+
+    /// Read an argument inside the structured form, where strings are literal.
+    pub fn from_yaml(value: &Value) -> SimResult<Self> {
+        match value {
+            Value::Null => Ok(Arg::None),
+            Value::Bool(b) => Ok(Arg::Bool(*b)),
+            Value::Number(n) => n
+                .as_f64()
+                .map(Arg::Number)
+                .ok_or_else(|| SimError::Value(format!("Unsupported number {n}"))),
+            Value::String(s) => Ok(Arg::Str(s.clone())),
+            Value::Mapping(_) => Ok(Arg::Expr(Expr::from_yaml(value)?)),
+            Value::Tagged(tagged) => Arg::from_yaml(&tagged.value),
+            Value::Sequence(_) => Err(SimError::Value("Expr arguments cannot be lists".into())),
+        }
+    }
+
+    pub fn to_yaml(&self) -> Value {
+        match self {
+            Arg::Expr(expr) => expr.to_yaml(),
+            Arg::Number(v) => Value::from(*v),
+            Arg::Str(s) => Value::from(s.clone()),
+            Arg::Bool(b) => Value::from(*b),
+            Arg::None => Value::Null,
+        }
+    }
+
+    /// Read a Python value: an `Expr`, a structured dict, a number, a bool,
+    /// None, or (when `parse_strings`) a string to parse.
+    pub fn from_py(obj: &PyAny, parse_strings: bool) -> PyResult<Self> {
+        Arg::from_py_at(obj, parse_strings, 0)
+    }
+
+    /// `from_py` for a value `depth` calls deep.
+    fn from_py_at(obj: &PyAny, parse_strings: bool, depth: usize) -> PyResult<Self> {
+        if obj.is_none() {
+            return Ok(Arg::None);
+        }
+        if let Ok(expr) = obj.extract::<PyRef<'_, PyExpr>>() {
+            check_depth(depth + expr.expr.depth())?;
+            return Ok(Arg::Expr(expr.expr.clone()));
+        }
+        if let Ok(b) = obj.downcast::<pyo3::types::PyBool>() {
+            return Ok(Arg::Bool(b.is_true()));
+        }
+        if let Ok(s) = obj.extract::<String>() {
+            return Ok(if parse_strings {
+                Arg::parse_at(&s, depth)?
+            } else {
+                Arg::Str(s)
+            });
+        }
+        if let Ok(v) = obj.extract::<f64>() {
+            return Ok(Arg::Number(v));
+        }
+        if let Ok(dict) = obj.downcast::<PyDict>() {
+            check_depth(depth + 1)?;
+            let mut expr = Expr::new(
+                dict.get_item("head")?
+                    .ok_or_else(|| PyValueError::new_err("Structured Expr has no 'head'"))?
+                    .extract::<String>()?,
+                Vec::new(),
+            );
+            for (key, value) in dict.iter() {
+                match key.extract::<&str>()? {
+                    "head" => {}
+                    "args" => {
+                        for item in value.iter()? {
+                            expr.args.push(Arg::from_py_at(item?, false, depth + 1)?);
+                        }
+                    }
+                    "kwargs" => {
+                        for (k, v) in value.downcast::<PyDict>()?.iter() {
+                            expr.kwargs
+                                .push((k.extract()?, Arg::from_py_at(v, false, depth + 1)?));
+                        }
+                    }
+                    other => {
+                        return Err(PyValueError::new_err(format!(
+                        "Unexpected key {other:?} in structured Expr (expected head, args, kwargs)"
+                    )))
+                    }
+                }
+            }
+            return Ok(Arg::Expr(expr));
+        }
+        Err(PyValueError::new_err(format!(
+            "Cannot convert {} to an Expr argument",
+            obj.get_type().name()?
+        )))
+    }
+
+    pub fn to_py(&self, py: Python<'_>) -> PyResult<PyObject> {
+        Ok(match self {
+            Arg::Expr(expr) => Py::new(py, PyExpr { expr: expr.clone() })?.into_py(py),
+            Arg::Number(v) => v.into_py(py),
+            Arg::Str(s) => s.into_py(py),
+            Arg::Bool(b) => b.into_py(py),
+            Arg::None => py.None(),
+        })
+    }
+}
+
+impl fmt::Display for Arg {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Arg::Expr(expr) => write!(f, "{expr}"),
+            Arg::Number(v) => write!(f, "{v:?}"),
+            Arg::Str(s) if is_identifier(s) && !is_keyword(s) => f.write_str(s),
+            Arg::Str(s) => write!(f, "'{}'", s.replace('\\', "\\\\").replace('\'', "\\'")),
+            Arg::Bool(true) => f.write_str("True"),
+            Arg::Bool(false) => f.write_str("False"),
+            Arg::None => f.write_str("None"),
+        }
+    }
+}
+
+fn check_depth(depth: usize) -> SimResult<()> {
+    if depth > MAX_DEPTH {
+        return Err(SimError::Value(format!(
+            "Expr is nested more than {MAX_DEPTH} calls deep"
+        )));
+    }
+    Ok(())
+}
+
+fn is_identifier(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+}
+
+fn is_keyword(s: &str) -> bool {
+    matches!(s, "True" | "False" | "None")
+}
+
+/// Recursive-descent parser for the call syntax.
+struct Parser {
+    chars: Vec<char>,
+    pos: usize,
+    /// Calls open at `pos`.
+    depth: usize,
+}
+
+impl Parser {
+    fn new(s: &str) -> Self {
+        Self {
+            chars: s.chars().collect(),
+            pos: 0,
+            depth: 0,
+        }
+    }
+
+    fn error(&self, msg: &str) -> SimError {
+        let text: String = self.chars.iter().collect();
+        SimError::Value(format!(
+            "Cannot parse Expr {text:?}: {msg} at position {}",
+            self.pos
+        ))
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.chars.get(self.pos).is_some_and(|c| c.is_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    fn peek(&mut self) -> Option<char> {
+        self.skip_whitespace();
+        self.chars.get(self.pos).copied()
+    }
+
+    fn arg(&mut self) -> SimResult<Arg> {
+        match self.peek() {
+            Some(c) if c.is_ascii_alphabetic() || c == '_' => {
+                let name = self.identifier();
+                if self.peek() == Some('(') {
+                    if self.depth >= MAX_DEPTH {
+                        return Err(self.error(&format!("nested more than {MAX_DEPTH} calls deep")));
+                    }
+                    self.pos += 1;
+                    self.depth += 1;
+                    let expr = self.call(name)?;
+                    self.depth -= 1;
+                    return Ok(Arg::Expr(expr));
+                }
+                Ok(match name.as_str() {
+                    "True" => Arg::Bool(true),
+                    "False" => Arg::Bool(false),
+                    "None" => Arg::None,
+                    _ => Arg::Str(name),
+                })
+            }
+            Some(c) if c.is_ascii_digit() || c == '.' || c == '-' || c == '+' => self.number(),
+            Some(q @ ('\'' | '"')) => self.string(q),
+            Some(_) => Err(self.error("unexpected character")),
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn identifier(&mut self) -> String {
+        let start = self.pos;
+        while self
+            .chars
+            .get(self.pos)
+            .is_some_and(|&c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        {
+            self.pos += 1;
+        }
+        self.chars[start..self.pos].iter().collect()
+    }
+
+    /// Arguments after `head(`, through the closing parenthesis.
+    fn call(&mut self, head: String) -> SimResult<Expr> {
+        let mut expr = Expr::new(head, Vec::new());
+        loop {
+            if self.peek() == Some(')') {
+                self.pos += 1;
+                return Ok(expr);
+            }
+            // A keyword argument is an identifier followed by '='.
+            let save = self.pos;
+            let mut keyword = None;
+            if self
+                .peek()
+                .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+            {
+                let name = self.identifier();
+                if self.peek() == Some('=') {
+                    self.pos += 1;
+                    keyword = Some(name);
+                } else {
+                    self.pos = save;
+                }
+            }
+            let value = self.arg()?;
+            match keyword {
+                Some(name) => {
+                    if expr.kwarg(&name).is_some() {
+                        return Err(self.error(&format!("repeated keyword {name:?}")));
+                    }
+                    expr.kwargs.push((name, value));
+                }
+                None if !expr.kwargs.is_empty() => {
+                    return Err(self.error("positional argument after keyword argument"))
+                }
+                None => expr.args.push(value),
+            }
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(')') => {}
+                _ => return Err(self.error("expected ',' or ')'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> SimResult<Arg> {
+        let start = self.pos;
+        if matches!(self.chars.get(self.pos), Some('-' | '+')) {
+            self.pos += 1;
+        }
+        while let Some(&c) = self.chars.get(self.pos) {
+            let exponent_sign =
+                matches!(c, '-' | '+') && matches!(self.chars[self.pos - 1], 'e' | 'E');
+            if c.is_ascii_digit() || matches!(c, '.' | 'e' | 'E' | '_') || exponent_sign {
+                self.pos += 1;
+            } else {
+                break;
+            }
+        }
+        let text: String = self.chars[start..self.pos]
+            .iter()
+            .filter(|&&c| c != '_')
+            .collect();
+        text.parse()
+            .map(Arg::Number)
+            .map_err(|_| self.error(&format!("invalid number {text:?}")))
+    }
+
+    fn string(&mut self, quote: char) -> SimResult<Arg> {
+        self.pos += 1;
+        let mut value = String::new();
+        while let Some(&c) = self.chars.get(self.pos) {
+            self.pos += 1;
+            match c {
+                '\\' => {
+                    let escaped = self
+                        .chars
+                        .get(self.pos)
+                        .copied()
+                        .ok_or_else(|| self.error("unterminated string"))?;
+                    self.pos += 1;
+                    value.push(escaped);
+                }
+                c if c == quote => return Ok(Arg::Str(value)),
+                c => value.push(c),
+            }
+        }
+        Err(self.error("unterminated string"))
+    }
+}
+
+/// Python-facing Expr tree.
+#[pyclass(name = "Expr", module = "alienbio_sim")]
+#[derive(Clone)]
+pub struct PyExpr {
+    pub expr: Expr,
+}
+
+#[pymethods]
+impl PyExpr {
+    /// Build an Expr from a head and positional/keyword arguments.
+    #[new]
+    #[pyo3(signature = (head, *args, **kwargs))]
+    fn new(head: String, args: &PyTuple, kwargs: Option<&PyDict>) -> PyResult<Self> {
+        let mut expr = Expr::new(
+            head,
+            args.iter()
+                .map(|a| Arg::from_py_at(a, false, 1))
+                .collect::<PyResult<_>>()?,
+        );
+        for (k, v) in kwargs.into_iter().flatten() {
+            expr.kwargs
+                .push((k.extract()?, Arg::from_py_at(v, false, 1)?));
+        }
+        Ok(Self { expr })
+    }
+
+    /// Parse a string into an Expr tree.
+    #[classmethod]
+    fn parse(_cls: &PyType, s: &str) -> PyResult<Self> {
+        Ok(Self {
+            expr: Expr::parse(s)?,
+        })
+    }
+
+    /// Read the structured {head, args, kwargs} dict form.
+    #[classmethod]
+    fn from_dict(_cls: &PyType, data: &PyAny) -> PyResult<Self> {
+        match Arg::from_py(data, false)? {
+            Arg::Expr(expr) => Ok(Self { expr }),
+            _ => Err(PyValueError::new_err("Structured Expr must be a dict")),
+        }
+    }
+
+    /// Function/operation name.
+    #[getter]
+    fn head(&self) -> &str {
+        &self.expr.head
+    }
+
+    /// Positional arguments.
+    #[getter]
+    fn args<'py>(&self, py: Python<'py>) -> PyResult<&'py PyTuple> {
+        let items = self
+            .expr
+            .args
+            .iter()
+            .map(|a| a.to_py(py))
+            .collect::<PyResult<Vec<_>>>()?;
+        Ok(PyTuple::new(py, items))
+    }
+
+    /// Keyword arguments.
+    #[getter]
+    fn kwargs<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (k, v) in &self.expr.kwargs {
+            dict.set_item(k, v.to_py(py)?)?;
+        }
+        Ok(dict)
+    }
+
+    /// Structured {head, args, kwargs} form (empty parts omitted).
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("head", &self.expr.head)?;
+        if !self.expr.args.is_empty() {
+            let args = self
+                .expr
+                .args
+                .iter()
+                .map(|a| arg_to_plain(py, a))
+                .collect::<PyResult<Vec<_>>>()?;
+            dict.set_item("args", args)?;
+        }
+        if !self.expr.kwargs.is_empty() {
+            let kwargs = PyDict::new(py);
+            for (k, v) in &self.expr.kwargs {
+                kwargs.set_item(k, arg_to_plain(py, v)?)?;
+            }
+            dict.set_item("kwargs", kwargs)?;
+        }
+        Ok(dict)
+    }
+
+    /// Format as Python-style function call.
+    fn print(&self) -> String {
+        self.expr.print()
+    }
+
+    fn __str__(&self) -> String {
+        self.expr.print()
+    }
+
+    fn __repr__(&self) -> String {
+        let args: Vec<String> = self.expr.args.iter().map(repr_arg).collect();
+        let kwargs: Vec<String> = self
+            .expr
+            .kwargs
+            .iter()
+            .map(|(k, v)| format!("'{k}': {}", repr_arg(v)))
+            .collect();
+        let args = match args.len() {
+            1 => format!("({},)", args[0]),
+            _ => format!("({})", args.join(", ")),
+        };
+        format!(
+            "Expr('{}', args={args}, kwargs={{{}}})",
+            self.expr.head,
+            kwargs.join(", ")
+        )
+    }
+
+    /// Compile as a rate law over `molecules` (see `CompiledRate`).
+    #[pyo3(signature = (molecules, params=None))]
+    fn compile(&self, molecules: Vec<String>, params: Option<&PyAny>) -> PyResult<PyCompiledRate> {
+        PyCompiledRate::compile(&Arg::Expr(self.expr.clone()), molecules, params)
+    }
+
+    fn __eq__(&self, other: &PyAny) -> bool {
+        other
+            .extract::<PyRef<'_, PyExpr>>()
+            .is_ok_and(|o| o.expr == self.expr)
+    }
+}
+
+/// Nested Exprs as structured dicts, literals as themselves.
+fn arg_to_plain(py: Python<'_>, arg: &Arg) -> PyResult<PyObject> {
+    match arg {
+        Arg::Expr(expr) => Ok(PyExpr { expr: expr.clone() }.to_dict(py)?.into_py(py)),
+        other => other.to_py(py),
+    }
+}
+
+fn repr_arg(arg: &Arg) -> String {
+    match arg {
+        Arg::Expr(expr) => PyExpr { expr: expr.clone() }.__repr__(),
+        Arg::Str(s) => format!("'{s}'"),
+        other => other.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn num(v: f64) -> Arg {
+        Arg::Number(v)
+    }
+
+    #[test]
+    fn parse_nested_calls() {
+        let expr = Expr::parse("div(var(S), add(var(S), 0.5))").unwrap();
+        let var_s = Arg::Expr(Expr::new("var", vec![Arg::Str("S".into())]));
+        assert_eq!(
+            expr,
+            Expr::new(
+                "div",
+                vec![
+                    var_s.clone(),
+                    Arg::Expr(Expr::new("add", vec![var_s, num(0.5)]))
+                ]
+            )
+        );
+        assert_eq!(expr.print(), "div(var(S), add(var(S), 0.5))");
+    }
+
+    #[test]
+    fn parse_literals_and_kwargs() {
+        let expr = Expr::parse("react(A, 'two words', -1.5e-3, True, None, rate=1.2)").unwrap();
+        assert_eq!(expr.head, "react");
+        assert_eq!(
+            expr.args,
+            vec![
+                Arg::Str("A".into()),
+                Arg::Str("two words".into()),
+                num(-1.5e-3),
+                Arg::Bool(true),
+                Arg::None
+            ]
+        );
+        assert_eq!(expr.kwarg("rate"), Some(&num(1.2)));
+        assert_eq!(
+            Expr::parse(&expr.print()).unwrap(),
+            expr,
+            "{}",
+            expr.print()
+        );
+        assert_eq!(
+            Expr::parse("constant").unwrap(),
+            Expr::new("constant", vec![])
+        );
+        assert_eq!(Expr::parse(" f( ) ").unwrap().print(), "f()");
+    }
+
+    #[test]
+    fn parse_errors() {
+        for bad in [
+            "f(",
+            "f(a b)",
+            "f(k=1, 2)",
+            "f(k=1, k=2)",
+            "1 + 2",
+            "'text'",
+            "f(1))",
+            "f('open)",
+        ] {
+            assert!(Expr::parse(bad).is_err(), "{bad}");
+        }
+    }
+
+    #[test]
+    fn nesting_is_limited() {
+        let nested = |n: usize| format!("{}S{}", "neg(".repeat(n), ")".repeat(n));
+        let expr = Expr::parse(&nested(MAX_DEPTH)).unwrap();
+        assert_eq!(expr.depth(), MAX_DEPTH);
+        let err = Expr::parse(&nested(MAX_DEPTH + 1)).unwrap_err();
+        assert!(err.to_string().contains("deep"), "{err}");
+        assert!(Expr::parse(&nested(100_000)).is_err());
+        // A string read below structured calls only gets what they left over.
+        assert!(Arg::parse_at(&nested(MAX_DEPTH - 10), 10).is_ok());
+        let err = Arg::parse_at(&nested(MAX_DEPTH - 10), 11).unwrap_err();
+        assert!(err.to_string().contains("deep"), "{err}");
+    }
+
+    #[test]
+    fn yaml_structured_form_round_trips() {
+        let yaml = "head: michaelis_menten\nkwargs:\n  vmax: 10.0\n  km: 5.0\n";
+        let value: Value = serde_yaml::from_str(yaml).unwrap();
+        let expr = Expr::from_yaml(&value).unwrap();
+        assert_eq!(expr.print(), "michaelis_menten(vmax=10.0, km=5.0)");
+        assert_eq!(Expr::from_yaml(&expr.to_yaml()).unwrap(), expr);
+
+        let nested: Value =
+            serde_yaml::from_str("{head: mul, args: [2, {head: var, args: [S]}]}").unwrap();
+        assert_eq!(
+            Arg::from_yaml_rate(&nested).unwrap().to_string(),
+            "mul(2.0, var(S))"
+        );
+        let text: Value = serde_yaml::from_str("'mul(2, var(S))'").unwrap();
+        assert_eq!(
+            Arg::from_yaml_rate(&text).unwrap(),
+            Arg::from_yaml_rate(&nested).unwrap()
+        );
+        assert!(Expr::from_yaml(&serde_yaml::from_str("{args: [1]}").unwrap()).is_err());
+    }
+}
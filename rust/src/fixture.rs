@@ -0,0 +1,582 @@
+//! Fixture: shared YAML test systems readable by both Python and Rust.
+//!
+//! A fixture (see `tests/fixtures/systems/`) declares molecules, reactions,
+//! an optional compartment tree with flows, initial concentrations and sim
+//! settings.
+//! Without a `tree:` it is a single-compartment chemistry with constant rates
+//! (`ReferenceSimulatorImpl` semantics); with one it is a mass-action world
+//! (`WorldSimulatorImpl` semantics). The parity runner in `tests/parity/`
+//! loads the same file into the Python simulators and compares trajectories.
+//!
+//! ```yaml
+//! name: two_compartment
+//! molecules: [A, B]
+//! tree: {parents: [null, 0], names: [organism, cell]}
+//! reactions:
+//!   r1: {reactants: {A: 1}, products: {B: 1}, rate: 0.1, compartments: [cell]}
+//! initial:
+//!   organism: {A: 100.0}
+//! multiplicities: {cell: 1000}
+//! flows:
+//!   - {type: diffusion, name: leak, permeability: {A: 0.2}, areas: {cell: 0.5}}
+//!   - {type: membrane, name: pump, origin: cell, stoichiometry: {B: -1}, rate_constant: 0.3}
+//! sim: {steps: 1000, dt: 0.1, sample_every: 100}
+//! tolerance: {atol: 1.0e-12, rtol: 1.0e-9}
+//! ```
+
+use std::path::Path;
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use serde::de::DeserializeOwned;
+use serde::Deserialize;
+use serde_yaml::{Mapping, Value};
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::expr::Arg;
+use crate::flow::{DiffusionFlow, MembraneFlow};
+use crate::rate::RateExpr;
+use crate::reaction::{MoleculeId, RateLaw, Reaction};
+use crate::simulator::reference_step;
+use crate::tree::{CompartmentId, CompartmentTree, Topology};
+use crate::world_simulator::WorldModel;
+use crate::world_state::WorldState;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FixtureSpec {
+    name: String,
+    #[serde(default)]
+    description: String,
+    molecules: Vec<String>,
+    #[serde(default)]
+    tree: Option<TreeSpec>,
+    #[serde(default)]
+    reactions: Mapping,
+    #[serde(default)]
+    initial: Mapping,
+    #[serde(default)]
+    multiplicities: Mapping,
+    #[serde(default)]
+    flows: Vec<FlowEntry>,
+    #[serde(default)]
+    sim: SimSpec,
+    #[serde(default)]
+    tolerance: Tolerance,
+}
+
+#[derive(Debug, Deserialize)]
+struct TreeSpec {
+    parents: Vec<Option<CompartmentId>>,
+    names: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ReactionEntry {
+    #[serde(default)]
+    reactants: Mapping,
+    #[serde(default)]
+    products: Mapping,
+    #[serde(default = "default_rate")]
+    rate: f64,
+    #[serde(default)]
+    compartments: Option<Vec<String>>,
+}
+
+/// A flow, tagged by `type` as in `Flow.attributes()`.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum FlowEntry {
+    Diffusion {
+        #[serde(default)]
+        name: String,
+        permeability: Mapping,
+        #[serde(default)]
+        areas: Option<Mapping>,
+    },
+    Membrane {
+        #[serde(default)]
+        name: String,
+        origin: String,
+        stoichiometry: Mapping,
+        #[serde(default = "default_rate")]
+        rate_constant: f64,
+        #[serde(default)]
+        rate: Option<String>,
+    },
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+struct SimSpec {
+    steps: usize,
+    dt: f64,
+    sample_every: usize,
+}
+
+impl Default for SimSpec {
+    fn default() -> Self {
+        Self {
+            steps: 100,
+            dt: 1.0,
+            sample_every: 1,
+        }
+    }
+}
+
+/// Allowed divergence between two trajectories: `|a - b| <= atol + rtol * |b|`.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields, default)]
+pub struct Tolerance {
+    pub atol: f64,
+    pub rtol: f64,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Self {
+            atol: 1e-12,
+            rtol: 1e-9,
+        }
+    }
+}
+
+fn default_rate() -> f64 {
+    1.0
+}
+
+/// Deserialize every value of a mapping, keeping the YAML key order.
+fn ordered<T: DeserializeOwned>(mapping: &Mapping, what: &str) -> SimResult<Vec<(String, T)>> {
+    mapping
+        .iter()
+        .map(|(key, value)| {
+            let key = match key {
+                Value::String(s) => s.clone(),
+                other => {
+                    return Err(SimError::Value(format!(
+                        "{what}: key {other:?} is not a string"
+                    )))
+                }
+            };
+            let value = serde_yaml::from_value(value.clone())
+                .map_err(|e| SimError::Value(format!("{what} {key:?}: {e}")))?;
+            Ok((key, value))
+        })
+        .collect()
+}
+
+/// A fixture flow, resolved to molecule and compartment IDs.
+#[derive(Debug, Clone)]
+pub enum FixtureFlow {
+    Membrane(MembraneFlow),
+    Diffusion(DiffusionFlow),
+}
+
+/// A loaded fixture, resolved to molecule and compartment IDs.
+#[derive(Debug, Clone)]
+pub struct Fixture {
+    pub name: String,
+    pub description: String,
+    pub molecules: MoleculeIndex,
+    /// Compartment topology; `None` for a single-compartment chemistry.
+    pub topology: Option<Arc<Topology>>,
+    pub reactions: Vec<Reaction>,
+    /// Flat initial concentrations `[compartment * num_molecules + molecule]`.
+    pub initial: Vec<f64>,
+    pub multiplicities: Vec<f64>,
+    /// Flows, applied in order after the reactions each step.
+    pub flows: Vec<FixtureFlow>,
+    pub steps: usize,
+    pub dt: f64,
+    pub sample_every: usize,
+    pub tolerance: Tolerance,
+}
+
+impl Fixture {
+    pub fn from_yaml(text: &str) -> SimResult<Self> {
+        let spec: FixtureSpec =
+            serde_yaml::from_str(text).map_err(|e| SimError::Value(format!("fixture: {e}")))?;
+        Self::from_spec(spec)
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> SimResult<Self> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path)
+            .map_err(|e| SimError::Value(format!("{}: {e}", path.display())))?;
+        Self::from_yaml(&text).map_err(|e| SimError::Value(format!("{}: {e}", path.display())))
+    }
+
+    fn from_spec(spec: FixtureSpec) -> SimResult<Self> {
+        if spec.sim.sample_every == 0 {
+            return Err(SimError::Value("sim.sample_every must be positive".into()));
+        }
+        let molecules = MoleculeIndex::new(spec.molecules);
+        let topology = spec
+            .tree
+            .map(|t| Topology::from_parents(t.parents, t.names).map(Arc::new))
+            .transpose()?;
+        let compartment_names: Vec<String> = match &topology {
+            Some(t) => t.names().to_vec(),
+            None => Vec::new(),
+        };
+        let compartment = |name: &str| -> SimResult<CompartmentId> {
+            compartment_names
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| SimError::Key(format!("Unknown compartment: {name:?}")))
+        };
+        let side = |mapping: &Mapping, what: &str| -> SimResult<Vec<(MoleculeId, f64)>> {
+            ordered::<f64>(mapping, what)?
+                .into_iter()
+                .map(|(name, coef)| Ok((molecules.id(&name)?, coef)))
+                .collect()
+        };
+
+        let law = if topology.is_some() {
+            RateLaw::MassAction
+        } else {
+            RateLaw::Constant
+        };
+        let mut reactions = Vec::new();
+        for (name, entry) in ordered::<ReactionEntry>(&spec.reactions, "reaction")? {
+            let mut reaction = Reaction::new(
+                name.clone(),
+                side(&entry.reactants, &name)?,
+                side(&entry.products, &name)?,
+                entry.rate,
+            )
+            .with_law(law);
+            if let Some(names) = entry.compartments {
+                if topology.is_none() {
+                    return Err(SimError::Value(format!(
+                        "reaction {name:?}: compartments require a tree"
+                    )));
+                }
+                let ids = names
+                    .iter()
+                    .map(|n| compartment(n))
+                    .collect::<SimResult<_>>()?;
+                reaction = reaction.in_compartments(ids);
+            }
+            reactions.push(reaction);
+        }
+
+        let num_compartments = topology.as_ref().map_or(1, |t| t.num_compartments());
+        let n = molecules.len();
+        let mut initial = vec![0.0; num_compartments * n];
+        if topology.is_some() {
+            for (comp_name, values) in ordered::<Mapping>(&spec.initial, "initial")? {
+                let comp = compartment(&comp_name)?;
+                for (mol, value) in ordered::<f64>(&values, &comp_name)? {
+                    initial[comp * n + molecules.id(&mol)?] = value;
+                }
+            }
+        } else {
+            for (mol, value) in ordered::<f64>(&spec.initial, "initial")? {
+                initial[molecules.id(&mol)?] = value;
+            }
+        }
+
+        let mut multiplicities = vec![1.0; num_compartments];
+        for (comp_name, value) in ordered::<f64>(&spec.multiplicities, "multiplicities")? {
+            multiplicities[compartment(&comp_name)?] = value;
+        }
+
+        let mut flows = Vec::new();
+        for entry in spec.flows {
+            let Some(topology) = &topology else {
+                return Err(SimError::Value("flows require a tree".into()));
+            };
+            match entry {
+                FlowEntry::Diffusion {
+                    name,
+                    permeability,
+                    areas,
+                } => {
+                    let mut dense = vec![0.0; n];
+                    for (mol, value) in ordered::<f64>(&permeability, &name)? {
+                        dense[molecules.id(&mol)?] = value;
+                    }
+                    let mut flow = DiffusionFlow::new(name.clone(), dense);
+                    if let Some(areas) = areas {
+                        let mut dense = vec![1.0; num_compartments];
+                        for (comp, value) in ordered::<f64>(&areas, &name)? {
+                            dense[compartment(&comp)?] = value;
+                        }
+                        flow = flow.with_areas(dense);
+                    }
+                    flow.validate(topology, n)?;
+                    flows.push(FixtureFlow::Diffusion(flow));
+                }
+                FlowEntry::Membrane {
+                    name,
+                    origin,
+                    stoichiometry,
+                    rate_constant,
+                    rate,
+                } => {
+                    let mut flow = MembraneFlow::new(
+                        name.clone(),
+                        compartment(&origin)?,
+                        side(&stoichiometry, &name)?,
+                        rate_constant,
+                    );
+                    if let Some(rate) = rate {
+                        let expr = RateExpr::new(
+                            &Arg::parse(&rate)?,
+                            &MembraneFlow::symbols(&molecules),
+                            Vec::new(),
+                        )?;
+                        flow = flow.with_rate(Arc::new(expr));
+                    }
+                    flow.validate(topology, n)?;
+                    flows.push(FixtureFlow::Membrane(flow));
+                }
+            }
+        }
+
+        Ok(Self {
+            name: spec.name,
+            description: spec.description,
+            molecules,
+            topology,
+            reactions,
+            initial,
+            multiplicities,
+            flows,
+            steps: spec.sim.steps,
+            dt: spec.sim.dt,
+            sample_every: spec.sim.sample_every,
+            tolerance: spec.tolerance,
+        })
+    }
+
+    pub fn num_compartments(&self) -> usize {
+        self.topology.as_ref().map_or(1, |t| t.num_compartments())
+    }
+
+    /// Run the fixture natively, returning flat concentration samples.
+    ///
+    /// Samples are taken before steps `0, k, 2k, ...` (k = `sample_every`),
+    /// plus the final state, as in `WorldSimulatorImpl.run`.
+    pub fn run(&self) -> SimResult<Vec<Vec<f64>>> {
+        let mut conc = self.initial.clone();
+        let mut samples = Vec::with_capacity(self.steps / self.sample_every + 2);
+        let model = match &self.topology {
+            Some(topology) => Some(WorldModel::new(
+                topology.clone(),
+                self.reactions.clone(),
+                self.molecules.len(),
+            )?),
+            None => None,
+        };
+        // Only used without a tree, where every reaction has a constant rate.
+        let rates: Vec<f64> = self.reactions.iter().map(|r| r.rate(&conc)).collect();
+        for i in 0..self.steps {
+            if i % self.sample_every == 0 {
+                samples.push(conc.clone());
+            }
+            match &model {
+                Some(model) => {
+                    model.apply_reactions(&mut conc, self.dt);
+                    for flow in &self.flows {
+                        match flow {
+                            FixtureFlow::Membrane(flow) => {
+                                model.apply_flow(flow, &mut conc, &self.multiplicities, self.dt)
+                            }
+                            FixtureFlow::Diffusion(flow) => model.apply_diffusion(
+                                flow,
+                                &mut conc,
+                                &self.multiplicities,
+                                self.dt,
+                            ),
+                        }
+                    }
+                }
+                None => reference_step(&self.reactions, &rates, &mut conc, self.dt),
+            }
+        }
+        samples.push(conc);
+        Ok(samples)
+    }
+}
+
+/// A parity fixture loaded by the native loader.
+#[pyclass(name = "Fixture", module = "alienbio_sim")]
+pub struct PyFixture {
+    fixture: Fixture,
+}
+
+#[pymethods]
+impl PyFixture {
+    /// Load a fixture from a YAML file.
+    #[staticmethod]
+    fn load(path: &str) -> PyResult<Self> {
+        Ok(Self {
+            fixture: Fixture::load(path)?,
+        })
+    }
+
+    /// Load a fixture from YAML text.
+    #[staticmethod]
+    fn from_yaml(text: &str) -> PyResult<Self> {
+        Ok(Self {
+            fixture: Fixture::from_yaml(text)?,
+        })
+    }
+
+    #[getter]
+    fn name(&self) -> String {
+        self.fixture.name.clone()
+    }
+
+    /// Molecule names in ID order.
+    #[getter]
+    fn molecules(&self) -> Vec<String> {
+        self.fixture.molecules.names().to_vec()
+    }
+
+    /// Compartment names in ID order (empty for a single-compartment fixture).
+    #[getter]
+    fn compartments(&self) -> Vec<String> {
+        self.fixture
+            .topology
+            .as_ref()
+            .map(|t| t.names().to_vec())
+            .unwrap_or_default()
+    }
+
+    #[getter]
+    fn steps(&self) -> usize {
+        self.fixture.steps
+    }
+
+    #[getter]
+    fn dt(&self) -> f64 {
+        self.fixture.dt
+    }
+
+    #[getter]
+    fn sample_every(&self) -> usize {
+        self.fixture.sample_every
+    }
+
+    #[getter]
+    fn atol(&self) -> f64 {
+        self.fixture.tolerance.atol
+    }
+
+    #[getter]
+    fn rtol(&self) -> f64 {
+        self.fixture.tolerance.rtol
+    }
+
+    /// The fixture's compartment tree (None for a single-compartment fixture).
+    fn tree(&self) -> Option<CompartmentTree> {
+        self.fixture
+            .topology
+            .clone()
+            .map(CompartmentTree::from_topology)
+    }
+
+    /// Initial WorldState (requires a tree).
+    fn initial_state(&self, py: Python<'_>) -> PyResult<WorldState> {
+        let tree = self
+            .tree()
+            .ok_or_else(|| SimError::Value("fixture has no tree".into()))?;
+        Ok(WorldState::from_parts(
+            Py::new(py, tree)?,
+            self.fixture.num_compartments(),
+            self.fixture.molecules.len(),
+            self.fixture.initial.clone(),
+            self.fixture.multiplicities.clone(),
+        ))
+    }
+
+    /// Run natively: samples as `[sample][compartment][molecule]`.
+    fn run(&self) -> PyResult<Vec<Vec<Vec<f64>>>> {
+        let n = self.fixture.molecules.len().max(1);
+        Ok(self
+            .fixture
+            .run()?
+            .into_iter()
+            .map(|flat| flat.chunks(n).map(<[f64]>::to_vec).collect())
+            .collect())
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "Fixture({:?}, compartments={}, molecules={}, reactions={})",
+            self.fixture.name,
+            self.fixture.num_compartments(),
+            self.fixture.molecules.len(),
+            self.fixture.reactions.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fixtures_dir() -> std::path::PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("../tests/fixtures/systems")
+    }
+
+    #[test]
+    fn shared_fixtures_load_and_run() {
+        let mut count = 0;
+        for entry in std::fs::read_dir(fixtures_dir()).unwrap() {
+            let path = entry.unwrap().path();
+            if path.extension().and_then(|e| e.to_str()) != Some("yaml") {
+                continue;
+            }
+            let fixture = Fixture::load(&path).unwrap();
+            let samples = fixture.run().unwrap();
+            assert_eq!(
+                samples.len(),
+                fixture.steps.div_ceil(fixture.sample_every) + 1
+            );
+            assert!(samples.iter().flatten().all(|c| c.is_finite() && *c >= 0.0));
+            count += 1;
+        }
+        assert!(count > 0, "no fixtures found");
+    }
+
+    #[test]
+    fn resolves_names_to_ids() {
+        let fixture = Fixture::from_yaml(
+            "name: t\nmolecules: [A, B]\ntree: {parents: [null, 0], names: [outer, inner]}\n\
+             reactions:\n  r1: {reactants: {B: 2}, products: {A: 1}, rate: 0.5, compartments: [inner]}\n\
+             initial:\n  inner: {B: 3.0}\nmultiplicities: {inner: 10}\n",
+        )
+        .unwrap();
+        assert_eq!(fixture.reactions[0].reactants, vec![(1, 2.0)]);
+        assert_eq!(fixture.reactions[0].compartments, Some(vec![1]));
+        assert_eq!(fixture.initial, vec![0.0, 0.0, 0.0, 3.0]);
+        assert_eq!(fixture.multiplicities, vec![1.0, 10.0]);
+    }
+
+    #[test]
+    fn membrane_flows_resolve_names() {
+        let fixture = Fixture::from_yaml(
+            "name: t\nmolecules: [A, B]\ntree: {parents: [null, 0], names: [outer, inner]}\n\
+             flows:\n  - {type: membrane, origin: inner, stoichiometry: {B: -1}, rate: parent_A}\n",
+        )
+        .unwrap();
+        let [FixtureFlow::Membrane(flow)] = &fixture.flows[..] else {
+            panic!("{:?}", fixture.flows);
+        };
+        assert_eq!((flow.origin, flow.rate_constant), (1, 1.0));
+        assert_eq!(flow.stoichiometry, vec![(1, -1.0)]);
+        assert!(flow.rate.is_some());
+    }
+
+    #[test]
+    fn unknown_molecule_rejected() {
+        let err = Fixture::from_yaml("name: t\nmolecules: [A]\ninitial: {Z: 1.0}\n").unwrap_err();
+        assert_eq!(err, SimError::Key("Unknown molecule: \"Z\"".into()));
+    }
+}
@@ -0,0 +1,1255 @@
+//! Flow: native transport across compartment membranes.
+//!
+//! Native counterpart of `alienbio.bio.flow.MembraneFlow`. Each flow is
+//! anchored to an origin compartment and moves molecules across the membrane
+//! to its parent. The event rate is the flow's rate constant, optionally
+//! scaled by a compiled rate law, and each event moves the stoichiometry:
+//! positive counts move molecules into the origin, negative counts move them
+//! out into the parent.
+//!
+//! Events are counted per origin instance and move amounts, not
+//! concentrations: with per-instance volumes `V` and multiplicities `M`, an
+//! amount `a` through one origin membrane changes the origin by `a / V_o` and
+//! the parent by `a · M_o / (M_p · V_p)`, so 1e6 red blood cells exporting into
+//! plasma shift the plasma concentration by their combined amount over the
+//! plasma volume. Unit volumes and multiplicities move concentrations 1:1.
+//!
+//! A rate law reads the origin's concentrations by molecule name and the
+//! parent's as `parent_<name>`, e.g. `mul(0.5, sub(parent_glucose, glucose))`.
+//!
+//! `GeneralFlow` moves molecules between arbitrary compartments (siblings,
+//! across the tree, several legs per event) at an Expr-defined rate.
+//!
+//! `InstanceFlow` moves whole instances (multiplicity) between compartments,
+//! carrying their per-instance concentrations along.
+//!
+//! `DiffusionFlow` is passive transport down the gradient across every
+//! membrane at once, `flux = permeability × area × (parent − child)` per child
+//! instance, for all molecules in one pass over the tree's edge arrays.
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::expr::Arg;
+use crate::linalg::CsrMatrix;
+use crate::rate::RateExpr;
+use crate::reaction::MoleculeId;
+use crate::remodel::Remap;
+use crate::tree::{CompartmentId, Topology};
+
+/// Prefix of parent-side molecule names in a membrane rate law.
+pub const PARENT_PREFIX: &str = "parent_";
+
+/// Stoichiometric transport between a compartment and its parent.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MembraneFlow {
+    pub name: String,
+    pub origin: CompartmentId,
+    /// (molecule, count) moved per event; positive = into the origin.
+    pub stoichiometry: Vec<(MoleculeId, f64)>,
+    /// Events per unit time, or the scale of `rate`.
+    pub rate_constant: f64,
+    /// Rate law over the origin's concentrations followed by the parent's
+    /// (molecule `n + m` is the parent's molecule `m`).
+    pub rate: Option<Arc<RateExpr>>,
+}
+
+impl MembraneFlow {
+    pub fn new(
+        name: impl Into<String>,
+        origin: CompartmentId,
+        stoichiometry: Vec<(MoleculeId, f64)>,
+        rate_constant: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            origin,
+            stoichiometry,
+            rate_constant,
+            rate: None,
+        }
+    }
+
+    /// Scale the event rate by a compiled rate law (see `symbols`).
+    pub fn with_rate(mut self, rate: Arc<RateExpr>) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// Names a rate law can read: each molecule on the origin side, then
+    /// `parent_<name>` on the parent side.
+    pub fn symbols(molecules: &MoleculeIndex) -> MoleculeIndex {
+        let names = molecules.names();
+        MoleculeIndex::new(
+            names
+                .iter()
+                .cloned()
+                .chain(names.iter().map(|name| format!("{PARENT_PREFIX}{name}")))
+                .collect(),
+        )
+    }
+
+    /// The parent compartment, or None when the origin is the root.
+    pub fn parent(&self, topology: &Topology) -> Option<CompartmentId> {
+        topology.parent(self.origin)
+    }
+
+    /// Follow a topology change: one flow per compartment inheriting the
+    /// origin's membrane (none if it died).
+    pub fn remap(&self, remap: &Remap) -> Vec<MembraneFlow> {
+        remap
+            .targets(self.origin)
+            .into_iter()
+            .map(|origin| Self {
+                origin,
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Check molecule and compartment IDs against the simulator dimensions.
+    pub fn validate(&self, topology: &Topology, num_molecules: usize) -> SimResult<()> {
+        if self.origin >= topology.num_compartments() {
+            return Err(SimError::Value(format!(
+                "Flow {}: origin {} out of range (num_compartments={})",
+                self.name,
+                self.origin,
+                topology.num_compartments()
+            )));
+        }
+        for &(mol, _) in &self.stoichiometry {
+            if mol >= num_molecules {
+                return Err(SimError::Value(format!(
+                    "Flow {}: molecule {mol} out of range (num_molecules={num_molecules})",
+                    self.name
+                )));
+            }
+        }
+        if let Some(&mol) = self
+            .rate
+            .iter()
+            .flat_map(|r| r.variables())
+            .find(|&&m| m >= 2 * num_molecules)
+        {
+            return Err(SimError::Value(format!(
+                "Flow {}: rate reads variable {mol} out of range",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// The origin's concentrations followed by the parent's, for the rate law.
+    fn local(
+        conc: &[f64],
+        n: usize,
+        origin: CompartmentId,
+        parent: CompartmentId,
+        buf: &mut Vec<f64>,
+    ) {
+        buf.clear();
+        buf.extend_from_slice(&conc[origin * n..(origin + 1) * n]);
+        buf.extend_from_slice(&conc[parent * n..(parent + 1) * n]);
+    }
+
+    /// Events per unit time across the membrane to `parent`.
+    pub fn event_rate(
+        &self,
+        conc: &[f64],
+        n: usize,
+        parent: CompartmentId,
+        buf: &mut Vec<f64>,
+    ) -> f64 {
+        match &self.rate {
+            Some(rate) => {
+                Self::local(conc, n, self.origin, parent, buf);
+                self.rate_constant * rate.eval(buf)
+            }
+            None => self.rate_constant,
+        }
+    }
+
+    /// Move `events` events between the origin and `parent`, scaled down
+    /// uniformly if either side would go negative. `factors` are the
+    /// concentration changes of the origin and parent per unit amount (see
+    /// `membrane_factors`).
+    pub fn transfer(
+        &self,
+        conc: &mut [f64],
+        n: usize,
+        parent: CompartmentId,
+        events: f64,
+        factors: (f64, f64),
+    ) {
+        let (to_origin, to_parent) = factors;
+        let mut scale: f64 = 1.0;
+        for &(mol, count) in &self.stoichiometry {
+            let moved = events * count;
+            for (index, delta) in [
+                (self.origin * n + mol, moved * to_origin),
+                (parent * n + mol, -moved * to_parent),
+            ] {
+                if delta < 0.0 {
+                    scale = scale.min(conc[index].max(0.0) / -delta);
+                }
+            }
+        }
+        let events = events * scale;
+        for &(mol, count) in &self.stoichiometry {
+            let moved = events * count;
+            conc[self.origin * n + mol] += moved * to_origin;
+            conc[parent * n + mol] -= moved * to_parent;
+        }
+    }
+
+    /// One explicit Euler step of `dt` across the membrane to `parent`.
+    pub fn apply(
+        &self,
+        conc: &mut [f64],
+        n: usize,
+        parent: CompartmentId,
+        dt: f64,
+        factors: (f64, f64),
+    ) {
+        let events = self.event_rate(conc, n, parent, &mut Vec::new()) * dt;
+        self.transfer(conc, n, parent, events, factors);
+    }
+
+    /// `∂(event rate)/∂conc` as (flat index, derivative) pairs over the
+    /// origin and parent concentrations the rate law reads.
+    pub fn rate_partials(
+        &self,
+        conc: &[f64],
+        n: usize,
+        parent: CompartmentId,
+        out: &mut Vec<(usize, f64)>,
+    ) {
+        out.clear();
+        if let Some(rate) = &self.rate {
+            let mut buf = Vec::with_capacity(2 * n);
+            Self::local(conc, n, self.origin, parent, &mut buf);
+            out.extend(rate.derivatives().conc.iter().map(|(m, d)| {
+                let index = if *m < n {
+                    self.origin * n + m
+                } else {
+                    parent * n + (m - n)
+                };
+                (index, self.rate_constant * d.eval(&buf))
+            }));
+        }
+    }
+
+    /// `∂(event rate)/∂p` by parameter name: `rate_constant`, then the rate
+    /// law's parameters.
+    pub fn param_partials(
+        &self,
+        conc: &[f64],
+        n: usize,
+        parent: CompartmentId,
+    ) -> Vec<(String, f64)> {
+        let Some(rate) = &self.rate else {
+            return vec![("rate_constant".to_string(), 1.0)];
+        };
+        let mut buf = Vec::with_capacity(2 * n);
+        Self::local(conc, n, self.origin, parent, &mut buf);
+        let mut partials = vec![("rate_constant".to_string(), rate.eval(&buf))];
+        partials.extend(
+            rate.params()
+                .iter()
+                .zip(&rate.derivatives().params)
+                .map(|((name, _), d)| (name.clone(), self.rate_constant * d.eval(&buf))),
+        );
+        partials
+    }
+
+    /// Read a Python `MembraneFlow`, resolving stoichiometry names (or IDs)
+    /// through `molecules`. A callable `rate_fn` is not read; see `has_rate_fn`.
+    pub fn from_py(flow: &PyAny, molecules: &MoleculeIndex) -> PyResult<Self> {
+        let mut stoichiometry = Vec::new();
+        for item in flow
+            .getattr("stoichiometry")?
+            .call_method0("items")?
+            .iter()?
+        {
+            let (key, count): (&PyAny, f64) = item?.extract()?;
+            let mol = match key.extract::<MoleculeId>() {
+                Ok(id) => id,
+                Err(_) => molecules.id(key.extract::<&str>()?)?,
+            };
+            stoichiometry.push((mol, count));
+        }
+        let mut native = Self::new(
+            flow.getattr("name")?.extract::<String>()?,
+            flow.getattr("origin")?.extract()?,
+            stoichiometry,
+            flow.getattr("rate_constant")?.extract()?,
+        );
+        if let Ok(rate) = flow.getattr("rate") {
+            if !rate.is_none() {
+                let expr = RateExpr::new(
+                    &Arg::from_py(rate, true)?,
+                    &Self::symbols(molecules),
+                    Vec::new(),
+                )
+                .map_err(|e| SimError::Value(format!("Flow {}: {e}", native.name)))?;
+                native = native.with_rate(Arc::new(expr));
+            }
+        }
+        Ok(native)
+    }
+
+    /// Whether a Python flow's event rate comes from a callable `rate_fn`.
+    pub fn has_rate_fn(flow: &PyAny) -> bool {
+        flow.getattr("_rate_fn")
+            .map(|f| !f.is_none())
+            .unwrap_or(false)
+    }
+}
+
+/// Passive diffusion across every parent/child membrane of the tree.
+#[derive(Debug, Clone, PartialEq)]
+pub struct DiffusionFlow {
+    pub name: String,
+    /// Permeability of each molecule (amount per unit time, area and
+    /// concentration difference); 0 for molecules that do not cross.
+    pub permeability: Vec<f64>,
+    /// Membrane area of each compartment (its membrane to the parent);
+    /// `None` means 1.0 everywhere.
+    pub areas: Option<Vec<f64>>,
+}
+
+impl DiffusionFlow {
+    pub fn new(name: impl Into<String>, permeability: Vec<f64>) -> Self {
+        Self {
+            name: name.into(),
+            permeability,
+            areas: None,
+        }
+    }
+
+    /// Scale each compartment's membrane by its area (0 seals it).
+    pub fn with_areas(mut self, areas: Vec<f64>) -> Self {
+        self.areas = Some(areas);
+        self
+    }
+
+    fn area(&self, compartment: CompartmentId) -> f64 {
+        self.areas.as_ref().map_or(1.0, |a| a[compartment])
+    }
+
+    /// Follow a topology change; new compartments inherit their source's area.
+    pub fn remap(&self, remap: &Remap) -> DiffusionFlow {
+        Self {
+            areas: self.areas.as_ref().map(|areas| remap.inherit(areas)),
+            ..self.clone()
+        }
+    }
+
+    /// Check the permeability and area vectors against the simulator dimensions.
+    pub fn validate(&self, topology: &Topology, num_molecules: usize) -> SimResult<()> {
+        let valid = |v: &[f64]| v.iter().all(|x| x.is_finite() && *x >= 0.0);
+        if self.permeability.len() != num_molecules || !valid(&self.permeability) {
+            return Err(SimError::Value(format!(
+                "Flow {}: permeability must be {num_molecules} non-negative numbers",
+                self.name
+            )));
+        }
+        let nc = topology.num_compartments();
+        if let Some(areas) = &self.areas {
+            if areas.len() != nc || !valid(areas) {
+                return Err(SimError::Value(format!(
+                    "Flow {}: areas must be {nc} non-negative numbers",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// One explicit Euler step of `dt`, edge by edge in child order. Each
+    /// molecule moves at most the amount that equalizes both sides, so the
+    /// step neither overshoots nor drives either side negative.
+    pub fn apply(
+        &self,
+        topology: &Topology,
+        conc: &mut [f64],
+        n: usize,
+        dt: f64,
+        volumes: &[f64],
+        multiplicities: &[f64],
+    ) {
+        for (&parent, &child) in topology.edge_parents().iter().zip(topology.edge_children()) {
+            let (to_child, to_parent) = membrane_factors(child, parent, volumes, multiplicities);
+            let conductance = self.area(child) * dt;
+            if to_child == 0.0 || conductance == 0.0 {
+                continue;
+            }
+            let cap = 1.0 / (to_child + to_parent);
+            for m in 0..n {
+                let gradient = conc[parent * n + m] - conc[child * n + m];
+                let moved = gradient * (self.permeability[m] * conductance).min(cap);
+                conc[child * n + m] += moved * to_child;
+                conc[parent * n + m] -= moved * to_parent;
+            }
+        }
+    }
+
+    /// Add `dc/dt` of every membrane to `dcdt`.
+    pub fn derivatives(
+        &self,
+        topology: &Topology,
+        conc: &[f64],
+        n: usize,
+        volumes: &[f64],
+        multiplicities: &[f64],
+        dcdt: &mut [f64],
+    ) {
+        for (&parent, &child) in topology.edge_parents().iter().zip(topology.edge_children()) {
+            let (to_child, to_parent) = membrane_factors(child, parent, volumes, multiplicities);
+            let area = self.area(child);
+            for m in 0..n {
+                let flux =
+                    self.permeability[m] * area * (conc[parent * n + m] - conc[child * n + m]);
+                dcdt[child * n + m] += flux * to_child;
+                dcdt[parent * n + m] -= flux * to_parent;
+            }
+        }
+    }
+
+    /// Each permeable molecule couples its own concentration on both sides of
+    /// every membrane.
+    pub fn jacobian_pattern(
+        &self,
+        topology: &Topology,
+        n: usize,
+        pattern: &mut Vec<(usize, usize)>,
+    ) {
+        for (&parent, &child) in topology.edge_parents().iter().zip(topology.edge_children()) {
+            for m in (0..n).filter(|&m| self.permeability[m] > 0.0) {
+                let (c, p) = (child * n + m, parent * n + m);
+                pattern.extend([(c, c), (c, p), (p, c), (p, p)]);
+            }
+        }
+    }
+
+    /// Add the (constant) Jacobian entries of every membrane to `jac`.
+    pub fn jacobian(
+        &self,
+        topology: &Topology,
+        n: usize,
+        volumes: &[f64],
+        multiplicities: &[f64],
+        jac: &mut CsrMatrix,
+    ) {
+        for (&parent, &child) in topology.edge_parents().iter().zip(topology.edge_children()) {
+            let (to_child, to_parent) = membrane_factors(child, parent, volumes, multiplicities);
+            let area = self.area(child);
+            for m in (0..n).filter(|&m| self.permeability[m] > 0.0) {
+                let k = self.permeability[m] * area;
+                let (c, p) = (child * n + m, parent * n + m);
+                jac.add(c, c, -k * to_child);
+                jac.add(c, p, k * to_child);
+                jac.add(p, c, k * to_parent);
+                jac.add(p, p, -k * to_parent);
+            }
+        }
+    }
+
+    /// Read a Python `DiffusionFlow`, resolving permeability keys through
+    /// `molecules` and area keys through the topology's compartment names.
+    pub fn from_py(flow: &PyAny, molecules: &MoleculeIndex, topology: &Topology) -> PyResult<Self> {
+        let mut permeability = vec![0.0; molecules.len()];
+        for (key, value) in flow.getattr("permeability")?.downcast::<PyDict>()? {
+            permeability[molecule_key(key, molecules)?] = value.extract()?;
+        }
+        let mut native = Self::new(flow.getattr("name")?.extract::<String>()?, permeability);
+        let areas = flow.getattr("areas")?;
+        if !areas.is_none() {
+            let mut dense = vec![1.0; topology.num_compartments()];
+            for (key, value) in areas.downcast::<PyDict>()? {
+                dense[compartment_key(key, topology)?] = value.extract()?;
+            }
+            native = native.with_areas(dense);
+        }
+        native.validate(topology, molecules.len())?;
+        Ok(native)
+    }
+}
+
+/// A molecule given by ID or by name.
+pub(crate) fn molecule_key(key: &PyAny, molecules: &MoleculeIndex) -> PyResult<MoleculeId> {
+    match key.extract::<MoleculeId>() {
+        Ok(id) if id < molecules.len() => Ok(id),
+        Ok(id) => Err(SimError::Key(format!("Unknown molecule: {id}")).into()),
+        Err(_) => Ok(molecules.id(key.extract::<&str>()?)?),
+    }
+}
+
+/// A compartment given by ID or by a name no other compartment shares.
+pub(crate) fn compartment_key(key: &PyAny, topology: &Topology) -> PyResult<CompartmentId> {
+    match key.extract::<CompartmentId>() {
+        Ok(id) if id < topology.num_compartments() => Ok(id),
+        Ok(id) => Err(SimError::Key(format!("Unknown compartment: {id}")).into()),
+        Err(_) => {
+            let name = key.extract::<&str>()?;
+            let id = topology
+                .names()
+                .iter()
+                .position(|n| n == name)
+                .ok_or_else(|| SimError::Key(format!("Unknown compartment: {name:?}")))?;
+            match namesakes(topology, id) {
+                1 => Ok(id),
+                count => Err(SimError::Value(format!(
+                    "Compartment name {name:?} is ambiguous ({count} compartments share it); \
+                     pass a compartment ID"
+                ))
+                .into()),
+            }
+        }
+    }
+}
+
+/// How many compartments carry `compartment`'s name, itself included.
+fn namesakes(topology: &Topology, compartment: CompartmentId) -> usize {
+    let name = topology.name(compartment);
+    topology.names().iter().filter(|n| *n == name).count()
+}
+
+/// One leg of a `GeneralFlow` event: molecules moved between two compartments.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Move {
+    pub from: CompartmentId,
+    pub to: CompartmentId,
+    /// (molecule, amount) moved per event.
+    pub molecules: Vec<(MoleculeId, f64)>,
+}
+
+/// Stoichiometric transfer between arbitrary compartments: siblings,
+/// across the tree, or along several edges at once.
+///
+/// Each event moves every leg's amounts. Unlike `MembraneFlow`, events count
+/// the whole population: an amount `a` changes a compartment's per-instance
+/// concentration by `a / (V · M)`. The rate law reads `<compartment>.<molecule>`
+/// for any compartment and bare molecule names for the origin.
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneralFlow {
+    pub name: String,
+    pub origin: CompartmentId,
+    pub moves: Vec<Move>,
+    /// Events per unit time, or the scale of `rate`.
+    pub rate_constant: f64,
+    /// Rate law over every compartment's concentrations (flat index
+    /// `c * n + m`), then the origin's (index `num_compartments * n + m`).
+    pub rate: Option<Arc<RateExpr>>,
+}
+
+impl GeneralFlow {
+    pub fn new(
+        name: impl Into<String>,
+        origin: CompartmentId,
+        moves: Vec<Move>,
+        rate_constant: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            origin,
+            moves,
+            rate_constant,
+            rate: None,
+        }
+    }
+
+    /// Scale the event rate by a compiled rate law (see `symbols`).
+    pub fn with_rate(mut self, rate: Arc<RateExpr>) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// Names a rate law can read: `<compartment>.<molecule>` for every
+    /// compartment, then the bare molecule names for the origin.
+    pub fn symbols(molecules: &MoleculeIndex, topology: &Topology) -> MoleculeIndex {
+        let names = molecules.names();
+        MoleculeIndex::new(
+            topology
+                .names()
+                .iter()
+                .flat_map(|comp| names.iter().map(move |mol| format!("{comp}.{mol}")))
+                .chain(names.iter().cloned())
+                .collect(),
+        )
+    }
+
+    /// Compile a Python rate law against `symbols`. Reading
+    /// `<compartment>.<molecule>` through a name several compartments share
+    /// is an error, since the name cannot say which one is meant.
+    pub(crate) fn rate_from_py(
+        flow: &str,
+        rate: &PyAny,
+        molecules: &MoleculeIndex,
+        topology: &Topology,
+    ) -> PyResult<Arc<RateExpr>> {
+        let expr = RateExpr::new(
+            &Arg::from_py(rate, true)?,
+            &Self::symbols(molecules, topology),
+            Vec::new(),
+        )
+        .map_err(|e| SimError::Value(format!("Flow {flow}: {e}")))?;
+        let n = molecules.len();
+        let qualified = topology.num_compartments() * n;
+        for &var in expr.variables().iter().filter(|&&var| var < qualified) {
+            let (comp, mol) = (var / n, var % n);
+            let count = namesakes(topology, comp);
+            if count > 1 {
+                let name = topology.name(comp);
+                return Err(SimError::Value(format!(
+                    "Flow {flow}: rate reads {name}.{}, but {count} compartments are named \
+                     {name:?}; give them distinct names",
+                    molecules.names()[mol]
+                ))
+                .into());
+            }
+        }
+        Ok(Arc::new(expr))
+    }
+
+    /// Follow a topology change; `None` if a compartment it moves between or
+    /// reads has died.
+    pub fn remap(&self, remap: &Remap, n: usize) -> Option<GeneralFlow> {
+        let moves = self
+            .moves
+            .iter()
+            .map(|leg| {
+                Some(Move {
+                    from: remap.map[leg.from]?,
+                    to: remap.map[leg.to]?,
+                    molecules: leg.molecules.clone(),
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+        let rate = match &self.rate {
+            Some(rate) => Some(Arc::new(rate.map_variables(|v| remap.index(v, n))?)),
+            None => None,
+        };
+        let origin = remap.map[self.origin]?;
+        // Two compartments fused into one leave nothing to move between.
+        moves.iter().all(|leg| leg.from != leg.to).then(|| Self {
+            name: self.name.clone(),
+            origin,
+            moves,
+            rate_constant: self.rate_constant,
+            rate,
+        })
+    }
+
+    /// Check compartment and molecule IDs against the simulator dimensions.
+    pub fn validate(&self, topology: &Topology, num_molecules: usize) -> SimResult<()> {
+        let nc = topology.num_compartments();
+        let compartments = self.moves.iter().flat_map(|m| [m.from, m.to]);
+        if let Some(comp) = std::iter::once(self.origin)
+            .chain(compartments)
+            .find(|&c| c >= nc)
+        {
+            return Err(SimError::Value(format!(
+                "Flow {}: compartment {comp} out of range (num_compartments={nc})",
+                self.name
+            )));
+        }
+        for leg in &self.moves {
+            if leg.from == leg.to {
+                return Err(SimError::Value(format!(
+                    "Flow {}: move from compartment {} to itself",
+                    self.name, leg.from
+                )));
+            }
+            if let Some(&(mol, _)) = leg.molecules.iter().find(|(m, _)| *m >= num_molecules) {
+                return Err(SimError::Value(format!(
+                    "Flow {}: molecule {mol} out of range (num_molecules={num_molecules})",
+                    self.name
+                )));
+            }
+        }
+        if let Some(&var) = self
+            .rate
+            .iter()
+            .flat_map(|r| r.variables())
+            .find(|&&v| v >= (nc + 1) * num_molecules)
+        {
+            return Err(SimError::Value(format!(
+                "Flow {}: rate reads variable {var} out of range",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Every compartment's concentrations followed by the origin's.
+    fn local(&self, conc: &[f64], n: usize, buf: &mut Vec<f64>) {
+        buf.clear();
+        buf.extend_from_slice(conc);
+        buf.extend_from_slice(&conc[self.origin * n..(self.origin + 1) * n]);
+    }
+
+    /// Flat index of rate-law variable `var`.
+    fn flat(&self, var: usize, n: usize, size: usize) -> usize {
+        if var < size {
+            var
+        } else {
+            self.origin * n + (var - size)
+        }
+    }
+
+    /// Events per unit time.
+    pub fn event_rate(&self, conc: &[f64], n: usize, buf: &mut Vec<f64>) -> f64 {
+        match &self.rate {
+            Some(rate) => {
+                self.local(conc, n, buf);
+                self.rate_constant * rate.eval(buf)
+            }
+            None => self.rate_constant,
+        }
+    }
+
+    /// Concentration change per event as (flat index, change) pairs, merged
+    /// per index; empty while any compartment involved has no instances.
+    pub fn deltas(&self, n: usize, volumes: &[f64], multiplicities: &[f64]) -> Vec<(usize, f64)> {
+        let per_amount = |c: CompartmentId| {
+            let size = volumes[c] * multiplicities[c];
+            (size > 0.0).then(|| 1.0 / size)
+        };
+        let mut deltas = Vec::new();
+        for leg in &self.moves {
+            let (Some(from), Some(to)) = (per_amount(leg.from), per_amount(leg.to)) else {
+                return Vec::new();
+            };
+            for &(mol, amount) in &leg.molecules {
+                deltas.push((leg.from * n + mol, -amount * from));
+                deltas.push((leg.to * n + mol, amount * to));
+            }
+        }
+        deltas.sort_by_key(|&(i, _)| i);
+        deltas.dedup_by(|next, kept| {
+            let same = next.0 == kept.0;
+            if same {
+                kept.1 += next.1;
+            }
+            same
+        });
+        deltas
+    }
+
+    /// Move `events` events, scaled down uniformly if any compartment would
+    /// go negative.
+    pub fn transfer(&self, conc: &mut [f64], events: f64, deltas: &[(usize, f64)]) {
+        let mut scale: f64 = 1.0;
+        for &(index, delta) in deltas {
+            let change = events * delta;
+            if change < 0.0 {
+                scale = scale.min(conc[index].max(0.0) / -change);
+            }
+        }
+        for &(index, delta) in deltas {
+            conc[index] += events * scale * delta;
+        }
+    }
+
+    /// One explicit Euler step of `dt`.
+    pub fn apply(
+        &self,
+        conc: &mut [f64],
+        n: usize,
+        dt: f64,
+        volumes: &[f64],
+        multiplicities: &[f64],
+    ) {
+        let deltas = self.deltas(n, volumes, multiplicities);
+        if !deltas.is_empty() {
+            let events = self.event_rate(conc, n, &mut Vec::new()) * dt;
+            self.transfer(conc, events, &deltas);
+        }
+    }
+
+    /// `∂(event rate)/∂conc` as (flat index, derivative) pairs.
+    pub fn rate_partials(&self, conc: &[f64], n: usize, out: &mut Vec<(usize, f64)>) {
+        out.clear();
+        if let Some(rate) = &self.rate {
+            let mut buf = Vec::with_capacity(conc.len() + n);
+            self.local(conc, n, &mut buf);
+            out.extend(rate.derivatives().conc.iter().map(|(v, d)| {
+                (
+                    self.flat(*v, n, conc.len()),
+                    self.rate_constant * d.eval(&buf),
+                )
+            }));
+        }
+    }
+
+    /// Flat indices the rate law reads.
+    pub fn rate_inputs(&self, n: usize, size: usize) -> Vec<usize> {
+        self.rate
+            .iter()
+            .flat_map(|r| r.variables())
+            .map(|&v| self.flat(v, n, size))
+            .collect()
+    }
+
+    /// Read a Python `GeneralFlow` with declarative `moves`, resolving
+    /// compartments and molecules by ID or name.
+    pub fn from_py(flow: &PyAny, molecules: &MoleculeIndex, topology: &Topology) -> PyResult<Self> {
+        let name = flow.getattr("name")?.extract::<String>()?;
+        let mut moves = Vec::new();
+        for leg in flow.getattr("moves")?.iter()? {
+            let leg = leg?;
+            let mut amounts = Vec::new();
+            for (key, value) in leg.get_item("molecules")?.downcast::<PyDict>()? {
+                amounts.push((molecule_key(key, molecules)?, value.extract()?));
+            }
+            moves.push(Move {
+                from: compartment_key(leg.get_item("from")?, topology)?,
+                to: compartment_key(leg.get_item("to")?, topology)?,
+                molecules: amounts,
+            });
+        }
+        let origin = compartment_key(flow.getattr("origin")?, topology)?;
+        let mut native = Self::new(
+            name,
+            origin,
+            moves,
+            flow.getattr("rate_constant")?.extract()?,
+        );
+        let rate = flow.getattr("rate")?;
+        if !rate.is_none() {
+            let expr = Self::rate_from_py(&native.name, rate, molecules, topology)?;
+            native = native.with_rate(expr);
+        }
+        native.validate(topology, molecules.len())?;
+        Ok(native)
+    }
+
+    /// Whether a Python flow is a declarative `GeneralFlow` (has `moves`).
+    pub fn is_declarative(flow: &PyAny) -> bool {
+        flow.getattr("moves")
+            .and_then(|m| m.len())
+            .map(|len| len > 0)
+            .unwrap_or(false)
+    }
+}
+
+/// Migration of whole instances from `origin` to `target`, carrying their
+/// per-instance contents (circulating cells, migrating organisms).
+///
+/// Each origin instance leaves at `rate_constant × rate` per unit time, so a
+/// step of `dt` moves the fraction `1 − exp(−rate · dt)` of the origin's
+/// instances; the origin's per-instance concentrations are unchanged and the
+/// target's become the amount-weighted mix of both populations. The rate law
+/// reads the same names as a `GeneralFlow`'s.
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstanceFlow {
+    pub name: String,
+    pub origin: CompartmentId,
+    pub target: CompartmentId,
+    /// Per-instance departure rate, or the scale of `rate`.
+    pub rate_constant: f64,
+    pub rate: Option<Arc<RateExpr>>,
+}
+
+impl InstanceFlow {
+    pub fn new(
+        name: impl Into<String>,
+        origin: CompartmentId,
+        target: CompartmentId,
+        rate_constant: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            origin,
+            target,
+            rate_constant,
+            rate: None,
+        }
+    }
+
+    /// Scale the departure rate by a compiled rate law (see `GeneralFlow::symbols`).
+    pub fn with_rate(mut self, rate: Arc<RateExpr>) -> Self {
+        self.rate = Some(rate);
+        self
+    }
+
+    /// Follow a topology change; `None` if either end or a compartment its
+    /// rate reads has died, or both ends fused.
+    pub fn remap(&self, remap: &Remap, n: usize) -> Option<InstanceFlow> {
+        let (origin, target) = (remap.map[self.origin]?, remap.map[self.target]?);
+        let rate = match &self.rate {
+            Some(rate) => Some(Arc::new(rate.map_variables(|v| remap.index(v, n))?)),
+            None => None,
+        };
+        (origin != target).then(|| Self {
+            name: self.name.clone(),
+            origin,
+            target,
+            rate_constant: self.rate_constant,
+            rate,
+        })
+    }
+
+    /// Check compartment IDs against the topology.
+    pub fn validate(&self, topology: &Topology, num_molecules: usize) -> SimResult<()> {
+        let nc = topology.num_compartments();
+        if self.origin >= nc || self.target >= nc || self.origin == self.target {
+            return Err(SimError::Value(format!(
+                "Flow {}: invalid transfer {} -> {} (num_compartments={nc})",
+                self.name, self.origin, self.target
+            )));
+        }
+        if let Some(&var) = self
+            .rate
+            .iter()
+            .flat_map(|r| r.variables())
+            .find(|&&v| v >= (nc + 1) * num_molecules)
+        {
+            return Err(SimError::Value(format!(
+                "Flow {}: rate reads variable {var} out of range",
+                self.name
+            )));
+        }
+        Ok(())
+    }
+
+    /// Departure rate per origin instance.
+    pub fn departure_rate(&self, conc: &[f64], n: usize) -> f64 {
+        match &self.rate {
+            Some(rate) => {
+                let mut buf = conc.to_vec();
+                buf.extend_from_slice(&conc[self.origin * n..(self.origin + 1) * n]);
+                self.rate_constant * rate.eval(&buf)
+            }
+            None => self.rate_constant,
+        }
+    }
+
+    /// Move instances for one step of `dt`, returning how many moved.
+    pub fn apply(
+        &self,
+        conc: &mut [f64],
+        multiplicities: &mut [f64],
+        n: usize,
+        dt: f64,
+        volumes: &[f64],
+    ) -> f64 {
+        let available = multiplicities[self.origin];
+        let rate = self.departure_rate(conc, n);
+        if available <= 0.0 || rate.is_nan() || rate <= 0.0 {
+            return 0.0;
+        }
+        let moved = available * -(-rate * dt).exp_m1();
+        let (from, to) = (self.origin, self.target);
+        let before = multiplicities[to];
+        let after = before + moved;
+        // Amount-weighted mix of the resident and arriving instances.
+        let (resident, arriving) = (
+            before * volumes[to] / (after * volumes[to]),
+            moved * volumes[from] / (after * volumes[to]),
+        );
+        for m in 0..n {
+            conc[to * n + m] = conc[to * n + m] * resident + conc[from * n + m] * arriving;
+        }
+        multiplicities[from] = available - moved;
+        multiplicities[to] = after;
+        moved
+    }
+
+    /// Read a Python `InstanceFlow`, resolving compartments by ID or name.
+    pub fn from_py(flow: &PyAny, molecules: &MoleculeIndex, topology: &Topology) -> PyResult<Self> {
+        let mut native = Self::new(
+            flow.getattr("name")?.extract::<String>()?,
+            compartment_key(flow.getattr("origin")?, topology)?,
+            compartment_key(flow.getattr("target")?, topology)?,
+            flow.getattr("rate_constant")?.extract()?,
+        );
+        let rate = flow.getattr("rate")?;
+        if !rate.is_none() {
+            let expr = GeneralFlow::rate_from_py(&native.name, rate, molecules, topology)?;
+            native = native.with_rate(expr);
+        }
+        native.validate(topology, molecules.len())?;
+        Ok(native)
+    }
+}
+
+/// Concentration change of the origin and of its parent per unit amount moved
+/// through one origin instance's membrane: `1 / V_o` and `M_o / (M_p · V_p)`.
+/// Nothing moves while either side has no instances.
+pub fn membrane_factors(
+    origin: CompartmentId,
+    parent: CompartmentId,
+    volumes: &[f64],
+    multiplicities: &[f64],
+) -> (f64, f64) {
+    let (m_origin, m_parent) = (multiplicities[origin], multiplicities[parent]);
+    if m_origin <= 0.0 || m_parent <= 0.0 {
+        return (0.0, 0.0);
+    }
+    (
+        1.0 / volumes[origin],
+        m_origin / (m_parent * volumes[parent]),
+    )
+}
+
+/// Total amount of each molecule, `Σ concentration × volume × multiplicity`
+/// over compartments.
+pub fn total_amounts(conc: &[f64], n: usize, volumes: &[f64], multiplicities: &[f64]) -> Vec<f64> {
+    let mut totals = vec![0.0; n];
+    for (comp, slice) in conc.chunks(n.max(1)).enumerate() {
+        let size = volumes[comp] * multiplicities[comp];
+        for (total, &c) in totals.iter_mut().zip(slice) {
+            *total += c * size;
+        }
+    }
+    totals
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Central difference of `f` at `x` along coordinate `i`.
+    fn central(f: impl Fn(&[f64]) -> f64, x: &[f64], i: usize) -> f64 {
+        let h = 1e-6 * x[i].abs().max(1.0);
+        let (mut up, mut down) = (x.to_vec(), x.to_vec());
+        up[i] += h;
+        down[i] -= h;
+        (f(&up) - f(&down)) / (2.0 * h)
+    }
+
+    fn topology() -> Topology {
+        Topology::from_parents(vec![None, Some(0)], vec!["organism".into(), "cell".into()]).unwrap()
+    }
+
+    fn molecules() -> MoleculeIndex {
+        MoleculeIndex::new(vec!["Na".into(), "K".into(), "glucose".into()])
+    }
+
+    #[test]
+    fn sign_convention_and_clamping() {
+        let topo = topology();
+        // 3 Na out, 2 K in per event.
+        let pump = MembraneFlow::new("pump", 1, vec![(0, -3.0), (1, 2.0)], 1.0);
+        let parent = pump.parent(&topo).unwrap();
+        let mut conc = vec![0.0, 10.0, 0.0, 6.0, 0.0, 0.0];
+        pump.apply(&mut conc, 3, parent, 0.5, (1.0, 1.0));
+        assert_eq!(conc, vec![1.5, 9.0, 0.0, 4.5, 1.0, 0.0]);
+
+        // 10 events wanted but only 4.5 Na inside: 1.5 events happen.
+        pump.apply(&mut conc, 3, parent, 10.0, (1.0, 1.0));
+        assert_eq!(conc, vec![6.0, 6.0, 0.0, 0.0, 4.0, 0.0]);
+        assert!(conc.iter().all(|&c| c >= 0.0));
+
+        // At the root there is no membrane.
+        let root = MembraneFlow::new("root", 0, vec![(0, 1.0)], 1.0);
+        assert_eq!(root.parent(&topo), None);
+    }
+
+    #[test]
+    fn amounts_convert_through_volume_and_multiplicity() {
+        // 1e6 cells of volume 1e-3 inside 2 units of plasma.
+        let (volumes, multiplicities) = ([2.0, 1e-3], [1.0, 1e6]);
+        let factors = membrane_factors(1, 0, &volumes, &multiplicities);
+        assert_eq!(factors, (1e3, 5e5));
+        assert_eq!(membrane_factors(1, 0, &volumes, &[1.0, 0.0]), (0.0, 0.0));
+
+        // Each cell exports 1e-4 of Na: its concentration drops by 0.1 and
+        // plasma gains 1e6 * 1e-4 / 2 = 50.
+        let export = MembraneFlow::new("export", 1, vec![(0, -1.0)], 1e-4);
+        let mut conc = vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0];
+        let before = total_amounts(&conc, 3, &volumes, &multiplicities);
+        export.apply(&mut conc, 3, 0, 1.0, factors);
+        assert!((conc[3] - 0.9).abs() < 1e-12 && (conc[0] - 50.0).abs() < 1e-9);
+        let after = total_amounts(&conc, 3, &volumes, &multiplicities);
+        assert!(
+            (after[0] - before[0]).abs() < 1e-9,
+            "{before:?} vs {after:?}"
+        );
+
+        // Clamping works on amounts: the cells hold 0.9 * 1e-3 each.
+        export.apply(&mut conc, 3, 0, 100.0, factors);
+        assert!(conc[3].abs() < 1e-12 && (conc[0] - 500.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn diffusion_equalizes_without_overshoot() {
+        // organism <- cell <- vesicle, with 4 cells per organism.
+        let topo = Topology::from_parents(
+            vec![None, Some(0), Some(1)],
+            vec!["organism".into(), "cell".into(), "vesicle".into()],
+        )
+        .unwrap();
+        let (volumes, multiplicities) = ([1.0; 3], [1.0, 4.0, 4.0]);
+        let flow = DiffusionFlow::new("leak", vec![0.5, 0.0, 2.0]).with_areas(vec![1.0, 1.0, 0.0]);
+        flow.validate(&topo, 3).unwrap();
+        assert!(DiffusionFlow::new("bad", vec![1.0])
+            .validate(&topo, 3)
+            .is_err());
+
+        let mut conc = vec![10.0, 1.0, 10.0, 0.0, 1.0, 0.0, 5.0, 0.0, 0.0];
+        let mut dcdt = vec![0.0; 9];
+        flow.derivatives(&topo, &conc, 3, &volumes, &multiplicities, &mut dcdt);
+        // Per cell: 0.5 * (10 - 0) into the cell; the organism loses 4x that.
+        assert_eq!(dcdt, vec![-20.0, 0.0, -80.0, 5.0, 0.0, 20.0, 0.0, 0.0, 0.0]);
+
+        let mut jac = CsrMatrix::from_pattern(9, {
+            let mut pattern = Vec::new();
+            flow.jacobian_pattern(&topo, 3, &mut pattern);
+            pattern
+        });
+        flow.jacobian(&topo, 3, &volumes, &multiplicities, &mut jac);
+        assert_eq!(
+            (jac.get(3, 0), jac.get(3, 3), jac.get(0, 3)),
+            (0.5, -0.5, 2.0)
+        );
+
+        // A huge step stops at equilibrium: 10 = c_o + 4 c_cell with c_o = c_cell.
+        flow.apply(&topo, &mut conc, 3, 100.0, &volumes, &multiplicities);
+        assert!((conc[0] - 2.0).abs() < 1e-12 && (conc[3] - 2.0).abs() < 1e-12);
+        assert_eq!((conc[1], conc[4], conc[6]), (1.0, 1.0, 5.0));
+        let totals = total_amounts(&conc, 3, &volumes, &multiplicities);
+        assert!((totals[0] - 30.0).abs() < 1e-12, "{totals:?}");
+    }
+
+    #[test]
+    fn general_flow_moves_between_any_compartments() {
+        // organism <- {liver, kidney}; 10 kidney instances.
+        let topo = Topology::from_parents(
+            vec![None, Some(0), Some(0)],
+            vec!["organism".into(), "liver".into(), "kidney".into()],
+        )
+        .unwrap();
+        let (volumes, multiplicities) = ([1.0, 2.0, 0.5], [1.0, 1.0, 10.0]);
+        let symbols = GeneralFlow::symbols(&molecules(), &topo);
+        assert_eq!(symbols.get("kidney.K"), Some(7));
+        assert_eq!(symbols.get("glucose"), Some(11));
+        // Per event: 2 glucose liver -> kidney and 1 Na kidney -> organism.
+        let law = Arg::parse("mul(k, glucose, kidney.Na)").unwrap();
+        let rate = RateExpr::new(&law, &symbols, vec![("k".into(), 0.5)]).unwrap();
+        let flow = GeneralFlow::new(
+            "shuttle",
+            1,
+            vec![
+                Move {
+                    from: 1,
+                    to: 2,
+                    molecules: vec![(2, 2.0)],
+                },
+                Move {
+                    from: 2,
+                    to: 0,
+                    molecules: vec![(0, 1.0)],
+                },
+            ],
+            1.0,
+        )
+        .with_rate(Arc::new(rate));
+        flow.validate(&topo, 3).unwrap();
+
+        let mut conc = vec![0.0, 0.0, 0.0, 0.0, 0.0, 4.0, 1.0, 0.0, 0.0];
+        assert_eq!(flow.event_rate(&conc, 3, &mut Vec::new()), 2.0);
+        let deltas = flow.deltas(3, &volumes, &multiplicities);
+        assert_eq!(deltas, vec![(0, 1.0), (5, -1.0), (6, -0.2), (8, 0.4)]);
+        let before = total_amounts(&conc, 3, &volumes, &multiplicities);
+        flow.apply(&mut conc, 3, 0.25, &volumes, &multiplicities);
+        assert_eq!(conc, vec![0.5, 0.0, 0.0, 0.0, 0.0, 3.5, 0.9, 0.0, 0.2]);
+        let after = total_amounts(&conc, 3, &volumes, &multiplicities);
+        for (a, b) in after.iter().zip(&before) {
+            assert!((a - b).abs() < 1e-12);
+        }
+
+        // Liver glucose (3.5) limits a large step to 3.5 events.
+        flow.apply(&mut conc, 3, 100.0, &volumes, &multiplicities);
+        let expected = [4.0, 0.0, 0.0, 0.0, 0.0, 0.0, 0.2, 0.0, 1.6];
+        for (c, e) in conc.iter().zip(expected) {
+            assert!((c - e).abs() < 1e-12, "{conc:?}");
+        }
+
+        let mut partials = Vec::new();
+        flow.rate_partials(&conc, 3, &mut partials);
+        partials.sort_by_key(|&(i, _)| i);
+        assert_eq!(
+            partials.iter().map(|&(i, _)| i).collect::<Vec<_>>(),
+            vec![5, 6]
+        );
+
+        let looped = GeneralFlow::new(
+            "loop",
+            1,
+            vec![Move {
+                from: 1,
+                to: 1,
+                molecules: vec![(0, 1.0)],
+            }],
+            1.0,
+        );
+        assert!(looped.validate(&topo, 3).is_err());
+    }
+
+    #[test]
+    fn instance_flow_carries_contents() {
+        let topo = Topology::from_parents(
+            vec![None, Some(0), Some(0)],
+            vec!["body".into(), "artery".into(), "vein".into()],
+        )
+        .unwrap();
+        let volumes = [1.0, 2.0, 1.0];
+        let flow = InstanceFlow::new("circulate", 1, 2, 2.0f64.ln());
+        flow.validate(&topo, 1).unwrap();
+        assert!(InstanceFlow::new("self", 1, 1, 1.0)
+            .validate(&topo, 1)
+            .is_err());
+
+        // Half of the 100 arterial cells leave in one unit of time.
+        let (mut conc, mut mult) = (vec![0.0, 3.0, 1.0], vec![1.0, 100.0, 50.0]);
+        let before = total_amounts(&conc, 1, &volumes, &mult);
+        let moved = flow.apply(&mut conc, &mut mult, 1, 1.0, &volumes);
+        assert!((moved - 50.0).abs() < 1e-9);
+        assert!((mult[1] - 50.0).abs() < 1e-9 && (mult[2] - 100.0).abs() < 1e-9);
+        assert_eq!(conc[1], 3.0);
+        // 50 veins at 1.0 plus 50 cells carrying 3.0 * 2 volume each.
+        assert!((conc[2] - (50.0 + 300.0) / 100.0).abs() < 1e-9);
+        let after = total_amounts(&conc, 1, &volumes, &mult);
+        assert!((after[0] - before[0]).abs() < 1e-9);
+
+        // Nothing left to move.
+        let (mut conc, mut mult) = (vec![0.0, 3.0, 1.0], vec![1.0, 0.0, 50.0]);
+        assert_eq!(flow.apply(&mut conc, &mut mult, 1, 1.0, &volumes), 0.0);
+        assert_eq!(conc, vec![0.0, 3.0, 1.0]);
+    }
+
+    #[test]
+    fn compiled_rate_reads_both_sides() {
+        let topo = topology();
+        let symbols = MembraneFlow::symbols(&molecules());
+        let law = Arg::parse("mul(k, sub(parent_glucose, glucose))").unwrap();
+        let rate = RateExpr::new(&law, &symbols, vec![("k".into(), 0.5)]).unwrap();
+        let flow = MembraneFlow::new("glut", 1, vec![(2, 1.0)], 2.0).with_rate(Arc::new(rate));
+        flow.validate(&topo, 3).unwrap();
+
+        let mut conc = vec![0.0, 0.0, 10.0, 0.0, 0.0, 2.0];
+        assert_eq!(flow.event_rate(&conc, 3, 0, &mut Vec::new()), 8.0);
+        flow.apply(&mut conc, 3, 0, 0.25, (1.0, 1.0));
+        assert_eq!((conc[2], conc[5]), (8.0, 4.0));
+
+        let mut partials = Vec::new();
+        flow.rate_partials(&conc, 3, 0, &mut partials);
+        partials.sort_by_key(|&(i, _)| i);
+        assert_eq!(partials, vec![(2, 1.0), (5, -1.0)]);
+        for &(index, d) in &partials {
+            let approx = central(|c| flow.event_rate(c, 3, 0, &mut Vec::new()), &conc, index);
+            assert!((approx - d).abs() < 1e-6, "{index}: {approx} vs {d}");
+        }
+        assert_eq!(
+            flow.param_partials(&conc, 3, 0),
+            vec![("rate_constant".to_string(), 2.0), ("k".to_string(), 8.0)]
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Growth: multiplicity dynamics from birth and death rate laws.
+//!
+//! A compartment's multiplicity (its number of instances) changes at the
+//! per-instance birth rate minus the death rate, both rate laws over the
+//! compartment's own concentrations. Over a step of `dt` the multiplicity is
+//! scaled by `exp((birth − death) · dt)`, which never drives it negative.
+//! New instances share the existing contents, so births dilute per-instance
+//! concentrations by `exp(−birth · dt)` and conserve amounts; instances that
+//! die take their contents with them, leaving concentrations unchanged.
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::expr::Arg;
+use crate::flow;
+use crate::rate::RateExpr;
+use crate::remodel::Remap;
+use crate::tree::{CompartmentId, Topology};
+
+/// Birth and death laws of one compartment's instances.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Growth {
+    pub compartment: CompartmentId,
+    /// Births per instance per unit time; a negative value counts as deaths.
+    pub birth: Option<Arc<RateExpr>>,
+    /// Deaths per instance per unit time.
+    pub death: Option<Arc<RateExpr>>,
+}
+
+impl Growth {
+    pub fn new(compartment: CompartmentId) -> Self {
+        Self {
+            compartment,
+            birth: None,
+            death: None,
+        }
+    }
+
+    pub fn with_birth(mut self, birth: Arc<RateExpr>) -> Self {
+        self.birth = Some(birth);
+        self
+    }
+
+    pub fn with_death(mut self, death: Arc<RateExpr>) -> Self {
+        self.death = Some(death);
+        self
+    }
+
+    /// Check the compartment and the molecules the laws read.
+    pub fn validate(&self, topology: &Topology, num_molecules: usize) -> SimResult<()> {
+        topology.check(self.compartment)?;
+        let laws = self.birth.iter().chain(&self.death);
+        if let Some(&var) = laws
+            .flat_map(|law| law.variables())
+            .find(|&&v| v >= num_molecules)
+        {
+            return Err(SimError::Value(format!(
+                "Growth of {}: law reads molecule {var} out of range",
+                topology.name(self.compartment)
+            )));
+        }
+        Ok(())
+    }
+
+    /// (birth, death) rates per instance from the compartment's
+    /// concentrations; negative births are moved to deaths.
+    pub fn rates(&self, conc: &[f64], n: usize) -> (f64, f64) {
+        let own = &conc[self.compartment * n..(self.compartment + 1) * n];
+        let eval = |law: &Option<Arc<RateExpr>>| law.as_ref().map_or(0.0, |l| l.eval(own));
+        let (birth, death) = (eval(&self.birth), eval(&self.death));
+        (birth.max(0.0), death.max(0.0) + (-birth).max(0.0))
+    }
+
+    /// Grow or shrink the population over `dt`, diluting on births.
+    pub fn apply(&self, conc: &mut [f64], multiplicities: &mut [f64], n: usize, dt: f64) {
+        let instances = multiplicities[self.compartment];
+        let (birth, death) = self.rates(conc, n);
+        if instances <= 0.0 || !(birth.is_finite() && death.is_finite()) {
+            return;
+        }
+        multiplicities[self.compartment] = instances * ((birth - death) * dt).exp();
+        let dilution = (-birth * dt).exp();
+        for c in &mut conc[self.compartment * n..(self.compartment + 1) * n] {
+            *c *= dilution;
+        }
+    }
+
+    /// Follow a topology change: copies made by division grow alike.
+    pub fn remap(&self, remap: &Remap) -> Vec<Growth> {
+        remap
+            .targets(self.compartment)
+            .into_iter()
+            .map(|compartment| Self {
+                compartment,
+                ..self.clone()
+            })
+            .collect()
+    }
+
+    /// Read `{compartment: law}` growth laws from Python. A law is a rate
+    /// Expr (net births, negative for deaths; a string or a structured
+    /// `{"head": ..}` dict) or `{"birth": .., "death": ..}`;
+    /// compartments are IDs or names and laws read molecule names.
+    pub fn from_py(
+        growth: &PyDict,
+        molecules: &MoleculeIndex,
+        topology: &Topology,
+    ) -> PyResult<Vec<Growth>> {
+        let compile = |law: &PyAny| -> PyResult<Arc<RateExpr>> {
+            let expr = RateExpr::new(&Arg::from_py(law, true)?, molecules, Vec::new())?;
+            Ok(Arc::new(expr))
+        };
+        let mut laws = Vec::with_capacity(growth.len());
+        for (key, law) in growth.iter() {
+            let mut native = Growth::new(flow::compartment_key(key, topology)?);
+            // A structured Expr is a dict too; only other dicts name parts.
+            match law
+                .downcast::<PyDict>()
+                .ok()
+                .filter(|d| !d.contains("head").unwrap_or(false))
+            {
+                Some(parts) => {
+                    for (part, law) in parts.iter() {
+                        match part.extract::<&str>()? {
+                            "birth" => native = native.with_birth(compile(law)?),
+                            "death" => native = native.with_death(compile(law)?),
+                            other => {
+                                return Err(SimError::Key(format!(
+                                    "Unknown growth law {other:?} (expected birth or death)"
+                                ))
+                                .into())
+                            }
+                        }
+                    }
+                }
+                None => native = native.with_birth(compile(law)?),
+            }
+            native.validate(topology, molecules.len())?;
+            laws.push(native);
+        }
+        Ok(laws)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn births_dilute_and_deaths_keep_contents() {
+        let molecules = MoleculeIndex::new(vec!["atp".into(), "toxin".into()]);
+        let law = |src: &str| {
+            Arc::new(RateExpr::new(&Arg::parse(src).unwrap(), &molecules, Vec::new()).unwrap())
+        };
+        let growth = Growth::new(1)
+            .with_birth(law("mul(0.5, atp)"))
+            .with_death(law("toxin"));
+        // Births at 1.0 per cell, deaths at 0.25 per cell.
+        let (mut conc, mut mult) = (vec![9.0, 9.0, 2.0, 0.25], vec![1.0, 100.0]);
+        assert_eq!(growth.rates(&conc, 2), (1.0, 0.25));
+        let total = conc[2] * mult[1];
+        growth.apply(&mut conc, &mut mult, 2, 0.1);
+        assert!((mult[1] - 100.0 * (0.075f64).exp()).abs() < 1e-9);
+        assert!((conc[2] - 2.0 * (-0.1f64).exp()).abs() < 1e-12);
+        assert_eq!(&conc[..2], &[9.0, 9.0]);
+        // Only the deaths removed ATP.
+        assert!((conc[2] * mult[1] - total * (-0.025f64).exp()).abs() < 1e-9);
+
+        // A negative net law is pure death: no dilution.
+        let shrink = Growth::new(1).with_birth(law("sub(0.0, toxin)"));
+        let (mut conc, mut mult) = (vec![0.0, 0.0, 2.0, 1.0], vec![1.0, 10.0]);
+        shrink.apply(&mut conc, &mut mult, 2, 1.0);
+        assert!((mult[1] - 10.0 * (-1.0f64).exp()).abs() < 1e-12);
+        assert_eq!(conc[2], 2.0);
+    }
+}
@@ -0,0 +1,668 @@
+//! HybridSimulator: deterministic/stochastic partitioning of the reaction network.
+//!
+//! Reaction channels (see `stochastic`) are split into a fast set, integrated
+//! as ODEs on real-valued molecule amounts, and a slow set, fired one event
+//! at a time. Between slow events the fast set and the integrated slow
+//! propensity `G(t) = ∫ Σ a_slow(x(s)) ds` are advanced together with RK45;
+//! the next slow event happens when `G` reaches an exponential threshold
+//! (Haseltine & Rawlings 2002), so slow propensities may change with the
+//! fast dynamics.
+//!
+//! The split is recomputed at the start of every `dt` interval and after
+//! each slow event, either dynamically from propensities and copy numbers
+//! or from a per-molecule copy-number threshold.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyType};
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::SimResult;
+use crate::integrate::{OdeSystem, Rk45};
+use crate::reaction::{RateLaw, Reaction};
+use crate::script;
+use crate::stochastic::{channels_for, check_sampling, choose, StochasticModel};
+use crate::tree::CompartmentTree;
+use crate::world_simulator::WorldModel;
+use crate::world_state::WorldState;
+
+/// Rule splitting channels into fast (ODE) and slow (SSA) sets.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Partition {
+    /// Fast when the channel fires at least `min_rate` times per unit time
+    /// and every amount it reads or changes is at least `min_count`.
+    Dynamic { min_rate: f64, min_count: f64 },
+    /// Fast when every molecule it reads or changes is at or above that
+    /// molecule's threshold (indexed by molecule ID).
+    Threshold(Vec<f64>),
+}
+
+impl Partition {
+    pub fn is_fast(&self, model: &StochasticModel, ch: usize, x: &[f64]) -> bool {
+        let channel = &model.channels()[ch];
+        let mut touched = channel.reactant_indices().chain(channel.changed_indices());
+        match self {
+            Partition::Dynamic {
+                min_rate,
+                min_count,
+            } => model.propensity_at(ch, x) >= *min_rate && touched.all(|i| x[i] >= *min_count),
+            Partition::Threshold(thresholds) => {
+                let n = model.num_molecules();
+                touched.all(|i| x[i] >= thresholds[i % n])
+            }
+        }
+    }
+
+    /// Fast and slow channel indices at amounts `x`.
+    pub fn split(&self, model: &StochasticModel, x: &[f64]) -> (Vec<usize>, Vec<usize>) {
+        (0..model.channels().len()).partition(|&ch| self.is_fast(model, ch, x))
+    }
+}
+
+/// Fast-set ODEs on amounts, plus the integrated slow propensity as the last variable.
+struct Augmented<'a> {
+    model: &'a StochasticModel,
+    fast: &'a [usize],
+    slow: &'a [usize],
+    n: usize,
+}
+
+impl OdeSystem for Augmented<'_> {
+    fn dimension(&self) -> usize {
+        self.n + 1
+    }
+
+    fn derivatives(&self, _t: f64, y: &[f64], dydt: &mut [f64]) {
+        dydt.fill(0.0);
+        let x = &y[..self.n];
+        for &ch in self.fast {
+            let a = self.model.propensity_at(ch, x);
+            for &(i, delta) in self.model.channels()[ch].changes() {
+                dydt[i] += delta as f64 * a;
+            }
+        }
+        dydt[self.n] = self
+            .slow
+            .iter()
+            .map(|&ch| self.model.propensity_at(ch, x))
+            .sum();
+    }
+}
+
+// Slow events are located by sampling `G` on a grid of the dense output and
+// refining the first bin that crosses the threshold.
+const EVENT_GRID: usize = 32;
+const EVENT_LEVELS: usize = 3;
+
+/// Hybrid ODE/SSA stepping over real-valued amounts.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hybrid {
+    pub partition: Partition,
+    pub rk45: Rk45,
+}
+
+impl Hybrid {
+    pub fn new(partition: Partition, atol: f64, rtol: f64) -> SimResult<Self> {
+        Ok(Self {
+            partition,
+            rk45: Rk45::new(atol, rtol)?,
+        })
+    }
+
+    /// Advance amounts `x` from `t` to `t_end`; returns the number of slow events.
+    pub fn advance(
+        &self,
+        model: &StochasticModel,
+        x: &mut [f64],
+        mut t: f64,
+        t_end: f64,
+        rng: &mut ChaCha8Rng,
+    ) -> SimResult<usize> {
+        let n = x.len();
+        let mut y = vec![0.0; n + 1];
+        let mut events = 0;
+        while t < t_end {
+            let (fast, slow) = self.partition.split(model, x);
+            let system = Augmented {
+                model,
+                fast: &fast,
+                slow: &slow,
+                n,
+            };
+            y[..n].copy_from_slice(x);
+            y[n] = 0.0;
+            let threshold = -(1.0 - rng.gen::<f64>()).ln();
+            let event = self.next_event(&system, t, &mut y, t_end, threshold)?;
+            for (xi, &yi) in x.iter_mut().zip(&y[..n]) {
+                *xi = yi.max(0.0);
+            }
+            let Some(t_event) = event else {
+                break;
+            };
+
+            let rates: Vec<f64> = slow.iter().map(|&ch| model.propensity_at(ch, x)).collect();
+            let total: f64 = rates.iter().sum();
+            if total <= 0.0 {
+                // The integrated propensity reached the threshold, but no slow
+                // channel can fire at the event's state.
+                t = t_event;
+                continue;
+            }
+            let chosen = slow[choose(&rates, rng.gen::<f64>() * total)];
+            for &(i, delta) in model.channels()[chosen].changes() {
+                x[i] = (x[i] + delta as f64).max(0.0);
+            }
+            t = t_event;
+            events += 1;
+        }
+        Ok(events)
+    }
+
+    /// Integrate `y` from `t` until its last component reaches `threshold`.
+    ///
+    /// Returns the event time with `y` at that time, or `None` with `y` at
+    /// `t_end` when the threshold is not reached.
+    fn next_event(
+        &self,
+        system: &Augmented<'_>,
+        t: f64,
+        y: &mut [f64],
+        t_end: f64,
+        threshold: f64,
+    ) -> SimResult<Option<f64>> {
+        let g = system.n;
+        let (mut lo, mut hi) = (t, t_end);
+        let mut start = y.to_vec();
+        let mut upper = y.to_vec();
+        let mut samples = vec![vec![0.0; y.len()]; EVENT_GRID];
+        for level in 0..EVENT_LEVELS {
+            let times: Vec<f64> = (1..=EVENT_GRID)
+                .map(|i| lo + (hi - lo) * i as f64 / EVENT_GRID as f64)
+                .collect();
+            let mut end = start.clone();
+            self.rk45.integrate(system, lo, &mut end, &times, |i, s| {
+                samples[i].copy_from_slice(s)
+            })?;
+            let crossing = samples.iter().position(|s| s[g] >= threshold);
+            let k = match crossing {
+                Some(k) => k,
+                None if level == 0 => {
+                    y.copy_from_slice(&end);
+                    return Ok(None);
+                }
+                // Re-integrating a refined bin can land just short of the threshold.
+                None => EVENT_GRID - 1,
+            };
+            if k > 0 {
+                lo = times[k - 1];
+                start.copy_from_slice(&samples[k - 1]);
+            }
+            hi = times[k];
+            upper.copy_from_slice(&samples[k]);
+        }
+        // Linear interpolation inside the final bin.
+        let (g0, g1) = (start[g], upper[g]);
+        let theta = if g1 > g0 {
+            ((threshold - g0) / (g1 - g0)).clamp(0.0, 1.0)
+        } else {
+            1.0
+        };
+        for (i, yi) in y.iter_mut().enumerate() {
+            *yi = start[i] + theta * (upper[i] - start[i]);
+        }
+        Ok(Some(lo + theta * (hi - lo)))
+    }
+}
+
+/// Amounts `concentration × volume × multiplicity` (not rounded).
+fn to_amounts(model: &StochasticModel, conc: &[f64]) -> Vec<f64> {
+    let n = model.num_molecules();
+    conc.iter()
+        .enumerate()
+        .map(|(i, &c)| c * model.omega()[i / n])
+        .collect()
+}
+
+/// Write concentrations for amounts `x`; compartments with no volume keep their values.
+fn to_concentrations(model: &StochasticModel, x: &[f64], conc: &mut [f64]) {
+    let n = model.num_molecules();
+    for (i, (&amount, c)) in x.iter().zip(conc.iter_mut()).enumerate() {
+        let size = model.omega()[i / n];
+        if size > 0.0 {
+            *c = amount / size;
+        }
+    }
+}
+
+/// Partition from the Python arguments: a threshold (one number or one per
+/// molecule) selects `Threshold`, otherwise `Dynamic`.
+fn partition_from_args(
+    threshold: Option<&PyAny>,
+    min_events: f64,
+    min_count: f64,
+    dt: f64,
+    num_molecules: usize,
+) -> PyResult<Partition> {
+    let Some(threshold) = threshold else {
+        if !(min_events >= 0.0 && min_count >= 0.0) {
+            return Err(PyValueError::new_err(
+                "min_events and min_count must be non-negative",
+            ));
+        }
+        return Ok(Partition::Dynamic {
+            min_rate: min_events / dt,
+            min_count,
+        });
+    };
+    let thresholds = match threshold.extract::<f64>() {
+        Ok(value) => vec![value; num_molecules],
+        Err(_) => threshold.extract::<Vec<f64>>()?,
+    };
+    if thresholds.len() != num_molecules || thresholds.iter().any(|t| t.is_nan()) {
+        return Err(PyValueError::new_err(format!(
+            "threshold must be a number or {num_molecules} numbers, one per molecule"
+        )));
+    }
+    Ok(Partition::Threshold(thresholds))
+}
+
+/// Hybrid deterministic/stochastic simulator over `WorldState`s.
+#[pyclass(module = "alienbio_sim")]
+pub struct HybridSimulator {
+    model: WorldModel,
+    hybrid: Hybrid,
+    tree: Py<CompartmentTree>,
+    reaction_specs: Py<PyList>,
+    volumes: Vec<f64>,
+    dt: f64,
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl HybridSimulator {
+    fn build(
+        model: WorldModel,
+        hybrid: Hybrid,
+        tree: Py<CompartmentTree>,
+        reaction_specs: Py<PyList>,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        let volumes = check_sampling(&model, dt, volumes)?;
+        script::check_memory(model.reactions(), "hybrid")?;
+        Ok(Self {
+            model,
+            hybrid,
+            tree,
+            reaction_specs,
+            volumes,
+            dt,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        })
+    }
+
+    fn advance(&mut self, channels: &StochasticModel, x: &mut [f64], t: f64) -> PyResult<usize> {
+        let fired = self
+            .hybrid
+            .advance(channels, x, t, t + self.dt, &mut self.rng)?;
+        script::check(self.model.reactions())?;
+        Ok(fired)
+    }
+}
+
+#[pymethods]
+impl HybridSimulator {
+    /// Initialize hybrid simulator.
+    ///
+    /// Args:
+    ///     tree: Compartment topology (CompartmentTree or CompartmentTreeImpl)
+    ///     reactions: List of ReactionSpec (integer stoichiometry)
+    ///     num_molecules: Number of molecules in vocabulary
+    ///     dt: Sampling interval for step() and run()
+    ///     seed: Random seed; equal seeds give identical trajectories
+    ///     volumes: Volume of each compartment instance (default 1.0 each)
+    ///     threshold: Copy number (one, or one per molecule) at or above which
+    ///         a molecule is treated as continuous; reactions touching only
+    ///         such molecules are fast. If None, partition dynamically.
+    ///     min_events: Dynamic partition: fast reactions fire at least this
+    ///         often per dt interval
+    ///     min_count: Dynamic partition: fast reactions only touch amounts at
+    ///         least this large
+    ///     atol, rtol: RK45 tolerances for the fast set (amounts, default 1e-6)
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (tree, reactions, num_molecules, dt=1.0, seed=0, volumes=None, threshold=None, min_events=10.0, min_count=100.0, atol=1e-6, rtol=1e-6))]
+    fn new(
+        py: Python<'_>,
+        tree: &PyAny,
+        reactions: &PyAny,
+        num_molecules: usize,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+        threshold: Option<&PyAny>,
+        min_events: f64,
+        min_count: f64,
+        atol: f64,
+        rtol: f64,
+    ) -> PyResult<Self> {
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let reaction_specs = PyList::new(py, reactions.iter()?.collect::<PyResult<Vec<_>>>()?);
+        let parsed = reaction_specs
+            .iter()
+            .map(Reaction::from_spec)
+            .collect::<PyResult<Vec<_>>>()?;
+        let model = WorldModel::new(topology, parsed, num_molecules)?;
+        let partition = partition_from_args(threshold, min_events, min_count, dt, num_molecules)?;
+        let hybrid = Hybrid::new(partition, atol, rtol)?;
+        Self::build(
+            model,
+            hybrid,
+            tree,
+            reaction_specs.into(),
+            dt,
+            seed,
+            volumes,
+        )
+    }
+
+    /// Compartment topology.
+    #[getter]
+    fn tree(&self, py: Python<'_>) -> Py<CompartmentTree> {
+        self.tree.clone_ref(py)
+    }
+
+    /// Reaction specifications.
+    #[getter]
+    fn reactions(&self, py: Python<'_>) -> Py<PyList> {
+        self.reaction_specs.clone_ref(py)
+    }
+
+    /// Number of molecules in vocabulary.
+    #[getter]
+    fn num_molecules(&self) -> usize {
+        self.model.num_molecules()
+    }
+
+    /// Partitioning rule: "dynamic" or "threshold".
+    #[getter]
+    fn partition(&self) -> &'static str {
+        match self.hybrid.partition {
+            Partition::Dynamic { .. } => "dynamic",
+            Partition::Threshold(_) => "threshold",
+        }
+    }
+
+    /// Sampling interval.
+    #[getter]
+    fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Seed the random stream was started from.
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Volume of each compartment instance.
+    #[getter]
+    fn volumes(&self) -> Vec<f64> {
+        self.volumes.clone()
+    }
+
+    /// Restart the random stream from `seed`.
+    fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Reactions integrated deterministically at `state`.
+    ///
+    /// Returns:
+    ///     List of (reaction index, compartment) pairs in the fast set
+    fn fast_reactions(&self, state: PyRef<'_, WorldState>) -> PyResult<Vec<(usize, usize)>> {
+        let channels = channels_for(&self.model, &self.volumes, &state)?;
+        let x = to_amounts(&channels, state.concentrations());
+        let (fast, _) = self.hybrid.partition.split(&channels, &x);
+        Ok(fast
+            .into_iter()
+            .map(|ch| {
+                let channel = &channels.channels()[ch];
+                (channel.reaction, channel.compartment)
+            })
+            .collect())
+    }
+
+    /// Simulate one interval of length dt, returning a new state.
+    fn step(&mut self, py: Python<'_>, state: PyRef<'_, WorldState>) -> PyResult<WorldState> {
+        let channels = channels_for(&self.model, &self.volumes, &state)?;
+        let mut x = to_amounts(&channels, state.concentrations());
+        self.advance(&channels, &mut x, 0.0)?;
+        let mut next = state.copy(py);
+        to_concentrations(&channels, &x, next.concentrations_mut());
+        Ok(next)
+    }
+
+    /// Run simulation for multiple sampling intervals.
+    ///
+    /// Args:
+    ///     state: Initial state (not modified)
+    ///     steps: Number of dt intervals to simulate
+    ///     sample_every: If set, only keep every Nth state (plus final)
+    ///
+    /// Returns:
+    ///     List of states (timeline)
+    #[pyo3(signature = (state, steps, sample_every=None))]
+    fn run(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        steps: usize,
+        sample_every: Option<usize>,
+    ) -> PyResult<Vec<WorldState>> {
+        let sample_every = sample_every.unwrap_or(1);
+        if sample_every == 0 {
+            return Err(PyValueError::new_err("sample_every must be positive"));
+        }
+        script::reset(self.model.reactions());
+        let channels = channels_for(&self.model, &self.volumes, &state)?;
+        let mut x = to_amounts(&channels, state.concentrations());
+        let mut history = Vec::with_capacity(steps / sample_every + 2);
+        let snapshot = |x: &[f64]| {
+            let mut sample = state.copy(py);
+            to_concentrations(&channels, x, sample.concentrations_mut());
+            sample
+        };
+        for i in 0..steps {
+            if i % sample_every == 0 {
+                history.push(snapshot(&x));
+            }
+            self.advance(&channels, &mut x, i as f64 * self.dt)?;
+        }
+        // Always include final state
+        history.push(snapshot(&x));
+        Ok(history)
+    }
+
+    /// Create simulator from a Chemistry and compartment tree.
+    ///
+    /// Molecule IDs follow the order of `chemistry.molecules`; a constant
+    /// rate is a zero-order flux, as in `StochasticSimulator.from_chemistry`.
+    #[classmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (chemistry, tree, dt=1.0, seed=0, volumes=None, threshold=None, min_events=10.0, min_count=100.0, atol=1e-6, rtol=1e-6))]
+    fn from_chemistry(
+        _cls: &PyType,
+        py: Python<'_>,
+        chemistry: &PyAny,
+        tree: &PyAny,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+        threshold: Option<&PyAny>,
+        min_events: f64,
+        min_count: f64,
+        atol: f64,
+        rtol: f64,
+    ) -> PyResult<Self> {
+        let molecules = MoleculeIndex::from_chemistry(chemistry)?;
+        let reactions =
+            molecules.native_reactions(chemistry, RateLaw::Constant, false, "HybridSimulator")?;
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let model = WorldModel::new(topology, reactions, molecules.len())?;
+        let partition = partition_from_args(threshold, min_events, min_count, dt, molecules.len())?;
+        let hybrid = Hybrid::new(partition, atol, rtol)?;
+        Self::build(
+            model,
+            hybrid,
+            tree,
+            PyList::empty(py).into(),
+            dt,
+            seed,
+            volumes,
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "HybridSimulator(compartments={}, molecules={}, reactions={}, partition={:?}, dt={}, seed={})",
+            self.model.num_compartments(),
+            self.model.num_molecules(),
+            self.model.reactions().len(),
+            self.partition(),
+            self.dt,
+            self.seed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Topology;
+    use std::sync::Arc;
+
+    fn stochastic(reactions: Vec<Reaction>, n: usize) -> StochasticModel {
+        let mut t = Topology::new();
+        t.add_root("root").unwrap();
+        let model = WorldModel::new(Arc::new(t), reactions, n).unwrap();
+        StochasticModel::new(&model, &[1.0], &[1.0]).unwrap()
+    }
+
+    /// Bulk conversion A -> B, plus a rare gene switch G -> G* catalysed by B.
+    fn switch() -> StochasticModel {
+        stochastic(
+            vec![
+                Reaction::new("bulk", vec![(0, 1.0)], vec![(1, 1.0)], 0.5),
+                Reaction::new(
+                    "switch",
+                    vec![(2, 1.0), (1, 1.0)],
+                    vec![(3, 1.0), (1, 1.0)],
+                    1e-4,
+                ),
+            ],
+            4,
+        )
+    }
+
+    #[test]
+    fn dynamic_partition_uses_rates_and_counts() {
+        let model = switch();
+        let partition = Partition::Dynamic {
+            min_rate: 10.0,
+            min_count: 100.0,
+        };
+        assert_eq!(
+            partition.split(&model, &[10_000.0, 0.0, 1.0, 0.0]),
+            (vec![], vec![0, 1]) // B is scarce, so bulk is slow
+        );
+        assert_eq!(
+            partition.split(&model, &[10_000.0, 500.0, 1.0, 0.0]),
+            (vec![0], vec![1])
+        );
+        assert_eq!(
+            partition.split(&model, &[10.0, 500.0, 1.0, 0.0]),
+            (vec![], vec![0, 1]) // bulk only fires 5 times per unit time
+        );
+    }
+
+    #[test]
+    fn threshold_partition_is_per_molecule() {
+        let model = switch();
+        let partition = Partition::Threshold(vec![100.0, 100.0, 0.0, 0.0]);
+        assert_eq!(
+            partition.split(&model, &[150.0, 100.0, 1.0, 0.0]),
+            (vec![0, 1], vec![])
+        );
+        let partition = Partition::Threshold(vec![100.0, 100.0, 5.0, 5.0]);
+        assert_eq!(
+            partition.split(&model, &[150.0, 100.0, 1.0, 0.0]),
+            (vec![0], vec![1])
+        );
+    }
+
+    #[test]
+    fn all_fast_matches_ode_solution() {
+        let model = switch();
+        let hybrid = Hybrid::new(Partition::Threshold(vec![0.0; 4]), 1e-9, 1e-9).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let mut x = vec![1000.0, 0.0, 0.0, 0.0];
+        let events = hybrid.advance(&model, &mut x, 0.0, 2.0, &mut rng).unwrap();
+        assert_eq!(events, 0);
+        let expected = 1000.0 * (-1.0f64).exp();
+        assert!((x[0] - expected).abs() < 1e-5, "{x:?}");
+        assert!((x[0] + x[1] - 1000.0).abs() < 1e-6);
+    }
+
+    #[test]
+    fn slow_events_follow_fast_dynamics() {
+        // The switch propensity grows as B accumulates, so its mean firing
+        // count is ∫ 1e-4 · G · B(t) dt with G = 100 switchable copies.
+        let model = switch();
+        let hybrid =
+            Hybrid::new(Partition::Threshold(vec![100.0, 0.0, 1e9, 1e9]), 1e-8, 1e-8).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(3);
+        let runs = 200;
+        let mut switched = 0.0;
+        for _ in 0..runs {
+            let mut x = vec![1000.0, 0.0, 100.0, 0.0];
+            let events = hybrid.advance(&model, &mut x, 0.0, 1.0, &mut rng).unwrap();
+            assert_eq!(x[2] + x[3], 100.0);
+            assert_eq!(x[3], events as f64);
+            switched += x[3];
+        }
+        // B(t) = 1000 (1 - e^{-t/2}); ∫0^1 B dt = 1000 (1 - 2 (1 - e^{-1/2})) ≈ 213.1,
+        // and G decays slowly, so the mean is just under 100 · 1e-4 · 213.1 ≈ 2.13.
+        let mean = switched / runs as f64;
+        assert!((mean - 2.1).abs() < 0.35, "{mean}");
+    }
+
+    #[test]
+    fn hybrid_is_seeded() {
+        let model = switch();
+        let hybrid = Hybrid::new(
+            Partition::Dynamic {
+                min_rate: 10.0,
+                min_count: 100.0,
+            },
+            1e-6,
+            1e-6,
+        )
+        .unwrap();
+        let run = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut x = vec![5000.0, 200.0, 50.0, 0.0];
+            let events = hybrid.advance(&model, &mut x, 0.0, 3.0, &mut rng).unwrap();
+            (x, events)
+        };
+        assert_eq!(run(8), run(8));
+    }
+}
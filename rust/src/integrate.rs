@@ -0,0 +1,851 @@
+//! ODE integrators for the reaction network.
+//!
+//! `WorldModel::apply_reactions` is the explicit-Euler step used by the Python
+//! simulators. This module treats the same network as a continuous system
+//! `dC/dt = f(C)` and integrates it with adaptive error control instead of a
+//! hand-tuned `dt`.
+
+use crate::error::{SimError, SimResult};
+use crate::linalg::{CsrMatrix, Lu, LuChoice};
+
+/// A system of ODEs `dy/dt = f(t, y)` over a flat state vector.
+pub trait OdeSystem {
+    /// Number of state variables.
+    fn dimension(&self) -> usize;
+
+    /// Write `f(t, y)` into `dydt`.
+    fn derivatives(&self, t: f64, y: &[f64], dydt: &mut [f64]);
+}
+
+/// An ODE system that can supply its Jacobian `∂f/∂y` analytically.
+pub trait JacobianSystem: OdeSystem {
+    /// Structural nonzeros of the Jacobian as `(row, column)`; duplicates allowed.
+    fn jacobian_pattern(&self) -> Vec<(usize, usize)>;
+
+    /// Write `∂f/∂y` at `(t, y)` into `jac`, whose pattern came from
+    /// `jacobian_pattern` and whose values have been zeroed.
+    fn jacobian(&self, t: f64, y: &[f64], jac: &mut CsrMatrix);
+}
+
+/// Counters from one integration.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Stats {
+    pub accepted: usize,
+    pub rejected: usize,
+    pub evaluations: usize,
+    /// Size of the last accepted step, a good first step for a continuation.
+    pub last_step: f64,
+}
+
+// Dormand–Prince 5(4) tableau.
+const C2: f64 = 1.0 / 5.0;
+const C3: f64 = 3.0 / 10.0;
+const C4: f64 = 4.0 / 5.0;
+const C5: f64 = 8.0 / 9.0;
+const A21: f64 = 1.0 / 5.0;
+const A31: f64 = 3.0 / 40.0;
+const A32: f64 = 9.0 / 40.0;
+const A41: f64 = 44.0 / 45.0;
+const A42: f64 = -56.0 / 15.0;
+const A43: f64 = 32.0 / 9.0;
+const A51: f64 = 19372.0 / 6561.0;
+const A52: f64 = -25360.0 / 2187.0;
+const A53: f64 = 64448.0 / 6561.0;
+const A54: f64 = -212.0 / 729.0;
+const A61: f64 = 9017.0 / 3168.0;
+const A62: f64 = -355.0 / 33.0;
+const A63: f64 = 46732.0 / 5247.0;
+const A64: f64 = 49.0 / 176.0;
+const A65: f64 = -5103.0 / 18656.0;
+const A71: f64 = 35.0 / 384.0;
+const A73: f64 = 500.0 / 1113.0;
+const A74: f64 = 125.0 / 192.0;
+const A75: f64 = -2187.0 / 6784.0;
+const A76: f64 = 11.0 / 84.0;
+// Fifth-order minus embedded fourth-order weights.
+const E1: f64 = 71.0 / 57600.0;
+const E3: f64 = -71.0 / 16695.0;
+const E4: f64 = 71.0 / 1920.0;
+const E5: f64 = -17253.0 / 339200.0;
+const E6: f64 = 22.0 / 525.0;
+const E7: f64 = -1.0 / 40.0;
+// Continuous extension (Hairer & Wanner, DOPRI5 dense output).
+const D1: f64 = -12715105075.0 / 11282082432.0;
+const D3: f64 = 87487479700.0 / 32700410799.0;
+const D4: f64 = -10690763975.0 / 1880347072.0;
+const D5: f64 = 701980252875.0 / 199316789632.0;
+const D6: f64 = -1453857185.0 / 822651844.0;
+const D7: f64 = 69997945.0 / 29380423.0;
+
+const SAFETY: f64 = 0.9;
+const MIN_FACTOR: f64 = 0.2;
+const MAX_FACTOR: f64 = 10.0;
+
+fn check_tolerances(atol: f64, rtol: f64) -> SimResult<()> {
+    if !(atol >= 0.0 && rtol >= 0.0 && atol + rtol > 0.0) {
+        return Err(SimError::Value(format!(
+            "Tolerances must be non-negative and not both zero, got atol={atol}, rtol={rtol}"
+        )));
+    }
+    Ok(())
+}
+
+/// `sqrt(mean((err / (atol + rtol * max(|y0|, |y1|)))^2))`.
+fn weighted_rms(atol: f64, rtol: f64, err: &[f64], y0: &[f64], y1: &[f64]) -> f64 {
+    if err.is_empty() {
+        return 0.0;
+    }
+    let sum: f64 = err
+        .iter()
+        .zip(y0.iter().zip(y1))
+        .map(|(e, (a, b))| (e / (atol + rtol * a.abs().max(b.abs()))).powi(2))
+        .sum();
+    (sum / err.len() as f64).sqrt()
+}
+
+/// Starting step from the size of `y` and `f(y)` (Hairer's heuristic).
+///
+/// `order` is the order of the embedded error estimate.
+#[allow(clippy::too_many_arguments)]
+fn initial_step<S: OdeSystem + ?Sized>(
+    system: &S,
+    (atol, rtol): (f64, f64),
+    order: i32,
+    t0: f64,
+    y0: &[f64],
+    f0: &[f64],
+    limit: f64,
+    stats: &mut Stats,
+) -> f64 {
+    let zero = vec![0.0; y0.len()];
+    let rms = |v: &[f64]| weighted_rms(atol, rtol, v, y0, &zero);
+    let d0 = rms(y0);
+    let d1 = rms(f0);
+    let h0 = if d0 < 1e-5 || d1 < 1e-5 {
+        1e-6
+    } else {
+        0.01 * d0 / d1
+    }
+    .min(limit);
+
+    let y1: Vec<f64> = y0.iter().zip(f0).map(|(y, f)| y + h0 * f).collect();
+    let mut f1 = vec![0.0; y0.len()];
+    system.derivatives(t0 + h0, &y1, &mut f1);
+    stats.evaluations += 1;
+    let df: Vec<f64> = f1.iter().zip(f0).map(|(a, b)| a - b).collect();
+    let d2 = rms(&df) / h0;
+
+    let h1 = if d1.max(d2) <= 1e-15 {
+        (h0 * 1e-3).max(1e-6)
+    } else {
+        (0.01 / d1.max(d2)).powf(1.0 / f64::from(order + 1))
+    };
+    (100.0 * h0).min(h1).min(limit)
+}
+
+/// Validate an integration request and emit the samples at or before `t0`.
+///
+/// Returns the index of the first remaining output time and the end time,
+/// or `None` when nothing is left to integrate.
+fn start<S: OdeSystem + ?Sized>(
+    system: &S,
+    t0: f64,
+    y: &[f64],
+    times: &[f64],
+    sample: &mut impl FnMut(usize, &[f64]),
+) -> SimResult<Option<(usize, f64)>> {
+    let n = system.dimension();
+    if y.len() != n {
+        return Err(SimError::Value(format!(
+            "State has {} values, system expects {n}",
+            y.len()
+        )));
+    }
+    if times.windows(2).any(|w| w[1] < w[0]) || times.first().is_some_and(|&t| t < t0) {
+        return Err(SimError::Value(
+            "Output times must be non-decreasing and not before the start time".into(),
+        ));
+    }
+    let mut next = 0;
+    while next < times.len() && times[next] <= t0 {
+        sample(next, y);
+        next += 1;
+    }
+    Ok(times
+        .last()
+        .filter(|_| next < times.len())
+        .map(|&t_end| (next, t_end)))
+}
+
+/// Adaptive Dormand–Prince RK45 with dense output.
+///
+/// Steps are accepted when the weighted RMS error
+/// `sqrt(mean((err / (atol + rtol * |y|))^2))` is at most one. Output times
+/// are produced by the fourth-order continuous extension, so sampling never
+/// shortens a step.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rk45 {
+    pub atol: f64,
+    pub rtol: f64,
+    /// Initial step size; estimated from the system when `None`.
+    pub first_step: Option<f64>,
+    pub max_step: f64,
+    /// Upper bound on attempted steps per integration.
+    pub max_steps: usize,
+}
+
+impl Default for Rk45 {
+    fn default() -> Self {
+        Self {
+            atol: 1e-9,
+            rtol: 1e-6,
+            first_step: None,
+            max_step: f64::INFINITY,
+            max_steps: 1_000_000,
+        }
+    }
+}
+
+impl Rk45 {
+    pub fn new(atol: f64, rtol: f64) -> SimResult<Self> {
+        check_tolerances(atol, rtol)?;
+        Ok(Self {
+            atol,
+            rtol,
+            ..Self::default()
+        })
+    }
+
+    /// Integrate from `t0` through each of `times`, leaving `y` at the last one.
+    ///
+    /// `times` must be non-decreasing and not before `t0`. `sample(i, y)` is
+    /// called with the interpolated state at `times[i]`, in order.
+    pub fn integrate<S: OdeSystem + ?Sized>(
+        &self,
+        system: &S,
+        t0: f64,
+        y: &mut [f64],
+        times: &[f64],
+        mut sample: impl FnMut(usize, &[f64]),
+    ) -> SimResult<Stats> {
+        let n = system.dimension();
+        let mut stats = Stats::default();
+        let Some((mut next, t_end)) = start(system, t0, y, times, &mut sample)? else {
+            return Ok(stats);
+        };
+
+        let mut k = vec![vec![0.0; n]; 7];
+        let mut stage = vec![0.0; n];
+        let mut y1 = vec![0.0; n];
+        let mut err = vec![0.0; n];
+        let mut out = vec![0.0; n];
+        let mut dense = vec![vec![0.0; n]; 5];
+
+        system.derivatives(t0, y, &mut k[0]);
+        stats.evaluations += 1;
+        let mut h = match self.first_step {
+            Some(h) => h,
+            None => initial_step(
+                system,
+                (self.atol, self.rtol),
+                4,
+                t0,
+                y,
+                &k[0],
+                (t_end - t0).min(self.max_step),
+                &mut stats,
+            ),
+        }
+        .min(self.max_step);
+        let mut t = t0;
+        let mut rejected_last = false;
+
+        while next < times.len() {
+            if stats.accepted + stats.rejected >= self.max_steps {
+                return Err(SimError::Value(format!(
+                    "RK45 exceeded {} steps before t={t_end}",
+                    self.max_steps
+                )));
+            }
+            let min_step = 16.0 * f64::EPSILON * t.abs().max(1.0);
+            if h.is_nan() || h < min_step {
+                return Err(SimError::Value(format!(
+                    "RK45 step size underflow at t={t} (h={h:e})"
+                )));
+            }
+            h = h.min(t_end - t);
+
+            let stages: [(f64, &[f64]); 5] = [
+                (C2, &[A21]),
+                (C3, &[A31, A32]),
+                (C4, &[A41, A42, A43]),
+                (C5, &[A51, A52, A53, A54]),
+                (1.0, &[A61, A62, A63, A64, A65]),
+            ];
+            for (s, (c, a)) in stages.iter().enumerate() {
+                for i in 0..n {
+                    let incr: f64 = a.iter().enumerate().map(|(j, a)| a * k[j][i]).sum();
+                    stage[i] = y[i] + h * incr;
+                }
+                system.derivatives(t + c * h, &stage, &mut k[s + 1]);
+            }
+            for i in 0..n {
+                y1[i] = y[i]
+                    + h * (A71 * k[0][i]
+                        + A73 * k[2][i]
+                        + A74 * k[3][i]
+                        + A75 * k[4][i]
+                        + A76 * k[5][i]);
+            }
+            system.derivatives(t + h, &y1, &mut k[6]);
+            stats.evaluations += 6;
+            for i in 0..n {
+                err[i] = h
+                    * (E1 * k[0][i]
+                        + E3 * k[2][i]
+                        + E4 * k[3][i]
+                        + E5 * k[4][i]
+                        + E6 * k[5][i]
+                        + E7 * k[6][i]);
+            }
+
+            let norm = weighted_rms(self.atol, self.rtol, &err, y, &y1);
+            if !norm.is_finite() {
+                stats.rejected += 1;
+                rejected_last = true;
+                h *= MIN_FACTOR;
+                continue;
+            }
+            if norm > 1.0 {
+                stats.rejected += 1;
+                rejected_last = true;
+                h *= (SAFETY * norm.powf(-0.2)).max(MIN_FACTOR);
+                continue;
+            }
+
+            // Accepted: build the interpolant before overwriting y.
+            let t_new = if t_end - (t + h) <= min_step {
+                t_end
+            } else {
+                t + h
+            };
+            if next < times.len() && times[next] <= t_new {
+                for i in 0..n {
+                    let diff = y1[i] - y[i];
+                    let bspl = h * k[0][i] - diff;
+                    dense[0][i] = y[i];
+                    dense[1][i] = diff;
+                    dense[2][i] = bspl;
+                    dense[3][i] = diff - h * k[6][i] - bspl;
+                    dense[4][i] = h
+                        * (D1 * k[0][i]
+                            + D3 * k[2][i]
+                            + D4 * k[3][i]
+                            + D5 * k[4][i]
+                            + D6 * k[5][i]
+                            + D7 * k[6][i]);
+                }
+                while next < times.len() && times[next] <= t_new {
+                    if times[next] >= t_new {
+                        sample(next, &y1);
+                    } else {
+                        let theta = (times[next] - t) / h;
+                        let theta1 = 1.0 - theta;
+                        for i in 0..n {
+                            out[i] = dense[0][i]
+                                + theta
+                                    * (dense[1][i]
+                                        + theta1
+                                            * (dense[2][i]
+                                                + theta * (dense[3][i] + theta1 * dense[4][i])));
+                        }
+                        sample(next, &out);
+                    }
+                    next += 1;
+                }
+            }
+
+            y.copy_from_slice(&y1);
+            k.swap(0, 6); // first same as last
+            t = t_new;
+            stats.accepted += 1;
+            stats.last_step = h;
+
+            let mut factor = (SAFETY * norm.max(1e-10).powf(-0.2)).clamp(MIN_FACTOR, MAX_FACTOR);
+            if rejected_last {
+                factor = factor.min(1.0);
+            }
+            rejected_last = false;
+            h = (h * factor).min(self.max_step);
+        }
+        Ok(stats)
+    }
+}
+
+/// Linearly implicit Rosenbrock method for stiff systems (Shampine's ode23s).
+///
+/// L-stable, second order with a third-order error estimate, and one LU
+/// factorization of `I - h·d·J` per step attempt. `J` is the analytic
+/// Jacobian from `JacobianSystem`, factored dense or sparse according to
+/// `lu`. The system is treated as autonomous (`∂f/∂t = 0`).
+#[derive(Debug, Clone, PartialEq)]
+pub struct Rosenbrock {
+    pub atol: f64,
+    pub rtol: f64,
+    /// Initial step size; estimated from the system when `None`.
+    pub first_step: Option<f64>,
+    pub max_step: f64,
+    /// Upper bound on attempted steps per integration.
+    pub max_steps: usize,
+    pub lu: LuChoice,
+}
+
+impl Default for Rosenbrock {
+    fn default() -> Self {
+        Self {
+            atol: 1e-9,
+            rtol: 1e-6,
+            first_step: None,
+            max_step: f64::INFINITY,
+            max_steps: 1_000_000,
+            lu: LuChoice::Auto,
+        }
+    }
+}
+
+impl Rosenbrock {
+    pub fn new(atol: f64, rtol: f64, lu: LuChoice) -> SimResult<Self> {
+        check_tolerances(atol, rtol)?;
+        Ok(Self {
+            atol,
+            rtol,
+            lu,
+            ..Self::default()
+        })
+    }
+
+    /// Integrate from `t0` through each of `times`, leaving `y` at the last one.
+    ///
+    /// Same contract as `Rk45::integrate`.
+    pub fn integrate<S: JacobianSystem + ?Sized>(
+        &self,
+        system: &S,
+        t0: f64,
+        y: &mut [f64],
+        times: &[f64],
+        mut sample: impl FnMut(usize, &[f64]),
+    ) -> SimResult<Stats> {
+        let d = 1.0 / (2.0 + std::f64::consts::SQRT_2);
+        let e32 = 6.0 + std::f64::consts::SQRT_2;
+
+        let n = system.dimension();
+        let mut stats = Stats::default();
+        let Some((mut next, t_end)) = start(system, t0, y, times, &mut sample)? else {
+            return Ok(stats);
+        };
+
+        let pattern = system.jacobian_pattern();
+        let mut jac = CsrMatrix::from_pattern(n, pattern.iter().copied());
+        let mut w = CsrMatrix::from_pattern(n, pattern.into_iter().chain((0..n).map(|i| (i, i))));
+        let (mut f0, mut f1, mut f2) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+        let (mut k1, mut k2, mut k3) = (vec![0.0; n], vec![0.0; n], vec![0.0; n]);
+        let mut stage = vec![0.0; n];
+        let mut y1 = vec![0.0; n];
+        let mut err = vec![0.0; n];
+        let mut out = vec![0.0; n];
+
+        system.derivatives(t0, y, &mut f0);
+        stats.evaluations += 1;
+        let mut h = match self.first_step {
+            Some(h) => h,
+            None => initial_step(
+                system,
+                (self.atol, self.rtol),
+                2,
+                t0,
+                y,
+                &f0,
+                (t_end - t0).min(self.max_step),
+                &mut stats,
+            ),
+        }
+        .min(self.max_step);
+        let mut t = t0;
+        let mut rejected_last = false;
+        let mut jacobian_current = false;
+
+        while next < times.len() {
+            if stats.accepted + stats.rejected >= self.max_steps {
+                return Err(SimError::Value(format!(
+                    "Rosenbrock exceeded {} steps before t={t_end}",
+                    self.max_steps
+                )));
+            }
+            let min_step = 16.0 * f64::EPSILON * t.abs().max(1.0);
+            if h.is_nan() || h < min_step {
+                return Err(SimError::Value(format!(
+                    "Rosenbrock step size underflow at t={t} (h={h:e})"
+                )));
+            }
+            h = h.min(t_end - t);
+
+            if !jacobian_current {
+                jac.clear();
+                system.jacobian(t, y, &mut jac);
+                jacobian_current = true;
+            }
+            w.set_identity_minus(h * d, &jac);
+            let lu = match Lu::factor(self.lu, &w) {
+                Ok(lu) => lu,
+                Err(_) => {
+                    // I - h·d·J is singular only for unlucky h; retry smaller.
+                    stats.rejected += 1;
+                    rejected_last = true;
+                    h *= 0.5;
+                    continue;
+                }
+            };
+
+            k1.copy_from_slice(&f0);
+            lu.solve(&mut k1);
+            for i in 0..n {
+                stage[i] = y[i] + 0.5 * h * k1[i];
+            }
+            system.derivatives(t + 0.5 * h, &stage, &mut f1);
+            for i in 0..n {
+                k2[i] = f1[i] - k1[i];
+            }
+            lu.solve(&mut k2);
+            for i in 0..n {
+                k2[i] += k1[i];
+                y1[i] = y[i] + h * k2[i];
+            }
+            system.derivatives(t + h, &y1, &mut f2);
+            for i in 0..n {
+                k3[i] = f2[i] - e32 * (k2[i] - f1[i]) - 2.0 * (k1[i] - f0[i]);
+            }
+            lu.solve(&mut k3);
+            stats.evaluations += 2;
+            for i in 0..n {
+                err[i] = h / 6.0 * (k1[i] - 2.0 * k2[i] + k3[i]);
+            }
+
+            let norm = weighted_rms(self.atol, self.rtol, &err, y, &y1);
+            if !norm.is_finite() || norm > 1.0 {
+                stats.rejected += 1;
+                rejected_last = true;
+                h *= if norm.is_finite() {
+                    (SAFETY * norm.powf(-1.0 / 3.0)).max(MIN_FACTOR)
+                } else {
+                    MIN_FACTOR
+                };
+                continue;
+            }
+
+            let t_new = if t_end - (t + h) <= min_step {
+                t_end
+            } else {
+                t + h
+            };
+            while next < times.len() && times[next] <= t_new {
+                if times[next] >= t_new {
+                    sample(next, &y1);
+                } else {
+                    // y(t + s·h) = y + h·(s(1-s)·k1 + s(s-2d)·k2) / (1-2d)
+                    let s = (times[next] - t) / h;
+                    let c1 = s * (1.0 - s) / (1.0 - 2.0 * d);
+                    let c2 = s * (s - 2.0 * d) / (1.0 - 2.0 * d);
+                    for i in 0..n {
+                        out[i] = y[i] + h * (c1 * k1[i] + c2 * k2[i]);
+                    }
+                    sample(next, &out);
+                }
+                next += 1;
+            }
+
+            y.copy_from_slice(&y1);
+            std::mem::swap(&mut f0, &mut f2); // f(t + h, y1) starts the next step
+            t = t_new;
+            stats.accepted += 1;
+            stats.last_step = h;
+            jacobian_current = false;
+
+            let mut factor =
+                (SAFETY * norm.max(1e-10).powf(-1.0 / 3.0)).clamp(MIN_FACTOR, MAX_FACTOR);
+            if rejected_last {
+                factor = factor.min(1.0);
+            }
+            rejected_last = false;
+            h = (h * factor).min(self.max_step);
+        }
+        Ok(stats)
+    }
+}
+
+/// How a simulator advances the reaction network over one `dt`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Method {
+    /// One explicit-Euler step per `dt`, clamping reactants at zero (the Python semantics).
+    Euler,
+    /// Adaptive Dormand–Prince; `dt` only sets the output grid.
+    Rk45(Rk45),
+    /// Adaptive implicit Rosenbrock for stiff chemistries; `dt` only sets the output grid.
+    Rosenbrock(Rosenbrock),
+}
+
+impl Method {
+    /// Parse a method name (`euler`, `rk45`, `rosenbrock`) with optional
+    /// tolerances and, for the implicit method, the LU choice.
+    pub fn parse(name: &str, atol: Option<f64>, rtol: Option<f64>, lu: &str) -> SimResult<Self> {
+        let lu = LuChoice::parse(lu)?;
+        match name {
+            "euler" => Ok(Method::Euler),
+            "rk45" => {
+                let defaults = Rk45::default();
+                Ok(Method::Rk45(Rk45::new(
+                    atol.unwrap_or(defaults.atol),
+                    rtol.unwrap_or(defaults.rtol),
+                )?))
+            }
+            "rosenbrock" => {
+                let defaults = Rosenbrock::default();
+                Ok(Method::Rosenbrock(Rosenbrock::new(
+                    atol.unwrap_or(defaults.atol),
+                    rtol.unwrap_or(defaults.rtol),
+                    lu,
+                )?))
+            }
+            _ => Err(SimError::Value(format!(
+                "Unknown integration method {name:?} (expected 'euler', 'rk45' or 'rosenbrock')"
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Method::Euler => "euler",
+            Method::Rk45(_) => "rk45",
+            Method::Rosenbrock(_) => "rosenbrock",
+        }
+    }
+
+    /// Whether the method integrates adaptively (everything but `Euler`).
+    pub fn is_adaptive(&self) -> bool {
+        !matches!(self, Method::Euler)
+    }
+
+    /// Integrate `system` with an adaptive method; see `Rk45::integrate`.
+    pub fn integrate<S: JacobianSystem + ?Sized>(
+        &self,
+        system: &S,
+        t0: f64,
+        y: &mut [f64],
+        times: &[f64],
+        sample: impl FnMut(usize, &[f64]),
+    ) -> SimResult<Stats> {
+        match self {
+            Method::Euler => Err(SimError::Value(
+                "euler is a fixed-step method and cannot integrate to output times".into(),
+            )),
+            Method::Rk45(rk) => rk.integrate(system, t0, y, times, sample),
+            Method::Rosenbrock(ros) => ros.integrate(system, t0, y, times, sample),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// dy/dt = -k y
+    struct Decay(f64);
+
+    impl OdeSystem for Decay {
+        fn dimension(&self) -> usize {
+            1
+        }
+        fn derivatives(&self, _t: f64, y: &[f64], dydt: &mut [f64]) {
+            dydt[0] = -self.0 * y[0];
+        }
+    }
+
+    /// Harmonic oscillator: x'' = -x
+    struct Oscillator;
+
+    impl OdeSystem for Oscillator {
+        fn dimension(&self) -> usize {
+            2
+        }
+        fn derivatives(&self, _t: f64, y: &[f64], dydt: &mut [f64]) {
+            dydt[0] = y[1];
+            dydt[1] = -y[0];
+        }
+    }
+
+    impl JacobianSystem for Decay {
+        fn jacobian_pattern(&self) -> Vec<(usize, usize)> {
+            vec![(0, 0)]
+        }
+        fn jacobian(&self, _t: f64, _y: &[f64], jac: &mut CsrMatrix) {
+            jac.add(0, 0, -self.0);
+        }
+    }
+
+    /// Robertson's stiff chemical kinetics problem.
+    struct Robertson;
+
+    impl OdeSystem for Robertson {
+        fn dimension(&self) -> usize {
+            3
+        }
+        fn derivatives(&self, _t: f64, y: &[f64], dydt: &mut [f64]) {
+            let (a, b, c) = (0.04 * y[0], 1e4 * y[1] * y[2], 3e7 * y[1] * y[1]);
+            dydt[0] = -a + b;
+            dydt[1] = a - b - c;
+            dydt[2] = c;
+        }
+    }
+
+    impl JacobianSystem for Robertson {
+        fn jacobian_pattern(&self) -> Vec<(usize, usize)> {
+            vec![(0, 0), (0, 1), (0, 2), (1, 0), (1, 1), (1, 2), (2, 1)]
+        }
+        fn jacobian(&self, _t: f64, y: &[f64], jac: &mut CsrMatrix) {
+            jac.add(0, 0, -0.04);
+            jac.add(0, 1, 1e4 * y[2]);
+            jac.add(0, 2, 1e4 * y[1]);
+            jac.add(1, 0, 0.04);
+            jac.add(1, 1, -1e4 * y[2] - 6e7 * y[1]);
+            jac.add(1, 2, -1e4 * y[1]);
+            jac.add(2, 1, 6e7 * y[1]);
+        }
+    }
+
+    #[test]
+    fn decay_matches_analytic_solution() {
+        let rk = Rk45::new(1e-12, 1e-10).unwrap();
+        let mut y = vec![100.0];
+        let stats = rk
+            .integrate(&Decay(0.7), 0.0, &mut y, &[5.0], |_, _| {})
+            .unwrap();
+        assert!((y[0] - 100.0 * (-3.5f64).exp()).abs() < 1e-7);
+        assert!(stats.accepted > 0);
+    }
+
+    #[test]
+    fn dense_output_hits_requested_times() {
+        let rk = Rk45::new(1e-8, 1e-6).unwrap();
+        let times: Vec<f64> = (0..=40).map(|i| i as f64 * 0.25).collect();
+        let mut y = vec![1.0, 0.0];
+        let mut samples = Vec::new();
+        let stats = rk
+            .integrate(&Oscillator, 0.0, &mut y, &times, |i, v| {
+                samples.push((i, v.to_vec()))
+            })
+            .unwrap();
+        assert_eq!(samples.len(), times.len());
+        for (i, v) in &samples {
+            assert!((v[0] - times[*i].cos()).abs() < 1e-5, "t={}", times[*i]);
+            assert!((v[1] + times[*i].sin()).abs() < 1e-5, "t={}", times[*i]);
+        }
+        assert_eq!(samples.last().unwrap().1, y);
+
+        // Sampling does not change the step sequence.
+        let mut y_end = vec![1.0, 0.0];
+        let unsampled = rk
+            .integrate(&Oscillator, 0.0, &mut y_end, &[10.0], |_, _| {})
+            .unwrap();
+        assert_eq!(unsampled.accepted, stats.accepted);
+        assert_eq!(y_end, y);
+    }
+
+    #[test]
+    fn tighter_tolerance_is_more_accurate() {
+        let exact = (-10.0f64).exp();
+        let error = |rtol| {
+            let mut y = vec![1.0];
+            Rk45::new(0.0, rtol)
+                .unwrap()
+                .integrate(&Decay(1.0), 0.0, &mut y, &[10.0], |_, _| {})
+                .unwrap();
+            (y[0] - exact).abs() / exact
+        };
+        assert!(error(1e-9) < error(1e-4));
+    }
+
+    #[test]
+    fn rosenbrock_decay_with_dense_output() {
+        let times: Vec<f64> = (0..=10).map(|i| i as f64 * 0.5).collect();
+        let mut y = vec![100.0];
+        let mut samples = Vec::new();
+        Rosenbrock::new(1e-10, 1e-8, LuChoice::Auto)
+            .unwrap()
+            .integrate(&Decay(0.7), 0.0, &mut y, &times, |i, v| {
+                samples.push((times[i], v[0]))
+            })
+            .unwrap();
+        assert_eq!(samples.len(), times.len());
+        for (t, v) in samples {
+            let exact = 100.0 * (-0.7 * t).exp();
+            assert!(
+                (v - exact).abs() < 1e-5 * exact.max(1.0),
+                "t={t}: {v} vs {exact}"
+            );
+        }
+    }
+
+    #[test]
+    fn rosenbrock_solves_stiff_robertson() {
+        let mut results = Vec::new();
+        for lu in [LuChoice::Dense, LuChoice::Sparse] {
+            let mut y = vec![1.0, 0.0, 0.0];
+            let stats = Rosenbrock::new(1e-10, 1e-6, lu)
+                .unwrap()
+                .integrate(&Robertson, 0.0, &mut y, &[40.0], |_, _| {})
+                .unwrap();
+            // Reference values at t = 40 (Hairer & Wanner).
+            assert!((y[0] - 0.7158).abs() < 1e-3, "{y:?}");
+            assert!((y.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+            results.push((y, stats));
+        }
+        assert_eq!(results[0].1.accepted, results[1].1.accepted);
+        // An explicit method needs vastly more steps on the same problem.
+        let mut y = vec![1.0, 0.0, 0.0];
+        let explicit = Rk45::new(1e-10, 1e-6)
+            .unwrap()
+            .integrate(&Robertson, 0.0, &mut y, &[40.0], |_, _| {})
+            .unwrap();
+        assert!(explicit.accepted > 10 * results[0].1.accepted);
+    }
+
+    #[test]
+    fn method_parsing() {
+        assert_eq!(
+            Method::parse("euler", None, None, "auto").unwrap(),
+            Method::Euler
+        );
+        let Method::Rosenbrock(ros) =
+            Method::parse("rosenbrock", Some(1e-6), None, "sparse").unwrap()
+        else {
+            panic!("expected rosenbrock");
+        };
+        assert_eq!((ros.atol, ros.lu), (1e-6, LuChoice::Sparse));
+        assert!(Method::parse("bdf", None, None, "auto").is_err());
+        assert!(Method::parse("rosenbrock", None, None, "qr").is_err());
+        assert!(!Method::Euler.is_adaptive());
+    }
+
+    #[test]
+    fn rejects_bad_tolerances_and_times() {
+        assert!(Rk45::new(0.0, 0.0).is_err());
+        assert!(Rk45::new(-1.0, 1e-6).is_err());
+        let mut y = vec![1.0];
+        let rk = Rk45::default();
+        assert!(rk
+            .integrate(&Decay(1.0), 0.0, &mut y, &[2.0, 1.0], |_, _| {})
+            .is_err());
+        assert!(rk
+            .integrate(&Decay(1.0), 1.0, &mut y, &[0.5], |_, _| {})
+            .is_err());
+    }
+}
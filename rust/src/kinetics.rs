@@ -0,0 +1,562 @@
+//! Kinetic templates: named rate laws that expand into Expr trees.
+//!
+//! Native counterpart of the Interpreter's template functions
+//! (`michaelis_menten(vmax, km)` and friends). A template declares its
+//! parameters and the molecules it reads by role (`S` = first reactant,
+//! `P` = first product, `I` / `A` / `R` = first effector) and expands, for
+//! one reaction's `Binding`, into an ordinary Expr that the rate compiler
+//! lowers like any other:
+//!
+//! ```text
+//! michaelis_menten(vmax=10, km=5)  on  S -> P
+//!   → div(mul(10.0, var(0.0)), add(5.0, var(0.0)))
+//! ```
+//!
+//! Parameters are given as keyword or positional arguments; a role can be
+//! rebound to another molecule by keyword (`competitive_inhibition(..., I=X)`).
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::OnceLock;
+
+use pyo3::exceptions::PyKeyError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+
+use crate::error::{SimError, SimResult};
+use crate::expr::{Arg, Expr, PyExpr};
+use crate::reaction::{MoleculeId, Reaction};
+
+/// Where a template finds a molecule in the reaction it is bound to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Role {
+    Reactant(usize),
+    Product(usize),
+    Effector(usize),
+}
+
+impl fmt::Display for Role {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Role::Reactant(i) => write!(f, "reactant {i}"),
+            Role::Product(i) => write!(f, "product {i}"),
+            Role::Effector(i) => write!(f, "effector {i}"),
+        }
+    }
+}
+
+/// Molecules of one reaction, for binding template roles.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Binding {
+    pub reactants: Vec<(MoleculeId, f64)>,
+    pub products: Vec<(MoleculeId, f64)>,
+    pub effectors: Vec<MoleculeId>,
+}
+
+impl Binding {
+    pub fn of(reaction: &Reaction) -> Self {
+        Self {
+            reactants: reaction.reactants.clone(),
+            products: reaction.products.clone(),
+            effectors: reaction.effectors.iter().map(|&(m, _)| m).collect(),
+        }
+    }
+
+    fn get(&self, role: Role) -> Option<MoleculeId> {
+        match role {
+            Role::Reactant(i) => self.reactants.get(i).map(|&(m, _)| m),
+            Role::Product(i) => self.products.get(i).map(|&(m, _)| m),
+            Role::Effector(i) => self.effectors.get(i).copied(),
+        }
+    }
+}
+
+/// How a template's Expr is produced.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Body {
+    /// A fixed Expr over the parameter and role symbols.
+    Expr(Arg),
+    /// `k · ∏ reactant^stoich`, or with `reversible` minus `kr · ∏ product^stoich`.
+    MassAction { reversible: bool },
+}
+
+/// A named rate law with parameters and molecule roles.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    pub name: String,
+    pub description: String,
+    /// Parameter names with optional defaults, in positional order.
+    pub params: Vec<(String, Option<f64>)>,
+    /// Role symbols used by the body.
+    pub roles: Vec<(String, Role)>,
+    pub body: Body,
+}
+
+impl Template {
+    pub fn new(
+        name: impl Into<String>,
+        description: impl Into<String>,
+        params: &[(&str, Option<f64>)],
+        roles: &[(&str, Role)],
+        body: &str,
+    ) -> SimResult<Self> {
+        Ok(Self {
+            name: name.into(),
+            description: description.into(),
+            params: params.iter().map(|&(n, d)| (n.to_string(), d)).collect(),
+            roles: roles.iter().map(|&(n, r)| (n.to_string(), r)).collect(),
+            body: Body::Expr(Arg::parse(body)?),
+        })
+    }
+
+    /// Expand for `binding`, taking parameters and role overrides from `call`.
+    ///
+    /// Roles resolve to `var(<id>)`; an overriding keyword such as `I=X` is
+    /// substituted as `var(X)` and left for the rate compiler to resolve.
+    pub fn expand(&self, call: &Expr, binding: &Binding) -> SimResult<Arg> {
+        if call.args.len() > self.params.len() {
+            return Err(SimError::Value(format!(
+                "{}() takes at most {} positional arguments, got {call}",
+                self.name,
+                self.params.len()
+            )));
+        }
+        let mut values: Vec<(String, Arg)> = Vec::new();
+        for (i, (name, default)) in self.params.iter().enumerate() {
+            let value = match (call.args.get(i), call.kwarg(name)) {
+                (Some(_), Some(_)) => {
+                    return Err(SimError::Value(format!(
+                        "{}() got parameter {name:?} twice in {call}",
+                        self.name
+                    )))
+                }
+                (Some(v), None) | (None, Some(v)) => v.clone(),
+                (None, None) => match default {
+                    Some(d) => Arg::Number(*d),
+                    None => {
+                        return Err(SimError::Value(format!(
+                            "{}() is missing parameter {name:?} in {call}",
+                            self.name
+                        )))
+                    }
+                },
+            };
+            values.push((name.clone(), value));
+        }
+        for (symbol, role) in &self.roles {
+            let molecule = match call.kwarg(symbol) {
+                Some(Arg::Str(name)) => Arg::Str(name.clone()),
+                Some(Arg::Number(id)) => Arg::Number(*id),
+                Some(other) => {
+                    return Err(SimError::Value(format!(
+                        "{}(): {symbol} must name a molecule, got {other}",
+                        self.name
+                    )))
+                }
+                None => match binding.get(*role) {
+                    Some(id) => Arg::Number(id as f64),
+                    None => {
+                        return Err(SimError::Value(format!(
+                            "{}() needs {symbol} ({role}); bind it with {symbol}=<molecule>",
+                            self.name
+                        )))
+                    }
+                },
+            };
+            values.push((symbol.clone(), Arg::Expr(Expr::new("var", vec![molecule]))));
+        }
+        if let Some((key, _)) = call
+            .kwargs
+            .iter()
+            .find(|(k, _)| !values.iter().any(|(n, _)| n == k))
+        {
+            return Err(SimError::Value(format!(
+                "{}() got an unexpected keyword argument {key:?}",
+                self.name
+            )));
+        }
+        let lookup = |name: &str| values.iter().find(|(n, _)| n == name).map(|(_, v)| v);
+        match &self.body {
+            Body::Expr(body) => Ok(substitute(body, &lookup)),
+            Body::MassAction { reversible } => {
+                let side = |k: &Arg, molecules: &[(MoleculeId, f64)]| {
+                    let mut factors = vec![k.clone()];
+                    for &(m, s) in molecules {
+                        let var = Arg::Expr(Expr::new("var", vec![Arg::Number(m as f64)]));
+                        factors.push(if s == 1.0 {
+                            var
+                        } else {
+                            Arg::Expr(Expr::new("power", vec![var, Arg::Number(s)]))
+                        });
+                    }
+                    Arg::Expr(Expr::new("mul", factors))
+                };
+                let forward = side(lookup(&self.params[0].0).unwrap(), &binding.reactants);
+                if !reversible {
+                    return Ok(forward);
+                }
+                let reverse = side(lookup(&self.params[1].0).unwrap(), &binding.products);
+                Ok(Arg::Expr(Expr::new("sub", vec![forward, reverse])))
+            }
+        }
+    }
+}
+
+/// Replace template symbols (bare names and zero-argument calls) in `arg`.
+fn substitute<'a>(arg: &Arg, lookup: &impl Fn(&str) -> Option<&'a Arg>) -> Arg {
+    match arg {
+        Arg::Str(name) => lookup(name).cloned().unwrap_or_else(|| arg.clone()),
+        Arg::Expr(expr) if expr.args.is_empty() && expr.kwargs.is_empty() => {
+            lookup(&expr.head).cloned().unwrap_or_else(|| arg.clone())
+        }
+        Arg::Expr(expr) => {
+            let mut out = Expr::new(
+                expr.head.clone(),
+                expr.args.iter().map(|a| substitute(a, lookup)).collect(),
+            );
+            for (k, v) in &expr.kwargs {
+                out.kwargs.push((k.clone(), substitute(v, lookup)));
+            }
+            Arg::Expr(out)
+        }
+        other => other.clone(),
+    }
+}
+
+/// Templates by name.
+#[derive(Debug, Clone, Default)]
+pub struct TemplateRegistry {
+    templates: BTreeMap<String, Template>,
+}
+
+impl TemplateRegistry {
+    /// The built-in kinetic laws.
+    pub fn builtin() -> Self {
+        use Role::*;
+        let s = ("S", Reactant(0));
+        let i = ("I", Effector(0));
+        let mut registry = Self::default();
+        let expr_templates = [
+            Template::new(
+                "michaelis_menten",
+                "Michaelis–Menten: vmax·S / (km + S)",
+                &[("vmax", None), ("km", None)],
+                &[s],
+                "div(mul(vmax, S), add(km, S))",
+            ),
+            Template::new(
+                "hill_activation",
+                "Hill activation by effector A: vmax·Aⁿ / (kⁿ + Aⁿ)",
+                &[("vmax", None), ("k", None), ("n", Some(1.0))],
+                &[("A", Effector(0))],
+                "div(mul(vmax, power(A, n)), add(power(k, n), power(A, n)))",
+            ),
+            Template::new(
+                "hill_repression",
+                "Hill repression by effector R: vmax·kⁿ / (kⁿ + Rⁿ)",
+                &[("vmax", None), ("k", None), ("n", Some(1.0))],
+                &[("R", Effector(0))],
+                "div(mul(vmax, power(k, n)), add(power(k, n), power(R, n)))",
+            ),
+            Template::new(
+                "competitive_inhibition",
+                "Competitive inhibition by effector I: vmax·S / (km·(1 + I/ki) + S)",
+                &[("vmax", None), ("km", None), ("ki", None)],
+                &[s, i],
+                "div(mul(vmax, S), add(mul(km, add(1, div(I, ki))), S))",
+            ),
+            Template::new(
+                "uncompetitive_inhibition",
+                "Uncompetitive inhibition by effector I: vmax·S / (km + S·(1 + I/ki))",
+                &[("vmax", None), ("km", None), ("ki", None)],
+                &[s, i],
+                "div(mul(vmax, S), add(km, mul(S, add(1, div(I, ki)))))",
+            ),
+            Template::new(
+                "noncompetitive_inhibition",
+                "Non-competitive inhibition by effector I: vmax·S / ((km + S)·(1 + I/ki))",
+                &[("vmax", None), ("km", None), ("ki", None)],
+                &[s, i],
+                "div(mul(vmax, S), mul(add(km, S), add(1, div(I, ki))))",
+            ),
+            Template::new(
+                "substrate_inhibition",
+                "Substrate inhibition: vmax·S / (km + S + S²/ksi)",
+                &[("vmax", None), ("km", None), ("ksi", None)],
+                &[s],
+                "div(mul(vmax, S), add(km, S, div(power(S, 2), ksi)))",
+            ),
+        ];
+        for template in expr_templates {
+            registry.register(template.expect("built-in template parses"));
+        }
+        registry.register(Template {
+            name: "mass_action".into(),
+            description: "Mass action: k·∏ reactantˢ".into(),
+            params: vec![("k".into(), None)],
+            roles: Vec::new(),
+            body: Body::MassAction { reversible: false },
+        });
+        registry.register(Template {
+            name: "reversible_mass_action".into(),
+            description: "Reversible mass action: kf·∏ reactantˢ − kr·∏ productˢ".into(),
+            params: vec![("kf".into(), None), ("kr".into(), None)],
+            roles: Vec::new(),
+            body: Body::MassAction { reversible: true },
+        });
+        registry
+    }
+
+    pub fn register(&mut self, template: Template) {
+        self.templates.insert(template.name.clone(), template);
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Template> {
+        self.templates.get(name)
+    }
+
+    pub fn templates(&self) -> impl Iterator<Item = &Template> {
+        self.templates.values()
+    }
+}
+
+/// The shared built-in registry.
+pub fn builtins() -> &'static TemplateRegistry {
+    static REGISTRY: OnceLock<TemplateRegistry> = OnceLock::new();
+    REGISTRY.get_or_init(TemplateRegistry::builtin)
+}
+
+/// A built-in kinetic template.
+#[pyclass(name = "KineticTemplate", module = "alienbio_sim")]
+pub struct PyKineticTemplate {
+    template: Template,
+}
+
+#[pymethods]
+impl PyKineticTemplate {
+    #[getter]
+    fn name(&self) -> &str {
+        &self.template.name
+    }
+
+    #[getter]
+    fn description(&self) -> &str {
+        &self.template.description
+    }
+
+    /// Parameter names and defaults (None = required), in positional order.
+    #[getter]
+    fn params<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (name, default) in &self.template.params {
+            dict.set_item(name, default)?;
+        }
+        Ok(dict)
+    }
+
+    /// Role symbols and the molecules they bind to, e.g. {"S": "reactant 0"}.
+    #[getter]
+    fn roles<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        for (symbol, role) in &self.template.roles {
+            dict.set_item(symbol, role.to_string())?;
+        }
+        Ok(dict)
+    }
+
+    /// Expand into an Expr for a reaction with the given molecule IDs.
+    ///
+    /// Args:
+    ///     reactants: {molecule_id: stoichiometry} (or a list of IDs)
+    ///     products: {molecule_id: stoichiometry} (or a list of IDs)
+    ///     effectors: Effector molecule IDs
+    ///     **kwargs: Parameters and role overrides
+    #[pyo3(signature = (reactants=None, products=None, effectors=None, **kwargs))]
+    fn expand(
+        &self,
+        reactants: Option<&PyAny>,
+        products: Option<&PyAny>,
+        effectors: Option<Vec<MoleculeId>>,
+        kwargs: Option<&PyDict>,
+    ) -> PyResult<PyExpr> {
+        let side = |obj: Option<&PyAny>| -> PyResult<Vec<(MoleculeId, f64)>> {
+            match obj {
+                None => Ok(Vec::new()),
+                Some(obj) if obj.downcast::<PyDict>().is_ok() => {
+                    crate::reaction::extract_stoichiometry(obj)
+                }
+                Some(obj) => Ok(obj
+                    .extract::<Vec<MoleculeId>>()?
+                    .into_iter()
+                    .map(|m| (m, 1.0))
+                    .collect()),
+            }
+        };
+        let binding = Binding {
+            reactants: side(reactants)?,
+            products: side(products)?,
+            effectors: effectors.unwrap_or_default(),
+        };
+        let mut call = Expr::new(self.template.name.clone(), Vec::new());
+        for (k, v) in kwargs.into_iter().flatten() {
+            call.kwargs.push((k.extract()?, Arg::from_py(v, false)?));
+        }
+        match self.template.expand(&call, &binding)? {
+            Arg::Expr(expr) => Ok(PyExpr { expr }),
+            other => Ok(PyExpr {
+                expr: Expr::new("const", vec![other]),
+            }),
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        let params: Vec<String> = self
+            .template
+            .params
+            .iter()
+            .map(|(n, d)| match d {
+                Some(d) => format!("{n}={d:?}"),
+                None => n.clone(),
+            })
+            .collect();
+        format!(
+            "KineticTemplate({}({}))",
+            self.template.name,
+            params.join(", ")
+        )
+    }
+}
+
+/// Built-in kinetic templates by name.
+#[pyfunction]
+pub fn kinetic_templates(py: Python<'_>) -> PyResult<&PyDict> {
+    let dict = PyDict::new(py);
+    for template in builtins().templates() {
+        let py_template = PyKineticTemplate {
+            template: template.clone(),
+        };
+        dict.set_item(&template.name, Py::new(py, py_template)?)?;
+    }
+    Ok(dict)
+}
+
+/// Look up one built-in kinetic template.
+#[pyfunction]
+pub fn kinetic_template(name: &str) -> PyResult<PyKineticTemplate> {
+    builtins()
+        .get(name)
+        .map(|template| PyKineticTemplate {
+            template: template.clone(),
+        })
+        .ok_or_else(|| PyKeyError::new_err(format!("Unknown kinetic template: {name:?}")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chemistry::MoleculeIndex;
+    use crate::rate::RateExpr;
+
+    fn binding() -> Binding {
+        Binding {
+            reactants: vec![(0, 1.0)],
+            products: vec![(1, 1.0)],
+            effectors: vec![2],
+        }
+    }
+
+    fn rate(source: &str, binding: &Binding) -> SimResult<RateExpr> {
+        let molecules = MoleculeIndex::new(vec!["S".into(), "P".into(), "E".into(), "X".into()]);
+        RateExpr::for_reaction(&Arg::parse(source)?, &molecules, Vec::new(), binding)
+    }
+
+    #[test]
+    fn expands_with_roles_bound_to_ids() {
+        let call = Expr::parse("michaelis_menten(vmax=10, km=5)").unwrap();
+        let expr = builtins()
+            .get("michaelis_menten")
+            .unwrap()
+            .expand(&call, &binding())
+            .unwrap();
+        assert_eq!(
+            expr.to_string(),
+            "div(mul(10.0, var(0.0)), add(5.0, var(0.0)))"
+        );
+    }
+
+    #[test]
+    fn every_template_matches_its_formula() {
+        let (s, e) = (4.0, 3.0);
+        let conc = [s, 0.5, e, 0.0];
+        let cases = [
+            ("michaelis_menten(10, 2)", 10.0 * s / (2.0 + s)),
+            (
+                "hill_activation(vmax=2, k=3, n=2)",
+                2.0 * e * e / (9.0 + e * e),
+            ),
+            (
+                "hill_repression(vmax=2, k=3, n=2)",
+                2.0 * 9.0 / (9.0 + e * e),
+            ),
+            ("hill_activation(vmax=1, k=1)", e / (1.0 + e)),
+            (
+                "competitive_inhibition(vmax=10, km=2, ki=1.5)",
+                10.0 * s / (2.0 * (1.0 + e / 1.5) + s),
+            ),
+            (
+                "uncompetitive_inhibition(vmax=10, km=2, ki=1.5)",
+                10.0 * s / (2.0 + s * (1.0 + e / 1.5)),
+            ),
+            (
+                "noncompetitive_inhibition(vmax=10, km=2, ki=1.5)",
+                10.0 * s / ((2.0 + s) * (1.0 + e / 1.5)),
+            ),
+            (
+                "substrate_inhibition(vmax=10, km=2, ksi=8)",
+                10.0 * s / (2.0 + s + s * s / 8.0),
+            ),
+            ("mass_action(k=0.5)", 0.5 * s),
+            ("reversible_mass_action(kf=0.5, kr=2)", 0.5 * s - 2.0 * 0.5),
+        ];
+        for (source, expected) in cases {
+            let compiled = rate(source, &binding()).unwrap();
+            let got = compiled.eval(&conc);
+            assert!(
+                (got - expected).abs() < 1e-12,
+                "{source}: {got} vs {expected}"
+            );
+        }
+    }
+
+    #[test]
+    fn stoichiometry_and_role_overrides() {
+        let dimer = Binding {
+            reactants: vec![(0, 2.0)],
+            products: vec![(1, 1.0), (3, 1.0)],
+            effectors: Vec::new(),
+        };
+        let conc = [3.0, 2.0, 0.0, 5.0];
+        let reversible = rate("reversible_mass_action(1, 0.1)", &dimer).unwrap();
+        assert!((reversible.eval(&conc) - (9.0 - 0.1 * 2.0 * 5.0)).abs() < 1e-12);
+        assert_eq!(reversible.variables(), &[0, 1, 3]);
+
+        // No effector to bind I to, unless it is named.
+        assert!(rate("competitive_inhibition(1, 1, 1)", &dimer).is_err());
+        let named = rate("competitive_inhibition(1, 1, 1, I=X)", &dimer).unwrap();
+        assert_eq!(named.variables(), &[0, 3]);
+    }
+
+    #[test]
+    fn invalid_calls_rejected() {
+        for bad in [
+            "michaelis_menten(vmax=1)",
+            "michaelis_menten(1, 2, 3)",
+            "michaelis_menten(1, vmax=2)",
+            "michaelis_menten(1, 2, q=3)",
+            "michaelis_menten(1, 2, S=add(1, 2))",
+            "no_such_law(k=1)",
+        ] {
+            assert!(rate(bad, &binding()).is_err(), "{bad}");
+        }
+    }
+}
@@ -1,11 +1,81 @@
 // Alien Biology Rust Simulator
-// Placeholder - to be implemented in Milestone 12
+//
+// Native counterparts of the `alienbio.bio` simulation classes.
+
+// pyo3 0.20's #[pymethods] expansion trips this lint on newer compilers.
+#![allow(non_local_definitions)]
 
 use pyo3::prelude::*;
 
+pub mod bytecode;
+pub mod chemistry;
+pub mod codegen;
+pub mod derivative;
+pub mod error;
+pub mod events;
+pub mod expr;
+pub mod fixture;
+pub mod flow;
+pub mod growth;
+pub mod hybrid;
+pub mod integrate;
+pub mod kinetics;
+pub mod linalg;
+pub mod rate;
+pub mod reaction;
+pub mod remodel;
+pub mod script;
+pub mod simulator;
+pub mod ssa;
+pub mod stochastic;
+pub mod tree;
+pub mod world_simulator;
+pub mod world_state;
+
+pub use bytecode::{Instr, Program};
+pub use chemistry::MoleculeIndex;
+pub use codegen::Language;
+pub use derivative::{Derivatives, Wrt};
+pub use error::{SimError, SimResult};
+pub use events::{Action, Event, Schedule, Site};
+pub use expr::{Arg, Expr};
+pub use fixture::{Fixture, FixtureFlow, Tolerance};
+pub use flow::{DiffusionFlow, GeneralFlow, InstanceFlow, MembraneFlow};
+pub use growth::Growth;
+pub use hybrid::{Hybrid, HybridSimulator, Partition};
+pub use integrate::{JacobianSystem, Method, OdeSystem, Rk45, Rosenbrock};
+pub use kinetics::{Binding, Template, TemplateRegistry};
+pub use linalg::{CsrMatrix, LuChoice};
+pub use rate::{Formula, Op, RateExpr};
+pub use reaction::{MoleculeId, RateLaw, Reaction};
+pub use remodel::{Remap, Remodel, Remodeled, Split};
+pub use script::{Script, ScriptFlow, ScriptRate};
+pub use simulator::ChemistrySimulator;
+pub use ssa::SsaMethod;
+pub use stochastic::{StochasticModel, StochasticSimulator};
+pub use tree::{CompartmentTree, Topology};
+pub use world_simulator::{WorldModel, WorldSimulator};
+pub use world_state::WorldState;
+
 /// A Python module implemented in Rust.
 #[pymodule]
 fn alienbio_sim(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add("__version__", "0.1.0")?;
+    m.add_class::<CompartmentTree>()?;
+    m.add_class::<WorldState>()?;
+    m.add_class::<WorldSimulator>()?;
+    m.add_class::<ChemistrySimulator>()?;
+    m.add_class::<StochasticSimulator>()?;
+    m.add_class::<HybridSimulator>()?;
+    m.add_class::<fixture::PyFixture>()?;
+    m.add_class::<expr::PyExpr>()?;
+    m.add_class::<rate::PyCompiledRate>()?;
+    m.add_class::<kinetics::PyKineticTemplate>()?;
+    m.add_function(wrap_pyfunction!(kinetics::kinetic_templates, m)?)?;
+    m.add_function(wrap_pyfunction!(kinetics::kinetic_template, m)?)?;
+    m.add_function(wrap_pyfunction!(codegen::py_to_code, m)?)?;
+    m.add_function(wrap_pyfunction!(codegen::chemistry_to_rust, m)?)?;
+    m.add_function(wrap_pyfunction!(script::enable_rhai, m)?)?;
+    m.add_function(wrap_pyfunction!(script::rhai_enabled, m)?)?;
     Ok(())
 }
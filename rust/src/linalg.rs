@@ -0,0 +1,447 @@
+//! Sparse matrices and LU factorization for the implicit integrators.
+//!
+//! Reaction Jacobians are block-sparse: a molecule's rate of change depends
+//! only on the molecules of the reactions it takes part in, within its own
+//! compartment. Small systems are factored densely; large ones keep the
+//! sparsity and eliminate row by row with partial pivoting.
+
+use crate::error::{SimError, SimResult};
+
+/// Systems up to this size use dense LU under `LuChoice::Auto`.
+pub const DENSE_LU_LIMIT: usize = 100;
+
+/// Square matrix in compressed sparse row form with a fixed pattern.
+#[derive(Debug, Clone, PartialEq)]
+pub struct CsrMatrix {
+    n: usize,
+    row_ptr: Vec<usize>,
+    cols: Vec<usize>,
+    values: Vec<f64>,
+}
+
+impl CsrMatrix {
+    /// Zero matrix with the given structural nonzeros (duplicates are merged).
+    pub fn from_pattern(n: usize, entries: impl IntoIterator<Item = (usize, usize)>) -> Self {
+        let mut rows = vec![Vec::new(); n];
+        for (row, col) in entries {
+            rows[row].push(col);
+        }
+        let mut row_ptr = Vec::with_capacity(n + 1);
+        let mut cols = Vec::new();
+        row_ptr.push(0);
+        for mut row in rows {
+            row.sort_unstable();
+            row.dedup();
+            cols.extend(row);
+            row_ptr.push(cols.len());
+        }
+        let values = vec![0.0; cols.len()];
+        Self {
+            n,
+            row_ptr,
+            cols,
+            values,
+        }
+    }
+
+    pub fn dimension(&self) -> usize {
+        self.n
+    }
+
+    pub fn nnz(&self) -> usize {
+        self.cols.len()
+    }
+
+    /// `(column, value)` pairs of one row, in column order.
+    pub fn row(&self, row: usize) -> impl Iterator<Item = (usize, f64)> + '_ {
+        let range = self.row_ptr[row]..self.row_ptr[row + 1];
+        self.cols[range.clone()]
+            .iter()
+            .copied()
+            .zip(self.values[range].iter().copied())
+    }
+
+    fn position(&self, row: usize, col: usize) -> Option<usize> {
+        let start = self.row_ptr[row];
+        self.cols[start..self.row_ptr[row + 1]]
+            .binary_search(&col)
+            .ok()
+            .map(|i| start + i)
+    }
+
+    pub fn get(&self, row: usize, col: usize) -> f64 {
+        self.position(row, col).map_or(0.0, |i| self.values[i])
+    }
+
+    /// Add `value` at `(row, col)`, which must be in the pattern.
+    pub fn add(&mut self, row: usize, col: usize, value: f64) {
+        let i = self
+            .position(row, col)
+            .unwrap_or_else(|| panic!("({row}, {col}) is not in the sparsity pattern"));
+        self.values[i] += value;
+    }
+
+    pub fn clear(&mut self) {
+        self.values.fill(0.0);
+    }
+
+    /// `self = identity - scale * other`, where `other` shares this pattern minus the diagonal.
+    pub fn set_identity_minus(&mut self, scale: f64, other: &CsrMatrix) {
+        self.clear();
+        for row in 0..self.n {
+            self.add(row, row, 1.0);
+            for (col, value) in other.row(row) {
+                self.add(row, col, -scale * value);
+            }
+        }
+    }
+}
+
+/// Which LU factorization to use.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum LuChoice {
+    /// Dense up to `DENSE_LU_LIMIT` unknowns, sparse above.
+    #[default]
+    Auto,
+    Dense,
+    Sparse,
+}
+
+impl LuChoice {
+    pub fn parse(name: &str) -> SimResult<Self> {
+        match name {
+            "auto" => Ok(LuChoice::Auto),
+            "dense" => Ok(LuChoice::Dense),
+            "sparse" => Ok(LuChoice::Sparse),
+            _ => Err(SimError::Value(format!(
+                "Unknown LU choice {name:?} (expected 'auto', 'dense' or 'sparse')"
+            ))),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            LuChoice::Auto => "auto",
+            LuChoice::Dense => "dense",
+            LuChoice::Sparse => "sparse",
+        }
+    }
+}
+
+fn singular(col: usize) -> SimError {
+    SimError::Value(format!(
+        "Singular matrix in LU factorization (column {col})"
+    ))
+}
+
+/// Dense LU with partial pivoting, row-major.
+#[derive(Debug, Clone)]
+pub struct DenseLu {
+    n: usize,
+    lu: Vec<f64>,
+    pivots: Vec<usize>,
+}
+
+impl DenseLu {
+    pub fn factor(matrix: &CsrMatrix) -> SimResult<Self> {
+        let n = matrix.dimension();
+        let mut lu = vec![0.0; n * n];
+        for row in 0..n {
+            for (col, value) in matrix.row(row) {
+                lu[row * n + col] = value;
+            }
+        }
+        let mut pivots = vec![0; n];
+        for k in 0..n {
+            let p = (k..n)
+                .max_by(|&a, &b| lu[a * n + k].abs().total_cmp(&lu[b * n + k].abs()))
+                .unwrap_or(k);
+            let pivot = lu[p * n + k];
+            if pivot == 0.0 || !pivot.is_finite() {
+                return Err(singular(k));
+            }
+            pivots[k] = p;
+            if p != k {
+                for col in 0..n {
+                    lu.swap(k * n + col, p * n + col);
+                }
+            }
+            for row in k + 1..n {
+                let factor = lu[row * n + k] / pivot;
+                if factor == 0.0 {
+                    continue;
+                }
+                lu[row * n + k] = factor;
+                for col in k + 1..n {
+                    lu[row * n + col] -= factor * lu[k * n + col];
+                }
+            }
+        }
+        Ok(Self { n, lu, pivots })
+    }
+
+    /// Solve `A x = b` in place.
+    pub fn solve(&self, b: &mut [f64]) {
+        let n = self.n;
+        for k in 0..n {
+            b.swap(k, self.pivots[k]);
+        }
+        for row in 1..n {
+            let sum: f64 = (0..row).map(|col| self.lu[row * n + col] * b[col]).sum();
+            b[row] -= sum;
+        }
+        for row in (0..n).rev() {
+            let sum: f64 = (row + 1..n)
+                .map(|col| self.lu[row * n + col] * b[col])
+                .sum();
+            b[row] = (b[row] - sum) / self.lu[row * n + row];
+        }
+    }
+}
+
+/// Sparse LU by row elimination with partial pivoting.
+///
+/// Columns keep their natural order; at step `k` the remaining row with the
+/// largest entry in column `k` becomes pivot row `k`, and only rows with a
+/// structural entry in that column are updated.
+#[derive(Debug, Clone)]
+pub struct SparseLu {
+    /// Original row index of pivot `k`.
+    perm: Vec<usize>,
+    /// Upper factor: pivot row `k` as `(column, value)`, diagonal first.
+    upper: Vec<Vec<(usize, f64)>>,
+    /// Multipliers applied at step `k`: `(original row, factor)`.
+    lower: Vec<Vec<(usize, f64)>>,
+}
+
+impl SparseLu {
+    pub fn factor(matrix: &CsrMatrix) -> SimResult<Self> {
+        let n = matrix.dimension();
+        let mut rows: Vec<Vec<(usize, f64)>> = (0..n)
+            .map(|r| matrix.row(r).filter(|&(_, v)| v != 0.0).collect())
+            .collect();
+        let mut col_rows: Vec<Vec<usize>> = vec![Vec::new(); n];
+        for (r, row) in rows.iter().enumerate() {
+            for &(c, _) in row {
+                col_rows[c].push(r);
+            }
+        }
+        let mut done = vec![false; n];
+        let mut perm = Vec::with_capacity(n);
+        let mut upper = Vec::with_capacity(n);
+        let mut lower = Vec::with_capacity(n);
+        let leading = |row: &[(usize, f64)], k: usize| match row.first() {
+            Some(&(c, v)) if c == k => v,
+            _ => 0.0,
+        };
+
+        for k in 0..n {
+            let mut candidates = Vec::new();
+            for r in std::mem::take(&mut col_rows[k]) {
+                if done[r] || rows[r].first().map(|&(c, _)| c) != Some(k) {
+                    continue;
+                }
+                if leading(&rows[r], k) == 0.0 {
+                    // Cancelled to zero: nothing to eliminate in this row.
+                    rows[r].remove(0);
+                } else {
+                    candidates.push(r);
+                }
+            }
+            let p = candidates
+                .iter()
+                .copied()
+                .max_by(|&a, &b| {
+                    leading(&rows[a], k)
+                        .abs()
+                        .total_cmp(&leading(&rows[b], k).abs())
+                })
+                .ok_or_else(|| singular(k))?;
+            done[p] = true;
+            let pivot_row = std::mem::take(&mut rows[p]);
+            let pivot = pivot_row[0].1;
+            if !pivot.is_finite() {
+                return Err(singular(k));
+            }
+
+            let mut multipliers = Vec::new();
+            for &r in &candidates {
+                if r == p {
+                    continue;
+                }
+                let factor = leading(&rows[r], k) / pivot;
+                multipliers.push((r, factor));
+                let merged = subtract_scaled(&rows[r][1..], &pivot_row[1..], factor);
+                for &(c, _) in &merged {
+                    if rows[r].binary_search_by_key(&c, |&(c, _)| c).is_err() {
+                        col_rows[c].push(r);
+                    }
+                }
+                rows[r] = merged;
+            }
+            perm.push(p);
+            upper.push(pivot_row);
+            lower.push(multipliers);
+        }
+        Ok(Self { perm, upper, lower })
+    }
+
+    /// Solve `A x = b` in place.
+    pub fn solve(&self, b: &mut [f64]) {
+        let n = self.perm.len();
+        let mut y = vec![0.0; n];
+        for k in 0..n {
+            y[k] = b[self.perm[k]];
+            for &(r, factor) in &self.lower[k] {
+                b[r] -= factor * y[k];
+            }
+        }
+        for k in (0..n).rev() {
+            let row = &self.upper[k];
+            let sum: f64 = row[1..].iter().map(|&(c, v)| v * y[c]).sum();
+            y[k] = (y[k] - sum) / row[0].1;
+        }
+        b.copy_from_slice(&y);
+    }
+}
+
+/// `a - factor * b` for sorted sparse rows.
+fn subtract_scaled(a: &[(usize, f64)], b: &[(usize, f64)], factor: f64) -> Vec<(usize, f64)> {
+    let mut out = Vec::with_capacity(a.len() + b.len());
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        match (a.get(i), b.get(j)) {
+            (Some(&(ca, va)), Some(&(cb, vb))) if ca == cb => {
+                out.push((ca, va - factor * vb));
+                i += 1;
+                j += 1;
+            }
+            (Some(&(ca, va)), Some(&(cb, _))) if ca < cb => {
+                out.push((ca, va));
+                i += 1;
+            }
+            (Some(&(ca, va)), None) => {
+                out.push((ca, va));
+                i += 1;
+            }
+            (_, Some(&(cb, vb))) => {
+                out.push((cb, -factor * vb));
+                j += 1;
+            }
+            (None, None) => unreachable!(),
+        }
+    }
+    out
+}
+
+/// A factored matrix, dense or sparse.
+#[derive(Debug, Clone)]
+pub enum Lu {
+    Dense(DenseLu),
+    Sparse(SparseLu),
+}
+
+impl Lu {
+    pub fn factor(choice: LuChoice, matrix: &CsrMatrix) -> SimResult<Self> {
+        let dense = match choice {
+            LuChoice::Auto => matrix.dimension() <= DENSE_LU_LIMIT,
+            LuChoice::Dense => true,
+            LuChoice::Sparse => false,
+        };
+        if dense {
+            DenseLu::factor(matrix).map(Lu::Dense)
+        } else {
+            SparseLu::factor(matrix).map(Lu::Sparse)
+        }
+    }
+
+    pub fn solve(&self, b: &mut [f64]) {
+        match self {
+            Lu::Dense(lu) => lu.solve(b),
+            Lu::Sparse(lu) => lu.solve(b),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matrix(n: usize, entries: &[(usize, usize, f64)]) -> CsrMatrix {
+        let mut m = CsrMatrix::from_pattern(n, entries.iter().map(|&(r, c, _)| (r, c)));
+        for &(r, c, v) in entries {
+            m.add(r, c, v);
+        }
+        m
+    }
+
+    fn multiply(m: &CsrMatrix, x: &[f64]) -> Vec<f64> {
+        (0..m.dimension())
+            .map(|r| m.row(r).map(|(c, v)| v * x[c]).sum())
+            .collect()
+    }
+
+    #[test]
+    fn dense_and_sparse_agree_with_pivoting() {
+        // Zero on the leading diagonal forces a row swap.
+        let m = matrix(
+            4,
+            &[
+                (0, 1, 2.0),
+                (0, 3, 1.0),
+                (1, 0, 3.0),
+                (1, 1, 1.0),
+                (2, 2, 4.0),
+                (2, 0, -1.0),
+                (3, 3, 5.0),
+                (3, 1, 1.0),
+            ],
+        );
+        let x = vec![1.0, -2.0, 0.5, 3.0];
+        let b = multiply(&m, &x);
+        for choice in [LuChoice::Dense, LuChoice::Sparse] {
+            let lu = Lu::factor(choice, &m).unwrap();
+            let mut solution = b.clone();
+            lu.solve(&mut solution);
+            for (got, want) in solution.iter().zip(&x) {
+                assert!((got - want).abs() < 1e-12, "{choice:?}: {solution:?}");
+            }
+        }
+    }
+
+    #[test]
+    fn sparse_handles_fill_in() {
+        // Arrow matrix: eliminating the dense first row fills everything.
+        let n = 6;
+        let mut entries = vec![];
+        for i in 0..n {
+            entries.push((i, i, 4.0 + i as f64));
+            if i > 0 {
+                entries.push((0, i, 1.0));
+                entries.push((i, 0, 1.0));
+            }
+        }
+        let m = matrix(n, &entries);
+        let x: Vec<f64> = (0..n).map(|i| i as f64 - 2.5).collect();
+        let mut b = multiply(&m, &x);
+        SparseLu::factor(&m).unwrap().solve(&mut b);
+        for (got, want) in b.iter().zip(&x) {
+            assert!((got - want).abs() < 1e-12);
+        }
+    }
+
+    #[test]
+    fn singular_matrix_rejected() {
+        let m = matrix(2, &[(0, 0, 1.0), (1, 0, 2.0)]);
+        assert!(DenseLu::factor(&m).is_err());
+        assert!(SparseLu::factor(&m).is_err());
+    }
+
+    #[test]
+    fn identity_minus_scaled() {
+        let j = matrix(2, &[(0, 1, 2.0), (1, 1, -1.0)]);
+        let mut w = CsrMatrix::from_pattern(2, [(0, 0), (0, 1), (1, 1)]);
+        w.set_identity_minus(0.5, &j);
+        assert_eq!((w.get(0, 0), w.get(0, 1), w.get(1, 1)), (1.0, -1.0, 1.5));
+    }
+}
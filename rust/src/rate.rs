@@ -0,0 +1,768 @@
+//! Rate expressions: Expr rate laws compiled to native functions.
+//!
+//! An `Expr` (see `expr`) is lowered against the molecule names of a
+//! chemistry and a list of named parameters into a `Formula`, then compiled
+//! into a closure over one compartment's concentration slice and into
+//! bytecode (see `bytecode`) for evaluation across compartments. Booleans are
+//! `1.0` / `0.0`, and any non-zero value counts as true.
+//!
+//! Variables are written `var(S)`, or as bare names (`div(S, add(S, 0.5))`);
+//! parameters shadow molecules of the same name, and `var(2)` refers to
+//! molecule ID 2 directly. Heads naming a kinetic template
+//! (`michaelis_menten(vmax=10, km=5)`) are expanded first, see `kinetics`.
+//! Partial derivatives are compiled on demand, see `derivative`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::fmt;
+use std::sync::{Arc, OnceLock};
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+
+use crate::bytecode::Program;
+use crate::chemistry::MoleculeIndex;
+use crate::codegen::{self, Language};
+use crate::derivative::{Derivatives, Wrt};
+use crate::error::{SimError, SimResult};
+use crate::expr::{Arg, Expr};
+use crate::kinetics::{self, Binding, TemplateRegistry};
+use crate::reaction::MoleculeId;
+use crate::tree::CompartmentId;
+use crate::world_state::WorldState;
+
+/// Operations available as Expr heads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Power,
+    Neg,
+    Exp,
+    Log,
+    Min,
+    Max,
+    If,
+    Gt,
+    Lt,
+    Ge,
+    Le,
+    Eq,
+    And,
+    Or,
+    Not,
+}
+
+impl Op {
+    pub const ALL: [Op; 19] = [
+        Op::Add,
+        Op::Sub,
+        Op::Mul,
+        Op::Div,
+        Op::Power,
+        Op::Neg,
+        Op::Exp,
+        Op::Log,
+        Op::Min,
+        Op::Max,
+        Op::If,
+        Op::Gt,
+        Op::Lt,
+        Op::Ge,
+        Op::Le,
+        Op::Eq,
+        Op::And,
+        Op::Or,
+        Op::Not,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Op::Add => "add",
+            Op::Sub => "sub",
+            Op::Mul => "mul",
+            Op::Div => "div",
+            Op::Power => "power",
+            Op::Neg => "neg",
+            Op::Exp => "exp",
+            Op::Log => "log",
+            Op::Min => "min",
+            Op::Max => "max",
+            Op::If => "if",
+            Op::Gt => "gt",
+            Op::Lt => "lt",
+            Op::Ge => "ge",
+            Op::Le => "le",
+            Op::Eq => "eq",
+            Op::And => "and",
+            Op::Or => "or",
+            Op::Not => "not",
+        }
+    }
+
+    pub fn from_head(head: &str) -> Option<Op> {
+        Op::ALL.into_iter().find(|op| op.name() == head)
+    }
+
+    /// Minimum and maximum argument count (`None` = any number).
+    pub fn arity(self) -> (usize, Option<usize>) {
+        match self {
+            Op::Add | Op::Mul | Op::Min | Op::Max | Op::And | Op::Or => (1, None),
+            Op::Neg | Op::Exp | Op::Log | Op::Not => (1, Some(1)),
+            Op::If => (3, Some(3)),
+            _ => (2, Some(2)),
+        }
+    }
+
+    /// Apply to already-evaluated arguments (`If` included, non-lazily).
+    pub fn apply(self, args: &[f64]) -> f64 {
+        let truth = |b: bool| if b { 1.0 } else { 0.0 };
+        match self {
+            Op::Add => args.iter().sum(),
+            Op::Mul => args.iter().product(),
+            Op::Sub => args[0] - args[1],
+            Op::Div => args[0] / args[1],
+            Op::Power => args[0].powf(args[1]),
+            Op::Neg => -args[0],
+            Op::Exp => args[0].exp(),
+            Op::Log => args[0].ln(),
+            Op::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Op::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max),
+            Op::If => {
+                if args[0] != 0.0 {
+                    args[1]
+                } else {
+                    args[2]
+                }
+            }
+            Op::Gt => truth(args[0] > args[1]),
+            Op::Lt => truth(args[0] < args[1]),
+            Op::Ge => truth(args[0] >= args[1]),
+            Op::Le => truth(args[0] <= args[1]),
+            Op::Eq => truth(args[0] == args[1]),
+            Op::And => truth(args.iter().all(|&a| a != 0.0)),
+            Op::Or => truth(args.iter().any(|&a| a != 0.0)),
+            Op::Not => truth(args[0] == 0.0),
+        }
+    }
+}
+
+/// A rate law resolved to molecule IDs and parameter indices.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Formula {
+    Const(f64),
+    /// Concentration of a molecule in the current compartment.
+    Var(MoleculeId),
+    /// Named parameter, by index into the parameter list.
+    Param(usize),
+    Apply(Op, Vec<Formula>),
+}
+
+/// Names visible to a rate expression.
+#[derive(Debug, Clone, Copy)]
+pub struct Symbols<'a> {
+    pub molecules: &'a MoleculeIndex,
+    pub params: &'a [(String, f64)],
+    /// Kinetic templates available as heads (see `kinetics`).
+    pub templates: &'a TemplateRegistry,
+    /// Molecules of the reaction, for template roles.
+    pub binding: Option<&'a Binding>,
+}
+
+impl<'a> Symbols<'a> {
+    pub fn new(molecules: &'a MoleculeIndex, params: &'a [(String, f64)]) -> Self {
+        Self {
+            molecules,
+            params,
+            templates: kinetics::builtins(),
+            binding: None,
+        }
+    }
+
+    /// Bind template roles to a reaction's molecules.
+    pub fn with_binding(mut self, binding: &'a Binding) -> Self {
+        self.binding = Some(binding);
+        self
+    }
+
+    fn expand(&self, expr: &Expr) -> Option<SimResult<Arg>> {
+        let template = self.templates.get(&expr.head)?;
+        let unbound = Binding::default();
+        Some(template.expand(expr, self.binding.unwrap_or(&unbound)))
+    }
+
+    fn lookup(&self, name: &str) -> SimResult<Formula> {
+        if let Some(p) = self.params.iter().position(|(n, _)| n == name) {
+            return Ok(Formula::Param(p));
+        }
+        self.molecules
+            .get(name)
+            .map(Formula::Var)
+            .ok_or_else(|| SimError::Key(format!("Unknown variable {name:?} in rate expression")))
+    }
+}
+
+impl Formula {
+    /// Resolve an Expr argument against `symbols`.
+    pub fn lower(arg: &Arg, symbols: &Symbols<'_>) -> SimResult<Formula> {
+        match arg {
+            Arg::Number(v) => Ok(Formula::Const(*v)),
+            Arg::Bool(b) => Ok(Formula::Const(if *b { 1.0 } else { 0.0 })),
+            Arg::Str(name) => symbols.lookup(name),
+            Arg::None => Err(SimError::Value(
+                "None is not a value in a rate expression".into(),
+            )),
+            Arg::Expr(expr) => Formula::lower_expr(expr, symbols),
+        }
+    }
+
+    fn lower_expr(expr: &Expr, symbols: &Symbols<'_>) -> SimResult<Formula> {
+        let head = expr.head.as_str();
+        let single = || match (expr.args.as_slice(), expr.kwargs.is_empty()) {
+            ([arg], true) => Ok(arg),
+            _ => Err(SimError::Value(format!(
+                "{head}() takes exactly one argument, got {expr}"
+            ))),
+        };
+        match head {
+            "var" => match single()? {
+                Arg::Str(name) => symbols.lookup(name),
+                Arg::Number(id) if id.fract() == 0.0 && *id >= 0.0 => {
+                    Ok(Formula::Var(*id as MoleculeId))
+                }
+                other => Err(SimError::Value(format!(
+                    "var() needs a name or molecule ID, got {other}"
+                ))),
+            },
+            "const" => match single()? {
+                Arg::Number(v) => Ok(Formula::Const(*v)),
+                Arg::Bool(b) => Ok(Formula::Const(if *b { 1.0 } else { 0.0 })),
+                other => Err(SimError::Value(format!(
+                    "const() needs a number, got {other}"
+                ))),
+            },
+            _ => {
+                let Some(op) = Op::from_head(head) else {
+                    // A bare name such as `S` or `S()` reads as a variable.
+                    let bare = expr.args.is_empty() && expr.kwargs.is_empty();
+                    let variable = bare.then(|| symbols.lookup(head));
+                    if let Some(Ok(formula)) = variable {
+                        return Ok(formula);
+                    }
+                    if let Some(expanded) = symbols.expand(expr) {
+                        return Formula::lower(&expanded?, symbols);
+                    }
+                    return variable.unwrap_or_else(|| {
+                        Err(SimError::Value(format!(
+                            "Unknown rate expression head {head:?} in {expr}"
+                        )))
+                    });
+                };
+                if !expr.kwargs.is_empty() {
+                    return Err(SimError::Value(format!(
+                        "{head}() takes no keyword arguments, got {expr}"
+                    )));
+                }
+                let (min, max) = op.arity();
+                let n = expr.args.len();
+                if n < min || max.is_some_and(|max| n > max) {
+                    let expected = match max {
+                        Some(max) if max == min => format!("{min}"),
+                        Some(max) => format!("{min} to {max}"),
+                        None => format!("at least {min}"),
+                    };
+                    return Err(SimError::Value(format!(
+                        "{head}() takes {expected} arguments, got {n} in {expr}"
+                    )));
+                }
+                let args = expr
+                    .args
+                    .iter()
+                    .map(|a| Formula::lower(a, symbols))
+                    .collect::<SimResult<_>>()?;
+                Ok(Formula::Apply(op, args))
+            }
+        }
+    }
+
+    /// Evaluate on one compartment's concentrations with parameter values `params`.
+    pub fn eval(&self, conc: &[f64], params: &[f64]) -> f64 {
+        match self {
+            Formula::Const(v) => *v,
+            Formula::Var(i) => conc[*i],
+            Formula::Param(p) => params[*p],
+            Formula::Apply(Op::If, args) => {
+                if args[0].eval(conc, params) != 0.0 {
+                    args[1].eval(conc, params)
+                } else {
+                    args[2].eval(conc, params)
+                }
+            }
+            Formula::Apply(op, args) => {
+                let values: Vec<f64> = args.iter().map(|a| a.eval(conc, params)).collect();
+                op.apply(&values)
+            }
+        }
+    }
+
+    /// Molecule IDs read by the formula, sorted.
+    pub fn variables(&self) -> Vec<MoleculeId> {
+        fn collect(f: &Formula, out: &mut BTreeSet<MoleculeId>) {
+            match f {
+                Formula::Var(i) => {
+                    out.insert(*i);
+                }
+                Formula::Apply(_, args) => args.iter().for_each(|a| collect(a, out)),
+                Formula::Const(_) | Formula::Param(_) => {}
+            }
+        }
+        let mut out = BTreeSet::new();
+        collect(self, &mut out);
+        out.into_iter().collect()
+    }
+
+    /// Renumber variables through `f`; `None` if `f` drops one the formula reads.
+    pub fn map_variables(&self, f: &impl Fn(MoleculeId) -> Option<MoleculeId>) -> Option<Formula> {
+        Some(match self {
+            Formula::Var(i) => Formula::Var(f(*i)?),
+            Formula::Apply(op, args) => Formula::Apply(
+                *op,
+                args.iter()
+                    .map(|a| a.map_variables(f))
+                    .collect::<Option<_>>()?,
+            ),
+            Formula::Const(_) | Formula::Param(_) => self.clone(),
+        })
+    }
+
+    /// Compile into a closure, with parameters fixed at `params`.
+    pub fn compile(&self, params: &[f64]) -> RateFn {
+        match self {
+            Formula::Const(v) => {
+                let v = *v;
+                Box::new(move |_| v)
+            }
+            Formula::Var(i) => {
+                let i = *i;
+                Box::new(move |c| c[i])
+            }
+            Formula::Param(p) => {
+                let v = params[*p];
+                Box::new(move |_| v)
+            }
+            Formula::Apply(op, args) => {
+                let mut fs: Vec<RateFn> = args.iter().map(|a| a.compile(params)).collect();
+                match (*op, fs.len()) {
+                    (Op::Add, _) => Box::new(move |c| fs.iter().map(|f| f(c)).sum()),
+                    (Op::Mul, _) => Box::new(move |c| fs.iter().map(|f| f(c)).product()),
+                    (Op::If, _) => {
+                        let (b, a, cond) =
+                            (fs.pop().unwrap(), fs.pop().unwrap(), fs.pop().unwrap());
+                        Box::new(move |c| if cond(c) != 0.0 { a(c) } else { b(c) })
+                    }
+                    (op, 1) => {
+                        let a = fs.pop().unwrap();
+                        Box::new(move |c| op.apply(&[a(c)]))
+                    }
+                    (op, 2) => {
+                        let (b, a) = (fs.pop().unwrap(), fs.pop().unwrap());
+                        Box::new(move |c| op.apply(&[a(c), b(c)]))
+                    }
+                    (op, _) => Box::new(move |c| {
+                        let values: Vec<f64> = fs.iter().map(|f| f(c)).collect();
+                        op.apply(&values)
+                    }),
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Formula {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Formula::Const(v) => write!(f, "{v:?}"),
+            Formula::Var(i) => write!(f, "var({i})"),
+            Formula::Param(p) => write!(f, "param({p})"),
+            Formula::Apply(op, args) => {
+                write!(f, "{}(", op.name())?;
+                for (i, arg) in args.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{arg}")?;
+                }
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// Native rate function over one compartment's concentrations.
+pub type RateFn = Box<dyn Fn(&[f64]) -> f64 + Send + Sync>;
+
+/// A compiled rate law: the formula, its parameters and the native closure.
+pub struct RateExpr {
+    formula: Formula,
+    params: Vec<(String, f64)>,
+    variables: Vec<MoleculeId>,
+    rate: RateFn,
+    program: Program,
+    derivatives: OnceLock<Derivatives>,
+}
+
+impl RateExpr {
+    /// Lower and compile `source` against `molecules` and `params`.
+    pub fn new(
+        source: &Arg,
+        molecules: &MoleculeIndex,
+        params: Vec<(String, f64)>,
+    ) -> SimResult<Self> {
+        let formula = Formula::lower(source, &Symbols::new(molecules, &params))?;
+        Ok(Self::from_formula(formula, params))
+    }
+
+    /// As `new`, with kinetic template roles bound to `binding`.
+    pub fn for_reaction(
+        source: &Arg,
+        molecules: &MoleculeIndex,
+        params: Vec<(String, f64)>,
+        binding: &Binding,
+    ) -> SimResult<Self> {
+        let symbols = Symbols::new(molecules, &params).with_binding(binding);
+        let formula = Formula::lower(source, &symbols)?;
+        Ok(Self::from_formula(formula, params))
+    }
+
+    pub fn from_formula(formula: Formula, params: Vec<(String, f64)>) -> Self {
+        let values: Vec<f64> = params.iter().map(|(_, v)| *v).collect();
+        Self {
+            rate: formula.compile(&values),
+            program: Program::compile(&formula, &values),
+            variables: formula.variables(),
+            formula,
+            params,
+            derivatives: OnceLock::new(),
+        }
+    }
+
+    pub fn eval(&self, conc: &[f64]) -> f64 {
+        (self.rate)(conc)
+    }
+
+    /// Rates at every compartment of `sites` from the flat buffer `conc`,
+    /// with the bytecode VM (see `Program::eval_sites`).
+    pub fn eval_sites(
+        &self,
+        conc: &[f64],
+        num_molecules: usize,
+        sites: &[CompartmentId],
+        out: &mut [f64],
+        registers: &mut Vec<f64>,
+    ) {
+        self.program
+            .eval_sites(conc, num_molecules, sites, out, registers);
+    }
+
+    /// `∂rate/∂wrt`, compiled with the same parameters.
+    pub fn derivative(&self, wrt: Wrt) -> RateExpr {
+        RateExpr::from_formula(self.formula.derivative(wrt), self.params.clone())
+    }
+
+    /// First derivatives with respect to every variable and parameter,
+    /// compiled on first use.
+    pub fn derivatives(&self) -> &Derivatives {
+        self.derivatives.get_or_init(|| Derivatives::of(self))
+    }
+
+    pub fn formula(&self) -> &Formula {
+        &self.formula
+    }
+
+    pub fn program(&self) -> &Program {
+        &self.program
+    }
+
+    pub fn params(&self) -> &[(String, f64)] {
+        &self.params
+    }
+
+    /// Recompile with variables renumbered (see `Formula::map_variables`).
+    pub fn map_variables(&self, f: impl Fn(MoleculeId) -> Option<MoleculeId>) -> Option<RateExpr> {
+        let formula = self.formula.map_variables(&f)?;
+        Some(RateExpr::from_formula(formula, self.params.clone()))
+    }
+
+    /// Molecule IDs the rate reads, sorted.
+    pub fn variables(&self) -> &[MoleculeId] {
+        &self.variables
+    }
+}
+
+impl fmt::Debug for RateExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateExpr")
+            .field("formula", &self.formula.to_string())
+            .field("params", &self.params)
+            .finish()
+    }
+}
+
+impl PartialEq for RateExpr {
+    fn eq(&self, other: &Self) -> bool {
+        self.formula == other.formula && self.params == other.params
+    }
+}
+
+/// Read `{name: value}` parameters, preserving order.
+pub(crate) fn extract_params(params: Option<&PyAny>) -> PyResult<Vec<(String, f64)>> {
+    match params {
+        None => Ok(Vec::new()),
+        Some(obj) => obj
+            .call_method0("items")?
+            .iter()?
+            .map(|item| item?.extract::<(String, f64)>())
+            .collect(),
+    }
+}
+
+/// A rate expression compiled for a list of molecule names.
+#[pyclass(name = "CompiledRate", module = "alienbio_sim")]
+pub struct PyCompiledRate {
+    rate: Arc<RateExpr>,
+    molecules: MoleculeIndex,
+}
+
+impl PyCompiledRate {
+    fn name(&self, id: MoleculeId) -> String {
+        self.molecules
+            .names()
+            .get(id)
+            .cloned()
+            .unwrap_or_else(|| id.to_string())
+    }
+
+    fn check_len(&self, concentrations: &[f64]) -> PyResult<()> {
+        match self.rate.variables().last() {
+            Some(&max) if max >= concentrations.len() => Err(PyValueError::new_err(format!(
+                "Expected at least {} concentrations, got {}",
+                max + 1,
+                concentrations.len()
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    pub(crate) fn compile(
+        source: &Arg,
+        molecules: Vec<String>,
+        params: Option<&PyAny>,
+    ) -> PyResult<Self> {
+        let molecules = MoleculeIndex::new(molecules);
+        let rate = RateExpr::new(source, &molecules, extract_params(params)?)?;
+        Ok(Self {
+            rate: Arc::new(rate),
+            molecules,
+        })
+    }
+}
+
+#[pymethods]
+impl PyCompiledRate {
+    /// Compile a rate expression.
+    ///
+    /// Args:
+    ///     expr: Expr, Expr string, structured {head, args, kwargs} dict, or number
+    ///     molecules: Molecule names, in concentration-vector order
+    ///     params: Optional {name: value} parameters
+    #[new]
+    #[pyo3(signature = (expr, molecules, params=None))]
+    fn new(expr: &PyAny, molecules: Vec<String>, params: Option<&PyAny>) -> PyResult<Self> {
+        Self::compile(&Arg::from_py(expr, true)?, molecules, params)
+    }
+
+    /// Names of the molecules the rate reads.
+    #[getter]
+    fn variables(&self) -> Vec<String> {
+        self.rate
+            .variables()
+            .iter()
+            .map(|&i| self.name(i))
+            .collect()
+    }
+
+    /// Evaluate on concentrations given in molecule order.
+    fn __call__(&self, concentrations: Vec<f64>) -> PyResult<f64> {
+        self.check_len(&concentrations)?;
+        Ok(self.rate.eval(&concentrations))
+    }
+
+    /// Symbolic derivative with respect to a molecule or parameter name
+    /// (parameters shadow molecules, as in the expression itself).
+    fn derivative(&self, wrt: &str) -> PyResult<Self> {
+        let wrt = match self.rate.params().iter().position(|(n, _)| n == wrt) {
+            Some(p) => Wrt::Param(p),
+            None => Wrt::Var(self.molecules.id(wrt)?),
+        };
+        Ok(Self {
+            rate: Arc::new(self.rate.derivative(wrt)),
+            molecules: self.molecules.clone(),
+        })
+    }
+
+    /// `{molecule: ∂rate/∂conc}` for each variable, at the given concentrations.
+    fn gradient(&self, concentrations: Vec<f64>) -> PyResult<HashMap<String, f64>> {
+        self.check_len(&concentrations)?;
+        Ok(self
+            .rate
+            .derivatives()
+            .conc
+            .iter()
+            .map(|(m, d)| (self.name(*m), d.eval(&concentrations)))
+            .collect())
+    }
+
+    /// `{parameter: ∂rate/∂param}` at the given concentrations.
+    fn param_gradient(&self, concentrations: Vec<f64>) -> PyResult<HashMap<String, f64>> {
+        self.check_len(&concentrations)?;
+        Ok(self
+            .rate
+            .params()
+            .iter()
+            .zip(&self.rate.derivatives().params)
+            .map(|((name, _), d)| (name.clone(), d.eval(&concentrations)))
+            .collect())
+    }
+
+    /// Evaluate in every compartment of a `WorldState` in one vectorized pass.
+    ///
+    /// Molecule IDs of the state follow the `molecules` list given at compile time.
+    #[pyo3(signature = (state, compartments=None))]
+    fn eval_state(
+        &self,
+        state: PyRef<'_, WorldState>,
+        compartments: Option<Vec<CompartmentId>>,
+    ) -> PyResult<Vec<f64>> {
+        let n = state.molecules();
+        if self.rate.variables().last().is_some_and(|&max| max >= n) {
+            return Err(PyValueError::new_err(format!(
+                "Rate reads molecule {} but the state has {n} molecules",
+                self.rate.variables().last().unwrap()
+            )));
+        }
+        let sites = compartments.unwrap_or_else(|| (0..state.compartments()).collect());
+        if let Some(&bad) = sites.iter().find(|&&c| c >= state.compartments()) {
+            return Err(SimError::Index(format!("Compartment {bad} out of range")).into());
+        }
+        let mut out = vec![0.0; sites.len()];
+        self.rate
+            .eval_sites(state.concentrations(), n, &sites, &mut out, &mut Vec::new());
+        Ok(out)
+    }
+
+    /// Source code for the rate in "python", "lua", "rhai" or "rust"
+    /// (see `alienbio_sim.to_code`).
+    #[pyo3(signature = (language="python"))]
+    fn to_code(&self, language: &str) -> PyResult<String> {
+        let language = Language::parse(language)?;
+        Ok(codegen::to_code(&self.rate, &self.molecules, language))
+    }
+
+    /// The compiled instruction stream, one instruction per line.
+    #[getter]
+    fn bytecode(&self) -> String {
+        self.rate.program().to_string()
+    }
+
+    fn __repr__(&self) -> String {
+        format!("CompiledRate({})", self.rate.formula())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn compile(source: &str, names: &[&str], params: &[(&str, f64)]) -> SimResult<RateExpr> {
+        let molecules = MoleculeIndex::new(names.iter().map(|s| s.to_string()).collect());
+        let params = params.iter().map(|(n, v)| (n.to_string(), *v)).collect();
+        RateExpr::new(&Arg::parse(source)?, &molecules, params)
+    }
+
+    #[test]
+    fn michaelis_menten_closure() {
+        let rate = compile(
+            "div(mul(vmax, var(S)), add(km, var(S)))",
+            &["P", "S"],
+            &[("vmax", 10.0), ("km", 5.0)],
+        )
+        .unwrap();
+        assert_eq!(rate.variables(), &[1]);
+        assert_eq!(rate.eval(&[0.0, 5.0]), 5.0);
+        assert_eq!(rate.eval(&[0.0, 15.0]), 7.5);
+        assert_eq!(rate.formula().eval(&[0.0, 15.0], &[10.0, 5.0]), 7.5);
+    }
+
+    #[test]
+    fn every_op_matches_interpreter() {
+        let cases = [
+            ("add(S, 1, 2)", 5.0),
+            ("sub(S, 0.5)", 1.5),
+            ("mul(S, S, 3)", 12.0),
+            ("div(S, 4)", 0.5),
+            ("power(S, 3)", 8.0),
+            ("neg(S)", -2.0),
+            ("exp(0)", 1.0),
+            ("log(exp(S))", 2.0),
+            ("min(S, 7, -1)", -1.0),
+            ("max(S, 1)", 2.0),
+            ("if(gt(S, 1), 10, 20)", 10.0),
+            ("if(lt(S, 1), 10, 20)", 20.0),
+            ("ge(S, 2)", 1.0),
+            ("le(S, 1.5)", 0.0),
+            ("eq(S, 2)", 1.0),
+            ("and(S, 1, gt(S, 0))", 1.0),
+            ("and(S, 0)", 0.0),
+            ("or(0, eq(S, 3))", 0.0),
+            ("or(0, S)", 1.0),
+            ("not(S)", 0.0),
+            ("const(4.5)", 4.5),
+            ("var(0)", 2.0),
+            ("S", 2.0),
+            ("3.25", 3.25),
+        ];
+        for (source, expected) in cases {
+            let rate = compile(source, &["S"], &[]).unwrap();
+            assert_eq!(rate.eval(&[2.0]), expected, "{source}");
+            assert_eq!(rate.formula().eval(&[2.0], &[]), expected, "{source}");
+        }
+    }
+
+    #[test]
+    fn if_is_lazy() {
+        // The untaken branch would be NaN.
+        let rate = compile("if(gt(S, 0), S, log(neg(1)))", &["S"], &[]).unwrap();
+        assert_eq!(rate.eval(&[3.0]), 3.0);
+    }
+
+    #[test]
+    fn lowering_errors() {
+        assert!(matches!(
+            compile("mul(k, X)", &["S"], &[("k", 1.0)]),
+            Err(SimError::Key(_))
+        ));
+        for bad in [
+            "div(S)",
+            "if(S, 1)",
+            "neg(S, S)",
+            "hill(S, n=2)",
+            "add(S, k=1)",
+            "var(S, 1)",
+            "const(S)",
+            "add(None)",
+        ] {
+            assert!(compile(bad, &["S"], &[]).is_err(), "{bad}");
+        }
+    }
+}
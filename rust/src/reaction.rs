@@ -0,0 +1,341 @@
+//! Reaction: native reaction specification used by the simulators.
+//!
+//! Mirrors `alienbio.bio.world_simulator.ReactionSpec`: molecules are referred
+//! to by integer ID and the rate is mass-action in the reactant concentrations.
+//! Effectors (enzymes, activators, inhibitors) appear in the rate law but are
+//! neither consumed nor produced; under mass action each multiplies the rate
+//! by its concentration raised to its order.
+//! Reactions built from a `ChemistryImpl` for the single-compartment engine use
+//! `RateLaw::Constant` instead, matching `ReferenceSimulatorImpl`, reactions
+//! whose rate is an Expr use `RateLaw::Expr` with a compiled rate, and
+//! `rhai:` rates use `RateLaw::Script` (see `script`).
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+
+use crate::error::{SimError, SimResult};
+use crate::rate::RateExpr;
+use crate::script::ScriptRate;
+use crate::tree::CompartmentId;
+
+pub type MoleculeId = usize;
+
+/// How a reaction's rate depends on concentrations.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RateLaw {
+    /// `k * prod(conc[m] ** stoich)` over the reactants, times
+    /// `prod(conc[e] ** order)` over the effectors (`ReactionSpec`).
+    #[default]
+    MassAction,
+    /// The rate constant itself, independent of state (`ReactionImpl.rate`).
+    Constant,
+    /// A compiled rate expression of the concentrations (`Reaction::expr`).
+    Expr,
+    /// A sandboxed Rhai script of the concentrations (`Reaction::script`).
+    Script,
+}
+
+/// A reaction within a single compartment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Reaction {
+    pub name: String,
+    /// (molecule, stoichiometry) pairs, in declaration order.
+    pub reactants: Vec<(MoleculeId, f64)>,
+    pub products: Vec<(MoleculeId, f64)>,
+    /// (molecule, order) pairs read by the rate law but left unchanged.
+    pub effectors: Vec<(MoleculeId, f64)>,
+    pub rate_constant: f64,
+    pub law: RateLaw,
+    /// Compiled rate for `RateLaw::Expr`.
+    pub expr: Option<Arc<RateExpr>>,
+    /// Compiled script for `RateLaw::Script`.
+    pub script: Option<Arc<ScriptRate>>,
+    /// Compartments this reaction occurs in (None = all).
+    pub compartments: Option<Vec<CompartmentId>>,
+}
+
+impl Reaction {
+    pub fn new(
+        name: impl Into<String>,
+        reactants: Vec<(MoleculeId, f64)>,
+        products: Vec<(MoleculeId, f64)>,
+        rate_constant: f64,
+    ) -> Self {
+        Self {
+            name: name.into(),
+            reactants,
+            products,
+            effectors: Vec::new(),
+            rate_constant,
+            law: RateLaw::MassAction,
+            expr: None,
+            script: None,
+            compartments: None,
+        }
+    }
+
+    /// Add effector molecules with their orders in the mass-action rate.
+    pub fn with_effectors(mut self, effectors: Vec<(MoleculeId, f64)>) -> Self {
+        self.effectors = effectors;
+        self
+    }
+
+    /// Use a different rate law.
+    pub fn with_law(mut self, law: RateLaw) -> Self {
+        self.law = law;
+        self
+    }
+
+    /// Use a compiled rate expression as the rate law.
+    pub fn with_expr(mut self, expr: Arc<RateExpr>) -> Self {
+        self.law = RateLaw::Expr;
+        self.expr = Some(expr);
+        self
+    }
+
+    /// Use a `rhai:` script as the rate law.
+    pub fn with_script(mut self, script: Arc<ScriptRate>) -> Self {
+        self.law = RateLaw::Script;
+        self.script = Some(script);
+        self
+    }
+
+    /// Restrict the reaction to specific compartments.
+    pub fn in_compartments(mut self, compartments: Vec<CompartmentId>) -> Self {
+        self.compartments = Some(compartments);
+        self
+    }
+
+    /// Reaction rate for one compartment's concentration slice.
+    ///
+    /// Scripts run with the `memory` of compartment 0; see `site_rates`.
+    pub fn rate(&self, conc: &[f64]) -> f64 {
+        self.rate_at(conc, 0)
+    }
+
+    /// Reaction rate for the concentration slice of compartment `site`.
+    pub fn rate_at(&self, conc: &[f64], site: CompartmentId) -> f64 {
+        match self.law {
+            RateLaw::MassAction => {
+                let mut rate = self.rate_constant;
+                for &(mol, stoich) in self.reactants.iter().chain(&self.effectors) {
+                    rate *= conc[mol].powf(stoich);
+                }
+                rate
+            }
+            RateLaw::Constant => self.rate_constant,
+            RateLaw::Expr => self.expr.as_ref().map_or(0.0, |e| e.eval(conc)),
+            RateLaw::Script => self.script.as_ref().map_or(0.0, |s| s.eval_at(conc, site)),
+        }
+    }
+
+    /// Rates at every compartment of `sites` from the flat buffer `conc`.
+    ///
+    /// Expression rates run through the bytecode VM in one pass over all
+    /// sites; `registers` is its scratch space, reusable across reactions.
+    pub fn site_rates(
+        &self,
+        conc: &[f64],
+        num_molecules: usize,
+        sites: &[CompartmentId],
+        out: &mut Vec<f64>,
+        registers: &mut Vec<f64>,
+    ) {
+        out.resize(sites.len(), 0.0);
+        match (&self.expr, self.law) {
+            (Some(expr), RateLaw::Expr) => {
+                expr.eval_sites(conc, num_molecules, sites, out, registers)
+            }
+            _ => {
+                for (rate, &comp) in out.iter_mut().zip(sites) {
+                    let offset = comp * num_molecules;
+                    *rate = self.rate_at(&conc[offset..offset + num_molecules], comp);
+                }
+            }
+        }
+    }
+
+    /// Molecules the rate depends on, sorted.
+    pub fn rate_inputs(&self) -> Vec<MoleculeId> {
+        match self.law {
+            RateLaw::MassAction => {
+                let mut inputs: Vec<_> = self
+                    .reactants
+                    .iter()
+                    .chain(&self.effectors)
+                    .map(|&(m, _)| m)
+                    .collect();
+                inputs.sort_unstable();
+                inputs.dedup();
+                inputs
+            }
+            RateLaw::Constant => Vec::new(),
+            RateLaw::Expr => self
+                .expr
+                .as_ref()
+                .map_or_else(Vec::new, |e| e.variables().to_vec()),
+            RateLaw::Script => self
+                .script
+                .as_ref()
+                .map_or_else(Vec::new, |s| s.variables().to_vec()),
+        }
+    }
+
+    /// `∂rate/∂conc[m]` on one compartment's concentrations, written into
+    /// `out` as (molecule, derivative) pairs for each molecule the rate reads.
+    ///
+    /// Mass action differentiates analytically,
+    /// `∂rate/∂c_m = k · s_m · c_m^(s_m - 1) · ∏_{j≠m} c_j^(s_j)` over reactants
+    /// and effectors; expression laws evaluate their compiled symbolic
+    /// derivatives (see `derivative`) and scripts take central differences.
+    pub fn rate_partials(&self, conc: &[f64], out: &mut Vec<(MoleculeId, f64)>) {
+        out.clear();
+        match self.law {
+            RateLaw::MassAction => {
+                let factors = || self.reactants.iter().chain(&self.effectors);
+                for (m, &(col, order)) in factors().enumerate() {
+                    let mut d_rate = self.rate_constant * order * conc[col].powf(order - 1.0);
+                    for (j, &(other, stoich)) in factors().enumerate() {
+                        if j != m {
+                            d_rate *= conc[other].powf(stoich);
+                        }
+                    }
+                    out.push((col, d_rate));
+                }
+            }
+            RateLaw::Constant => {}
+            RateLaw::Expr => {
+                if let Some(expr) = &self.expr {
+                    out.extend(
+                        expr.derivatives()
+                            .conc
+                            .iter()
+                            .map(|(m, d)| (*m, d.eval(conc))),
+                    );
+                }
+            }
+            RateLaw::Script => {
+                if let Some(script) = &self.script {
+                    let mut probe = conc.to_vec();
+                    for &m in script.variables() {
+                        let h = 1e-6 * conc[m].abs().max(1.0);
+                        probe[m] = conc[m] + h;
+                        let up = script.eval(&probe);
+                        probe[m] = conc[m] - h;
+                        let down = script.eval(&probe);
+                        probe[m] = conc[m];
+                        out.push((m, (up - down) / (2.0 * h)));
+                    }
+                }
+            }
+        }
+    }
+
+    /// `∂rate/∂p` on one compartment's concentrations, by parameter name:
+    /// `rate_constant` for mass-action and constant laws, the expression's
+    /// parameters for `RateLaw::Expr`, none for scripts.
+    pub fn param_partials(&self, conc: &[f64]) -> Vec<(String, f64)> {
+        match self.law {
+            RateLaw::MassAction => {
+                let d_rate = self
+                    .reactants
+                    .iter()
+                    .chain(&self.effectors)
+                    .map(|&(mol, stoich)| conc[mol].powf(stoich))
+                    .product();
+                vec![("rate_constant".to_string(), d_rate)]
+            }
+            RateLaw::Constant => vec![("rate_constant".to_string(), 1.0)],
+            RateLaw::Expr => self.expr.as_ref().map_or_else(Vec::new, |expr| {
+                expr.params()
+                    .iter()
+                    .zip(&expr.derivatives().params)
+                    .map(|((name, _), d)| (name.clone(), d.eval(conc)))
+                    .collect()
+            }),
+            RateLaw::Script => Vec::new(),
+        }
+    }
+
+    /// Check molecule and compartment IDs against the simulator dimensions.
+    pub fn validate(&self, num_molecules: usize, num_compartments: usize) -> SimResult<()> {
+        if self.law == RateLaw::Expr && self.expr.is_none() {
+            return Err(SimError::Value(format!(
+                "Reaction {}: expression rate law without an expression",
+                self.name
+            )));
+        }
+        if self.law == RateLaw::Script && self.script.is_none() {
+            return Err(SimError::Value(format!(
+                "Reaction {}: script rate law without a script",
+                self.name
+            )));
+        }
+        let rate_inputs = self.rate_inputs();
+        let stoich = self.reactants.iter().chain(&self.products).map(|&(m, _)| m);
+        let effectors = self.effectors.iter().map(|&(m, _)| m);
+        for mol in stoich.chain(effectors).chain(rate_inputs) {
+            if mol >= num_molecules {
+                return Err(SimError::Value(format!(
+                    "Reaction {}: molecule {mol} out of range (num_molecules={num_molecules})",
+                    self.name
+                )));
+            }
+        }
+        for &(mol, order) in &self.effectors {
+            if self
+                .reactants
+                .iter()
+                .chain(&self.products)
+                .any(|&(m, _)| m == mol)
+            {
+                return Err(SimError::Value(format!(
+                    "Reaction {}: effector {mol} is also a reactant or product",
+                    self.name
+                )));
+            }
+            if !order.is_finite() {
+                return Err(SimError::Value(format!(
+                    "Reaction {}: effector {mol} has order {order}",
+                    self.name
+                )));
+            }
+        }
+        for &comp in self.compartments.iter().flatten() {
+            if comp >= num_compartments {
+                return Err(SimError::Value(format!(
+                    "Reaction {}: compartment {comp} out of range (num_compartments={num_compartments})",
+                    self.name
+                )));
+            }
+        }
+        Ok(())
+    }
+
+    /// Read a Python `ReactionSpec` (or any object with the same attributes).
+    pub fn from_spec(spec: &PyAny) -> PyResult<Self> {
+        Ok(Self {
+            name: spec.getattr("name")?.extract()?,
+            reactants: extract_stoichiometry(spec.getattr("reactants")?)?,
+            products: extract_stoichiometry(spec.getattr("products")?)?,
+            effectors: match spec.getattr("effectors") {
+                Ok(effectors) if !effectors.is_none() => extract_stoichiometry(effectors)?,
+                _ => Vec::new(),
+            },
+            rate_constant: spec.getattr("rate_constant")?.extract()?,
+            law: RateLaw::MassAction,
+            expr: None,
+            script: None,
+            compartments: spec.getattr("compartments")?.extract()?,
+        })
+    }
+}
+
+/// Read a `{molecule_id: stoichiometry}` dict, preserving insertion order.
+pub(crate) fn extract_stoichiometry(obj: &PyAny) -> PyResult<Vec<(MoleculeId, f64)>> {
+    obj.call_method0("items")?
+        .iter()?
+        .map(|item| item?.extract::<(MoleculeId, f64)>())
+        .collect()
+}
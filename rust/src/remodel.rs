@@ -0,0 +1,442 @@
+//! Dynamic topology: compartment division, death and fusion.
+//!
+//! Each operation builds a new `Topology` and remaps the flat per-compartment
+//! buffers (concentrations, multiplicities, volumes) onto it; `Remap` records
+//! where every old compartment went so simulators can rebind their reactions
+//! and flows (see `WorldModel::remodel`). Compartment IDs stay in creation
+//! order: removed compartments close the gap, and copies made by division
+//! are appended at the end.
+//!
+//! Contents are conserved as amounts, `concentration × volume × multiplicity`:
+//! division partitions them between the two daughters, death releases a
+//! subtree's amounts into the parent, and fusion pools two siblings.
+
+use rand::Rng;
+use rand_distr::{Binomial, Distribution};
+
+use crate::error::{SimError, SimResult};
+use crate::tree::{CompartmentId, Topology};
+
+/// How division partitions contents between the original and its daughter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Split {
+    /// The original keeps this fraction of every amount and of the volume.
+    Fixed(f64),
+    /// Every molecule stays with probability `p`, independently (binomial
+    /// partitioning of the integer part of each amount); the volume splits
+    /// as for `Fixed(p)`.
+    Binomial(f64),
+}
+
+impl Split {
+    /// Expected fraction kept by the original.
+    pub fn ratio(self) -> f64 {
+        match self {
+            Split::Fixed(f) | Split::Binomial(f) => f,
+        }
+    }
+
+    /// Amount of `amount` the original keeps.
+    fn kept<R: Rng>(self, amount: f64, rng: &mut R) -> f64 {
+        match self {
+            Split::Fixed(f) => amount * f,
+            Split::Binomial(p) if amount > 0.0 => {
+                let whole = amount.trunc();
+                let drawn = Binomial::new(whole as u64, p)
+                    .map(|b| b.sample(rng) as f64)
+                    .unwrap_or(whole * p);
+                drawn + (amount - whole) * p
+            }
+            Split::Binomial(_) => 0.0,
+        }
+    }
+}
+
+/// One topology operation.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Remodel {
+    /// Split `compartment` (with its subtree) into itself and a copy under
+    /// the same parent; copies are named with `suffix` appended.
+    Divide {
+        compartment: CompartmentId,
+        split: Split,
+        suffix: String,
+    },
+    /// Remove `compartment` and its subtree, releasing their contents into
+    /// its parent.
+    Die { compartment: CompartmentId },
+    /// Merge sibling `absorb` into `keep`; `absorb`'s children move to `keep`.
+    Fuse {
+        keep: CompartmentId,
+        absorb: CompartmentId,
+    },
+}
+
+/// Where every compartment went in a remodeled topology.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remap {
+    pub topology: Topology,
+    /// New ID of each old compartment (`None` if it died; a fused compartment
+    /// maps to the one that absorbed it).
+    pub map: Vec<Option<CompartmentId>>,
+    /// Old compartment each new compartment takes its properties from
+    /// (itself, or the original of a division copy).
+    pub source: Vec<CompartmentId>,
+}
+
+impl Remap {
+    pub fn old_compartments(&self) -> usize {
+        self.map.len()
+    }
+
+    /// New compartments that inherit the properties of `old`: where it went,
+    /// plus any copies of it.
+    pub fn targets(&self, old: CompartmentId) -> Vec<CompartmentId> {
+        let mut targets: Vec<CompartmentId> = (0..self.source.len())
+            .filter(|&new| self.source[new] == old)
+            .collect();
+        if let Some(new) = self.map[old].filter(|new| !targets.contains(new)) {
+            targets.insert(0, new);
+        }
+        targets
+    }
+
+    /// Move a flat buffer index (`compartment * n + molecule`) to the new
+    /// topology; indices past the compartments (a flow's origin slice) keep
+    /// their offset from the end.
+    pub fn index(&self, index: usize, n: usize) -> Option<usize> {
+        let (old, new) = (self.old_compartments(), self.topology.num_compartments());
+        if index >= old * n {
+            return Some(index - old * n + new * n);
+        }
+        self.map[index / n].map(|c| c * n + index % n)
+    }
+
+    /// Carry a per-compartment value over from each new compartment's source.
+    pub fn inherit<T: Clone>(&self, values: &[T]) -> Vec<T> {
+        self.source.iter().map(|&old| values[old].clone()).collect()
+    }
+}
+
+/// Buffers of a remodeled world, with the remap that produced them.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Remodeled {
+    pub remap: Remap,
+    pub concentrations: Vec<f64>,
+    pub multiplicities: Vec<f64>,
+    pub volumes: Vec<f64>,
+}
+
+impl Remodel {
+    /// Apply the operation to `topology` and its buffers (`n` molecules per
+    /// compartment); `rng` drives stochastic splits.
+    pub fn apply<R: Rng>(
+        &self,
+        topology: &Topology,
+        n: usize,
+        conc: &[f64],
+        multiplicities: &[f64],
+        volumes: &[f64],
+        rng: &mut R,
+    ) -> SimResult<Remodeled> {
+        let amounts: Vec<f64> = (0..conc.len())
+            .map(|i| conc[i] * volumes[i / n] * multiplicities[i / n])
+            .collect();
+        let mut world = Builder {
+            topology,
+            parents: topology.parents().to_vec(),
+            names: topology.names().to_vec(),
+            amounts,
+            multiplicities: multiplicities.to_vec(),
+            volumes: volumes.to_vec(),
+            source: (0..topology.num_compartments()).collect(),
+            removed: vec![false; topology.num_compartments()],
+            absorbed: None,
+            n,
+        };
+        match self {
+            Remodel::Divide {
+                compartment,
+                split,
+                suffix,
+            } => world.divide(*compartment, *split, suffix, rng)?,
+            Remodel::Die { compartment } => world.die(*compartment)?,
+            Remodel::Fuse { keep, absorb } => world.fuse(*keep, *absorb)?,
+        }
+        world.finish()
+    }
+}
+
+/// Working copy of a topology and its buffers, indexed by old ID with
+/// division copies appended.
+struct Builder<'a> {
+    topology: &'a Topology,
+    parents: Vec<Option<CompartmentId>>,
+    names: Vec<String>,
+    amounts: Vec<f64>,
+    multiplicities: Vec<f64>,
+    volumes: Vec<f64>,
+    source: Vec<CompartmentId>,
+    removed: Vec<bool>,
+    /// (absorbed, keep) of a fusion.
+    absorbed: Option<(CompartmentId, CompartmentId)>,
+    n: usize,
+}
+
+impl Builder<'_> {
+    fn non_root(&self, compartment: CompartmentId) -> SimResult<CompartmentId> {
+        self.topology.check(compartment)?;
+        self.topology.parent(compartment).ok_or_else(|| {
+            SimError::Value(format!(
+                "Compartment {} is the root",
+                self.topology.name(compartment)
+            ))
+        })
+    }
+
+    fn divide<R: Rng>(
+        &mut self,
+        compartment: CompartmentId,
+        split: Split,
+        suffix: &str,
+        rng: &mut R,
+    ) -> SimResult<()> {
+        self.non_root(compartment)?;
+        let ratio = split.ratio();
+        if !(ratio > 0.0 && ratio < 1.0) {
+            return Err(SimError::Value(format!(
+                "Division ratio must be between 0 and 1, got {ratio}"
+            )));
+        }
+        let mut subtree = vec![compartment];
+        subtree.extend(self.topology.descendants(compartment));
+        subtree.sort_unstable();
+        let first = self.parents.len();
+        let copy_of = |old: CompartmentId| first + subtree.iter().position(|&c| c == old).unwrap();
+        for &old in &subtree {
+            let parent = if old == compartment {
+                self.parents[old]
+            } else {
+                self.parents[old].map(copy_of)
+            };
+            self.parents.push(parent);
+            self.names.push(format!("{}{suffix}", self.names[old]));
+            self.source.push(old);
+            self.removed.push(false);
+            self.multiplicities.push(self.multiplicities[old]);
+            self.volumes.push(self.volumes[old] * (1.0 - ratio));
+            self.volumes[old] *= ratio;
+            for m in 0..self.n {
+                let amount = self.amounts[old * self.n + m];
+                let kept = split.kept(amount, rng);
+                self.amounts[old * self.n + m] = kept;
+                self.amounts.push(amount - kept);
+            }
+        }
+        Ok(())
+    }
+
+    fn die(&mut self, compartment: CompartmentId) -> SimResult<()> {
+        let parent = self.non_root(compartment)?;
+        let mut subtree = vec![compartment];
+        subtree.extend(self.topology.descendants(compartment));
+        for &old in &subtree {
+            self.removed[old] = true;
+            for m in 0..self.n {
+                let released = std::mem::take(&mut self.amounts[old * self.n + m]);
+                self.amounts[parent * self.n + m] += released;
+            }
+        }
+        if self.multiplicities[parent] <= 0.0
+            && (0..self.n).any(|m| self.amounts[parent * self.n + m] != 0.0)
+        {
+            return Err(SimError::Value(format!(
+                "Cannot release {} into {}: it has no instances",
+                self.topology.name(compartment),
+                self.topology.name(parent)
+            )));
+        }
+        Ok(())
+    }
+
+    fn fuse(&mut self, keep: CompartmentId, absorb: CompartmentId) -> SimResult<()> {
+        let (keep_parent, absorb_parent) = (self.non_root(keep)?, self.non_root(absorb)?);
+        let (a, b) = (self.topology.name(keep), self.topology.name(absorb));
+        if keep == absorb || keep_parent != absorb_parent {
+            return Err(SimError::Value(format!(
+                "Only two distinct siblings can fuse, got {a} and {b}"
+            )));
+        }
+        let instances = self.multiplicities[keep];
+        if instances <= 0.0 {
+            return Err(SimError::Value(format!(
+                "Cannot fuse {b} into {a}: it has no instances"
+            )));
+        }
+        // The kept instances hold the combined volume of both populations.
+        self.volumes[keep] += self.volumes[absorb] * self.multiplicities[absorb] / instances;
+        for m in 0..self.n {
+            let moved = std::mem::take(&mut self.amounts[absorb * self.n + m]);
+            self.amounts[keep * self.n + m] += moved;
+        }
+        for parent in self.parents.iter_mut() {
+            if *parent == Some(absorb) {
+                *parent = Some(keep);
+            }
+        }
+        self.removed[absorb] = true;
+        self.absorbed = Some((absorb, keep));
+        Ok(())
+    }
+
+    fn finish(self) -> SimResult<Remodeled> {
+        let n = self.n;
+        let old = self.topology.num_compartments();
+        let mut ids = vec![None; self.parents.len()];
+        let mut next = 0;
+        for (id, removed) in ids.iter_mut().zip(&self.removed) {
+            if !removed {
+                *id = Some(next);
+                next += 1;
+            }
+        }
+        let kept: Vec<CompartmentId> = (0..ids.len()).filter(|&c| !self.removed[c]).collect();
+        let parents = kept
+            .iter()
+            .map(|&c| self.parents[c].map(|p| ids[p].expect("parent survives")))
+            .collect();
+        let names = kept.iter().map(|&c| self.names[c].clone()).collect();
+        let topology = Topology::from_parents(parents, names)?;
+
+        let mut map = ids[..old].to_vec();
+        if let Some((absorb, keep)) = self.absorbed {
+            map[absorb] = ids[keep];
+        }
+        let (mut concentrations, mut multiplicities, mut volumes) =
+            (Vec::with_capacity(kept.len() * n), Vec::new(), Vec::new());
+        for &c in &kept {
+            let (instances, volume) = (self.multiplicities[c], self.volumes[c]);
+            for m in 0..n {
+                let amount = self.amounts[c * n + m];
+                concentrations.push(if instances > 0.0 {
+                    amount / (volume * instances)
+                } else {
+                    0.0
+                });
+            }
+            multiplicities.push(instances);
+            volumes.push(volume);
+        }
+        Ok(Remodeled {
+            remap: Remap {
+                topology,
+                map,
+                source: kept.iter().map(|&c| self.source[c]).collect(),
+            },
+            concentrations,
+            multiplicities,
+            volumes,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    /// body -> {blood, liver -> hepatocyte}, with one molecule.
+    fn world() -> (Topology, Vec<f64>, Vec<f64>, Vec<f64>) {
+        let mut t = Topology::new();
+        let body = t.add_root("body").unwrap();
+        t.add_child(body, "blood").unwrap();
+        let liver = t.add_child(body, "liver").unwrap();
+        t.add_child(liver, "hepatocyte").unwrap();
+        (
+            t,
+            vec![1.0, 2.0, 4.0, 8.0],
+            vec![1.0, 1.0, 1.0, 10.0],
+            vec![10.0, 2.0, 1.0, 0.5],
+        )
+    }
+
+    fn total(r: &Remodeled) -> f64 {
+        (0..r.multiplicities.len())
+            .map(|c| r.concentrations[c] * r.volumes[c] * r.multiplicities[c])
+            .sum()
+    }
+
+    #[test]
+    fn division_copies_the_subtree() {
+        let (t, conc, mult, vol) = world();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let op = Remodel::Divide {
+            compartment: 2,
+            split: Split::Fixed(0.25),
+            suffix: "_2".into(),
+        };
+        let r = op.apply(&t, 1, &conc, &mult, &vol, &mut rng).unwrap();
+        assert_eq!(r.remap.topology.names()[4..], ["liver_2", "hepatocyte_2"]);
+        assert_eq!(
+            r.remap.topology.parents(),
+            &[None, Some(0), Some(0), Some(2), Some(0), Some(4)]
+        );
+        assert_eq!(r.remap.source, vec![0, 1, 2, 3, 2, 3]);
+        assert_eq!(r.remap.targets(3), vec![3, 5]);
+        // Concentrations are unchanged when amount and volume split alike.
+        assert_eq!(r.concentrations, vec![1.0, 2.0, 4.0, 8.0, 4.0, 8.0]);
+        assert_eq!(r.volumes, vec![10.0, 2.0, 0.25, 0.125, 0.75, 0.375]);
+        assert_eq!(r.multiplicities[5], 10.0);
+        assert!((total(&r) - 58.0).abs() < 1e-12);
+
+        let op = Remodel::Divide {
+            compartment: 3,
+            split: Split::Binomial(0.5),
+            suffix: "_2".into(),
+        };
+        let r = op.apply(&t, 1, &conc, &mult, &vol, &mut rng).unwrap();
+        assert!((total(&r) - 58.0).abs() < 1e-12);
+        assert_ne!(r.concentrations[3], r.concentrations[4]);
+        assert!(Remodel::Divide {
+            compartment: 0,
+            split: Split::Fixed(0.5),
+            suffix: "_2".into()
+        }
+        .apply(&t, 1, &conc, &mult, &vol, &mut rng)
+        .is_err());
+    }
+
+    #[test]
+    fn death_releases_contents_to_parent() {
+        let (t, conc, mult, vol) = world();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let r = Remodel::Die { compartment: 2 }
+            .apply(&t, 1, &conc, &mult, &vol, &mut rng)
+            .unwrap();
+        assert_eq!(r.remap.topology.names(), ["body", "blood"]);
+        assert_eq!(r.remap.map, vec![Some(0), Some(1), None, None]);
+        // liver (4) and 10 hepatocytes (40) released into the body volume of 10.
+        assert_eq!(r.concentrations, vec![1.0 + 4.4, 2.0]);
+        assert_eq!(r.remap.index(4, 1), Some(2));
+        assert_eq!(r.remap.index(2, 1), None);
+    }
+
+    #[test]
+    fn fusion_pools_siblings() {
+        let (t, conc, mult, vol) = world();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let r = Remodel::Fuse { keep: 1, absorb: 2 }
+            .apply(&t, 1, &conc, &mult, &vol, &mut rng)
+            .unwrap();
+        assert_eq!(r.remap.topology.names(), ["body", "blood", "hepatocyte"]);
+        assert_eq!(r.remap.topology.parents(), &[None, Some(0), Some(1)]);
+        assert_eq!(r.remap.map, vec![Some(0), Some(1), Some(1), Some(2)]);
+        assert_eq!(r.volumes[1], 3.0);
+        assert!((r.concentrations[1] - 8.0 / 3.0).abs() < 1e-12);
+        assert!((total(&r) - 58.0).abs() < 1e-12);
+        assert!(Remodel::Fuse { keep: 1, absorb: 3 }
+            .apply(&t, 1, &conc, &mult, &vol, &mut rng)
+            .is_err());
+    }
+}
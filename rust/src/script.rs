@@ -0,0 +1,615 @@
+//! Script: the sandboxed `rhai:` escape hatch for rates and flow bodies.
+//!
+//! A `rhai:` string is compiled by an embedded Rhai engine with no module
+//! loading, no clock, no `eval` and silenced `print`/`debug`, so a script can
+//! only compute over the values it is given. Every evaluation is bounded by
+//! an operation count, call depth and container sizes.
+//!
+//! Molecule names (and any parameters) are in scope as numbers, as are
+//! `dt` for flow bodies, and `memory`, an object map kept per compartment
+//! between evaluations for history-dependent logic. `memory` starts empty at
+//! every `run`, and rates that use it need a fixed-step method, which
+//! evaluates each rate once per step. A script may open with a
+//! closure-style header, `rhai:|S, vmax, km| vmax * S / (km + S)`, to name
+//! exactly the variables it reads.
+//!
+//! Scripts are disabled until `enable_rhai()` is called, mirroring the
+//! Interpreter's `enable_rhai` switch.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use pyo3::prelude::*;
+use rhai::{Dynamic, Engine, Map, Scope, AST};
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::reaction::{MoleculeId, Reaction};
+use crate::remodel::Remap;
+use crate::tree::CompartmentId;
+
+/// Prefix marking a Rhai script.
+pub const PREFIX: &str = "rhai:";
+
+/// Default operation budget for one evaluation.
+pub const DEFAULT_MAX_OPERATIONS: u64 = 100_000;
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static MAX_OPERATIONS: AtomicU64 = AtomicU64::new(DEFAULT_MAX_OPERATIONS);
+
+/// Allow or forbid compiling `rhai:` scripts from now on.
+pub fn set_enabled(enabled: bool) {
+    ENABLED.store(enabled, Ordering::SeqCst);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::SeqCst)
+}
+
+/// Operation budget given to scripts compiled from now on.
+pub fn set_max_operations(max_operations: u64) {
+    MAX_OPERATIONS.store(max_operations, Ordering::SeqCst);
+}
+
+pub fn max_operations() -> u64 {
+    MAX_OPERATIONS.load(Ordering::SeqCst)
+}
+
+/// The script body of a `rhai:` string, or None for anything else.
+pub fn strip(source: &str) -> Option<&str> {
+    source.trim_start().strip_prefix(PREFIX)
+}
+
+/// An engine with no access outside the values placed in its scope.
+fn sandbox(max_operations: u64) -> Engine {
+    let mut engine = Engine::new();
+    engine.on_print(|_| {});
+    engine.on_debug(|_, _, _| {});
+    engine.disable_symbol("eval");
+    engine.set_strict_variables(true);
+    engine.set_max_operations(max_operations);
+    engine.set_max_call_levels(32);
+    engine.set_max_expr_depths(64, 32);
+    engine.set_max_string_size(4096);
+    engine.set_max_array_size(4096);
+    engine.set_max_map_size(1024);
+    engine
+}
+
+/// What a variable in scope holds.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Symbol {
+    Molecule(MoleculeId),
+    Param(f64),
+    /// A per-call value such as `dt`, by position.
+    Extra(usize),
+}
+
+/// A compiled script and the variables bound into its scope.
+pub struct Script {
+    source: String,
+    engine: Engine,
+    ast: AST,
+    symbols: Vec<(String, Symbol)>,
+    /// `memory` map of each compartment that has run the script.
+    memory: Mutex<HashMap<CompartmentId, Map>>,
+    /// Whether the body mentions `memory`.
+    uses_memory: bool,
+}
+
+impl Script {
+    /// Compile a `rhai:` string, binding molecule names, `params` and the
+    /// per-call `extras` (e.g. `dt`).
+    ///
+    /// Without a header every molecule with an identifier-like name is in
+    /// scope; with one, each header name must be a molecule, parameter or extra.
+    pub fn compile(
+        source: &str,
+        molecules: &MoleculeIndex,
+        params: &[(String, f64)],
+        extras: &[&str],
+    ) -> SimResult<Self> {
+        if !is_enabled() {
+            return Err(SimError::Value(
+                "rhai: scripts are disabled; call alienbio_sim.enable_rhai() to allow them"
+                    .to_string(),
+            ));
+        }
+        let body = strip(source).ok_or_else(|| {
+            SimError::Value(format!("Expected a {PREFIX} script, got {source:?}"))
+        })?;
+        let lookup = |name: &str| {
+            extras
+                .iter()
+                .position(|e| *e == name)
+                .map(Symbol::Extra)
+                .or_else(|| {
+                    params
+                        .iter()
+                        .find(|(p, _)| p == name)
+                        .map(|&(_, v)| Symbol::Param(v))
+                })
+                .or_else(|| molecules.get(name).map(Symbol::Molecule))
+        };
+
+        let (symbols, body) = match header(body) {
+            Some((names, rest)) => {
+                let symbols = names
+                    .into_iter()
+                    .map(|name| match lookup(&name) {
+                        Some(symbol) => Ok((name, symbol)),
+                        None => Err(SimError::Key(format!(
+                            "rhai script reads unknown variable {name:?}"
+                        ))),
+                    })
+                    .collect::<SimResult<Vec<_>>>()?;
+                (symbols, rest)
+            }
+            None => {
+                let names = molecules
+                    .names()
+                    .iter()
+                    .chain(params.iter().map(|(p, _)| p))
+                    .map(String::as_str)
+                    .chain(extras.iter().copied());
+                let mut symbols: Vec<(String, Symbol)> = Vec::new();
+                for name in names.filter(|n| is_identifier(n)) {
+                    if !symbols.iter().any(|(s, _)| s == name) {
+                        symbols.push((name.to_string(), lookup(name).unwrap()));
+                    }
+                }
+                (symbols, body)
+            }
+        };
+
+        let engine = sandbox(max_operations());
+        // Mutable placeholders: constants in scope would be folded into the AST.
+        let mut scope = Scope::new();
+        for (name, _) in &symbols {
+            scope.push(name.clone(), 0.0);
+        }
+        scope.push("memory", Map::new());
+        let ast = engine
+            .compile_with_scope(&scope, body)
+            .map_err(|e| SimError::Value(format!("rhai script {body:?}: {e}")))?;
+        Ok(Self {
+            source: source.to_string(),
+            engine,
+            ast,
+            symbols,
+            memory: Mutex::new(HashMap::new()),
+            uses_memory: mentions(body, "memory"),
+        })
+    }
+
+    /// The original `rhai:` string.
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    /// Molecule IDs in scope, sorted.
+    pub fn variables(&self) -> Vec<MoleculeId> {
+        let mut ids: Vec<_> = self
+            .symbols
+            .iter()
+            .filter_map(|(_, s)| match s {
+                Symbol::Molecule(m) => Some(*m),
+                _ => None,
+            })
+            .collect();
+        ids.sort_unstable();
+        ids.dedup();
+        ids
+    }
+
+    /// Run the script on one compartment's concentrations.
+    ///
+    /// With a `site`, `memory` starts from and is saved back to that
+    /// compartment's map; without one it starts empty and is discarded.
+    /// Returns the script's value and the final values of its variables.
+    fn run(
+        &self,
+        conc: &[f64],
+        extras: &[f64],
+        site: Option<CompartmentId>,
+    ) -> SimResult<(Dynamic, Scope<'static>)> {
+        let memory = match site {
+            Some(site) => self.memory_of(site),
+            None => Map::new(),
+        };
+        let mut scope = Scope::new();
+        for (name, symbol) in &self.symbols {
+            let value = match *symbol {
+                Symbol::Molecule(m) => conc[m],
+                Symbol::Param(v) => v,
+                Symbol::Extra(i) => extras[i],
+            };
+            scope.push(name.clone(), value);
+        }
+        scope.push("memory", memory);
+        let value = self
+            .engine
+            .eval_ast_with_scope::<Dynamic>(&mut scope, &self.ast)
+            .map_err(|e| SimError::Value(format!("rhai script {:?}: {e}", self.source)))?;
+        if let Some(site) = site {
+            if let Some(memory) = scope.get_value::<Map>("memory") {
+                self.memory.lock().unwrap().insert(site, memory);
+            }
+        }
+        Ok((value, scope))
+    }
+
+    /// The script's value as a number (booleans count as 1.0/0.0).
+    pub fn eval(
+        &self,
+        conc: &[f64],
+        extras: &[f64],
+        site: Option<CompartmentId>,
+    ) -> SimResult<f64> {
+        let (value, _) = self.run(conc, extras, site)?;
+        number(&value).ok_or_else(|| {
+            SimError::Value(format!(
+                "rhai script {:?} returned {} instead of a number",
+                self.source,
+                value.type_name()
+            ))
+        })
+    }
+
+    /// Run the script and write the molecule variables it assigned back into `conc`.
+    pub fn update(&self, conc: &mut [f64], extras: &[f64], site: CompartmentId) -> SimResult<()> {
+        let (_, scope) = self.run(conc, extras, Some(site))?;
+        for (name, symbol) in &self.symbols {
+            if let Symbol::Molecule(m) = *symbol {
+                let value = scope.get(name).and_then(number).ok_or_else(|| {
+                    SimError::Value(format!(
+                        "rhai script {:?} set {name} to a non-number",
+                        self.source
+                    ))
+                })?;
+                conc[m] = value;
+            }
+        }
+        Ok(())
+    }
+
+    /// A copy of `memory` for compartment `site`.
+    pub fn memory_of(&self, site: CompartmentId) -> Map {
+        self.memory
+            .lock()
+            .unwrap()
+            .get(&site)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Forget every compartment's `memory`.
+    pub fn reset(&self) {
+        self.memory.lock().unwrap().clear();
+    }
+
+    /// Whether the script reads or writes `memory`.
+    pub fn uses_memory(&self) -> bool {
+        self.uses_memory
+    }
+
+    /// Follow a topology change: each new compartment keeps the `memory` of
+    /// the compartment it came from, so division copies it.
+    pub fn remap_memory(&self, remap: &Remap) {
+        let mut memory = self.memory.lock().unwrap();
+        let remapped = (remap.source.iter().enumerate())
+            .filter_map(|(new, old)| Some((new, memory.get(old)?.clone())))
+            .collect();
+        *memory = remapped;
+    }
+}
+
+impl fmt::Debug for Script {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Script")
+            .field("source", &self.source)
+            .field("symbols", &self.symbols)
+            .finish()
+    }
+}
+
+impl PartialEq for Script {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source && self.symbols == other.symbols
+    }
+}
+
+/// `|a, b, c| body` split into its names and body.
+fn header(body: &str) -> Option<(Vec<String>, &str)> {
+    let rest = body.trim_start().strip_prefix('|')?;
+    let end = rest.find('|')?;
+    let names = rest[..end]
+        .split(',')
+        .map(str::trim)
+        .filter(|n| !n.is_empty())
+        .map(String::from)
+        .collect();
+    Some((names, &rest[end + 1..]))
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+        && name != "memory"
+}
+
+/// Whether `name` occurs in `body` as a whole identifier.
+fn mentions(body: &str, name: &str) -> bool {
+    let is_ident = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    body.match_indices(name).any(|(at, _)| {
+        !body[..at].ends_with(is_ident) && !body[at + name.len()..].starts_with(is_ident)
+    })
+}
+
+fn number(value: &Dynamic) -> Option<f64> {
+    value
+        .as_float()
+        .ok()
+        .or_else(|| value.as_int().ok().map(|i| i as f64))
+        .or_else(|| value.as_bool().ok().map(|b| if b { 1.0 } else { 0.0 }))
+}
+
+/// A `rhai:` rate law (`RateLaw::Script`).
+///
+/// Rates cannot fail mid-step, so an evaluation error yields NaN and is kept
+/// until `take_error`; the Python-facing simulators raise it after the step.
+pub struct ScriptRate {
+    script: Script,
+    variables: Vec<MoleculeId>,
+    error: Mutex<Option<String>>,
+}
+
+impl ScriptRate {
+    pub fn new(
+        source: &str,
+        molecules: &MoleculeIndex,
+        params: &[(String, f64)],
+    ) -> SimResult<Self> {
+        let script = Script::compile(source, molecules, params, &[])?;
+        Ok(Self {
+            variables: script.variables(),
+            script,
+            error: Mutex::new(None),
+        })
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+
+    /// Molecule IDs in the script's scope, sorted.
+    pub fn variables(&self) -> &[MoleculeId] {
+        &self.variables
+    }
+
+    /// Rate in compartment `site`, updating its `memory`.
+    pub fn eval_at(&self, conc: &[f64], site: CompartmentId) -> f64 {
+        self.record(self.script.eval(conc, &[], Some(site)))
+    }
+
+    /// Rate without touching `memory` (finite-difference probes).
+    pub fn eval(&self, conc: &[f64]) -> f64 {
+        self.record(self.script.eval(conc, &[], None))
+    }
+
+    fn record(&self, result: SimResult<f64>) -> f64 {
+        result.unwrap_or_else(|err| {
+            self.error.lock().unwrap().get_or_insert(err.to_string());
+            f64::NAN
+        })
+    }
+
+    /// The first evaluation error since the last call, if any.
+    pub fn take_error(&self) -> Option<String> {
+        self.error.lock().unwrap().take()
+    }
+}
+
+impl fmt::Debug for ScriptRate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ScriptRate")
+            .field(&self.script.source)
+            .finish()
+    }
+}
+
+impl PartialEq for ScriptRate {
+    fn eq(&self, other: &Self) -> bool {
+        self.script == other.script
+    }
+}
+
+/// Raise the first pending evaluation error of any script rate in `reactions`.
+pub fn check(reactions: &[Reaction]) -> SimResult<()> {
+    let mut first = None;
+    for reaction in reactions {
+        if let Some(err) = reaction.script.as_ref().and_then(|s| s.take_error()) {
+            first.get_or_insert(format!("Reaction {}: {err}", reaction.name));
+        }
+    }
+    first.map_or(Ok(()), |msg| Err(SimError::Value(msg)))
+}
+
+/// Forget the `memory` of every script rate in `reactions`, so a run starts
+/// afresh.
+pub fn reset(reactions: &[Reaction]) {
+    for script in reactions.iter().filter_map(|r| r.script.as_ref()) {
+        script.script().reset();
+    }
+}
+
+/// Reject script rates that use `memory` under `method`, which evaluates
+/// rates several times per step (stages, rejected steps), so `memory` would
+/// not advance once per step.
+pub fn check_memory(reactions: &[Reaction], method: &str) -> SimResult<()> {
+    match reactions
+        .iter()
+        .find(|r| r.script.as_ref().is_some_and(|s| s.script().uses_memory()))
+    {
+        Some(reaction) => Err(SimError::Value(format!(
+            "Reaction {}: rhai scripts using memory need method \"euler\", not {method:?}",
+            reaction.name
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// A GeneralFlow whose body is a `rhai:` script.
+///
+/// The body sees the origin compartment's concentrations as variables, plus
+/// `dt` and `memory`; molecule variables it assigns are written back.
+#[derive(Debug)]
+pub struct ScriptFlow {
+    pub name: String,
+    pub origin: CompartmentId,
+    script: Script,
+}
+
+impl ScriptFlow {
+    pub fn new(
+        name: impl Into<String>,
+        origin: CompartmentId,
+        source: &str,
+        molecules: &MoleculeIndex,
+    ) -> SimResult<Self> {
+        Ok(Self {
+            name: name.into(),
+            origin,
+            script: Script::compile(source, molecules, &[], &["dt"])?,
+        })
+    }
+
+    /// Run the body on the origin compartment of the flat buffer `conc`.
+    pub fn apply(&self, conc: &mut [f64], num_molecules: usize, dt: f64) -> SimResult<()> {
+        let offset = self.origin * num_molecules;
+        let slice = conc
+            .get_mut(offset..offset + num_molecules)
+            .ok_or_else(|| {
+                SimError::Index(format!(
+                    "Flow {}: origin compartment {} out of range",
+                    self.name, self.origin
+                ))
+            })?;
+        self.script
+            .update(slice, &[dt], self.origin)
+            .map_err(|e| SimError::Value(format!("Flow {}: {e}", self.name)))
+    }
+
+    pub fn script(&self) -> &Script {
+        &self.script
+    }
+}
+
+/// Allow (or forbid) `rhai:` rates and flow bodies.
+///
+/// Args:
+///     enabled: Whether scripts may be compiled
+///     max_operations: Operation budget per evaluation for scripts compiled
+///         from now on (default 100000)
+#[pyfunction]
+#[pyo3(signature = (enabled=true, max_operations=None))]
+pub fn enable_rhai(enabled: bool, max_operations: Option<u64>) {
+    set_enabled(enabled);
+    if let Some(max_operations) = max_operations {
+        set_max_operations(max_operations);
+    }
+}
+
+/// Whether `rhai:` scripts are currently allowed.
+#[pyfunction]
+pub fn rhai_enabled() -> bool {
+    is_enabled()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn molecules() -> MoleculeIndex {
+        MoleculeIndex::new(vec!["S".into(), "P".into(), "not-an-ident".into()])
+    }
+
+    #[test]
+    fn rate_reads_molecules_and_params() {
+        set_enabled(true);
+        let params = vec![("vmax".to_string(), 10.0), ("km".to_string(), 5.0)];
+        let rate = ScriptRate::new("rhai:vmax * S / (km + S)", &molecules(), &params).unwrap();
+        assert_eq!(rate.eval(&[5.0, 0.0, 0.0]), 5.0);
+        assert_eq!(rate.variables(), &[0, 1]);
+
+        let rate =
+            ScriptRate::new("rhai:|P| if P > 1.0 { 2 } else { 0 }", &molecules(), &[]).unwrap();
+        assert_eq!(rate.variables(), &[1]);
+        assert_eq!(rate.eval(&[0.0, 3.0, 0.0]), 2.0);
+        assert!(ScriptRate::new("rhai:|X| X", &molecules(), &[]).is_err());
+        assert!(ScriptRate::new("rhai:Q * 2.0", &molecules(), &[]).is_err());
+    }
+
+    #[test]
+    fn memory_persists_per_site() {
+        set_enabled(true);
+        let source = "rhai:let n = if \"n\" in memory { memory.n } else { 0 }; memory.n = n + 1; n";
+        let rate = ScriptRate::new(source, &molecules(), &[]).unwrap();
+        let conc = [0.0; 3];
+        assert_eq!(rate.eval_at(&conc, 0), 0.0);
+        assert_eq!(rate.eval_at(&conc, 0), 1.0);
+        assert_eq!(rate.eval_at(&conc, 1), 0.0);
+        assert_eq!(rate.eval(&conc), 0.0);
+        assert_eq!(rate.eval_at(&conc, 0), 2.0);
+        assert!(rate.script().uses_memory());
+
+        // Each new compartment takes its source's memory; the rest is dropped.
+        let mut topology = crate::tree::Topology::new();
+        let root = topology.add_root("organism").unwrap();
+        topology.add_child(root, "cell").unwrap();
+        rate.script().remap_memory(&Remap {
+            topology,
+            map: vec![None, Some(0)],
+            source: vec![1, 1],
+        });
+        assert_eq!(rate.eval_at(&conc, 0), 1.0);
+        assert_eq!(rate.eval_at(&conc, 1), 1.0);
+
+        rate.script().reset();
+        assert_eq!(rate.eval_at(&conc, 0), 0.0);
+        let stateless = ScriptRate::new("rhai:let memory_use = 1; S", &molecules(), &[]).unwrap();
+        assert!(!stateless.script().uses_memory());
+    }
+
+    #[test]
+    fn runaway_scripts_are_stopped() {
+        set_enabled(true);
+        let rate = ScriptRate::new("rhai:loop { }", &molecules(), &[]).unwrap();
+        assert!(rate.eval(&[0.0; 3]).is_nan());
+        let err = rate.take_error().unwrap();
+        assert!(err.contains("operations"), "{err}");
+        assert!(rate.take_error().is_none());
+
+        assert!(ScriptRate::new("rhai:eval(\"1\")", &molecules(), &[]).is_err());
+        assert!(ScriptRate::new("rhai:import \"os\" as os; 1", &molecules(), &[]).is_err());
+    }
+
+    #[test]
+    fn flow_writes_back_origin() {
+        set_enabled(true);
+        let flow = ScriptFlow::new(
+            "leak",
+            1,
+            "rhai:let d = S * 0.5 * dt; S -= d; P += d;",
+            &molecules(),
+        )
+        .unwrap();
+        let mut conc = vec![1.0, 0.0, 0.0, 4.0, 0.0, 7.0];
+        flow.apply(&mut conc, 3, 0.5).unwrap();
+        assert_eq!(conc, vec![1.0, 0.0, 0.0, 3.0, 1.0, 7.0]);
+        assert!(flow.apply(&mut conc, 3 * 2, 1.0).is_err());
+    }
+}
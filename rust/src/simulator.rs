@@ -0,0 +1,278 @@
+//! ChemistrySimulator: single-compartment simulator over a `ChemistryImpl`.
+//!
+//! Native counterpart of `alienbio.bio.simulator.ReferenceSimulatorImpl`,
+//! satisfying the `Simulator` protocol (`chemistry`, `dt`, `step`, `run`) so it
+//! can be registered as the `rust` simulator factory. States are `StateImpl`
+//! objects on the Python side and flat vectors in molecule-ID order inside.
+//!
+//! With an adaptive `method` ("rk45", "rosenbrock") the same kinetics are
+//! integrated as an ODE system, and `run` samples it at exact multiples of
+//! `dt`: a constant rate is a zero-order flux in every mode. Reactions whose
+//! rate is an Expr (e.g. the `mass_action` template) or a `rhai:` script are
+//! evaluated natively in both modes.
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::PyDict;
+use std::sync::Arc;
+
+use crate::chemistry::MoleculeIndex;
+use crate::integrate::Method;
+use crate::reaction::{RateLaw, Reaction};
+use crate::script;
+use crate::tree::Topology;
+use crate::world_simulator::WorldModel;
+
+/// Apply all reactions once with the reference semantics.
+///
+/// Rates are evaluated on the incoming state (`rates[r]`), then reactants are
+/// consumed and products produced in reaction order, clamping reactants at zero.
+pub fn reference_step(reactions: &[Reaction], rates: &[f64], conc: &mut [f64], dt: f64) {
+    for (reaction, &rate) in reactions.iter().zip(rates) {
+        let amount = rate * dt;
+        for &(mol, coef) in &reaction.reactants {
+            conc[mol] = (conc[mol] - amount * coef).max(0.0);
+        }
+        for &(mol, coef) in &reaction.products {
+            conc[mol] += amount * coef;
+        }
+    }
+}
+
+/// Basic single-compartment simulator applying reactions once per step.
+#[pyclass(module = "alienbio_sim", subclass)]
+pub struct ChemistrySimulator {
+    chemistry: PyObject,
+    molecules: MoleculeIndex,
+    reactions: Vec<Reaction>,
+    /// `ReactionImpl` objects whose rate is a Python callable, by reaction index.
+    rate_fns: Vec<Option<PyObject>>,
+    dt: f64,
+    method: Method,
+    /// Mass-action form of the chemistry, for adaptive methods.
+    model: Option<WorldModel>,
+}
+
+impl ChemistrySimulator {
+    pub fn molecules(&self) -> &MoleculeIndex {
+        &self.molecules
+    }
+
+    pub fn reactions(&self) -> &[Reaction] {
+        &self.reactions
+    }
+
+    /// Read a `StateImpl` into a vector in molecule-ID order.
+    fn read_state(&self, state: &PyAny) -> PyResult<Vec<f64>> {
+        self.molecules
+            .names()
+            .iter()
+            .map(|name| state.get_item(name.as_str())?.extract())
+            .collect()
+    }
+
+    /// Build a new `StateImpl` of the same class holding `conc`.
+    fn make_state<'py>(&self, like: &'py PyAny, conc: &[f64]) -> PyResult<&'py PyAny> {
+        let py = like.py();
+        let initial = PyDict::new(py);
+        for (name, value) in self.molecules.names().iter().zip(conc) {
+            initial.set_item(name, value)?;
+        }
+        let kwargs = PyDict::new(py);
+        kwargs.set_item("initial", initial)?;
+        like.get_type().call((&self.chemistry,), Some(kwargs))
+    }
+
+    /// Rates for `state`: native rate laws on `conc`, or `reaction.get_rate(state)`.
+    fn rates(&self, state: &PyAny, conc: &[f64]) -> PyResult<Vec<f64>> {
+        self.reactions
+            .iter()
+            .zip(&self.rate_fns)
+            .map(|(reaction, rate_fn)| match rate_fn {
+                Some(rxn) => rxn
+                    .call_method1(state.py(), "get_rate", (state,))?
+                    .extract(state.py()),
+                None => Ok(reaction.rate(conc)),
+            })
+            .collect()
+    }
+
+    fn advance<'py>(&self, state: &'py PyAny, conc: &mut [f64]) -> PyResult<&'py PyAny> {
+        match &self.model {
+            Some(model) => {
+                self.method
+                    .integrate(model, 0.0, conc, &[self.dt], |_, _| {})?;
+            }
+            None => {
+                let rates = self.rates(state, conc)?;
+                reference_step(&self.reactions, &rates, conc, self.dt);
+            }
+        }
+        script::check(&self.reactions)?;
+        self.make_state(state, conc)
+    }
+
+    /// ODE model over one compartment for an adaptive `method`. Constant
+    /// rates stay zero-order fluxes, as in the Euler reference step.
+    fn ode_model(
+        reactions: &[Reaction],
+        rate_fns: &[Option<PyObject>],
+        num_molecules: usize,
+        method: &Method,
+    ) -> PyResult<WorldModel> {
+        if let Some(i) = rate_fns.iter().position(Option::is_some) {
+            return Err(PyValueError::new_err(format!(
+                "Reaction {:?} has a callable rate; method {:?} needs constant rate constants",
+                reactions[i].name,
+                method.name()
+            )));
+        }
+        let mut topology = Topology::new();
+        topology.add_root("root")?;
+        Ok(WorldModel::new(
+            Arc::new(topology),
+            reactions.to_vec(),
+            num_molecules,
+        )?)
+    }
+}
+
+#[pymethods]
+impl ChemistrySimulator {
+    /// Initialize simulator.
+    ///
+    /// Args:
+    ///     chemistry: The ChemistryImpl to simulate
+    ///     dt: Time step size (default 1.0)
+    ///     method: "euler" (as ReferenceSimulatorImpl), "rk45" or "rosenbrock"
+    ///     atol: Absolute tolerance for adaptive methods
+    ///     rtol: Relative tolerance for adaptive methods
+    ///     lu: "auto", "dense" or "sparse" LU for the rosenbrock Jacobian
+    #[new]
+    #[pyo3(signature = (chemistry, dt=1.0, method="euler", atol=None, rtol=None, lu="auto"))]
+    fn new(
+        chemistry: &PyAny,
+        dt: f64,
+        method: &str,
+        atol: Option<f64>,
+        rtol: Option<f64>,
+        lu: &str,
+    ) -> PyResult<Self> {
+        if !dt.is_finite() || dt <= 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "dt must be positive, got {dt}"
+            )));
+        }
+        let molecules = MoleculeIndex::from_chemistry(chemistry)?;
+        let (reactions, rate_fns): (Vec<_>, Vec<_>) = molecules
+            .reactions(chemistry, RateLaw::Constant, true)?
+            .into_iter()
+            .map(|(reaction, rate_fn)| (reaction, rate_fn.map(Into::into)))
+            .unzip();
+        let method = Method::parse(method, atol, rtol, lu)?;
+        if method.is_adaptive() {
+            script::check_memory(&reactions, method.name())?;
+        }
+        let model = if method.is_adaptive() {
+            Some(Self::ode_model(
+                &reactions,
+                &rate_fns,
+                molecules.len(),
+                &method,
+            )?)
+        } else {
+            None
+        };
+        Ok(Self {
+            chemistry: chemistry.into(),
+            molecules,
+            reactions,
+            rate_fns,
+            dt,
+            method,
+            model,
+        })
+    }
+
+    /// The Chemistry being simulated.
+    #[getter]
+    fn chemistry(&self, py: Python<'_>) -> PyObject {
+        self.chemistry.clone_ref(py)
+    }
+
+    /// Time step size.
+    #[getter]
+    fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Integration method name.
+    #[getter]
+    fn method(&self) -> &'static str {
+        self.method.name()
+    }
+
+    /// Apply all reactions once, returning a new state.
+    fn step<'py>(&self, state: &'py PyAny) -> PyResult<&'py PyAny> {
+        let mut conc = self.read_state(state)?;
+        self.advance(state, &mut conc)
+    }
+
+    /// Run simulation for multiple steps.
+    ///
+    /// Returns:
+    ///     Timeline of states (length = steps + 1, including initial)
+    fn run<'py>(&self, state: &'py PyAny, steps: usize) -> PyResult<Vec<&'py PyAny>> {
+        script::reset(&self.reactions);
+        let mut conc = self.read_state(state)?;
+        if let Some(model) = &self.model {
+            let times: Vec<f64> = (0..=steps).map(|i| i as f64 * self.dt).collect();
+            let mut samples = Vec::with_capacity(times.len());
+            self.method
+                .integrate(model, 0.0, &mut conc, &times, |_, values| {
+                    samples.push(values.to_vec())
+                })?;
+            script::check(&self.reactions)?;
+            return samples
+                .iter()
+                .map(|values| self.make_state(state, values))
+                .collect();
+        }
+        let mut timeline = Vec::with_capacity(steps + 1);
+        let mut current = self.make_state(state, &conc)?;
+        timeline.push(current);
+        for _ in 0..steps {
+            current = self.advance(current, &mut conc)?;
+            timeline.push(current);
+        }
+        Ok(timeline)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "ChemistrySimulator(molecules={}, reactions={}, dt={}, method={:?})",
+            self.molecules.len(),
+            self.reactions.len(),
+            self.dt,
+            self.method.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reference_step_uses_rates_as_flux() {
+        let reactions = vec![
+            Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.5).with_law(RateLaw::Constant),
+            Reaction::new("r2", vec![(1, 2.0)], vec![(2, 1.0)], 0.25).with_law(RateLaw::Constant),
+        ];
+        let rates: Vec<f64> = reactions.iter().map(|r| r.rate(&[])).collect();
+        let mut conc = vec![10.0, 0.0, 0.0];
+        reference_step(&reactions, &rates, &mut conc, 1.0);
+        // r2 sees B after r1 produced it, but its rate was fixed beforehand.
+        assert_eq!(conc, vec![9.5, 0.0, 0.25]);
+    }
+}
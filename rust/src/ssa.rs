@@ -0,0 +1,695 @@
+//! Stochastic simulation algorithms over a `StochasticModel`.
+//!
+//! Three methods share the channel model and random stream of
+//! `StochasticSimulator`:
+//!
+//! - `direct`: Gillespie's direct method, O(channels) per event.
+//! - `next_reaction`: Gibson–Bruck next-reaction method. A dependency graph
+//!   limits propensity updates to the channels an event can affect, and an
+//!   indexed priority queue of absolute firing times finds the next event in
+//!   O(log channels).
+//! - `tau_leap`: adaptive explicit tau-leaping (Cao, Gillespie & Petzold,
+//!   2006). Leaps are sized so no propensity changes by more than `epsilon`;
+//!   reactions close to exhausting a reactant are fired exactly, and a leap
+//!   that would still drive a count negative is halved and redrawn.
+
+use rand::Rng;
+use rand_chacha::ChaCha8Rng;
+use rand_distr::{Distribution, Poisson};
+
+use crate::error::{SimError, SimResult};
+use crate::stochastic::{direct_method, direct_steps, StochasticModel};
+
+/// Stochastic simulation algorithm selection.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SsaMethod {
+    Direct,
+    NextReaction,
+    TauLeap(TauLeap),
+}
+
+impl SsaMethod {
+    /// Parse a method name; `epsilon` only applies to `tau_leap`.
+    pub fn parse(name: &str, epsilon: Option<f64>) -> SimResult<Self> {
+        let method = match name {
+            "direct" => SsaMethod::Direct,
+            "next_reaction" => SsaMethod::NextReaction,
+            "tau_leap" => {
+                let defaults = TauLeap::default();
+                return Ok(SsaMethod::TauLeap(TauLeap::new(
+                    epsilon.unwrap_or(defaults.epsilon),
+                )?));
+            }
+            _ => {
+                return Err(SimError::Value(format!(
+                    "Unknown stochastic method {name:?} (expected 'direct', 'next_reaction' or 'tau_leap')"
+                )))
+            }
+        };
+        if epsilon.is_some() {
+            return Err(SimError::Value(format!(
+                "epsilon only applies to method 'tau_leap', not {name:?}"
+            )));
+        }
+        Ok(method)
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SsaMethod::Direct => "direct",
+            SsaMethod::NextReaction => "next_reaction",
+            SsaMethod::TauLeap(_) => "tau_leap",
+        }
+    }
+
+    /// Start a run of this method at time `t` with the given counts.
+    pub fn start(
+        &self,
+        model: &StochasticModel,
+        counts: &[u64],
+        t: f64,
+        rng: &mut ChaCha8Rng,
+    ) -> Engine {
+        match self {
+            SsaMethod::Direct => Engine::Direct,
+            SsaMethod::NextReaction => {
+                Engine::NextReaction(NextReaction::new(model, counts, t, rng))
+            }
+            SsaMethod::TauLeap(leap) => Engine::TauLeap(*leap),
+        }
+    }
+}
+
+/// Per-run state of a stochastic method.
+///
+/// The next-reaction method keeps absolute putative firing times, so one
+/// engine must be advanced over consecutive intervals of the same run, with
+/// the counts only changed by the engine in between.
+#[derive(Debug, Clone)]
+pub enum Engine {
+    Direct,
+    NextReaction(NextReaction),
+    TauLeap(TauLeap),
+}
+
+impl Engine {
+    /// Advance `counts` from `t` to `t_end`; returns the number of firings.
+    pub fn advance(
+        &mut self,
+        model: &StochasticModel,
+        counts: &mut [u64],
+        t: f64,
+        t_end: f64,
+        rng: &mut ChaCha8Rng,
+    ) -> usize {
+        match self {
+            Engine::Direct => direct_method(model, counts, t, t_end, rng),
+            Engine::NextReaction(nrm) => nrm.advance(model, counts, t_end, rng),
+            Engine::TauLeap(leap) => leap.advance(model, counts, t, t_end, rng),
+        }
+    }
+}
+
+/// Waiting time of an exponential clock with rate `a` (infinite if `a` is 0).
+fn exponential(a: f64, rng: &mut ChaCha8Rng) -> f64 {
+    if a > 0.0 {
+        -(1.0 - rng.gen::<f64>()).ln() / a
+    } else {
+        f64::INFINITY
+    }
+}
+
+/// For each channel, the channels whose propensity may change when it fires.
+///
+/// Channel `j` depends on channel `i` when `i` changes a count that `j`'s
+/// propensity reads: a reactant, an effector or an expression-rate input. Each list is sorted and always contains the channel
+/// itself, whose clock must be redrawn after it fires.
+pub fn dependency_graph(model: &StochasticModel) -> Vec<Vec<usize>> {
+    let channels = model.channels();
+    let size = channels
+        .iter()
+        .flat_map(|c| c.reactant_indices().chain(c.changed_indices()))
+        .max()
+        .map_or(0, |i| i + 1);
+    let mut readers = vec![Vec::new(); size];
+    for (j, channel) in channels.iter().enumerate() {
+        for i in channel.reactant_indices() {
+            readers[i].push(j);
+        }
+    }
+    channels
+        .iter()
+        .enumerate()
+        .map(|(ch, channel)| {
+            let mut deps: Vec<usize> = channel
+                .changed_indices()
+                .flat_map(|i| readers[i].iter().copied())
+                .chain(std::iter::once(ch))
+                .collect();
+            deps.sort_unstable();
+            deps.dedup();
+            deps
+        })
+        .collect()
+}
+
+/// Binary min-heap over items `0..n` whose keys can be changed in place.
+#[derive(Debug, Clone)]
+pub struct IndexedPriorityQueue {
+    keys: Vec<f64>,
+    /// Items in heap order.
+    heap: Vec<usize>,
+    /// Heap slot of each item.
+    position: Vec<usize>,
+}
+
+impl IndexedPriorityQueue {
+    pub fn new(keys: Vec<f64>) -> Self {
+        let n = keys.len();
+        let mut queue = Self {
+            keys,
+            heap: (0..n).collect(),
+            position: (0..n).collect(),
+        };
+        for slot in (0..n / 2).rev() {
+            queue.sift_down(slot);
+        }
+        queue
+    }
+
+    pub fn len(&self) -> usize {
+        self.heap.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.heap.is_empty()
+    }
+
+    /// Item with the smallest key, and that key.
+    pub fn peek(&self) -> Option<(usize, f64)> {
+        self.heap.first().map(|&item| (item, self.keys[item]))
+    }
+
+    pub fn key(&self, item: usize) -> f64 {
+        self.keys[item]
+    }
+
+    /// Change the key of `item`, restoring heap order in O(log n).
+    pub fn update(&mut self, item: usize, key: f64) {
+        let old = self.keys[item];
+        self.keys[item] = key;
+        let slot = self.position[item];
+        if key < old {
+            self.sift_up(slot);
+        } else {
+            self.sift_down(slot);
+        }
+    }
+
+    fn less(&self, a: usize, b: usize) -> bool {
+        self.keys[self.heap[a]] < self.keys[self.heap[b]]
+    }
+
+    fn swap(&mut self, a: usize, b: usize) {
+        self.heap.swap(a, b);
+        self.position[self.heap[a]] = a;
+        self.position[self.heap[b]] = b;
+    }
+
+    fn sift_up(&mut self, mut slot: usize) {
+        while slot > 0 {
+            let parent = (slot - 1) / 2;
+            if !self.less(slot, parent) {
+                break;
+            }
+            self.swap(slot, parent);
+            slot = parent;
+        }
+    }
+
+    fn sift_down(&mut self, mut slot: usize) {
+        let n = self.heap.len();
+        loop {
+            let left = 2 * slot + 1;
+            let right = left + 1;
+            let mut smallest = slot;
+            if left < n && self.less(left, smallest) {
+                smallest = left;
+            }
+            if right < n && self.less(right, smallest) {
+                smallest = right;
+            }
+            if smallest == slot {
+                return;
+            }
+            self.swap(slot, smallest);
+            slot = smallest;
+        }
+    }
+}
+
+/// Gibson–Bruck next-reaction method state.
+#[derive(Debug, Clone)]
+pub struct NextReaction {
+    dependencies: Vec<Vec<usize>>,
+    propensities: Vec<f64>,
+    /// Absolute putative firing time of each channel.
+    queue: IndexedPriorityQueue,
+    t: f64,
+}
+
+impl NextReaction {
+    pub fn new(model: &StochasticModel, counts: &[u64], t: f64, rng: &mut ChaCha8Rng) -> Self {
+        let propensities: Vec<f64> = (0..model.channels().len())
+            .map(|ch| model.propensity(ch, counts))
+            .collect();
+        let times = propensities
+            .iter()
+            .map(|&a| t + exponential(a, rng))
+            .collect();
+        Self {
+            dependencies: dependency_graph(model),
+            propensities,
+            queue: IndexedPriorityQueue::new(times),
+            t,
+        }
+    }
+
+    /// Current simulation time (the last event, or the last `t_end` reached).
+    pub fn time(&self) -> f64 {
+        self.t
+    }
+
+    /// Fire events until the next one would pass `t_end`.
+    pub fn advance(
+        &mut self,
+        model: &StochasticModel,
+        counts: &mut [u64],
+        t_end: f64,
+        rng: &mut ChaCha8Rng,
+    ) -> usize {
+        let mut events = 0;
+        while let Some((ch, t)) = self.queue.peek() {
+            if t > t_end {
+                break;
+            }
+            self.t = t;
+            model.fire(ch, counts);
+            events += 1;
+            for &dep in &self.dependencies[ch] {
+                let old = self.propensities[dep];
+                let new = model.propensity(dep, counts);
+                self.propensities[dep] = new;
+                let next = if dep == ch || old <= 0.0 || new <= 0.0 {
+                    // Fresh clock: the fired channel, or one switching on or off.
+                    t + exponential(new, rng)
+                } else {
+                    // Rescale the remaining waiting time (Gibson & Bruck, eq. 3).
+                    t + old / new * (self.queue.key(dep) - t)
+                };
+                self.queue.update(dep, next);
+            }
+        }
+        self.t = self.t.max(t_end);
+        events
+    }
+}
+
+/// Adaptive explicit tau-leaping parameters.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TauLeap {
+    /// Bound on the relative propensity change over one leap.
+    pub epsilon: f64,
+    /// Channels this many firings from exhausting a reactant are critical.
+    pub critical_firings: u64,
+    /// Leaps shorter than this many mean event intervals switch to the direct method.
+    pub ssa_threshold: f64,
+    /// Direct-method events taken per fallback.
+    pub ssa_events: usize,
+}
+
+impl Default for TauLeap {
+    fn default() -> Self {
+        Self {
+            epsilon: 0.03,
+            critical_firings: 10,
+            ssa_threshold: 10.0,
+            ssa_events: 100,
+        }
+    }
+}
+
+impl TauLeap {
+    pub fn new(epsilon: f64) -> SimResult<Self> {
+        if !(epsilon > 0.0 && epsilon < 1.0) {
+            return Err(SimError::Value(format!(
+                "epsilon must be in (0, 1), got {epsilon}"
+            )));
+        }
+        Ok(Self {
+            epsilon,
+            ..Self::default()
+        })
+    }
+
+    /// Leap from `t` to `t_end`; returns the number of firings.
+    pub fn advance(
+        &self,
+        model: &StochasticModel,
+        counts: &mut [u64],
+        mut t: f64,
+        t_end: f64,
+        rng: &mut ChaCha8Rng,
+    ) -> usize {
+        let channels = model.channels();
+        let mut propensities = vec![0.0; channels.len()];
+        let mut critical = vec![false; channels.len()];
+        let mut trial = vec![0i64; counts.len()];
+        let mut firings = vec![0u64; channels.len()];
+        let mut events = 0;
+        while t < t_end {
+            let mut total = 0.0;
+            for (ch, a) in propensities.iter_mut().enumerate() {
+                *a = model.propensity(ch, counts);
+                total += *a;
+                critical[ch] =
+                    *a > 0.0 && self.firings_left(model, ch, counts) < self.critical_firings;
+            }
+            if total <= 0.0 {
+                break;
+            }
+            let mut tau = self.leap_size(model, counts, &propensities, &critical);
+            if tau < self.ssa_threshold / total {
+                let (reached, fired) = direct_steps(model, counts, t, t_end, self.ssa_events, rng);
+                t = reached;
+                events += fired;
+                continue;
+            }
+            let critical_total: f64 = propensities
+                .iter()
+                .zip(&critical)
+                .filter(|&(_, &c)| c)
+                .map(|(a, _)| a)
+                .sum();
+            // Draw a leap that keeps every count non-negative, halving on failure.
+            let (leap, fired) = loop {
+                let until_critical = exponential(critical_total, rng);
+                let leap = tau.min(until_critical).min(t_end - t);
+                let fire_critical = until_critical <= leap;
+                trial
+                    .iter_mut()
+                    .zip(counts.iter())
+                    .for_each(|(x, &c)| *x = c as i64);
+                for (ch, k) in firings.iter_mut().enumerate() {
+                    let mean = propensities[ch] * leap;
+                    *k = if critical[ch] || mean <= 0.0 {
+                        0
+                    } else {
+                        Poisson::new(mean).map_or(0, |p| p.sample(rng) as u64)
+                    };
+                }
+                if fire_critical {
+                    let target = rng.gen::<f64>() * critical_total;
+                    let mut cumulative = 0.0;
+                    let mut chosen = None;
+                    for (ch, &a) in propensities.iter().enumerate() {
+                        if critical[ch] {
+                            chosen = Some(ch);
+                            cumulative += a;
+                            if target < cumulative {
+                                break;
+                            }
+                        }
+                    }
+                    if let Some(ch) = chosen {
+                        firings[ch] = 1;
+                    }
+                }
+                for (ch, &k) in firings.iter().enumerate() {
+                    if k > 0 {
+                        for &(i, delta) in channels[ch].changes() {
+                            trial[i] += delta * k as i64;
+                        }
+                    }
+                }
+                if trial.iter().all(|&x| x >= 0) {
+                    break (leap, firings.iter().sum::<u64>() as usize);
+                }
+                tau = leap / 2.0;
+            };
+            for (c, &x) in counts.iter_mut().zip(&trial) {
+                *c = x as u64;
+            }
+            t += leap;
+            events += fired;
+        }
+        events
+    }
+
+    /// Firings of `ch` left before one of the counts it consumes runs out.
+    fn firings_left(&self, model: &StochasticModel, ch: usize, counts: &[u64]) -> u64 {
+        model.channels()[ch]
+            .changes()
+            .iter()
+            .filter(|&&(_, delta)| delta < 0)
+            .map(|&(i, delta)| counts[i] / delta.unsigned_abs())
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Leap size bounding the relative change of every propensity by `epsilon`,
+    /// from the mean and variance of each reactant's drift over the
+    /// non-critical channels (Cao, Gillespie & Petzold 2006, eq. 33).
+    fn leap_size(
+        &self,
+        model: &StochasticModel,
+        counts: &[u64],
+        propensities: &[f64],
+        critical: &[bool],
+    ) -> f64 {
+        let channels = model.channels();
+        let mut mean = vec![0.0; counts.len()];
+        let mut variance = vec![0.0; counts.len()];
+        let mut g = vec![0.0f64; counts.len()];
+        for (ch, channel) in channels.iter().enumerate() {
+            for &(i, s) in channel.reactants() {
+                g[i] = g[i].max(highest_order_factor(channel.order(), s, counts[i]));
+            }
+            // Effectors and other rate inputs move the propensity without
+            // being consumed; bound them as first-order in the channel order.
+            for i in channel.reactant_indices() {
+                if channel.reactants().iter().all(|&(r, _)| r != i) {
+                    g[i] = g[i].max(channel.order().max(1) as f64);
+                }
+            }
+            if critical[ch] {
+                continue;
+            }
+            for &(i, delta) in channel.changes() {
+                let delta = delta as f64;
+                mean[i] += delta * propensities[ch];
+                variance[i] += delta * delta * propensities[ch];
+            }
+        }
+        let mut tau = f64::INFINITY;
+        for i in 0..counts.len() {
+            if g[i] == 0.0 {
+                continue; // not a reactant of any channel
+            }
+            let bound = (self.epsilon * counts[i] as f64 / g[i]).max(1.0);
+            if mean[i] != 0.0 {
+                tau = tau.min(bound / mean[i].abs());
+            }
+            if variance[i] > 0.0 {
+                tau = tau.min(bound * bound / variance[i]);
+            }
+        }
+        tau
+    }
+}
+
+/// `g_i` of Cao, Gillespie & Petzold: how strongly a relative change in a
+/// reactant count moves the propensity of a channel of the given order in
+/// which it appears with stoichiometry `s`.
+fn highest_order_factor(order: u64, s: u64, count: u64) -> f64 {
+    // Counts near s make the exact factor blow up; those channels are critical anyway.
+    let x = (count as f64).max(s as f64 + 1.0);
+    match (order, s) {
+        (0, _) => 0.0,
+        (2, 2) => 2.0 + 1.0 / (x - 1.0),
+        (3, 2) => 1.5 * (2.0 + 1.0 / (x - 1.0)),
+        (3, 3) => 3.0 + 1.0 / (x - 1.0) + 2.0 / (x - 2.0),
+        (order, _) => order as f64,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::reaction::{RateLaw, Reaction};
+    use crate::tree::Topology;
+    use crate::world_simulator::WorldModel;
+    use rand::SeedableRng;
+    use std::sync::Arc;
+
+    fn stochastic(reactions: Vec<Reaction>, n: usize) -> StochasticModel {
+        let mut t = Topology::new();
+        t.add_root("root").unwrap();
+        let model = WorldModel::new(Arc::new(t), reactions, n).unwrap();
+        StochasticModel::new(&model, &[1.0], &[1.0]).unwrap()
+    }
+
+    fn decay_chain() -> StochasticModel {
+        // A -> B -> C, plus a source feeding A.
+        stochastic(
+            vec![
+                Reaction::new("ab", vec![(0, 1.0)], vec![(1, 1.0)], 0.3),
+                Reaction::new("bc", vec![(1, 1.0)], vec![(2, 1.0)], 0.1),
+                Reaction::new("source", vec![], vec![(0, 1.0)], 2.0),
+            ],
+            3,
+        )
+    }
+
+    #[test]
+    fn parse_methods() {
+        assert_eq!(SsaMethod::parse("direct", None).unwrap(), SsaMethod::Direct);
+        assert_eq!(
+            SsaMethod::parse("next_reaction", None).unwrap().name(),
+            "next_reaction"
+        );
+        match SsaMethod::parse("tau_leap", Some(0.05)).unwrap() {
+            SsaMethod::TauLeap(leap) => assert_eq!(leap.epsilon, 0.05),
+            other => panic!("{other:?}"),
+        }
+        assert!(SsaMethod::parse("tau_leap", Some(1.5)).is_err());
+        assert!(SsaMethod::parse("direct", Some(0.05)).is_err());
+        assert!(SsaMethod::parse("gillespie", None).is_err());
+    }
+
+    #[test]
+    fn priority_queue_tracks_updates() {
+        let mut q = IndexedPriorityQueue::new(vec![5.0, 3.0, f64::INFINITY, 4.0, 1.0]);
+        assert_eq!(q.peek(), Some((4, 1.0)));
+        q.update(4, 10.0);
+        assert_eq!(q.peek(), Some((1, 3.0)));
+        q.update(2, 0.5);
+        assert_eq!(q.peek(), Some((2, 0.5)));
+        let mut order = Vec::new();
+        for _ in 0..q.len() {
+            let (item, _) = q.peek().unwrap();
+            order.push(item);
+            q.update(item, f64::INFINITY);
+        }
+        assert_eq!(order, vec![2, 1, 3, 0, 4]);
+    }
+
+    #[test]
+    fn dependency_graph_follows_reactants() {
+        let deps = dependency_graph(&decay_chain());
+        assert_eq!(deps[0], vec![0, 1]); // ab changes A and B
+        assert_eq!(deps[1], vec![1]); // bc changes B and C; only bc reads B
+        assert_eq!(deps[2], vec![0, 2]); // source changes A
+    }
+
+    #[test]
+    fn dependency_graph_follows_effectors() {
+        // The enzyme E is made by `express` and read, not changed, by `cat`.
+        let model = stochastic(
+            vec![
+                Reaction::new("cat", vec![(0, 1.0)], vec![(1, 1.0)], 0.1)
+                    .with_effectors(vec![(2, 1.0)]),
+                Reaction::new("express", vec![], vec![(2, 1.0)], 1.0).with_law(RateLaw::Constant),
+            ],
+            3,
+        );
+        let deps = dependency_graph(&model);
+        assert_eq!(deps[0], vec![0]);
+        assert_eq!(deps[1], vec![0, 1]);
+    }
+
+    fn mean_final(method: SsaMethod, runs: usize) -> Vec<f64> {
+        let model = decay_chain();
+        let mut rng = ChaCha8Rng::seed_from_u64(11);
+        let mut sums = [0.0; 3];
+        for _ in 0..runs {
+            let mut counts = vec![200, 0, 0];
+            let mut engine = method.start(&model, &counts, 0.0, &mut rng);
+            for i in 0..4 {
+                let t = i as f64;
+                engine.advance(&model, &mut counts, t, t + 1.0, &mut rng);
+            }
+            for (s, &c) in sums.iter_mut().zip(&counts) {
+                *s += c as f64;
+            }
+        }
+        sums.iter().map(|s| s / runs as f64).collect()
+    }
+
+    #[test]
+    fn methods_agree_in_mean() {
+        let direct = mean_final(SsaMethod::Direct, 300);
+        let nrm = mean_final(SsaMethod::NextReaction, 300);
+        let leap = mean_final(SsaMethod::parse("tau_leap", None).unwrap(), 300);
+        for i in 0..3 {
+            let tol = 0.05 * direct[i].max(10.0);
+            assert!((nrm[i] - direct[i]).abs() < tol, "{nrm:?} vs {direct:?}");
+            assert!((leap[i] - direct[i]).abs() < tol, "{leap:?} vs {direct:?}");
+        }
+    }
+
+    #[test]
+    fn next_reaction_conserves_and_is_seeded() {
+        let model = stochastic(
+            vec![
+                Reaction::new("bind", vec![(0, 1.0), (1, 1.0)], vec![(2, 1.0)], 0.01),
+                Reaction::new("unbind", vec![(2, 1.0)], vec![(0, 1.0), (1, 1.0)], 0.5),
+            ],
+            3,
+        );
+        let run = |seed| {
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let mut counts = vec![100, 80, 0];
+            let mut nrm = NextReaction::new(&model, &counts, 0.0, &mut rng);
+            let events = nrm.advance(&model, &mut counts, 10.0, &mut rng);
+            assert_eq!(nrm.time(), 10.0);
+            (counts, events)
+        };
+        let (counts, events) = run(3);
+        assert_eq!(run(3), (counts.clone(), events));
+        assert!(events > 0);
+        assert_eq!(counts[0] + counts[2], 100);
+        assert_eq!(counts[1] + counts[2], 80);
+    }
+
+    #[test]
+    fn tau_leap_never_goes_negative() {
+        // Fast consumption of a small pool: leaps must not overdraw A.
+        let model = stochastic(
+            vec![
+                Reaction::new("eat", vec![(0, 2.0)], vec![(1, 1.0)], 5.0),
+                Reaction::new("burn", vec![(0, 1.0)], vec![], 50.0),
+            ],
+            2,
+        );
+        let leap = TauLeap::new(0.1).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(5);
+        for _ in 0..50 {
+            let mut counts = vec![40, 0];
+            leap.advance(&model, &mut counts, 0.0, 1.0, &mut rng);
+            assert_eq!(counts[0], 0);
+            assert!(counts[1] <= 20);
+        }
+    }
+
+    #[test]
+    fn tau_leap_takes_few_leaps_for_large_counts() {
+        let model = stochastic(vec![Reaction::new("decay", vec![(0, 1.0)], vec![], 0.1)], 1);
+        let mut rng = ChaCha8Rng::seed_from_u64(2);
+        let mut counts = vec![1_000_000];
+        let events = TauLeap::default().advance(&model, &mut counts, 0.0, 5.0, &mut rng);
+        let expected = 1e6 * (-0.5f64).exp();
+        assert!((counts[0] as f64 - expected).abs() < 5_000.0, "{counts:?}");
+        assert_eq!(events as u64, 1_000_000 - counts[0]);
+    }
+}
@@ -0,0 +1,807 @@
+//! StochasticSimulator: exact stochastic simulation of the reaction network.
+//!
+//! Concentrations are converted to integer molecule counts per compartment,
+//! `count = round(concentration × volume × multiplicity)`, so all instances
+//! of a compartment are pooled into one well-mixed volume. Each
+//! (reaction, compartment) pair is a reaction channel with the mass-action
+//! propensity `k · Ω^(1-order) · ∏ N_j (N_j - 1) … (N_j - s_j + 1)`, where
+//! `Ω = volume × multiplicity`; effectors contribute `N_e^order` and count
+//! towards the order. Histories are sampled at exact multiples of
+//! `dt` and returned as `WorldState`s, the same shape as `WorldSimulator.run`.
+//! Reactions with an expression or script rate have propensity `Ω · rate(N / Ω)`,
+//! and constant-rate reactions `Ω · k` while their reactants last.
+//! The event loop is chosen with `method` (see `ssa`).
+
+use std::sync::Arc;
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyList, PyType};
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::rate::RateExpr;
+use crate::reaction::{RateLaw, Reaction};
+use crate::script::{self, ScriptRate};
+use crate::ssa::SsaMethod;
+use crate::tree::{CompartmentId, CompartmentTree};
+use crate::world_simulator::WorldModel;
+use crate::world_state::WorldState;
+
+/// One reaction firing in one compartment.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Channel {
+    pub reaction: usize,
+    pub compartment: CompartmentId,
+    /// Reactant count indices and integer stoichiometries.
+    reactants: Vec<(usize, u64)>,
+    /// Net change per firing, by count index.
+    changes: Vec<(usize, i64)>,
+    /// Effector count indices and orders (mass action only).
+    effectors: Vec<(usize, f64)>,
+    /// Count indices the propensity reads.
+    inputs: Vec<usize>,
+    /// `k · Ω^(1-order)` with effector orders included, `k · Ω` for
+    /// constant-rate reactions, or `Ω` for expression and script rates.
+    scale: f64,
+    law: RateLaw,
+    /// Compiled rate and first count index of the compartment, for `RateLaw::Expr`.
+    expr: Option<(Arc<RateExpr>, usize)>,
+    /// Script and first count index of the compartment, for `RateLaw::Script`.
+    script: Option<(Arc<ScriptRate>, usize)>,
+}
+
+impl Channel {
+    /// Count indices whose change alters this channel's propensity.
+    pub fn reactant_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.inputs.iter().copied()
+    }
+
+    /// Count indices changed by firing this channel.
+    pub fn changed_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.changes.iter().map(|&(i, _)| i)
+    }
+
+    /// Reactant count indices with their integer stoichiometries.
+    pub fn reactants(&self) -> &[(usize, u64)] {
+        &self.reactants
+    }
+
+    /// Net change per firing, by count index.
+    pub fn changes(&self) -> &[(usize, i64)] {
+        &self.changes
+    }
+
+    /// Total reactant stoichiometry plus effector orders rounded up (0 for
+    /// constant-rate channels, at least 1 for expression and script rates).
+    pub fn order(&self) -> u64 {
+        let total: u64 = self.reactants.iter().map(|&(_, s)| s).sum();
+        match self.law {
+            RateLaw::MassAction => {
+                total
+                    + self
+                        .effectors
+                        .iter()
+                        .map(|&(_, e)| e.max(0.0))
+                        .sum::<f64>()
+                        .ceil() as u64
+            }
+            RateLaw::Constant => 0,
+            RateLaw::Expr | RateLaw::Script => total.max(1),
+        }
+    }
+
+    /// `rate(amounts / Ω)` for expression and script rates, reading this
+    /// channel's compartment from `amounts`.
+    fn rate_of(&self, amounts: impl Fn(usize) -> f64, num_molecules: usize) -> Option<f64> {
+        let offset = match (&self.expr, &self.script) {
+            (Some((_, offset)), _) | (_, Some((_, offset))) => *offset,
+            _ => return None,
+        };
+        let conc: Vec<f64> = (offset..offset + num_molecules)
+            .map(|i| amounts(i) / self.scale)
+            .collect();
+        match (&self.expr, &self.script) {
+            (Some((expr, _)), _) => Some(expr.eval(&conc)),
+            (_, Some((script, _))) => Some(script.eval_at(&conc, self.compartment)),
+            _ => None,
+        }
+    }
+}
+
+fn integer_stoichiometry(reaction: &Reaction, coefficient: f64) -> SimResult<u64> {
+    if coefficient >= 0.0 && coefficient.fract() == 0.0 && coefficient.is_finite() {
+        Ok(coefficient as u64)
+    } else {
+        Err(SimError::Value(format!(
+            "Reaction {:?} has non-integer stoichiometry {coefficient}; stochastic simulation needs whole molecules",
+            reaction.name
+        )))
+    }
+}
+
+/// A `WorldModel` expressed as reaction channels over molecule counts.
+#[derive(Debug, Clone)]
+pub struct StochasticModel {
+    num_molecules: usize,
+    /// `volume × multiplicity` per compartment.
+    omega: Vec<f64>,
+    channels: Vec<Channel>,
+}
+
+impl StochasticModel {
+    /// Build channels for `model` with per-compartment volumes and multiplicities.
+    pub fn new(model: &WorldModel, volumes: &[f64], multiplicities: &[f64]) -> SimResult<Self> {
+        let nc = model.num_compartments();
+        if volumes.len() != nc || multiplicities.len() != nc {
+            return Err(SimError::Value(format!(
+                "Expected {nc} volumes and multiplicities, got {} and {}",
+                volumes.len(),
+                multiplicities.len()
+            )));
+        }
+        let n = model.num_molecules();
+        let omega: Vec<f64> = volumes
+            .iter()
+            .zip(multiplicities)
+            .map(|(v, m)| (v * m).max(0.0))
+            .collect();
+
+        let mut channels = Vec::new();
+        for (r, (reaction, sites)) in model.reactions().iter().zip(model.sites()).enumerate() {
+            let mut reactants = Vec::with_capacity(reaction.reactants.len());
+            let mut order = 0u64;
+            for &(mol, coef) in &reaction.reactants {
+                let s = integer_stoichiometry(reaction, coef)?;
+                reactants.push((mol, s));
+                order += s;
+            }
+            let mut net = vec![0i64; n];
+            for &(mol, s) in &reactants {
+                net[mol] -= s as i64;
+            }
+            for &(mol, coef) in &reaction.products {
+                net[mol] += integer_stoichiometry(reaction, coef)? as i64;
+            }
+
+            for &comp in sites {
+                let size = omega[comp];
+                if size == 0.0 {
+                    continue; // empty compartment: nothing can react
+                }
+                let offset = comp * n;
+                let effectors: Vec<(usize, f64)> = match reaction.law {
+                    RateLaw::MassAction => reaction
+                        .effectors
+                        .iter()
+                        .map(|&(m, e)| (offset + m, e))
+                        .collect(),
+                    _ => Vec::new(),
+                };
+                let effector_order: f64 = effectors.iter().map(|&(_, e)| e).sum();
+                let scale = match reaction.law {
+                    RateLaw::MassAction => {
+                        reaction.rate_constant * size.powf(1.0 - order as f64 - effector_order)
+                    }
+                    RateLaw::Constant => reaction.rate_constant * size,
+                    RateLaw::Expr | RateLaw::Script => size,
+                };
+                // Reactant counts gate every law; the rate law adds its own inputs.
+                let mut inputs: Vec<usize> = reactants
+                    .iter()
+                    .map(|&(m, _)| m)
+                    .chain(reaction.rate_inputs())
+                    .map(|m| offset + m)
+                    .collect();
+                inputs.sort_unstable();
+                inputs.dedup();
+                channels.push(Channel {
+                    reaction: r,
+                    compartment: comp,
+                    reactants: reactants.iter().map(|&(m, s)| (offset + m, s)).collect(),
+                    changes: net
+                        .iter()
+                        .enumerate()
+                        .filter(|&(_, &d)| d != 0)
+                        .map(|(m, &d)| (offset + m, d))
+                        .collect(),
+                    effectors,
+                    inputs,
+                    scale,
+                    law: reaction.law,
+                    expr: reaction.expr.clone().map(|e| (e, offset)),
+                    script: reaction.script.clone().map(|s| (s, offset)),
+                });
+            }
+        }
+        Ok(Self {
+            num_molecules: n,
+            omega,
+            channels,
+        })
+    }
+
+    pub fn channels(&self) -> &[Channel] {
+        &self.channels
+    }
+
+    pub fn num_molecules(&self) -> usize {
+        self.num_molecules
+    }
+
+    /// `volume × multiplicity` of each compartment.
+    pub fn omega(&self) -> &[f64] {
+        &self.omega
+    }
+
+    /// Molecule counts from a flat concentration buffer (negative values count as zero).
+    pub fn to_counts(&self, conc: &[f64]) -> Vec<u64> {
+        conc.iter()
+            .enumerate()
+            .map(|(i, &c)| (c * self.omega[i / self.num_molecules]).round().max(0.0) as u64)
+            .collect()
+    }
+
+    /// Write concentrations for `counts`; compartments with no volume keep their values.
+    pub fn to_concentrations(&self, counts: &[u64], conc: &mut [f64]) {
+        for (i, (&count, c)) in counts.iter().zip(conc.iter_mut()).enumerate() {
+            let size = self.omega[i / self.num_molecules];
+            if size > 0.0 {
+                *c = count as f64 / size;
+            }
+        }
+    }
+
+    /// Propensity of channel `ch` at `counts`.
+    pub fn propensity(&self, ch: usize, counts: &[u64]) -> f64 {
+        let channel = &self.channels[ch];
+        let mut a = channel.scale;
+        for &(i, s) in &channel.reactants {
+            let available = counts[i];
+            if available < s {
+                return 0.0;
+            }
+            if channel.law == RateLaw::MassAction {
+                for j in 0..s {
+                    a *= (available - j) as f64;
+                }
+            }
+        }
+        for &(i, e) in &channel.effectors {
+            a *= (counts[i] as f64).powf(e);
+        }
+        if let Some(rate) = channel.rate_of(|i| counts[i] as f64, self.num_molecules) {
+            a = channel.scale * rate.max(0.0);
+        }
+        a
+    }
+
+    /// Propensity of channel `ch` at real-valued amounts `x`.
+    ///
+    /// The falling factorial `x (x - 1) … (x - s + 1)` with each factor
+    /// clamped at zero, so it agrees with `propensity` on whole counts.
+    pub fn propensity_at(&self, ch: usize, x: &[f64]) -> f64 {
+        let channel = &self.channels[ch];
+        let mut a = channel.scale;
+        for &(i, s) in &channel.reactants {
+            match channel.law {
+                RateLaw::MassAction => {
+                    for j in 0..s {
+                        a *= (x[i] - j as f64).max(0.0);
+                    }
+                }
+                _ if x[i] < s as f64 => return 0.0,
+                _ => {}
+            }
+        }
+        for &(i, e) in &channel.effectors {
+            a *= x[i].max(0.0).powf(e);
+        }
+        if let Some(rate) = channel.rate_of(|i| x[i], self.num_molecules) {
+            a = channel.scale * rate.max(0.0);
+        }
+        a
+    }
+
+    /// Apply one firing of channel `ch`.
+    pub fn fire(&self, ch: usize, counts: &mut [u64]) {
+        for &(i, delta) in &self.channels[ch].changes {
+            counts[i] = counts[i].saturating_add_signed(delta);
+        }
+    }
+}
+
+/// Gillespie's direct method from `t` to `t_end`; returns the number of firings.
+///
+/// Propensities are recomputed after every event, so each event costs
+/// O(channels).
+pub fn direct_method(
+    model: &StochasticModel,
+    counts: &mut [u64],
+    t: f64,
+    t_end: f64,
+    rng: &mut ChaCha8Rng,
+) -> usize {
+    direct_steps(model, counts, t, t_end, usize::MAX, rng).1
+}
+
+/// At most `limit` direct-method events from `t`, stopping before `t_end`.
+///
+/// Returns the time reached (the last event time, or `t_end` when the next
+/// event would pass it or nothing can fire) and the number of firings.
+pub fn direct_steps(
+    model: &StochasticModel,
+    counts: &mut [u64],
+    mut t: f64,
+    t_end: f64,
+    limit: usize,
+    rng: &mut ChaCha8Rng,
+) -> (f64, usize) {
+    let mut propensities = vec![0.0; model.channels().len()];
+    let mut events = 0;
+    while events < limit {
+        let mut total = 0.0;
+        for (ch, a) in propensities.iter_mut().enumerate() {
+            *a = model.propensity(ch, counts);
+            total += *a;
+        }
+        if total <= 0.0 {
+            return (t_end, events);
+        }
+        let next = t - (1.0 - rng.gen::<f64>()).ln() / total;
+        if next > t_end {
+            return (t_end, events);
+        }
+        t = next;
+        let chosen = choose(&propensities, rng.gen::<f64>() * total);
+        model.fire(chosen, counts);
+        events += 1;
+    }
+    (t, events)
+}
+
+/// Index of the channel whose cumulative propensity first exceeds `target`.
+/// If rounding leaves `target` at the total, the last channel with a positive
+/// propensity, so a channel that cannot fire is never chosen.
+pub(crate) fn choose(propensities: &[f64], target: f64) -> usize {
+    let mut cumulative = 0.0;
+    for (ch, &a) in propensities.iter().enumerate() {
+        cumulative += a;
+        if target < cumulative {
+            return ch;
+        }
+    }
+    propensities
+        .iter()
+        .rposition(|&a| a > 0.0)
+        .expect("total propensity is positive")
+}
+
+/// Channels for `model` at the multiplicities of `state`, checking its shape.
+pub(crate) fn channels_for(
+    model: &WorldModel,
+    volumes: &[f64],
+    state: &WorldState,
+) -> PyResult<StochasticModel> {
+    if state.compartments() != model.num_compartments()
+        || state.molecules() != model.num_molecules()
+    {
+        return Err(PyValueError::new_err(format!(
+            "State shape {}x{} does not match simulator {}x{}",
+            state.compartments(),
+            state.molecules(),
+            model.num_compartments(),
+            model.num_molecules()
+        )));
+    }
+    Ok(StochasticModel::new(
+        model,
+        volumes,
+        state.multiplicities(),
+    )?)
+}
+
+/// Validate `dt` and per-compartment `volumes` (default 1.0 each).
+pub(crate) fn check_sampling(
+    model: &WorldModel,
+    dt: f64,
+    volumes: Option<Vec<f64>>,
+) -> PyResult<Vec<f64>> {
+    if !dt.is_finite() || dt <= 0.0 {
+        return Err(PyValueError::new_err(format!(
+            "dt must be positive, got {dt}"
+        )));
+    }
+    let nc = model.num_compartments();
+    let volumes = volumes.unwrap_or_else(|| vec![1.0; nc]);
+    if volumes.len() != nc || volumes.iter().any(|v| !(v.is_finite() && *v >= 0.0)) {
+        return Err(PyValueError::new_err(format!(
+            "volumes must be {nc} non-negative numbers, one per compartment"
+        )));
+    }
+    // Validate stoichiometry up front rather than on the first run.
+    StochasticModel::new(model, &volumes, &vec![1.0; nc])?;
+    Ok(volumes)
+}
+
+/// Stochastic simulator over `WorldState`s.
+#[pyclass(module = "alienbio_sim")]
+pub struct StochasticSimulator {
+    model: WorldModel,
+    method: SsaMethod,
+    tree: Py<CompartmentTree>,
+    reaction_specs: Py<PyList>,
+    volumes: Vec<f64>,
+    dt: f64,
+    seed: u64,
+    rng: ChaCha8Rng,
+}
+
+impl StochasticSimulator {
+    fn build(
+        model: WorldModel,
+        tree: Py<CompartmentTree>,
+        reaction_specs: Py<PyList>,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+        method: SsaMethod,
+    ) -> PyResult<Self> {
+        let volumes = check_sampling(&model, dt, volumes)?;
+        Ok(Self {
+            model,
+            method,
+            tree,
+            reaction_specs,
+            volumes,
+            dt,
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+        })
+    }
+
+    fn channels_for(&self, state: &WorldState) -> PyResult<StochasticModel> {
+        channels_for(&self.model, &self.volumes, state)
+    }
+}
+
+#[pymethods]
+impl StochasticSimulator {
+    /// Initialize stochastic simulator.
+    ///
+    /// Args:
+    ///     tree: Compartment topology (CompartmentTree or CompartmentTreeImpl)
+    ///     reactions: List of ReactionSpec (integer stoichiometry)
+    ///     num_molecules: Number of molecules in vocabulary
+    ///     dt: Sampling interval for step() and run()
+    ///     seed: Random seed; equal seeds give identical trajectories
+    ///     volumes: Volume of each compartment instance (default 1.0 each)
+    ///     method: "direct", "next_reaction" or "tau_leap"
+    ///     epsilon: tau_leap error bound on relative propensity change (default 0.03)
+    #[new]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (tree, reactions, num_molecules, dt=1.0, seed=0, volumes=None, method="direct", epsilon=None))]
+    fn new(
+        py: Python<'_>,
+        tree: &PyAny,
+        reactions: &PyAny,
+        num_molecules: usize,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+        method: &str,
+        epsilon: Option<f64>,
+    ) -> PyResult<Self> {
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let reaction_specs = PyList::new(py, reactions.iter()?.collect::<PyResult<Vec<_>>>()?);
+        let parsed = reaction_specs
+            .iter()
+            .map(Reaction::from_spec)
+            .collect::<PyResult<Vec<_>>>()?;
+        let model = WorldModel::new(topology, parsed, num_molecules)?;
+        Self::build(
+            model,
+            tree,
+            reaction_specs.into(),
+            dt,
+            seed,
+            volumes,
+            SsaMethod::parse(method, epsilon)?,
+        )
+    }
+
+    /// Compartment topology.
+    #[getter]
+    fn tree(&self, py: Python<'_>) -> Py<CompartmentTree> {
+        self.tree.clone_ref(py)
+    }
+
+    /// Reaction specifications.
+    #[getter]
+    fn reactions(&self, py: Python<'_>) -> Py<PyList> {
+        self.reaction_specs.clone_ref(py)
+    }
+
+    /// Number of molecules in vocabulary.
+    #[getter]
+    fn num_molecules(&self) -> usize {
+        self.model.num_molecules()
+    }
+
+    /// Stochastic simulation algorithm name.
+    #[getter]
+    fn method(&self) -> &'static str {
+        self.method.name()
+    }
+
+    /// Sampling interval.
+    #[getter]
+    fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Seed the random stream was started from.
+    #[getter]
+    fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Volume of each compartment instance.
+    #[getter]
+    fn volumes(&self) -> Vec<f64> {
+        self.volumes.clone()
+    }
+
+    /// Restart the random stream from `seed`.
+    fn reseed(&mut self, seed: u64) {
+        self.seed = seed;
+        self.rng = ChaCha8Rng::seed_from_u64(seed);
+    }
+
+    /// Simulate one interval of length dt, returning a new state.
+    fn step(&mut self, py: Python<'_>, state: PyRef<'_, WorldState>) -> PyResult<WorldState> {
+        let channels = self.channels_for(&state)?;
+        let mut counts = channels.to_counts(state.concentrations());
+        let mut engine = self.method.start(&channels, &counts, 0.0, &mut self.rng);
+        engine.advance(&channels, &mut counts, 0.0, self.dt, &mut self.rng);
+        script::check(self.model.reactions())?;
+        let mut next = state.copy(py);
+        channels.to_concentrations(&counts, next.concentrations_mut());
+        Ok(next)
+    }
+
+    /// Run simulation for multiple sampling intervals.
+    ///
+    /// Args:
+    ///     state: Initial state (not modified; concentrations rounded to counts)
+    ///     steps: Number of dt intervals to simulate
+    ///     sample_every: If set, only keep every Nth state (plus final)
+    ///
+    /// Returns:
+    ///     List of states (timeline)
+    #[pyo3(signature = (state, steps, sample_every=None))]
+    fn run(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        steps: usize,
+        sample_every: Option<usize>,
+    ) -> PyResult<Vec<WorldState>> {
+        let sample_every = sample_every.unwrap_or(1);
+        if sample_every == 0 {
+            return Err(PyValueError::new_err("sample_every must be positive"));
+        }
+        script::reset(self.model.reactions());
+        let channels = self.channels_for(&state)?;
+        let mut counts = channels.to_counts(state.concentrations());
+        let mut engine = self.method.start(&channels, &counts, 0.0, &mut self.rng);
+        let mut history = Vec::with_capacity(steps / sample_every + 2);
+        let snapshot = |counts: &[u64]| {
+            let mut sample = state.copy(py);
+            channels.to_concentrations(counts, sample.concentrations_mut());
+            sample
+        };
+        for i in 0..steps {
+            if i % sample_every == 0 {
+                history.push(snapshot(&counts));
+            }
+            let t = i as f64 * self.dt;
+            engine.advance(&channels, &mut counts, t, t + self.dt, &mut self.rng);
+            script::check(self.model.reactions())?;
+        }
+        // Always include final state
+        history.push(snapshot(&counts));
+        Ok(history)
+    }
+
+    /// Create simulator from a Chemistry and compartment tree.
+    ///
+    /// Molecule IDs follow the order of `chemistry.molecules`; a constant
+    /// rate is a zero-order flux, as in `ChemistrySimulator`, so every engine
+    /// runs a chemistry with the same kinetics.
+    #[classmethod]
+    #[allow(clippy::too_many_arguments)]
+    #[pyo3(signature = (chemistry, tree, dt=1.0, seed=0, volumes=None, method="direct", epsilon=None))]
+    fn from_chemistry(
+        _cls: &PyType,
+        py: Python<'_>,
+        chemistry: &PyAny,
+        tree: &PyAny,
+        dt: f64,
+        seed: u64,
+        volumes: Option<Vec<f64>>,
+        method: &str,
+        epsilon: Option<f64>,
+    ) -> PyResult<Self> {
+        let molecules = MoleculeIndex::from_chemistry(chemistry)?;
+        let reactions = molecules.native_reactions(
+            chemistry,
+            RateLaw::Constant,
+            false,
+            "StochasticSimulator",
+        )?;
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let model = WorldModel::new(topology, reactions, molecules.len())?;
+        Self::build(
+            model,
+            tree,
+            PyList::empty(py).into(),
+            dt,
+            seed,
+            volumes,
+            SsaMethod::parse(method, epsilon)?,
+        )
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "StochasticSimulator(compartments={}, molecules={}, reactions={}, method={:?}, dt={}, seed={})",
+            self.model.num_compartments(),
+            self.model.num_molecules(),
+            self.model.reactions().len(),
+            self.method.name(),
+            self.dt,
+            self.seed
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tree::Topology;
+    use std::sync::Arc;
+
+    fn one_compartment() -> Arc<Topology> {
+        let mut t = Topology::new();
+        t.add_root("root").unwrap();
+        Arc::new(t)
+    }
+
+    fn model(reactions: Vec<Reaction>, n: usize) -> WorldModel {
+        WorldModel::new(one_compartment(), reactions, n).unwrap()
+    }
+
+    #[test]
+    fn counts_use_volume_and_multiplicity() {
+        let m = model(vec![], 2);
+        let sm = StochasticModel::new(&m, &[2.0], &[10.0]).unwrap();
+        assert_eq!(sm.to_counts(&[1.26, -3.0]), vec![25, 0]);
+        let mut conc = vec![0.0; 2];
+        sm.to_concentrations(&[25, 0], &mut conc);
+        assert_eq!(conc, vec![1.25, 0.0]);
+    }
+
+    #[test]
+    fn choice_skips_channels_that_cannot_fire() {
+        let propensities = [0.5, 1.5, 0.0];
+        assert_eq!(choose(&propensities, 0.2), 0);
+        assert_eq!(choose(&propensities, 0.5), 1);
+        // A draw rounded up to the total falls back to a live channel.
+        assert_eq!(choose(&propensities, 2.0), 1);
+    }
+
+    #[test]
+    fn propensities_follow_combinatorics() {
+        // 2A -> B with k = 0.5 in Ω = 4: a = 0.5 · 4^(1-2) · N(N-1)
+        let m = model(
+            vec![Reaction::new("dimer", vec![(0, 2.0)], vec![(1, 1.0)], 0.5)],
+            2,
+        );
+        let sm = StochasticModel::new(&m, &[4.0], &[1.0]).unwrap();
+        assert_eq!(sm.propensity(0, &[10, 0]), 0.5 / 4.0 * 90.0);
+        assert_eq!(sm.propensity(0, &[1, 0]), 0.0);
+        let mut counts = vec![10, 0];
+        sm.fire(0, &mut counts);
+        assert_eq!(counts, vec![8, 1]);
+    }
+
+    #[test]
+    fn expression_propensity_scales_with_volume() {
+        // S -> P at vmax · s / (km + s) with s = N / Ω: a = Ω · rate(N / Ω).
+        let names = MoleculeIndex::new(vec!["S".into(), "P".into()]);
+        let mm = crate::rate::RateExpr::new(
+            &crate::expr::Arg::parse("div(mul(10, S), add(5, S))").unwrap(),
+            &names,
+            Vec::new(),
+        )
+        .unwrap();
+        let m = model(
+            vec![Reaction::new("mm", vec![(0, 1.0)], vec![(1, 1.0)], 1.0).with_expr(Arc::new(mm))],
+            2,
+        );
+        let sm = StochasticModel::new(&m, &[2.0], &[1.0]).unwrap();
+        assert_eq!(sm.propensity(0, &[10, 0]), 2.0 * 10.0 * 5.0 / 10.0);
+        assert_eq!(sm.propensity(0, &[0, 7]), 0.0);
+        assert_eq!(sm.propensity_at(0, &[10.0, 0.0]), 10.0);
+        assert_eq!(sm.channels()[0].order(), 1);
+    }
+
+    #[test]
+    fn effectors_scale_propensity_and_are_inputs() {
+        // S -> P catalysed by E with k = 0.5 in Ω = 4: a = 0.5 · 4^(1-2) · N_S · N_E
+        let m = model(
+            vec![Reaction::new("cat", vec![(0, 1.0)], vec![(1, 1.0)], 0.5)
+                .with_effectors(vec![(2, 1.0)])],
+            3,
+        );
+        let sm = StochasticModel::new(&m, &[4.0], &[1.0]).unwrap();
+        assert_eq!(sm.propensity(0, &[10, 0, 3]), 0.5 / 4.0 * 30.0);
+        assert_eq!(sm.propensity(0, &[10, 0, 0]), 0.0);
+        assert_eq!(sm.propensity_at(0, &[10.0, 0.0, 3.0]), 0.5 / 4.0 * 30.0);
+        let channel = &sm.channels()[0];
+        assert_eq!(channel.reactant_indices().collect::<Vec<_>>(), vec![0, 2]);
+        assert_eq!(channel.changed_indices().collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(channel.order(), 2);
+    }
+
+    #[test]
+    fn non_integer_stoichiometry_rejected() {
+        let m = model(vec![Reaction::new("r", vec![(0, 0.5)], vec![], 1.0)], 1);
+        assert!(StochasticModel::new(&m, &[1.0], &[1.0]).is_err());
+    }
+
+    #[test]
+    fn direct_method_is_seeded_and_conserves_mass() {
+        let m = model(
+            vec![Reaction::new("decay", vec![(0, 1.0)], vec![(1, 1.0)], 0.1)],
+            2,
+        );
+        let sm = StochasticModel::new(&m, &[1.0], &[1.0]).unwrap();
+        let run = |seed| {
+            let mut counts = vec![1000, 0];
+            let mut rng = ChaCha8Rng::seed_from_u64(seed);
+            let events = direct_method(&sm, &mut counts, 0.0, 5.0, &mut rng);
+            (counts, events)
+        };
+        let (counts, events) = run(7);
+        assert_eq!(run(7), (counts.clone(), events));
+        assert_eq!(counts[0] + counts[1], 1000);
+        assert_eq!(events as u64, counts[1]);
+        // Mean remaining is 1000·e^-0.5 ≈ 607 with sd ≈ 15.
+        assert!((counts[0] as f64 - 606.5).abs() < 75.0, "{counts:?}");
+    }
+
+    #[test]
+    fn mean_matches_deterministic_decay() {
+        let m = model(vec![Reaction::new("decay", vec![(0, 1.0)], vec![], 0.2)], 1);
+        let sm = StochasticModel::new(&m, &[1.0], &[1.0]).unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let runs = 400;
+        let total: u64 = (0..runs)
+            .map(|_| {
+                let mut counts = vec![50];
+                direct_method(&sm, &mut counts, 0.0, 3.0, &mut rng);
+                counts[0]
+            })
+            .sum();
+        let mean = total as f64 / runs as f64;
+        let expected = 50.0 * (-0.6f64).exp();
+        assert!((mean - expected).abs() < 1.0, "{mean} vs {expected}");
+    }
+}
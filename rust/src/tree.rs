@@ -0,0 +1,398 @@
+//! CompartmentTree: hierarchical topology of compartments.
+//!
+//! Native counterpart of `alienbio.bio.compartment_tree.CompartmentTreeImpl`.
+//! The topology itself (`Topology`) is immutable once shared: the Python-facing
+//! `CompartmentTree` holds it behind an `Arc`, simulators clone that `Arc`, and
+//! `add_root`/`add_child` copy-on-write so a simulator keeps the topology it was
+//! built with. The Python object itself does change in place, so everything
+//! holding it sees new compartments; a `WorldState` sized for the old tree
+//! refuses access until it is rebuilt.
+
+use std::sync::Arc;
+
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList};
+
+use crate::error::{SimError, SimResult};
+
+pub type CompartmentId = usize;
+
+/// Tree topology with precomputed parent/child membrane edges.
+///
+/// Edge `e` connects `edge_parents[e]` (outside) to `edge_children[e]` (inside);
+/// there is one edge per non-root compartment, in compartment-id order.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Topology {
+    parents: Vec<Option<CompartmentId>>,
+    names: Vec<String>,
+    children: Vec<Vec<CompartmentId>>,
+    root: Option<CompartmentId>,
+    edge_parents: Vec<CompartmentId>,
+    edge_children: Vec<CompartmentId>,
+}
+
+impl Topology {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rebuild a topology from a parents array and names (the `to_dict` layout).
+    pub fn from_parents(
+        parents: Vec<Option<CompartmentId>>,
+        names: Vec<String>,
+    ) -> SimResult<Self> {
+        if parents.len() != names.len() {
+            return Err(SimError::Value(format!(
+                "parents length {} != names length {}",
+                parents.len(),
+                names.len()
+            )));
+        }
+        let mut topology = Self {
+            children: vec![Vec::new(); parents.len()],
+            parents,
+            names,
+            ..Self::default()
+        };
+        for child in 0..topology.parents.len() {
+            match topology.parents[child] {
+                None if topology.root.is_some() => {
+                    return Err(SimError::Value("Tree has more than one root".into()))
+                }
+                None => topology.root = Some(child),
+                Some(parent) if parent >= topology.parents.len() => {
+                    return Err(SimError::Value(format!("Parent {parent} does not exist")))
+                }
+                Some(parent) => {
+                    topology.children[parent].push(child);
+                    topology.edge_parents.push(parent);
+                    topology.edge_children.push(child);
+                }
+            }
+        }
+        Ok(topology)
+    }
+
+    pub fn num_compartments(&self) -> usize {
+        self.parents.len()
+    }
+
+    pub fn parent(&self, child: CompartmentId) -> Option<CompartmentId> {
+        self.parents[child]
+    }
+
+    pub fn parents(&self) -> &[Option<CompartmentId>] {
+        &self.parents
+    }
+
+    pub fn children(&self, parent: CompartmentId) -> &[CompartmentId] {
+        &self.children[parent]
+    }
+
+    pub fn root(&self) -> Option<CompartmentId> {
+        self.root
+    }
+
+    pub fn name(&self, compartment: CompartmentId) -> &str {
+        &self.names[compartment]
+    }
+
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Parent side of every membrane edge.
+    pub fn edge_parents(&self) -> &[CompartmentId] {
+        &self.edge_parents
+    }
+
+    /// Child side of every membrane edge.
+    pub fn edge_children(&self) -> &[CompartmentId] {
+        &self.edge_children
+    }
+
+    pub fn num_edges(&self) -> usize {
+        self.edge_children.len()
+    }
+
+    pub fn add_root(&mut self, name: &str) -> SimResult<CompartmentId> {
+        if self.root.is_some() {
+            return Err(SimError::Value("Root already exists".into()));
+        }
+        let id = self.parents.len();
+        self.parents.push(None);
+        self.children.push(Vec::new());
+        self.names.push(name.to_string());
+        self.root = Some(id);
+        Ok(id)
+    }
+
+    pub fn add_child(&mut self, parent: CompartmentId, name: &str) -> SimResult<CompartmentId> {
+        if parent >= self.parents.len() {
+            return Err(SimError::Value(format!("Parent {parent} does not exist")));
+        }
+        let id = self.parents.len();
+        let name = if name.is_empty() {
+            format!("compartment_{id}")
+        } else {
+            name.to_string()
+        };
+        self.parents.push(Some(parent));
+        self.children.push(Vec::new());
+        self.children[parent].push(id);
+        self.names.push(name);
+        self.edge_parents.push(parent);
+        self.edge_children.push(id);
+        Ok(id)
+    }
+
+    /// All ancestors from compartment to root (inclusive).
+    pub fn ancestors(&self, compartment: CompartmentId) -> Vec<CompartmentId> {
+        let mut result = Vec::new();
+        let mut current = Some(compartment);
+        while let Some(c) = current {
+            result.push(c);
+            current = self.parents[c];
+        }
+        result
+    }
+
+    /// All descendants of a compartment (not including self), in the same
+    /// depth-first order as the Python implementation.
+    pub fn descendants(&self, compartment: CompartmentId) -> Vec<CompartmentId> {
+        let mut result = Vec::new();
+        let mut stack = self.children[compartment].clone();
+        while let Some(child) = stack.pop() {
+            result.push(child);
+            stack.extend_from_slice(&self.children[child]);
+        }
+        result
+    }
+
+    /// Depth of compartment (root = 0).
+    pub fn depth(&self, compartment: CompartmentId) -> usize {
+        self.ancestors(compartment).len() - 1
+    }
+
+    pub(crate) fn check(&self, compartment: CompartmentId) -> SimResult<()> {
+        if compartment >= self.parents.len() {
+            return Err(SimError::Index(format!(
+                "compartment {compartment} out of range (num_compartments={})",
+                self.parents.len()
+            )));
+        }
+        Ok(())
+    }
+}
+
+/// Hierarchical structure of compartments.
+///
+/// Compartments are identified by integer IDs (0, 1, 2, ...) in creation order.
+#[pyclass(module = "alienbio_sim")]
+#[derive(Clone, Default)]
+pub struct CompartmentTree {
+    topology: Arc<Topology>,
+}
+
+impl CompartmentTree {
+    pub fn from_topology(topology: Arc<Topology>) -> Self {
+        Self { topology }
+    }
+
+    /// Shared handle to the immutable topology.
+    pub fn topology(&self) -> &Arc<Topology> {
+        &self.topology
+    }
+
+    /// Accept a native tree as-is, or convert any object with a
+    /// `CompartmentTreeImpl`-style `to_dict()` into a new native tree.
+    pub fn coerce(py: Python<'_>, obj: &PyAny) -> PyResult<Py<CompartmentTree>> {
+        if let Ok(tree) = obj.extract::<Py<CompartmentTree>>() {
+            return Ok(tree);
+        }
+        let data = obj.call_method0("to_dict")?;
+        Py::new(py, Self::from_dict_any(data)?)
+    }
+
+    fn from_dict_any(data: &PyAny) -> PyResult<Self> {
+        let parents: Vec<Option<CompartmentId>> = data.get_item("parents")?.extract()?;
+        let names: Vec<String> = data.get_item("names")?.extract()?;
+        Ok(Self::from_topology(Arc::new(Topology::from_parents(
+            parents, names,
+        )?)))
+    }
+
+    fn format_subtree(&self, compartment: CompartmentId, prefix: &str, lines: &mut Vec<String>) {
+        let children = self.topology.children(compartment);
+        for (i, &child) in children.iter().enumerate() {
+            let is_last = i == children.len() - 1;
+            let branch = if is_last { "└── " } else { "├── " };
+            lines.push(format!(
+                "{prefix}{branch}{} ({child})",
+                self.topology.name(child)
+            ));
+            let next = format!("{prefix}{}", if is_last { "    " } else { "│   " });
+            self.format_subtree(child, &next, lines);
+        }
+    }
+}
+
+#[pymethods]
+impl CompartmentTree {
+    /// Initialize empty compartment tree.
+    #[new]
+    fn new() -> Self {
+        Self::default()
+    }
+
+    /// Total number of compartments.
+    #[getter]
+    fn num_compartments(&self) -> usize {
+        self.topology.num_compartments()
+    }
+
+    /// Get parent of a compartment (None for root).
+    fn parent(&self, child: CompartmentId) -> PyResult<Option<CompartmentId>> {
+        self.topology.check(child)?;
+        Ok(self.topology.parent(child))
+    }
+
+    /// Get children of a compartment.
+    fn children(&self, parent: CompartmentId) -> PyResult<Vec<CompartmentId>> {
+        self.topology.check(parent)?;
+        Ok(self.topology.children(parent).to_vec())
+    }
+
+    /// Get the root compartment.
+    fn root(&self) -> PyResult<CompartmentId> {
+        self.topology
+            .root()
+            .ok_or_else(|| SimError::Value("Tree has no root".into()).into())
+    }
+
+    /// Check if compartment is the root.
+    fn is_root(&self, compartment: CompartmentId) -> PyResult<bool> {
+        self.topology.check(compartment)?;
+        Ok(self.topology.parent(compartment).is_none())
+    }
+
+    /// Get the name of a compartment.
+    fn name(&self, compartment: CompartmentId) -> PyResult<String> {
+        self.topology.check(compartment)?;
+        Ok(self.topology.name(compartment).to_string())
+    }
+
+    /// Add the root compartment (always ID 0).
+    #[pyo3(signature = (name="root"))]
+    fn add_root(&mut self, name: &str) -> PyResult<CompartmentId> {
+        Ok(Arc::make_mut(&mut self.topology).add_root(name)?)
+    }
+
+    /// Add a child compartment and return its ID.
+    #[pyo3(signature = (parent, name=""))]
+    fn add_child(&mut self, parent: CompartmentId, name: &str) -> PyResult<CompartmentId> {
+        Ok(Arc::make_mut(&mut self.topology).add_child(parent, name)?)
+    }
+
+    /// Get all ancestors from compartment to root (inclusive).
+    fn ancestors(&self, compartment: CompartmentId) -> PyResult<Vec<CompartmentId>> {
+        self.topology.check(compartment)?;
+        Ok(self.topology.ancestors(compartment))
+    }
+
+    /// Get all descendants of a compartment (not including self).
+    fn descendants(&self, compartment: CompartmentId) -> PyResult<Vec<CompartmentId>> {
+        self.topology.check(compartment)?;
+        Ok(self.topology.descendants(compartment))
+    }
+
+    /// Get depth of compartment (root = 0).
+    fn depth(&self, compartment: CompartmentId) -> PyResult<usize> {
+        self.topology.check(compartment)?;
+        Ok(self.topology.depth(compartment))
+    }
+
+    /// Membrane edges as (parent, child) pairs, one per non-root compartment.
+    fn edges(&self) -> Vec<(CompartmentId, CompartmentId)> {
+        self.topology
+            .edge_parents()
+            .iter()
+            .copied()
+            .zip(self.topology.edge_children().iter().copied())
+            .collect()
+    }
+
+    /// Serialize tree structure.
+    fn to_dict<'py>(&self, py: Python<'py>) -> PyResult<&'py PyDict> {
+        let dict = PyDict::new(py);
+        dict.set_item("parents", PyList::new(py, self.topology.parents()))?;
+        dict.set_item("names", PyList::new(py, self.topology.names()))?;
+        Ok(dict)
+    }
+
+    /// Deserialize tree structure.
+    #[classmethod]
+    fn from_dict(_cls: &pyo3::types::PyType, data: &PyAny) -> PyResult<Self> {
+        Self::from_dict_any(data)
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "CompartmentTree(compartments={})",
+            self.topology.num_compartments()
+        )
+    }
+
+    fn __str__(&self) -> String {
+        let Some(root) = self.topology.root() else {
+            return "CompartmentTree(empty)".to_string();
+        };
+        let mut lines = vec![format!("{} ({root})", self.topology.name(root))];
+        self.format_subtree(root, "", &mut lines);
+        lines.join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> Topology {
+        let mut t = Topology::new();
+        let organism = t.add_root("organism").unwrap();
+        let organ_a = t.add_child(organism, "organ_a").unwrap();
+        t.add_child(organism, "organ_b").unwrap();
+        t.add_child(organ_a, "").unwrap();
+        t
+    }
+
+    #[test]
+    fn queries_match_python_semantics() {
+        let t = sample();
+        assert_eq!(t.parent(3), Some(1));
+        assert_eq!(t.children(0), &[1, 2]);
+        assert_eq!(t.ancestors(3), vec![3, 1, 0]);
+        assert_eq!(t.descendants(0), vec![2, 1, 3]);
+        assert_eq!(t.depth(3), 2);
+        assert_eq!(t.name(3), "compartment_3");
+        assert_eq!(t.edge_parents(), &[0, 0, 1]);
+        assert_eq!(t.edge_children(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn from_parents_round_trip() {
+        let t = sample();
+        let rebuilt = Topology::from_parents(t.parents().to_vec(), t.names().to_vec()).unwrap();
+        assert_eq!(rebuilt, t);
+        assert!(Topology::from_parents(vec![None, None], vec!["a".into(), "b".into()]).is_err());
+    }
+
+    #[test]
+    fn second_root_rejected() {
+        let mut t = sample();
+        assert_eq!(
+            t.add_root("again"),
+            Err(SimError::Value("Root already exists".into()))
+        );
+    }
+}
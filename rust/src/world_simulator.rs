@@ -0,0 +1,1842 @@
+//! WorldSimulator: multi-compartment simulation with reactions and flows.
+//!
+//! Native counterpart of `alienbio.bio.world_simulator.WorldSimulatorImpl`,
+//! with the same constructor, `step` and `run(state, steps, sample_every)`
+//! contract. Reactions run natively over the flat concentration buffer, as
+//! do MembraneFlows, DiffusionFlows, InstanceFlows and GeneralFlows with
+//! declarative `moves` (see `flow`) or a `rhai:` body (see
+//! `script::ScriptFlow`); other Python flow objects are still applied through
+//! their `apply()` method.
+//!
+//! `method="euler"` (the default) reproduces the Python arithmetic exactly.
+//! `method="rk45"` and, for stiff chemistries, `method="rosenbrock"` integrate
+//! the reactions adaptively and sample the history at the exact times
+//! `i * dt` via dense output. Native membrane, diffusion and general flows
+//! are part of the ODE system there; the remaining flows are applied once
+//! per `dt` interval.
+//!
+//! Membrane transport moves amounts, converted to concentrations through each
+//! compartment's per-instance `volumes` and the state's multiplicities (see
+//! `flow::membrane_factors`). `check_conservation` verifies after every step
+//! that the flows left the total amount of each molecule unchanged.
+//!
+//! `growth` laws change each compartment's multiplicity once per step, after
+//! the flows (see `growth`).
+//!
+//! `divide`, `kill` and `fuse` change the topology between steps (see
+//! `remodel`), returning the remapped state on a new tree and rebinding the
+//! model to it.
+//!
+//! `run(..., events=...)` applies scheduled interventions at exact times,
+//! cutting the step that crosses each one (see `events`); `event_log` records
+//! what the last run applied.
+
+use std::sync::Arc;
+
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+use pyo3::exceptions::PyValueError;
+use pyo3::prelude::*;
+use pyo3::types::{PyDict, PyList, PyType};
+
+use crate::chemistry::MoleculeIndex;
+use crate::error::{SimError, SimResult};
+use crate::events::{Action, Event, Schedule};
+use crate::flow::{self, DiffusionFlow, GeneralFlow, InstanceFlow, MembraneFlow};
+use crate::growth::Growth;
+use crate::integrate::{JacobianSystem, Method, OdeSystem};
+use crate::linalg::CsrMatrix;
+use crate::reaction::{RateLaw, Reaction};
+use crate::remodel::{Remodel, Remodeled, Split};
+use crate::script::{self, ScriptFlow};
+use crate::tree::{CompartmentId, CompartmentTree, Topology};
+use crate::world_state::WorldState;
+
+/// Reactions and membrane flows bound to a topology: the pure-Rust part of
+/// a world simulator.
+#[derive(Debug, Clone)]
+pub struct WorldModel {
+    topology: Arc<Topology>,
+    reactions: Vec<Reaction>,
+    num_molecules: usize,
+    /// Compartments each reaction runs in, with `None` resolved to all.
+    sites: Vec<Vec<CompartmentId>>,
+    flows: Vec<MembraneFlow>,
+    diffusion: Vec<DiffusionFlow>,
+    general: Vec<GeneralFlow>,
+    /// Birth and death laws of compartment populations.
+    growth: Vec<Growth>,
+    /// Volume of one instance of each compartment.
+    volumes: Vec<f64>,
+    /// Instances of each compartment seen by the ODE system.
+    multiplicities: Vec<f64>,
+    /// Multiplier of each reaction's rate, set by knockdowns and knockouts.
+    scales: Vec<f64>,
+    /// Concentrations held fixed, as (flat index, value).
+    clamps: Vec<(usize, f64)>,
+}
+
+impl WorldModel {
+    pub fn new(
+        topology: Arc<Topology>,
+        reactions: Vec<Reaction>,
+        num_molecules: usize,
+    ) -> SimResult<Self> {
+        let num_compartments = topology.num_compartments();
+        for reaction in &reactions {
+            reaction.validate(num_molecules, num_compartments)?;
+        }
+        let sites = reactions
+            .iter()
+            .map(|r| match &r.compartments {
+                Some(list) => list.clone(),
+                None => (0..num_compartments).collect(),
+            })
+            .collect();
+        Ok(Self {
+            topology,
+            scales: vec![1.0; reactions.len()],
+            reactions,
+            num_molecules,
+            sites,
+            flows: Vec::new(),
+            diffusion: Vec::new(),
+            general: Vec::new(),
+            growth: Vec::new(),
+            volumes: vec![1.0; num_compartments],
+            multiplicities: vec![1.0; num_compartments],
+            clamps: Vec::new(),
+        })
+    }
+
+    /// Add diffusion flows, validated against the topology.
+    pub fn with_diffusion(mut self, diffusion: Vec<DiffusionFlow>) -> SimResult<Self> {
+        for flow in &diffusion {
+            flow.validate(&self.topology, self.num_molecules)?;
+        }
+        self.diffusion = diffusion;
+        Ok(self)
+    }
+
+    /// Add declarative general flows, validated against the topology.
+    pub fn with_general(mut self, general: Vec<GeneralFlow>) -> SimResult<Self> {
+        for flow in &general {
+            flow.validate(&self.topology, self.num_molecules)?;
+        }
+        self.general = general;
+        Ok(self)
+    }
+
+    /// Add population growth laws, validated against the topology.
+    pub fn with_growth(mut self, growth: Vec<Growth>) -> SimResult<Self> {
+        for law in &growth {
+            law.validate(&self.topology, self.num_molecules)?;
+        }
+        self.growth = growth;
+        Ok(self)
+    }
+
+    /// Set the per-instance volume of each compartment (default 1.0 each).
+    pub fn with_volumes(mut self, volumes: Vec<f64>) -> SimResult<Self> {
+        let nc = self.num_compartments();
+        if volumes.len() != nc || volumes.iter().any(|v| !(v.is_finite() && *v > 0.0)) {
+            return Err(SimError::Value(format!(
+                "volumes must be {nc} positive numbers, one per compartment"
+            )));
+        }
+        self.volumes = volumes;
+        Ok(self)
+    }
+
+    /// Add membrane flows, validated against the topology.
+    pub fn with_flows(mut self, flows: Vec<MembraneFlow>) -> SimResult<Self> {
+        for flow in &flows {
+            flow.validate(&self.topology, self.num_molecules)?;
+        }
+        self.flows = flows;
+        Ok(self)
+    }
+
+    pub fn topology(&self) -> &Arc<Topology> {
+        &self.topology
+    }
+
+    pub fn reactions(&self) -> &[Reaction] {
+        &self.reactions
+    }
+
+    pub fn num_molecules(&self) -> usize {
+        self.num_molecules
+    }
+
+    pub fn flows(&self) -> &[MembraneFlow] {
+        &self.flows
+    }
+
+    pub fn diffusion(&self) -> &[DiffusionFlow] {
+        &self.diffusion
+    }
+
+    pub fn general(&self) -> &[GeneralFlow] {
+        &self.general
+    }
+
+    pub fn growth(&self) -> &[Growth] {
+        &self.growth
+    }
+
+    pub fn volumes(&self) -> &[f64] {
+        &self.volumes
+    }
+
+    pub fn multiplicities(&self) -> &[f64] {
+        &self.multiplicities
+    }
+
+    /// Set the multiplicities the ODE system converts membrane amounts with.
+    pub fn set_multiplicities(&mut self, multiplicities: &[f64]) {
+        self.multiplicities.copy_from_slice(multiplicities);
+    }
+
+    /// Total amount of each molecule over all compartments and instances.
+    pub fn total_amounts(&self, conc: &[f64], multiplicities: &[f64]) -> Vec<f64> {
+        flow::total_amounts(conc, self.num_molecules, &self.volumes, multiplicities)
+    }
+
+    /// Compartments each reaction runs in, by reaction index.
+    pub fn sites(&self) -> &[Vec<CompartmentId>] {
+        &self.sites
+    }
+
+    /// Rate multiplier of each reaction, by reaction index.
+    pub fn scales(&self) -> &[f64] {
+        &self.scales
+    }
+
+    /// Multiply reaction `r`'s rate by `factor` (0 knocks it out).
+    pub fn knock_down(&mut self, r: usize, factor: f64) {
+        self.scales[r] *= factor;
+    }
+
+    /// Concentrations held fixed, as (flat index, value).
+    pub fn clamps(&self) -> &[(usize, f64)] {
+        &self.clamps
+    }
+
+    /// Hold the concentration at flat `index` at `value`.
+    pub fn clamp(&mut self, index: usize, value: f64) {
+        self.release(index);
+        self.clamps.push((index, value));
+    }
+
+    pub fn release(&mut self, index: usize) {
+        self.clamps.retain(|&(i, _)| i != index);
+    }
+
+    /// Reset clamped concentrations to their values.
+    pub fn hold_clamps(&self, conc: &mut [f64]) {
+        for &(index, value) in &self.clamps {
+            conc[index] = value;
+        }
+    }
+
+    /// Drop every knockdown, knockout and clamp.
+    pub fn clear_interventions(&mut self) {
+        self.scales.fill(1.0);
+        self.clamps.clear();
+    }
+
+    pub fn num_compartments(&self) -> usize {
+        self.topology.num_compartments()
+    }
+
+    /// Size of the flat concentration buffer.
+    pub fn size(&self) -> usize {
+        self.num_compartments() * self.num_molecules
+    }
+
+    /// Apply every reaction once, in order, with explicit Euler.
+    ///
+    /// Matches `WorldSimulatorImpl.step`: each reaction sees the updates of
+    /// the reactions before it, and reactants are clamped at zero.
+    pub fn apply_reactions(&self, conc: &mut [f64], dt: f64) {
+        let n = self.num_molecules;
+        let (mut rates, mut registers) = (Vec::new(), Vec::new());
+        for ((reaction, sites), &scale) in self.reactions.iter().zip(&self.sites).zip(&self.scales)
+        {
+            // Sites are disjoint slices, so all rates can be read up front.
+            reaction.site_rates(conc, n, sites, &mut rates, &mut registers);
+            for (&comp, &rate) in sites.iter().zip(&rates) {
+                let slice = &mut conc[comp * n..(comp + 1) * n];
+                let amount = rate * scale * dt;
+                for &(mol, stoich) in &reaction.reactants {
+                    slice[mol] = (slice[mol] - amount * stoich).max(0.0);
+                }
+                for &(mol, stoich) in &reaction.products {
+                    slice[mol] += amount * stoich;
+                }
+            }
+        }
+    }
+
+    /// Apply every membrane, diffusion and general flow once, in that order,
+    /// with explicit Euler (transfers clamped so neither side goes negative).
+    pub fn apply_flows(&self, conc: &mut [f64], multiplicities: &[f64], dt: f64) {
+        for flow in &self.flows {
+            self.apply_flow(flow, conc, multiplicities, dt);
+        }
+        for flow in &self.diffusion {
+            self.apply_diffusion(flow, conc, multiplicities, dt);
+        }
+        for flow in &self.general {
+            flow.apply(conc, self.num_molecules, dt, &self.volumes, multiplicities);
+        }
+    }
+
+    /// Apply every growth law once over `dt` (see `growth`).
+    pub fn apply_growth(&self, conc: &mut [f64], multiplicities: &mut [f64], dt: f64) {
+        for law in &self.growth {
+            law.apply(conc, multiplicities, self.num_molecules, dt);
+        }
+    }
+
+    /// Apply one diffusion flow with explicit Euler.
+    pub fn apply_diffusion(
+        &self,
+        flow: &DiffusionFlow,
+        conc: &mut [f64],
+        multiplicities: &[f64],
+        dt: f64,
+    ) {
+        flow.apply(
+            &self.topology,
+            conc,
+            self.num_molecules,
+            dt,
+            &self.volumes,
+            multiplicities,
+        );
+    }
+
+    /// Apply one membrane flow with explicit Euler; a flow at the root does nothing.
+    pub fn apply_flow(
+        &self,
+        flow: &MembraneFlow,
+        conc: &mut [f64],
+        multiplicities: &[f64],
+        dt: f64,
+    ) {
+        if let Some(parent) = flow.parent(&self.topology) {
+            let factors =
+                flow::membrane_factors(flow.origin, parent, &self.volumes, multiplicities);
+            flow.apply(conc, self.num_molecules, parent, dt, factors);
+        }
+    }
+
+    /// Move `events` events of `flow` (see `MembraneFlow::transfer`).
+    pub fn transfer(
+        &self,
+        flow: &MembraneFlow,
+        conc: &mut [f64],
+        multiplicities: &[f64],
+        events: f64,
+    ) {
+        if let Some(parent) = flow.parent(&self.topology) {
+            let factors =
+                flow::membrane_factors(flow.origin, parent, &self.volumes, multiplicities);
+            flow.transfer(conc, self.num_molecules, parent, events, factors);
+        }
+    }
+
+    /// Rebind to a remodeled topology (see `remodel`). Reactions, membrane
+    /// flows and growth laws also run in the copies a division makes; general
+    /// flows touching a compartment that died are dropped. Knockdowns carry
+    /// over, and clamps stay on the original compartments.
+    pub fn remodel(&self, remodeled: &Remodeled) -> SimResult<WorldModel> {
+        let remap = &remodeled.remap;
+        let n = self.num_molecules;
+        let reactions = self
+            .reactions
+            .iter()
+            .map(|reaction| {
+                let compartments = reaction.compartments.as_ref().map(|list| {
+                    let mut sites = Vec::new();
+                    for &old in list {
+                        for new in remap.targets(old) {
+                            if !sites.contains(&new) {
+                                sites.push(new);
+                            }
+                        }
+                    }
+                    sites
+                });
+                if let Some(script) = &reaction.script {
+                    script.script().remap_memory(remap);
+                }
+                Reaction {
+                    compartments,
+                    ..reaction.clone()
+                }
+            })
+            .collect();
+        let mut model = WorldModel::new(Arc::new(remap.topology.clone()), reactions, n)?
+            .with_volumes(remodeled.volumes.clone())?
+            .with_flows(self.flows.iter().flat_map(|f| f.remap(remap)).collect())?
+            .with_diffusion(self.diffusion.iter().map(|f| f.remap(remap)).collect())?
+            .with_general(
+                self.general
+                    .iter()
+                    .filter_map(|f| f.remap(remap, n))
+                    .collect(),
+            )?
+            .with_growth(self.growth.iter().flat_map(|g| g.remap(remap)).collect())?;
+        model.set_multiplicities(&remodeled.multiplicities);
+        model.scales.clone_from(&self.scales);
+        model.clamps = self
+            .clamps
+            .iter()
+            .filter_map(|&(index, value)| Some((remap.index(index, n)?, value)))
+            .collect();
+        Ok(model)
+    }
+
+    fn factors(&self, flow: &MembraneFlow, parent: CompartmentId) -> (f64, f64) {
+        flow::membrane_factors(flow.origin, parent, &self.volumes, &self.multiplicities)
+    }
+}
+
+impl OdeSystem for WorldModel {
+    fn dimension(&self) -> usize {
+        self.size()
+    }
+
+    /// Mass-action rates of change, summed over every reaction site; no clamping.
+    fn derivatives(&self, _t: f64, conc: &[f64], dcdt: &mut [f64]) {
+        let n = self.num_molecules;
+        dcdt.fill(0.0);
+        let (mut rates, mut registers) = (Vec::new(), Vec::new());
+        for ((reaction, sites), &scale) in self.reactions.iter().zip(&self.sites).zip(&self.scales)
+        {
+            reaction.site_rates(conc, n, sites, &mut rates, &mut registers);
+            for (&comp, &rate) in sites.iter().zip(&rates) {
+                let (offset, rate) = (comp * n, rate * scale);
+                for &(mol, stoich) in &reaction.reactants {
+                    dcdt[offset + mol] -= rate * stoich;
+                }
+                for &(mol, stoich) in &reaction.products {
+                    dcdt[offset + mol] += rate * stoich;
+                }
+            }
+        }
+        let mut buf = Vec::new();
+        for flow in &self.flows {
+            if let Some(parent) = flow.parent(&self.topology) {
+                let rate = flow.event_rate(conc, n, parent, &mut buf);
+                let (to_origin, to_parent) = self.factors(flow, parent);
+                for &(mol, count) in &flow.stoichiometry {
+                    dcdt[flow.origin * n + mol] += rate * count * to_origin;
+                    dcdt[parent * n + mol] -= rate * count * to_parent;
+                }
+            }
+        }
+        for flow in &self.diffusion {
+            flow.derivatives(
+                &self.topology,
+                conc,
+                n,
+                &self.volumes,
+                &self.multiplicities,
+                dcdt,
+            );
+        }
+        for flow in &self.general {
+            let rate = flow.event_rate(conc, n, &mut buf);
+            for (index, delta) in flow.deltas(n, &self.volumes, &self.multiplicities) {
+                dcdt[index] += rate * delta;
+            }
+        }
+        for &(index, _) in &self.clamps {
+            dcdt[index] = 0.0;
+        }
+    }
+}
+
+impl JacobianSystem for WorldModel {
+    /// Within each compartment, every molecule a reaction touches depends on
+    /// each molecule its rate reads.
+    fn jacobian_pattern(&self) -> Vec<(usize, usize)> {
+        let n = self.num_molecules;
+        let mut pattern = Vec::new();
+        for (reaction, sites) in self.reactions.iter().zip(&self.sites) {
+            let inputs = reaction.rate_inputs();
+            for &comp in sites {
+                let offset = comp * n;
+                for &(row, _) in reaction.reactants.iter().chain(&reaction.products) {
+                    for &col in &inputs {
+                        pattern.push((offset + row, offset + col));
+                    }
+                }
+            }
+        }
+        // A membrane flow couples both sides to every input of its rate law.
+        for flow in &self.flows {
+            let (Some(parent), Some(rate)) = (flow.parent(&self.topology), &flow.rate) else {
+                continue;
+            };
+            for &input in rate.variables() {
+                let col = if input < n {
+                    flow.origin * n + input
+                } else {
+                    parent * n + input - n
+                };
+                for &(mol, _) in &flow.stoichiometry {
+                    pattern.push((flow.origin * n + mol, col));
+                    pattern.push((parent * n + mol, col));
+                }
+            }
+        }
+        for flow in &self.diffusion {
+            flow.jacobian_pattern(&self.topology, n, &mut pattern);
+        }
+        // A general flow couples every compartment it moves to each input of its rate.
+        for flow in &self.general {
+            let inputs = flow.rate_inputs(n, self.size());
+            for leg in &flow.moves {
+                for &(mol, _) in &leg.molecules {
+                    for &col in &inputs {
+                        pattern.push((leg.from * n + mol, col));
+                        pattern.push((leg.to * n + mol, col));
+                    }
+                }
+            }
+        }
+        pattern
+    }
+
+    /// Exact rate derivatives (see `Reaction::rate_partials` and
+    /// `MembraneFlow::rate_partials`): analytic for mass action, compiled
+    /// symbolic derivatives for expression rates and central differences
+    /// for scripts.
+    fn jacobian(&self, _t: f64, conc: &[f64], jac: &mut CsrMatrix) {
+        let n = self.num_molecules;
+        let mut partials = Vec::new();
+        for ((reaction, sites), &scale) in self.reactions.iter().zip(&self.sites).zip(&self.scales)
+        {
+            if reaction.law == RateLaw::Constant || scale == 0.0 {
+                continue;
+            }
+            for &comp in sites {
+                let offset = comp * n;
+                reaction.rate_partials(&conc[offset..offset + n], &mut partials);
+                for &(col, d_rate) in &partials {
+                    let d_rate = d_rate * scale;
+                    for &(row, stoich) in &reaction.reactants {
+                        jac.add(offset + row, offset + col, -stoich * d_rate);
+                    }
+                    for &(row, stoich) in &reaction.products {
+                        jac.add(offset + row, offset + col, stoich * d_rate);
+                    }
+                }
+            }
+        }
+        let mut flow_partials = Vec::new();
+        for flow in &self.flows {
+            let Some(parent) = flow.parent(&self.topology) else {
+                continue;
+            };
+            flow.rate_partials(conc, n, parent, &mut flow_partials);
+            let (to_origin, to_parent) = self.factors(flow, parent);
+            for &(col, d_rate) in &flow_partials {
+                for &(mol, count) in &flow.stoichiometry {
+                    jac.add(flow.origin * n + mol, col, count * d_rate * to_origin);
+                    jac.add(parent * n + mol, col, -count * d_rate * to_parent);
+                }
+            }
+        }
+        for flow in &self.diffusion {
+            flow.jacobian(&self.topology, n, &self.volumes, &self.multiplicities, jac);
+        }
+        for flow in &self.general {
+            flow.rate_partials(conc, n, &mut flow_partials);
+            for (index, delta) in flow.deltas(n, &self.volumes, &self.multiplicities) {
+                for &(col, d_rate) in &flow_partials {
+                    jac.add(index, col, delta * d_rate);
+                }
+            }
+        }
+        // Clamped concentrations do not move.
+        for &(row, _) in &self.clamps {
+            let entries: Vec<_> = jac.row(row).collect();
+            for (col, value) in entries {
+                jac.add(row, col, -value);
+            }
+        }
+    }
+}
+
+/// Multi-compartment simulator with reactions and flows.
+#[pyclass(module = "alienbio_sim")]
+pub struct WorldSimulator {
+    model: WorldModel,
+    tree: Py<CompartmentTree>,
+    reaction_specs: Py<PyList>,
+    flows: Py<PyList>,
+    /// How each flow is applied, with the index of its Python object.
+    flow_kinds: Vec<(usize, FlowKind)>,
+    molecules: MoleculeIndex,
+    dt: f64,
+    method: Method,
+    /// Relative tolerance of the per-step conservation check, if enabled.
+    conservation: Option<f64>,
+    /// (time, description) of each event applied by the last `run`.
+    event_log: Vec<(f64, String)>,
+}
+
+/// How one entry of `WorldSimulator.flows` is applied.
+#[derive(Debug)]
+enum FlowKind {
+    /// Through the Python object's `apply()`.
+    Python,
+    /// Native membrane flow `model.flows()[i]`.
+    Membrane(usize),
+    /// Native membrane transport at the event rate of a Python `rate_fn`.
+    MembraneRateFn(MembraneFlow),
+    /// Native diffusion flow `model.diffusion()[i]`.
+    Diffusion(usize),
+    /// Native declarative general flow `model.general()[i]`.
+    General(usize),
+    /// Native `rhai:` GeneralFlow body.
+    Script(Box<ScriptFlow>),
+    /// Native transfer of instances between compartments.
+    Instance(Box<InstanceFlow>),
+}
+
+/// Flows the model integrates natively, by kind.
+#[derive(Debug, Default)]
+struct NativeFlows {
+    membrane: Vec<MembraneFlow>,
+    diffusion: Vec<DiffusionFlow>,
+    general: Vec<GeneralFlow>,
+}
+
+impl NativeFlows {
+    fn bind(self, model: WorldModel) -> SimResult<WorldModel> {
+        model
+            .with_flows(self.membrane)?
+            .with_diffusion(self.diffusion)?
+            .with_general(self.general)
+    }
+}
+
+/// What a topology change replaces: the model, its flow kinds and the tree.
+struct Binding {
+    model: WorldModel,
+    flow_kinds: Vec<(usize, FlowKind)>,
+    tree: Py<CompartmentTree>,
+}
+
+/// Sort Python flows into native and Python-applied kinds, resolving
+/// molecule names through `molecules` and compartment names through
+/// `topology`; returns the model's native flows too.
+fn flow_kinds(
+    flows: &PyList,
+    molecules: &MoleculeIndex,
+    topology: &Topology,
+) -> PyResult<(Vec<(usize, FlowKind)>, NativeFlows)> {
+    let mut native = NativeFlows::default();
+    let mut kinds = Vec::with_capacity(flows.len());
+    for (index, flow) in flows.iter().enumerate() {
+        let is_diffusion = flow
+            .getattr("is_diffusion_flow")
+            .and_then(|f| f.extract::<bool>())
+            .unwrap_or(false);
+        let is_membrane = flow
+            .getattr("is_membrane_flow")
+            .and_then(|f| f.extract::<bool>())
+            .unwrap_or(false);
+        let is_instance = flow
+            .getattr("is_instance_flow")
+            .and_then(|f| f.extract::<bool>())
+            .unwrap_or(false);
+        let body = match flow.getattr("body") {
+            Ok(body) => body.extract::<Option<&str>>().unwrap_or(None),
+            Err(_) => None,
+        };
+        let kind = if is_diffusion {
+            native
+                .diffusion
+                .push(DiffusionFlow::from_py(flow, molecules, topology)?);
+            FlowKind::Diffusion(native.diffusion.len() - 1)
+        } else if is_instance {
+            FlowKind::Instance(Box::new(InstanceFlow::from_py(flow, molecules, topology)?))
+        } else if is_membrane {
+            let membrane = MembraneFlow::from_py(flow, molecules)?;
+            if MembraneFlow::has_rate_fn(flow) {
+                FlowKind::MembraneRateFn(membrane)
+            } else {
+                native.membrane.push(membrane);
+                FlowKind::Membrane(native.membrane.len() - 1)
+            }
+        } else if GeneralFlow::is_declarative(flow) {
+            native
+                .general
+                .push(GeneralFlow::from_py(flow, molecules, topology)?);
+            FlowKind::General(native.general.len() - 1)
+        } else if let Some(body) = body.filter(|b| script::strip(b).is_some()) {
+            FlowKind::Script(Box::new(ScriptFlow::new(
+                flow.getattr("name")?.extract::<String>()?,
+                flow.getattr("origin")?.extract()?,
+                body,
+                molecules,
+            )?))
+        } else {
+            FlowKind::Python
+        };
+        kinds.push((index, kind));
+    }
+    Ok((kinds, native))
+}
+
+impl WorldSimulator {
+    pub fn model(&self) -> &WorldModel {
+        &self.model
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn assemble(
+        model: WorldModel,
+        tree: Py<CompartmentTree>,
+        reaction_specs: &PyList,
+        flows: &PyList,
+        flow_kinds: Vec<(usize, FlowKind)>,
+        molecules: MoleculeIndex,
+        dt: f64,
+        method: Method,
+        volumes: Option<Vec<f64>>,
+        check_conservation: Option<f64>,
+        growth: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        if !dt.is_finite() || dt <= 0.0 {
+            return Err(PyValueError::new_err(format!(
+                "dt must be positive, got {dt}"
+            )));
+        }
+        let model = match volumes {
+            Some(volumes) => model.with_volumes(volumes)?,
+            None => model,
+        };
+        let model = match growth {
+            Some(growth) => {
+                let laws = Growth::from_py(growth, &molecules, model.topology())?;
+                model.with_growth(laws)?
+            }
+            None => model,
+        };
+        if method.is_adaptive() {
+            script::check_memory(model.reactions(), method.name())?;
+        }
+        if let Some(tol) = check_conservation.filter(|t| !(t.is_finite() && *t >= 0.0)) {
+            return Err(PyValueError::new_err(format!(
+                "check_conservation must be a non-negative tolerance, got {tol}"
+            )));
+        }
+        Ok(Self {
+            model,
+            tree,
+            reaction_specs: reaction_specs.into(),
+            flow_kinds,
+            flows: flows.into(),
+            molecules,
+            dt,
+            method,
+            conservation: check_conservation,
+            event_log: Vec::new(),
+        })
+    }
+
+    fn check_state(&self, state: &WorldState) -> PyResult<()> {
+        if state.compartments() != self.model.num_compartments()
+            || state.molecules() != self.model.num_molecules()
+        {
+            return Err(PyValueError::new_err(format!(
+                "State shape {}x{} does not match simulator {}x{}",
+                state.compartments(),
+                state.molecules(),
+                self.model.num_compartments(),
+                self.model.num_molecules()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Advance `state` in place by `dt`: one step, or the part of one up to
+    /// an event.
+    fn advance(&mut self, py: Python<'_>, state: &Py<WorldState>, dt: f64) -> PyResult<()> {
+        {
+            let mut current = state.borrow_mut(py);
+            self.model.set_multiplicities(current.multiplicities());
+            if self.method.is_adaptive() {
+                self.method.integrate(
+                    &self.model,
+                    0.0,
+                    current.concentrations_mut(),
+                    &[dt],
+                    |_, _| {},
+                )?;
+            } else {
+                self.model.apply_reactions(current.concentrations_mut(), dt);
+            }
+        }
+        script::check(self.model.reactions())?;
+        let before = self.conservation.map(|_| self.totals(py, state));
+        let n = self.model.num_molecules();
+        let topology = self.model.topology();
+        let flows = self.flows.as_ref(py);
+        for (index, kind) in &self.flow_kinds {
+            let flow = flows.get_item(*index)?;
+            match kind {
+                FlowKind::Python => {
+                    flow.call_method1("apply", (state, &self.tree, dt))?;
+                }
+                // Integrated with the reactions by adaptive methods.
+                FlowKind::Membrane(_) | FlowKind::Diffusion(_) | FlowKind::General(_)
+                    if self.method.is_adaptive() => {}
+                FlowKind::Membrane(i) => {
+                    let mut current = state.borrow_mut(py);
+                    let (conc, multiplicities) = current.buffers_mut();
+                    self.model
+                        .apply_flow(&self.model.flows()[*i], conc, multiplicities, dt);
+                }
+                FlowKind::Diffusion(i) => {
+                    let mut current = state.borrow_mut(py);
+                    let (conc, multiplicities) = current.buffers_mut();
+                    self.model.apply_diffusion(
+                        &self.model.diffusion()[*i],
+                        conc,
+                        multiplicities,
+                        dt,
+                    );
+                }
+                FlowKind::General(i) => {
+                    let mut current = state.borrow_mut(py);
+                    let (conc, multiplicities) = current.buffers_mut();
+                    self.model.general()[*i].apply(
+                        conc,
+                        n,
+                        dt,
+                        self.model.volumes(),
+                        multiplicities,
+                    );
+                }
+                FlowKind::MembraneRateFn(native) => {
+                    if native.parent(topology).is_some() {
+                        let rate: f64 = flow
+                            .call_method1("compute_flux", (state, &self.tree))?
+                            .extract()?;
+                        let mut current = state.borrow_mut(py);
+                        let (conc, multiplicities) = current.buffers_mut();
+                        self.model.transfer(native, conc, multiplicities, rate * dt);
+                    }
+                }
+                FlowKind::Script(native) => {
+                    native.apply(state.borrow_mut(py).concentrations_mut(), n, dt)?
+                }
+                FlowKind::Instance(native) => {
+                    let mut current = state.borrow_mut(py);
+                    let (conc, multiplicities) = current.buffers_mut();
+                    native.apply(conc, multiplicities, n, dt, self.model.volumes());
+                }
+            }
+        }
+        if let (Some(tol), Some(before)) = (self.conservation, before) {
+            let after = self.totals(py, state);
+            for (m, (&b, &a)) in before.iter().zip(&after).enumerate() {
+                if (a - b).abs() > tol * b.abs().max(a.abs()) {
+                    return Err(PyValueError::new_err(format!(
+                        "Flows changed the total amount of {} from {b} to {a}",
+                        self.molecules.names()[m]
+                    )));
+                }
+            }
+        }
+        // Populations change after the flows, so the check above excludes them.
+        let mut current = state.borrow_mut(py);
+        let (conc, multiplicities) = current.buffers_mut();
+        self.model.apply_growth(conc, multiplicities, dt);
+        self.model.hold_clamps(conc);
+        Ok(())
+    }
+
+    /// Total amount of each molecule in `state`.
+    fn totals(&self, py: Python<'_>, state: &Py<WorldState>) -> Vec<f64> {
+        let current = state.borrow(py);
+        self.model
+            .total_amounts(current.concentrations(), current.multiplicities())
+    }
+
+    /// Swap in a model, its flow kinds and its tree; returns the ones replaced.
+    fn rebind(&mut self, binding: Binding) -> Binding {
+        Binding {
+            model: std::mem::replace(&mut self.model, binding.model),
+            flow_kinds: std::mem::replace(&mut self.flow_kinds, binding.flow_kinds),
+            tree: std::mem::replace(&mut self.tree, binding.tree),
+        }
+    }
+
+    /// Apply a topology operation to `state`, rebinding the simulator to the
+    /// new tree; returns the remapped state and the binding it replaced.
+    fn remodel(
+        &mut self,
+        py: Python<'_>,
+        state: &WorldState,
+        op: Remodel,
+        seed: Option<u64>,
+    ) -> PyResult<(Py<WorldState>, Binding)> {
+        self.check_state(state)?;
+        let flows = self.flows.as_ref(py);
+        for (index, kind) in &self.flow_kinds {
+            if matches!(
+                kind,
+                FlowKind::Python | FlowKind::MembraneRateFn(_) | FlowKind::Script(_)
+            ) {
+                return Err(PyValueError::new_err(format!(
+                    "Flow {} is not declarative and cannot follow a topology change",
+                    flows.get_item(*index)?.getattr("name")?
+                )));
+            }
+        }
+        let mut rng = match seed {
+            Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+            None => ChaCha8Rng::from_entropy(),
+        };
+        let n = self.model.num_molecules();
+        let remodeled = op.apply(
+            self.model.topology(),
+            n,
+            state.concentrations(),
+            state.multiplicities(),
+            self.model.volumes(),
+            &mut rng,
+        )?;
+        let remap = &remodeled.remap;
+        let mut native = NativeFlows::default();
+        let mut kinds = Vec::with_capacity(self.flow_kinds.len());
+        for (index, kind) in &self.flow_kinds {
+            match kind {
+                FlowKind::Membrane(i) => {
+                    for flow in self.model.flows()[*i].remap(remap) {
+                        native.membrane.push(flow);
+                        kinds.push((*index, FlowKind::Membrane(native.membrane.len() - 1)));
+                    }
+                }
+                FlowKind::Diffusion(i) => {
+                    native
+                        .diffusion
+                        .push(self.model.diffusion()[*i].remap(remap));
+                    kinds.push((*index, FlowKind::Diffusion(native.diffusion.len() - 1)));
+                }
+                FlowKind::General(i) => {
+                    if let Some(flow) = self.model.general()[*i].remap(remap, n) {
+                        native.general.push(flow);
+                        kinds.push((*index, FlowKind::General(native.general.len() - 1)));
+                    }
+                }
+                FlowKind::Instance(flow) => {
+                    if let Some(flow) = flow.remap(remap, n) {
+                        kinds.push((*index, FlowKind::Instance(Box::new(flow))));
+                    }
+                }
+                FlowKind::Python | FlowKind::MembraneRateFn(_) | FlowKind::Script(_) => {
+                    unreachable!("rejected above")
+                }
+            }
+        }
+        let model = native.bind(self.model.remodel(&remodeled)?)?;
+        let tree = Py::new(py, CompartmentTree::from_topology(model.topology().clone()))?;
+        let next = WorldState::from_parts(
+            tree.clone_ref(py),
+            model.num_compartments(),
+            n,
+            remodeled.concentrations,
+            remodeled.multiplicities,
+        );
+        let previous = self.rebind(Binding {
+            model,
+            flow_kinds: kinds,
+            tree,
+        });
+        Ok((Py::new(py, next)?, previous))
+    }
+
+    /// Apply one scheduled event to `state` and log it; returns the state to
+    /// continue from, which is a new one after a removal. The first removal
+    /// stores the binding it replaced in `replaced`.
+    fn apply_event(
+        &mut self,
+        py: Python<'_>,
+        state: Py<WorldState>,
+        event: &Event,
+        replaced: &mut Option<Binding>,
+    ) -> PyResult<Py<WorldState>> {
+        let description = event.describe(
+            self.model.topology(),
+            &self.molecules,
+            self.model.reactions(),
+        );
+        let topology = self.model.topology().clone();
+        let n = self.model.num_molecules();
+        let next = match &event.action {
+            Action::Bolus {
+                compartment,
+                molecule,
+                amount,
+            } => {
+                let c = compartment.resolve(&topology)?;
+                let mut current = state.borrow_mut(py);
+                let (conc, multiplicities) = current.buffers_mut();
+                let space = self.model.volumes()[c] * multiplicities[c];
+                if space <= 0.0 {
+                    return Err(PyValueError::new_err(format!(
+                        "Cannot dose {}: it has no instances",
+                        topology.name(c)
+                    )));
+                }
+                let index = c * n + molecule;
+                conc[index] = (conc[index] + amount / space).max(0.0);
+                drop(current);
+                state
+            }
+            Action::Clamp {
+                compartment,
+                molecule,
+                value,
+            } => {
+                let index = compartment.resolve(&topology)? * n + molecule;
+                let mut current = state.borrow_mut(py);
+                let conc = current.concentrations_mut();
+                self.model.clamp(index, value.unwrap_or(conc[index]));
+                self.model.hold_clamps(conc);
+                drop(current);
+                state
+            }
+            Action::Release {
+                compartment,
+                molecule,
+            } => {
+                self.model
+                    .release(compartment.resolve(&topology)? * n + molecule);
+                state
+            }
+            Action::Knockdown { reaction, factor } => {
+                self.model.knock_down(*reaction, *factor);
+                state
+            }
+            Action::Knockout { reaction } => {
+                self.model.knock_down(*reaction, 0.0);
+                state
+            }
+            Action::Remove { compartment } => {
+                let compartment = compartment.resolve(&topology)?;
+                let current = state.borrow(py);
+                let (next, previous) =
+                    self.remodel(py, &current, Remodel::Die { compartment }, None)?;
+                replaced.get_or_insert(previous);
+                next
+            }
+        };
+        self.event_log.push((event.time, description));
+        Ok(next)
+    }
+
+    /// Step `dt` at a time, sampling every `sample_every` steps; a step that
+    /// crosses an event is cut at the event's time, and events at the end
+    /// apply to the final state. See `apply_event` for `replaced`.
+    fn run_steps(
+        &mut self,
+        py: Python<'_>,
+        mut current: Py<WorldState>,
+        steps: usize,
+        sample_every: usize,
+        mut schedule: Schedule,
+        replaced: &mut Option<Binding>,
+    ) -> PyResult<Vec<Py<WorldState>>> {
+        let slack = self.slack();
+        let mut history = Vec::with_capacity(steps / sample_every + 2);
+        let mut t = 0.0;
+        for i in 0..steps {
+            if i % sample_every == 0 {
+                history.push(Self::snapshot(py, &current)?);
+            }
+            let end = (i + 1) as f64 * self.dt;
+            while t < end {
+                while let Some(event) = schedule.pop_due(t + slack) {
+                    current = self.apply_event(py, current, &event, replaced)?;
+                }
+                let stop = match schedule.next_time() {
+                    Some(time) if time < end - slack => time,
+                    _ => end,
+                };
+                self.advance(py, &current, stop - t)?;
+                t = stop;
+            }
+        }
+        while let Some(event) = schedule.pop_due(t + slack) {
+            current = self.apply_event(py, current, &event, replaced)?;
+        }
+        // Always include final state
+        history.push(current);
+        Ok(history)
+    }
+
+    /// Events this close to a step boundary land on it.
+    fn slack(&self) -> f64 {
+        1e-9 * self.dt
+    }
+
+    fn snapshot(py: Python<'_>, state: &Py<WorldState>) -> PyResult<Py<WorldState>> {
+        let copy = state.borrow(py).copy(py);
+        Py::new(py, copy)
+    }
+
+    /// Reactions and native membrane flows only, with an adaptive method: one
+    /// integration over the whole run, sampled at `i * dt` by dense output
+    /// rather than stepping `dt` at a time.
+    fn run_dense(
+        &mut self,
+        py: Python<'_>,
+        state: &WorldState,
+        steps: usize,
+        sample_every: usize,
+    ) -> PyResult<Vec<Py<WorldState>>> {
+        self.model.set_multiplicities(state.multiplicities());
+        let times: Vec<f64> = (0..steps)
+            .step_by(sample_every)
+            .chain(std::iter::once(steps))
+            .map(|i| i as f64 * self.dt)
+            .collect();
+        let mut conc = state.concentrations().to_vec();
+        let mut samples = Vec::with_capacity(times.len());
+        self.method
+            .integrate(&self.model, 0.0, &mut conc, &times, |_, values| {
+                samples.push(values.to_vec())
+            })?;
+        script::check(self.model.reactions())?;
+        samples
+            .into_iter()
+            .map(|values| {
+                let mut sample = state.copy(py);
+                sample.concentrations_mut().copy_from_slice(&values);
+                Py::new(py, sample)
+            })
+            .collect()
+    }
+}
+
+#[pymethods]
+impl WorldSimulator {
+    /// Initialize world simulator.
+    ///
+    /// Args:
+    ///     tree: Compartment topology (CompartmentTree or CompartmentTreeImpl)
+    ///     reactions: List of ReactionSpec
+    ///     flows: List of flow objects; MembraneFlows and `rhai:` GeneralFlow
+    ///         bodies run natively, others via flow.apply(state, tree, dt)
+    ///     num_molecules: Number of molecules in vocabulary
+    ///     dt: Time step size (the output grid for adaptive methods)
+    ///     method: "euler" (fixed step, as WorldSimulatorImpl), "rk45" or "rosenbrock"
+    ///     atol: Absolute tolerance for adaptive methods
+    ///     rtol: Relative tolerance for adaptive methods
+    ///     lu: "auto", "dense" or "sparse" LU for the rosenbrock Jacobian
+    ///     molecules: Molecule names by ID, used by flows (default m0, m1, ...)
+    ///     volumes: Volume of each compartment instance (default 1.0 each)
+    ///     check_conservation: If set, the relative tolerance to which flows
+    ///         must conserve each molecule's total amount every step
+    ///     growth: Population laws {compartment: law}, where a law is a rate
+    ///         Expr of net births per instance or {"birth": .., "death": ..}
+    #[new]
+    #[pyo3(signature = (tree, reactions, flows, num_molecules, dt=1.0, method="euler", atol=None, rtol=None, lu="auto", molecules=None, volumes=None, check_conservation=None, growth=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn new(
+        py: Python<'_>,
+        tree: &PyAny,
+        reactions: &PyAny,
+        flows: &PyAny,
+        num_molecules: usize,
+        dt: f64,
+        method: &str,
+        atol: Option<f64>,
+        rtol: Option<f64>,
+        lu: &str,
+        molecules: Option<Vec<String>>,
+        volumes: Option<Vec<f64>>,
+        check_conservation: Option<f64>,
+        growth: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let reaction_specs = PyList::new(py, reactions.iter()?.collect::<PyResult<Vec<_>>>()?);
+        let parsed = reaction_specs
+            .iter()
+            .map(Reaction::from_spec)
+            .collect::<PyResult<Vec<_>>>()?;
+        let flows = PyList::new(py, flows.iter()?.collect::<PyResult<Vec<_>>>()?);
+        let names = match molecules {
+            Some(names) if names.len() != num_molecules => {
+                return Err(PyValueError::new_err(format!(
+                    "Expected {num_molecules} molecule names, got {}",
+                    names.len()
+                )))
+            }
+            Some(names) => names,
+            None => (0..num_molecules).map(|m| format!("m{m}")).collect(),
+        };
+        let molecules = MoleculeIndex::new(names);
+        let (flow_kinds, native) = flow_kinds(flows, &molecules, &topology)?;
+        let model = native.bind(WorldModel::new(topology, parsed, num_molecules)?)?;
+        Self::assemble(
+            model,
+            tree,
+            reaction_specs,
+            flows,
+            flow_kinds,
+            molecules,
+            dt,
+            Method::parse(method, atol, rtol, lu)?,
+            volumes,
+            check_conservation,
+            growth,
+        )
+    }
+
+    /// Compartment topology.
+    #[getter]
+    fn tree(&self, py: Python<'_>) -> Py<CompartmentTree> {
+        self.tree.clone_ref(py)
+    }
+
+    /// Reaction specifications.
+    #[getter]
+    fn reactions(&self, py: Python<'_>) -> Py<PyList> {
+        self.reaction_specs.clone_ref(py)
+    }
+
+    /// Flow specifications.
+    #[getter]
+    fn flows(&self, py: Python<'_>) -> Py<PyList> {
+        self.flows.clone_ref(py)
+    }
+
+    /// Number of molecules in vocabulary.
+    #[getter]
+    fn num_molecules(&self) -> usize {
+        self.model.num_molecules()
+    }
+
+    /// Time step size.
+    #[getter]
+    fn dt(&self) -> f64 {
+        self.dt
+    }
+
+    /// Integration method name.
+    #[getter]
+    fn method(&self) -> &'static str {
+        self.method.name()
+    }
+
+    /// Volume of each compartment instance.
+    #[getter]
+    fn volumes(&self) -> Vec<f64> {
+        self.model.volumes().to_vec()
+    }
+
+    /// Total amount of each molecule: concentration × volume × multiplicity,
+    /// summed over compartments.
+    fn total_amounts(&self, state: PyRef<'_, WorldState>) -> PyResult<Vec<f64>> {
+        self.check_state(&state)?;
+        Ok(self
+            .model
+            .total_amounts(state.concentrations(), state.multiplicities()))
+    }
+
+    /// Advance simulation by one time step, returning a new state.
+    fn step(&mut self, py: Python<'_>, state: PyRef<'_, WorldState>) -> PyResult<Py<WorldState>> {
+        self.check_state(&state)?;
+        let next = Py::new(py, state.copy(py))?;
+        drop(state);
+        self.advance(py, &next, self.dt)?;
+        Ok(next)
+    }
+
+    /// Run simulation for multiple steps.
+    ///
+    /// Args:
+    ///     state: Initial state (not modified)
+    ///     steps: Number of steps to run
+    ///     sample_every: If set, only keep every Nth state (plus final)
+    ///     events: Interventions, as dicts {"time": t, "type": kind, ...}:
+    ///         bolus (compartment, molecule, amount), clamp (compartment,
+    ///         molecule, optional value), release (compartment, molecule),
+    ///         knockdown (reaction, factor), knockout (reaction) or remove
+    ///         (compartment). Events last until the run ends, so a removal
+    ///         does not change the simulator's tree. A state sampled at an
+    ///         event's time precedes it, except the final state; events after
+    ///         the end of the run are rejected.
+    ///
+    /// Returns:
+    ///     List of states (timeline); applied events are in `event_log`
+    #[pyo3(signature = (state, steps, sample_every=None, events=None))]
+    fn run(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        steps: usize,
+        sample_every: Option<usize>,
+        events: Option<&PyAny>,
+    ) -> PyResult<Vec<Py<WorldState>>> {
+        self.check_state(&state)?;
+        let sample_every = sample_every.unwrap_or(1);
+        if sample_every == 0 {
+            return Err(PyValueError::new_err("sample_every must be positive"));
+        }
+        let mut scheduled = Vec::new();
+        if let Some(events) = events {
+            for spec in events.iter()? {
+                let spec = spec?.downcast::<PyDict>()?;
+                scheduled.push(Event::from_py(
+                    spec,
+                    &self.molecules,
+                    self.model.reactions(),
+                )?);
+            }
+        }
+        let schedule = Schedule::new(scheduled)?;
+        let end = steps as f64 * self.dt;
+        if let Some(last) = schedule.last_time().filter(|&t| t > end + self.slack()) {
+            return Err(PyValueError::new_err(format!(
+                "Event at time {last} is after the end of the run ({end})"
+            )));
+        }
+        self.event_log.clear();
+        script::reset(self.model.reactions());
+        for (_, kind) in &self.flow_kinds {
+            if let FlowKind::Script(flow) = kind {
+                flow.script().reset();
+            }
+        }
+        let all_native = self.flow_kinds.iter().all(|(_, kind)| {
+            matches!(
+                kind,
+                FlowKind::Membrane(_) | FlowKind::Diffusion(_) | FlowKind::General(_)
+            )
+        });
+        if self.method.is_adaptive()
+            && all_native
+            && self.model.growth().is_empty()
+            && schedule.is_empty()
+        {
+            return self.run_dense(py, &state, steps, sample_every);
+        }
+        let current = Py::new(py, state.copy(py))?;
+        drop(state);
+        let mut replaced = None;
+        let history = self.run_steps(py, current, steps, sample_every, schedule, &mut replaced);
+        if let Some(original) = replaced {
+            self.rebind(original);
+        }
+        self.model.clear_interventions();
+        history
+    }
+
+    /// (time, description) of each event applied by the last `run`.
+    #[getter]
+    fn event_log(&self) -> Vec<(f64, String)> {
+        self.event_log.clone()
+    }
+
+    /// Divide a compartment, with its subtree, into itself and a copy under
+    /// the same parent. The simulator switches to the new tree.
+    ///
+    /// Args:
+    ///     state: Current state (not modified)
+    ///     compartment: Compartment to divide (ID or name)
+    ///     ratio: Fraction of the volume and contents the original keeps
+    ///     stochastic: Partition each molecule binomially instead of exactly
+    ///     seed: Random seed for stochastic partitioning
+    ///     suffix: Appended to the names of the copies
+    ///
+    /// Returns:
+    ///     The remapped state, on the new tree
+    #[pyo3(signature = (state, compartment, ratio=0.5, stochastic=false, seed=None, suffix="_2"))]
+    #[allow(clippy::too_many_arguments)]
+    fn divide(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        compartment: &PyAny,
+        ratio: f64,
+        stochastic: bool,
+        seed: Option<u64>,
+        suffix: &str,
+    ) -> PyResult<Py<WorldState>> {
+        let compartment = flow::compartment_key(compartment, self.model.topology())?;
+        let split = if stochastic {
+            Split::Binomial(ratio)
+        } else {
+            Split::Fixed(ratio)
+        };
+        let op = Remodel::Divide {
+            compartment,
+            split,
+            suffix: suffix.to_string(),
+        };
+        Ok(self.remodel(py, &state, op, seed)?.0)
+    }
+
+    /// Remove a compartment and its subtree, releasing their contents into
+    /// its parent. The simulator switches to the new tree.
+    fn kill(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        compartment: &PyAny,
+    ) -> PyResult<Py<WorldState>> {
+        let compartment = flow::compartment_key(compartment, self.model.topology())?;
+        Ok(self
+            .remodel(py, &state, Remodel::Die { compartment }, None)?
+            .0)
+    }
+
+    /// Merge sibling `absorb` into `keep`, pooling their contents; `absorb`'s
+    /// children move to `keep`. The simulator switches to the new tree.
+    fn fuse(
+        &mut self,
+        py: Python<'_>,
+        state: PyRef<'_, WorldState>,
+        keep: &PyAny,
+        absorb: &PyAny,
+    ) -> PyResult<Py<WorldState>> {
+        let topology = self.model.topology();
+        let op = Remodel::Fuse {
+            keep: flow::compartment_key(keep, topology)?,
+            absorb: flow::compartment_key(absorb, topology)?,
+        };
+        Ok(self.remodel(py, &state, op, None)?.0)
+    }
+
+    /// Create simulator from a Chemistry and compartment tree.
+    ///
+    /// Molecule IDs follow the order of `chemistry.molecules`; Expr and
+    /// `rhai:` rates are compiled natively and callable rates fall back to
+    /// 1.0, as in `WorldSimulatorImpl.from_chemistry`. Flows name molecules
+    /// as the chemistry does.
+    #[classmethod]
+    #[pyo3(signature = (chemistry, tree, flows=None, dt=1.0, method="euler", atol=None, rtol=None, lu="auto", volumes=None, check_conservation=None, growth=None))]
+    #[allow(clippy::too_many_arguments)]
+    fn from_chemistry(
+        _cls: &PyType,
+        py: Python<'_>,
+        chemistry: &PyAny,
+        tree: &PyAny,
+        flows: Option<&PyAny>,
+        dt: f64,
+        method: &str,
+        atol: Option<f64>,
+        rtol: Option<f64>,
+        lu: &str,
+        volumes: Option<Vec<f64>>,
+        check_conservation: Option<f64>,
+        growth: Option<&PyDict>,
+    ) -> PyResult<Self> {
+        let molecules = MoleculeIndex::from_chemistry(chemistry)?;
+        let reactions = molecules
+            .reactions(chemistry, RateLaw::MassAction, false)?
+            .into_iter()
+            .map(|(reaction, _)| reaction)
+            .collect();
+
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let topology = tree.borrow(py).topology().clone();
+        let flows = match flows {
+            Some(flows) => PyList::new(py, flows.iter()?.collect::<PyResult<Vec<_>>>()?),
+            None => PyList::empty(py),
+        };
+        let (flow_kinds, native) = flow_kinds(flows, &molecules, &topology)?;
+        let model = native.bind(WorldModel::new(topology, reactions, molecules.len())?)?;
+        Self::assemble(
+            model,
+            tree,
+            PyList::empty(py),
+            flows,
+            flow_kinds,
+            molecules,
+            dt,
+            Method::parse(method, atol, rtol, lu)?,
+            volumes,
+            check_conservation,
+            growth,
+        )
+    }
+
+    fn __repr__(&self, py: Python<'_>) -> String {
+        format!(
+            "WorldSimulator(compartments={}, molecules={}, reactions={}, flows={}, dt={}, method={:?})",
+            self.model.num_compartments(),
+            self.model.num_molecules(),
+            self.model.reactions().len(),
+            self.flows.as_ref(py).len(),
+            self.dt,
+            self.method.name()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::expr::Arg;
+    use crate::rate::RateExpr;
+
+    fn two_compartments() -> Arc<Topology> {
+        let mut t = Topology::new();
+        let root = t.add_root("organism").unwrap();
+        t.add_child(root, "cell").unwrap();
+        Arc::new(t)
+    }
+
+    #[test]
+    fn euler_step_matches_reference_arithmetic() {
+        let reactions = vec![Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.1)];
+        let model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let mut conc = vec![100.0, 0.0, 10.0, 0.0];
+        model.apply_reactions(&mut conc, 0.5);
+        assert_eq!(conc, vec![95.0, 5.0, 9.5, 0.5]);
+    }
+
+    #[test]
+    fn reactants_clamp_at_zero() {
+        let reactions = vec![Reaction::new("r1", vec![(0, 2.0)], vec![(1, 1.0)], 1.0)];
+        let model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let mut conc = vec![3.0, 0.0, 0.0, 0.0];
+        model.apply_reactions(&mut conc, 1.0);
+        assert_eq!(conc[0], 0.0);
+        assert_eq!(conc[1], 9.0);
+    }
+
+    #[test]
+    fn compartment_restriction() {
+        let reactions =
+            vec![Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.5).in_compartments(vec![1])];
+        let model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let mut conc = vec![1.0, 0.0, 1.0, 0.0];
+        model.apply_reactions(&mut conc, 1.0);
+        assert_eq!(conc, vec![1.0, 0.0, 0.5, 0.5]);
+    }
+
+    #[test]
+    fn derivatives_sum_over_sites() {
+        let reactions = vec![
+            Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.1),
+            Reaction::new("r2", vec![(1, 2.0)], vec![(0, 1.0)], 0.5).in_compartments(vec![1]),
+        ];
+        let model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let mut dcdt = vec![0.0; 4];
+        model.derivatives(0.0, &[10.0, 2.0, 4.0, 2.0], &mut dcdt);
+        // cell: r1 = 0.4, r2 = 0.5 * 2^2 = 2.0
+        assert_eq!(dcdt, vec![-1.0, 1.0, -0.4 + 2.0, 0.4 - 4.0]);
+    }
+
+    #[test]
+    fn knockdowns_and_clamps_change_the_system() {
+        let reactions = vec![
+            Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.1),
+            Reaction::new("r2", vec![(1, 2.0)], vec![(0, 1.0)], 0.5).in_compartments(vec![1]),
+        ];
+        let mut model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let conc = [10.0, 2.0, 4.0, 2.0];
+        model.knock_down(0, 0.5);
+        model.knock_down(1, 0.0);
+        model.clamp(0, 10.0);
+        let mut dcdt = vec![0.0; 4];
+        model.derivatives(0.0, &conc, &mut dcdt);
+        assert_eq!(dcdt, vec![0.0, 0.5, -0.2, 0.2]);
+
+        let mut jac = CsrMatrix::from_pattern(4, model.jacobian_pattern());
+        model.jacobian(0.0, &conc, &mut jac);
+        assert_eq!(jac.get(0, 0), 0.0);
+        assert_eq!(jac.get(1, 0), 0.05);
+        assert_eq!(jac.get(3, 3), 0.0);
+
+        let mut stepped = conc.to_vec();
+        model.apply_reactions(&mut stepped, 1.0);
+        model.hold_clamps(&mut stepped);
+        assert_eq!(stepped, vec![10.0, 2.5, 3.8, 2.2]);
+
+        model.clear_interventions();
+        assert_eq!(model.scales(), &[1.0, 1.0]);
+        assert!(model.clamps().is_empty());
+    }
+
+    #[test]
+    fn rk45_conserves_mass_without_clamping() {
+        let reactions = vec![Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 5.0)];
+        let model = WorldModel::new(two_compartments(), reactions, 2).unwrap();
+        let mut conc = vec![1.0, 0.0, 2.0, 0.0];
+        crate::integrate::Rk45::new(1e-12, 1e-10)
+            .unwrap()
+            .integrate(&model, 0.0, &mut conc, &[1.0], |_, _| {})
+            .unwrap();
+        assert!((conc[0] - (-5.0f64).exp()).abs() < 1e-9);
+        assert!((conc[2] + conc[3] - 2.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn jacobian_matches_finite_differences() {
+        let reactions = vec![
+            Reaction::new("bind", vec![(0, 1.0), (1, 1.0)], vec![(2, 1.0)], 0.3),
+            Reaction::new("dimer", vec![(2, 2.0)], vec![(0, 1.0)], 0.05).in_compartments(vec![1]),
+            Reaction::new("feed", vec![], vec![(1, 1.0)], 2.0).with_law(RateLaw::Constant),
+            // C -> A catalysed by B with order 2.
+            Reaction::new("cat", vec![(2, 1.0)], vec![(0, 1.0)], 0.2)
+                .with_effectors(vec![(1, 2.0)]),
+            // Michaelis–Menten conversion of B into C, inhibited by A.
+            Reaction::new("mm", vec![(1, 1.0)], vec![(2, 1.0)], 1.0).with_expr(Arc::new(
+                RateExpr::new(
+                    &Arg::parse("div(mul(4, B), add(2, B, A))").unwrap(),
+                    &MoleculeIndex::new(vec!["A".into(), "B".into(), "C".into()]),
+                    Vec::new(),
+                )
+                .unwrap(),
+            )),
+        ];
+        let model = WorldModel::new(two_compartments(), reactions, 3).unwrap();
+        let conc = vec![2.0, 3.0, 0.5, 1.5, 0.25, 4.0];
+        let mut jac = CsrMatrix::from_pattern(6, model.jacobian_pattern());
+        model.jacobian(0.0, &conc, &mut jac);
+
+        let (mut f0, mut f1) = (vec![0.0; 6], vec![0.0; 6]);
+        model.derivatives(0.0, &conc, &mut f0);
+        for col in 0..6 {
+            let h = 1e-7;
+            let mut bumped = conc.clone();
+            bumped[col] += h;
+            model.derivatives(0.0, &bumped, &mut f1);
+            for row in 0..6 {
+                let fd = (f1[row] - f0[row]) / h;
+                assert!((jac.get(row, col) - fd).abs() < 1e-5, "({row}, {col})");
+            }
+        }
+        // No coupling across compartments.
+        assert_eq!(jac.get(0, 3), 0.0);
+    }
+
+    #[test]
+    fn effectors_scale_rate_but_are_not_changed() {
+        // A -> B catalysed by enzyme E (molecule 2).
+        let reactions = vec![Reaction::new("cat", vec![(0, 1.0)], vec![(1, 1.0)], 0.5)
+            .with_effectors(vec![(2, 1.0)])];
+        let model = WorldModel::new(two_compartments(), reactions, 3).unwrap();
+        let conc = vec![2.0, 0.0, 3.0, 2.0, 0.0, 0.0];
+        let mut dcdt = vec![0.0; 6];
+        model.derivatives(0.0, &conc, &mut dcdt);
+        assert_eq!(dcdt, vec![-3.0, 3.0, 0.0, 0.0, 0.0, 0.0]);
+
+        // The enzyme is a column of the Jacobian but never a row.
+        let pattern = model.jacobian_pattern();
+        assert!(pattern.contains(&(0, 2)) && pattern.contains(&(1, 2)));
+        assert!(pattern.iter().all(|&(row, _)| row % 3 != 2));
+
+        let overlapping = vec![Reaction::new("auto", vec![(0, 1.0)], vec![(1, 1.0)], 1.0)
+            .with_effectors(vec![(0, 1.0)])];
+        assert!(WorldModel::new(two_compartments(), overlapping, 3).is_err());
+    }
+
+    #[test]
+    fn membrane_flows_join_the_ode_system() {
+        // Facilitated uptake of A into the cell, driven by the gradient and
+        // coupled to B leaving.
+        let symbols = MembraneFlow::symbols(&MoleculeIndex::new(vec!["A".into(), "B".into()]));
+        let rate = RateExpr::new(
+            &Arg::parse("div(sub(parent_A, A), add(1, B))").unwrap(),
+            &symbols,
+            Vec::new(),
+        )
+        .unwrap();
+        let flow = MembraneFlow::new("uptake", 1, vec![(0, 1.0), (1, -0.5)], 2.0)
+            .with_rate(Arc::new(rate));
+        let reactions = vec![Reaction::new("r1", vec![(0, 1.0)], vec![(1, 1.0)], 0.1)];
+        let model = WorldModel::new(two_compartments(), reactions, 2)
+            .unwrap()
+            .with_flows(vec![flow])
+            .unwrap();
+        let conc = vec![5.0, 1.0, 1.0, 3.0];
+        let mut dcdt = vec![0.0; 4];
+        model.derivatives(0.0, &conc, &mut dcdt);
+        // events = 2 * (5 - 1) / (1 + 3) = 2
+        assert_eq!(dcdt, vec![-0.5 - 2.0, 0.5 + 1.0, -0.1 + 2.0, 0.1 - 1.0]);
+
+        let mut jac = CsrMatrix::from_pattern(4, model.jacobian_pattern());
+        model.jacobian(0.0, &conc, &mut jac);
+        let mut f1 = vec![0.0; 4];
+        for col in 0..4 {
+            let h = 1e-7;
+            let mut bumped = conc.clone();
+            bumped[col] += h;
+            model.derivatives(0.0, &bumped, &mut f1);
+            for row in 0..4 {
+                let fd = (f1[row] - dcdt[row]) / h;
+                assert!((jac.get(row, col) - fd).abs() < 1e-5, "({row}, {col})");
+            }
+        }
+
+        let mut stepped = conc.clone();
+        model.apply_flows(&mut stepped, &[1.0, 1.0], 0.5);
+        assert_eq!(stepped, vec![4.0, 1.5, 2.0, 2.5]);
+
+        let stray = MembraneFlow::new("stray", 2, vec![(0, 1.0)], 1.0);
+        assert!(WorldModel::new(two_compartments(), Vec::new(), 2)
+            .unwrap()
+            .with_flows(vec![stray])
+            .is_err());
+    }
+
+    #[test]
+    fn membrane_rates_convert_amounts_between_sides() {
+        // 1e6 cells of volume 1e-3 export A into 4 units of plasma.
+        let flow = MembraneFlow::new("export", 1, vec![(0, -1.0)], 2e-4);
+        let mut model = WorldModel::new(two_compartments(), Vec::new(), 1)
+            .unwrap()
+            .with_flows(vec![flow])
+            .unwrap()
+            .with_volumes(vec![4.0, 1e-3])
+            .unwrap();
+        model.set_multiplicities(&[1.0, 1e6]);
+        let conc = vec![0.0, 1.0];
+        let mut dcdt = vec![0.0; 2];
+        model.derivatives(0.0, &conc, &mut dcdt);
+        // Per cell: -2e-4 / 1e-3; plasma: +2e-4 * 1e6 / 4.
+        assert!((dcdt[1] + 0.2).abs() < 1e-12 && (dcdt[0] - 50.0).abs() < 1e-9);
+        let net: f64 = flow::total_amounts(&dcdt, 1, model.volumes(), model.multiplicities())
+            .iter()
+            .sum();
+        assert!(net.abs() < 1e-9);
+
+        assert!(WorldModel::new(two_compartments(), Vec::new(), 1)
+            .unwrap()
+            .with_volumes(vec![1.0, 0.0])
+            .is_err());
+    }
+
+    #[test]
+    fn general_flows_join_the_ode_system() {
+        // Organism with two sibling cells; A shuttles from cell to sister.
+        let mut t = Topology::new();
+        let root = t.add_root("organism").unwrap();
+        t.add_child(root, "cell").unwrap();
+        t.add_child(root, "sister").unwrap();
+        let topology = Arc::new(t);
+        let symbols = GeneralFlow::symbols(&MoleculeIndex::new(vec!["A".into()]), &topology);
+        let rate = RateExpr::new(
+            &Arg::parse("div(A, add(1, sister.A))").unwrap(),
+            &symbols,
+            Vec::new(),
+        )
+        .unwrap();
+        let shuttle = GeneralFlow::new(
+            "shuttle",
+            1,
+            vec![flow::Move {
+                from: 1,
+                to: 2,
+                molecules: vec![(0, 1.0)],
+            }],
+            3.0,
+        )
+        .with_rate(Arc::new(rate));
+        let mut model = WorldModel::new(topology, Vec::new(), 1)
+            .unwrap()
+            .with_general(vec![shuttle])
+            .unwrap();
+        model.set_multiplicities(&[1.0, 2.0, 4.0]);
+        let conc = vec![0.0, 2.0, 1.0];
+        let mut dcdt = vec![0.0; 3];
+        model.derivatives(0.0, &conc, &mut dcdt);
+        // 3 events/time, each moving 1 from 2 cells into 4 sisters.
+        assert_eq!(dcdt, vec![0.0, -1.5, 0.75]);
+
+        let mut jac = CsrMatrix::from_pattern(3, model.jacobian_pattern());
+        model.jacobian(0.0, &conc, &mut jac);
+        let mut f1 = vec![0.0; 3];
+        for col in 0..3 {
+            let h = 1e-7;
+            let mut bumped = conc.clone();
+            bumped[col] += h;
+            model.derivatives(0.0, &bumped, &mut f1);
+            for row in 0..3 {
+                let fd = (f1[row] - dcdt[row]) / h;
+                assert!((jac.get(row, col) - fd).abs() < 1e-5, "({row}, {col})");
+            }
+        }
+    }
+
+    #[test]
+    fn remodel_rebinds_reactions_and_flows() {
+        // organism -> {cell, sister}; cell exports A, which decays only in cells.
+        let mut t = Topology::new();
+        let root = t.add_root("organism").unwrap();
+        t.add_child(root, "cell").unwrap();
+        t.add_child(root, "sister").unwrap();
+        let mut decay = Reaction::new("decay", vec![(0, 1.0)], vec![], 0.5);
+        decay.compartments = Some(vec![1]);
+        let shuttle = GeneralFlow::new(
+            "shuttle",
+            1,
+            vec![flow::Move {
+                from: 1,
+                to: 2,
+                molecules: vec![(0, 1.0)],
+            }],
+            1.0,
+        );
+        let model = WorldModel::new(Arc::new(t), vec![decay], 1)
+            .unwrap()
+            .with_flows(vec![MembraneFlow::new("export", 1, vec![(0, -1.0)], 1.0)])
+            .unwrap()
+            .with_general(vec![shuttle])
+            .unwrap();
+        let mut rng = ChaCha8Rng::seed_from_u64(0);
+        let (conc, mult) = (vec![0.0, 4.0, 0.0], vec![1.0; 3]);
+
+        let op = Remodel::Divide {
+            compartment: 1,
+            split: Split::Fixed(0.5),
+            suffix: "_2".into(),
+        };
+        let divided = op
+            .apply(model.topology(), 1, &conc, &mult, model.volumes(), &mut rng)
+            .unwrap();
+        let rebound = model.remodel(&divided).unwrap();
+        assert_eq!(rebound.sites(), &[vec![1, 3]]);
+        assert_eq!(
+            rebound.flows().iter().map(|f| f.origin).collect::<Vec<_>>(),
+            vec![1, 3]
+        );
+        assert_eq!(rebound.general().len(), 1);
+        assert_eq!(rebound.volumes(), &[1.0, 0.5, 1.0, 0.5]);
+
+        let died = Remodel::Die { compartment: 1 }
+            .apply(model.topology(), 1, &conc, &mult, model.volumes(), &mut rng)
+            .unwrap();
+        let rebound = model.remodel(&died).unwrap();
+        assert!(rebound.sites()[0].is_empty() && rebound.flows().is_empty());
+        assert!(rebound.general().is_empty());
+        assert_eq!(died.concentrations, vec![4.0, 0.0]);
+    }
+
+    #[test]
+    fn invalid_ids_rejected() {
+        let reactions = vec![Reaction::new("r1", vec![(5, 1.0)], vec![], 0.5)];
+        assert!(WorldModel::new(two_compartments(), reactions, 2).is_err());
+    }
+}
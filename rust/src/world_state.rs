@@ -0,0 +1,437 @@
+//! WorldState: concentration storage for multi-compartment simulations.
+//!
+//! Native counterpart of `alienbio.bio.world_state.WorldStateImpl`. The
+//! concentrations live in one flat row-major `f64` buffer indexed by
+//! `[compartment * num_molecules + molecule]`, and are exported to Python
+//! through the buffer protocol so `as_array()` is a zero-copy NumPy view.
+
+use std::ffi::CString;
+use std::os::raw::{c_int, c_void};
+use std::ptr;
+
+use pyo3::exceptions::{PyBufferError, PyIndexError, PyValueError};
+use pyo3::ffi;
+use pyo3::prelude::*;
+
+use crate::tree::CompartmentTree;
+
+/// Dense concentration storage for all compartments.
+///
+/// Concentrations are per-instance; `multiplicities[c]` is the number of
+/// instances of compartment `c`, so total molecules = multiplicity * concentration.
+#[pyclass(module = "alienbio_sim")]
+pub struct WorldState {
+    tree: Py<CompartmentTree>,
+    num_compartments: usize,
+    num_molecules: usize,
+    concentrations: Vec<f64>,
+    multiplicities: Vec<f64>,
+}
+
+impl WorldState {
+    /// Build a state from raw buffers (sizes must already agree).
+    pub fn from_parts(
+        tree: Py<CompartmentTree>,
+        num_compartments: usize,
+        num_molecules: usize,
+        concentrations: Vec<f64>,
+        multiplicities: Vec<f64>,
+    ) -> Self {
+        debug_assert_eq!(concentrations.len(), num_compartments * num_molecules);
+        debug_assert_eq!(multiplicities.len(), num_compartments);
+        Self {
+            tree,
+            num_compartments,
+            num_molecules,
+            concentrations,
+            multiplicities,
+        }
+    }
+
+    /// The shared tree handle (cloning it keeps `state.tree is other.tree`).
+    pub fn tree_handle(&self) -> &Py<CompartmentTree> {
+        &self.tree
+    }
+
+    pub fn compartments(&self) -> usize {
+        self.num_compartments
+    }
+
+    pub fn molecules(&self) -> usize {
+        self.num_molecules
+    }
+
+    /// Flat concentration buffer `[compartment * num_molecules + molecule]`.
+    pub fn concentrations(&self) -> &[f64] {
+        &self.concentrations
+    }
+
+    pub fn concentrations_mut(&mut self) -> &mut [f64] {
+        &mut self.concentrations
+    }
+
+    pub fn multiplicities(&self) -> &[f64] {
+        &self.multiplicities
+    }
+
+    pub fn multiplicities_mut(&mut self) -> &mut [f64] {
+        &mut self.multiplicities
+    }
+
+    /// Concentration buffer and multiplicities borrowed mutably together.
+    pub fn buffers_mut(&mut self) -> (&mut [f64], &mut [f64]) {
+        (&mut self.concentrations, &mut self.multiplicities)
+    }
+
+    /// Copy concentrations and multiplicities from another state of equal shape.
+    pub fn copy_values_from(&mut self, other: &WorldState) {
+        self.concentrations.copy_from_slice(&other.concentrations);
+        self.multiplicities.copy_from_slice(&other.multiplicities);
+    }
+
+    /// Fail if compartments were added to the shared tree after this state
+    /// was built, so the buffer no longer covers it.
+    fn check_tree(&self, py: Python<'_>) -> PyResult<()> {
+        let size = self.tree.borrow(py).topology().num_compartments();
+        if size != self.num_compartments {
+            return Err(PyValueError::new_err(format!(
+                "Tree has {size} compartments but this state holds {}; \
+                 build a new WorldState after changing the tree",
+                self.num_compartments
+            )));
+        }
+        Ok(())
+    }
+
+    fn index(&self, py: Python<'_>, compartment: usize, molecule: usize) -> PyResult<usize> {
+        self.check_compartment(py, compartment)?;
+        if molecule >= self.num_molecules {
+            return Err(PyIndexError::new_err(format!(
+                "molecule {molecule} out of range (num_molecules={})",
+                self.num_molecules
+            )));
+        }
+        Ok(compartment * self.num_molecules + molecule)
+    }
+
+    fn check_compartment(&self, py: Python<'_>, compartment: usize) -> PyResult<()> {
+        self.check_tree(py)?;
+        if compartment >= self.num_compartments {
+            return Err(PyIndexError::new_err(format!(
+                "compartment {compartment} out of range (num_compartments={})",
+                self.num_compartments
+            )));
+        }
+        Ok(())
+    }
+}
+
+#[pymethods]
+impl WorldState {
+    /// Initialize world state.
+    ///
+    /// Args:
+    ///     tree: CompartmentTree defining the topology (shared reference); a
+    ///         Python CompartmentTreeImpl is converted via its to_dict()
+    ///     num_molecules: Number of molecules in vocabulary
+    ///     initial_concentrations: Optional flat array of initial concentrations
+    ///     initial_multiplicities: Optional array of initial multiplicities per compartment
+    #[new]
+    #[pyo3(signature = (tree, num_molecules, initial_concentrations=None, initial_multiplicities=None))]
+    fn new(
+        py: Python<'_>,
+        tree: &PyAny,
+        num_molecules: usize,
+        initial_concentrations: Option<Vec<f64>>,
+        initial_multiplicities: Option<Vec<f64>>,
+    ) -> PyResult<Self> {
+        let tree = CompartmentTree::coerce(py, tree)?;
+        let num_compartments = tree.borrow(py).topology().num_compartments();
+        let size = num_compartments * num_molecules;
+
+        let concentrations = match initial_concentrations {
+            Some(values) if values.len() != size => {
+                return Err(PyValueError::new_err(format!(
+                "Initial concentrations size {} != {num_compartments} * {num_molecules} = {size}",
+                values.len()
+            )))
+            }
+            Some(values) => values,
+            None => vec![0.0; size],
+        };
+
+        let multiplicities = match initial_multiplicities {
+            Some(values) if values.len() != num_compartments => {
+                return Err(PyValueError::new_err(format!(
+                    "Initial multiplicities size {} != num_compartments {num_compartments}",
+                    values.len()
+                )))
+            }
+            Some(values) => values,
+            None => vec![1.0; num_compartments],
+        };
+
+        Ok(Self::from_parts(
+            tree,
+            num_compartments,
+            num_molecules,
+            concentrations,
+            multiplicities,
+        ))
+    }
+
+    /// The compartment tree this state belongs to (shared reference).
+    #[getter]
+    fn tree(&self, py: Python<'_>) -> Py<CompartmentTree> {
+        self.tree.clone_ref(py)
+    }
+
+    /// Number of compartments (from tree).
+    #[getter]
+    fn num_compartments(&self) -> usize {
+        self.num_compartments
+    }
+
+    /// Number of molecules in vocabulary.
+    #[getter]
+    fn num_molecules(&self) -> usize {
+        self.num_molecules
+    }
+
+    /// Get concentration of molecule in compartment.
+    fn get(&self, py: Python<'_>, compartment: usize, molecule: usize) -> PyResult<f64> {
+        Ok(self.concentrations[self.index(py, compartment, molecule)?])
+    }
+
+    /// Set concentration of molecule in compartment.
+    fn set(
+        &mut self,
+        py: Python<'_>,
+        compartment: usize,
+        molecule: usize,
+        value: f64,
+    ) -> PyResult<()> {
+        let i = self.index(py, compartment, molecule)?;
+        self.concentrations[i] = value;
+        Ok(())
+    }
+
+    /// Get all concentrations for a compartment.
+    fn get_compartment(&self, py: Python<'_>, compartment: usize) -> PyResult<Vec<f64>> {
+        self.check_compartment(py, compartment)?;
+        let start = compartment * self.num_molecules;
+        Ok(self.concentrations[start..start + self.num_molecules].to_vec())
+    }
+
+    /// Set all concentrations for a compartment.
+    fn set_compartment(
+        &mut self,
+        py: Python<'_>,
+        compartment: usize,
+        values: Vec<f64>,
+    ) -> PyResult<()> {
+        self.check_compartment(py, compartment)?;
+        if values.len() != self.num_molecules {
+            return Err(PyValueError::new_err(format!(
+                "Values length {} != num_molecules {}",
+                values.len(),
+                self.num_molecules
+            )));
+        }
+        let start = compartment * self.num_molecules;
+        self.concentrations[start..start + self.num_molecules].copy_from_slice(&values);
+        Ok(())
+    }
+
+    // ── Multiplicity methods ──────────────────────────────────────────────────
+
+    /// Get multiplicity (instance count) for a compartment.
+    fn get_multiplicity(&self, py: Python<'_>, compartment: usize) -> PyResult<f64> {
+        self.check_compartment(py, compartment)?;
+        Ok(self.multiplicities[compartment])
+    }
+
+    /// Set multiplicity (instance count) for a compartment.
+    fn set_multiplicity(&mut self, py: Python<'_>, compartment: usize, value: f64) -> PyResult<()> {
+        self.check_compartment(py, compartment)?;
+        self.multiplicities[compartment] = value;
+        Ok(())
+    }
+
+    /// Get multiplicities for all compartments.
+    fn get_all_multiplicities(&self) -> Vec<f64> {
+        self.multiplicities.clone()
+    }
+
+    /// Get total molecules = multiplicity * concentration.
+    fn total_molecules(
+        &self,
+        py: Python<'_>,
+        compartment: usize,
+        molecule: usize,
+    ) -> PyResult<f64> {
+        let i = self.index(py, compartment, molecule)?;
+        Ok(self.multiplicities[compartment] * self.concentrations[i])
+    }
+
+    // ── Copy and array methods ────────────────────────────────────────────────
+
+    /// Create a copy of this state (shares tree reference).
+    pub fn copy(&self, py: Python<'_>) -> Self {
+        Self::from_parts(
+            self.tree.clone_ref(py),
+            self.num_compartments,
+            self.num_molecules,
+            self.concentrations.clone(),
+            self.multiplicities.clone(),
+        )
+    }
+
+    /// Get concentrations as 2D numpy array [compartments x molecules].
+    ///
+    /// The array is a writable view onto this state's buffer: no data is
+    /// copied, and writes through the array are visible to `get()`.
+    fn as_array(slf: &PyCell<Self>, py: Python<'_>) -> PyResult<PyObject> {
+        let numpy = py.import("numpy")?;
+        Ok(numpy.call_method1("asarray", (slf,))?.into())
+    }
+
+    /// Set concentrations from 2D array [compartments x molecules].
+    #[allow(clippy::wrong_self_convention)]
+    fn from_array(&mut self, py: Python<'_>, arr: &PyAny) -> PyResult<()> {
+        let numpy = py.import("numpy")?;
+        let flat = numpy
+            .call_method1("ascontiguousarray", (arr, "float64"))?
+            .call_method0("ravel")?;
+        let buffer = pyo3::buffer::PyBuffer::<f64>::get(flat)?;
+        if buffer.item_count() != self.concentrations.len() {
+            return Err(PyValueError::new_err(format!(
+                "Array size {} != expected {}",
+                buffer.item_count(),
+                self.concentrations.len()
+            )));
+        }
+        // A view from as_array() already aliases our buffer.
+        if buffer.buf_ptr() as *const f64 == self.concentrations.as_ptr() {
+            return Ok(());
+        }
+        buffer.copy_to_slice(py, &mut self.concentrations)
+    }
+
+    /// Export the concentration buffer as a C-contiguous 2D float64 array.
+    unsafe fn __getbuffer__(
+        slf: &PyCell<Self>,
+        view: *mut ffi::Py_buffer,
+        flags: c_int,
+    ) -> PyResult<()> {
+        if view.is_null() {
+            return Err(PyBufferError::new_err("View is null"));
+        }
+
+        let mut state = slf.borrow_mut();
+        let itemsize = std::mem::size_of::<f64>() as isize;
+        // shape[0..2] and strides[2..4], freed in __releasebuffer__.
+        let dims = Box::new([
+            state.num_compartments as isize,
+            state.num_molecules as isize,
+            state.num_molecules as isize * itemsize,
+            itemsize,
+        ]);
+        let dims = Box::into_raw(dims) as *mut isize;
+
+        (*view).obj = ffi::_Py_NewRef(slf.as_ptr());
+        (*view).buf = state.concentrations.as_mut_ptr() as *mut c_void;
+        (*view).len = (state.concentrations.len() as isize) * itemsize;
+        (*view).readonly = 0;
+        (*view).itemsize = itemsize;
+        (*view).format = if flags & ffi::PyBUF_FORMAT == ffi::PyBUF_FORMAT {
+            CString::new("d").unwrap().into_raw()
+        } else {
+            ptr::null_mut()
+        };
+        if flags & ffi::PyBUF_ND == ffi::PyBUF_ND {
+            (*view).ndim = 2;
+            (*view).shape = dims;
+        } else {
+            (*view).ndim = 1;
+            (*view).shape = ptr::null_mut();
+        }
+        (*view).strides = if flags & ffi::PyBUF_STRIDES == ffi::PyBUF_STRIDES {
+            dims.add(2)
+        } else {
+            ptr::null_mut()
+        };
+        (*view).suboffsets = ptr::null_mut();
+        (*view).internal = dims as *mut c_void;
+        Ok(())
+    }
+
+    unsafe fn __releasebuffer__(&self, view: *mut ffi::Py_buffer) {
+        if !(*view).format.is_null() {
+            drop(CString::from_raw((*view).format));
+        }
+        if !(*view).internal.is_null() {
+            drop(Box::from_raw((*view).internal as *mut [isize; 4]));
+        }
+    }
+
+    fn __repr__(&self) -> String {
+        format!(
+            "WorldState(compartments={}, molecules={})",
+            self.num_compartments, self.num_molecules
+        )
+    }
+
+    fn __str__(&self) -> String {
+        let total: f64 = self.concentrations.iter().sum();
+        let nonzero = self.concentrations.iter().filter(|&&c| c > 0.0).count();
+        format!(
+            "WorldState({}x{}, total={}, nonzero={nonzero})",
+            self.num_compartments,
+            self.num_molecules,
+            format_general(total, 3)
+        )
+    }
+}
+
+/// Format like Python's `{:.Ng}`: N significant digits, trailing zeros trimmed.
+pub(crate) fn format_general(value: f64, digits: usize) -> String {
+    if value == 0.0 || !value.is_finite() {
+        return format!("{value}");
+    }
+    let exponent = value.abs().log10().floor() as i32;
+    if exponent < -4 || exponent >= digits as i32 {
+        let s = format!("{:.*e}", digits.saturating_sub(1), value);
+        let (mantissa, exp) = s.split_once('e').unwrap();
+        let mantissa = trim_zeros(mantissa);
+        let exp: i32 = exp.parse().unwrap();
+        let sign = if exp < 0 { '-' } else { '+' };
+        format!("{mantissa}e{sign}{:02}", exp.abs())
+    } else {
+        let decimals = (digits as i32 - 1 - exponent).max(0) as usize;
+        trim_zeros(&format!("{value:.decimals$}")).to_string()
+    }
+}
+
+fn trim_zeros(s: &str) -> &str {
+    if s.contains('.') {
+        s.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        s
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_general;
+
+    #[test]
+    fn format_general_matches_python_g() {
+        assert_eq!(format_general(0.0, 3), "0");
+        assert_eq!(format_general(1.0, 3), "1");
+        assert_eq!(format_general(123.456, 3), "123");
+        assert_eq!(format_general(1234.5, 3), "1.23e+03");
+        assert_eq!(format_general(0.000123456, 3), "0.000123");
+        assert_eq!(format_general(0.0000123, 3), "1.23e-05");
+    }
+}
@@ -20,7 +20,9 @@ Implementations:
 - Flow hierarchy:
   - Flow: abstract base class for all flows
   - MembraneFlow: transport across parent-child membrane with stoichiometry
-  - GeneralFlow: arbitrary state modifications (placeholder, needs interpreter)
+  - DiffusionFlow: gradient-driven transport across every membrane
+  - InstanceFlow: instances moving between compartments with their contents
+  - GeneralFlow: moves between arbitrary compartments, or arbitrary edits
 - ChemistryImpl: container for atoms, molecules, and reactions
 - CompartmentImpl: biological compartment with flows, concentrations, reactions
 - CompartmentTreeImpl: hierarchical compartment topology (simulation)
@@ -28,6 +30,10 @@ Implementations:
 - StateImpl: single-compartment concentrations
 - ReferenceSimulatorImpl: basic single-compartment simulator
 - WorldSimulatorImpl: multi-compartment simulator with flows
+- RustSimulatorImpl: native simulator from alienbio_sim (None if not built)
+- RustRk45SimulatorImpl, RustRosenbrockSimulatorImpl: native adaptive simulators
+- RustStochasticSimulatorImpl, RustNextReactionSimulatorImpl, RustTauLeapSimulatorImpl:
+  native seeded stochastic simulators
 """
 
 # Protocols (for type hints) - from central protocols module
@@ -53,7 +59,7 @@ from .molecule import MoleculeImpl
 
 # Implementation classes - reactions and flows
 from .reaction import ReactionImpl
-from .flow import Flow, MembraneFlow, GeneralFlow
+from .flow import Flow, MembraneFlow, DiffusionFlow, InstanceFlow, GeneralFlow
 
 # Implementation classes - containers and compartments
 from .chemistry import ChemistryImpl
@@ -67,6 +73,14 @@ from .state import StateImpl
 # Implementation classes - simulation
 from .simulator import ReferenceSimulatorImpl, SimulatorBase
 from .world_simulator import WorldSimulatorImpl, ReactionSpec
+from .rust_simulator import (
+    RustNextReactionSimulatorImpl,
+    RustRk45SimulatorImpl,
+    RustRosenbrockSimulatorImpl,
+    RustSimulatorImpl,
+    RustStochasticSimulatorImpl,
+    RustTauLeapSimulatorImpl,
+)
 
 __all__ = [
     # Type aliases
@@ -87,6 +101,8 @@ __all__ = [
     "MoleculeImpl",
     "ReactionImpl",
     "MembraneFlow",
+    "DiffusionFlow",
+    "InstanceFlow",
     "GeneralFlow",
     "ChemistryImpl",
     "CompartmentImpl",
@@ -95,6 +111,12 @@ __all__ = [
     "StateImpl",
     "ReferenceSimulatorImpl",
     "WorldSimulatorImpl",
+    "RustSimulatorImpl",
+    "RustRk45SimulatorImpl",
+    "RustRosenbrockSimulatorImpl",
+    "RustStochasticSimulatorImpl",
+    "RustNextReactionSimulatorImpl",
+    "RustTauLeapSimulatorImpl",
     "ReactionSpec",
     # Abstract base for subclassing
     "SimulatorBase",
@@ -3,13 +3,16 @@
 Flow hierarchy:
 - Flow (abstract base): common interface for all flows
 - MembraneFlow: transport across parent-child boundary with stoichiometry
-- GeneralFlow: arbitrary state modifications (placeholder, needs general interpreter)
+- DiffusionFlow: passive transport down the gradient across every membrane
+- InstanceFlow: whole instances moving between compartments with their contents
+- GeneralFlow: declarative moves between arbitrary compartments, or arbitrary edits
 """
 
 from __future__ import annotations
 
+import math
 from abc import ABC, abstractmethod
-from typing import Any, Callable, Dict, Optional, TYPE_CHECKING
+from typing import Any, Callable, Dict, List, Optional, TYPE_CHECKING
 
 if TYPE_CHECKING:
     from .world_state import WorldStateImpl
@@ -27,7 +30,9 @@ class Flow(ABC):
 
     Subclasses:
     - MembraneFlow: transport across parent-child membrane with stoichiometry
-    - GeneralFlow: arbitrary state modifications (placeholder)
+    - DiffusionFlow: gradient-driven transport across every membrane
+    - InstanceFlow: instances moving between compartments with their contents
+    - GeneralFlow: moves between arbitrary compartments, or arbitrary edits
 
     Common interface:
     - origin: the compartment where this flow is anchored
@@ -74,6 +79,16 @@ class Flow(ABC):
         """True if this is a general flow (arbitrary edits)."""
         ...
 
+    @property
+    def is_diffusion_flow(self) -> bool:
+        """True if this is a diffusion flow (every membrane, down the gradient)."""
+        return False
+
+    @property
+    def is_instance_flow(self) -> bool:
+        """True if this flow moves instances (multiplicity) between compartments."""
+        return False
+
     @abstractmethod
     def compute_flux(
         self,
@@ -112,6 +127,44 @@ class Flow(ABC):
         """Semantic content for serialization."""
         ...
 
+    @staticmethod
+    def from_dict(data: Dict[str, Any]) -> Flow:
+        """Rebuild a flow from its attributes() dict (e.g. loaded from YAML)."""
+        kind = data.get("type")
+        if kind == "membrane":
+            return MembraneFlow(
+                origin=data["origin"],
+                stoichiometry=data["stoichiometry"],
+                rate_constant=data.get("rate_constant", 1.0),
+                name=data.get("name", ""),
+                rate=data.get("rate"),
+            )
+        if kind == "diffusion":
+            return DiffusionFlow(
+                permeability=data["permeability"],
+                areas=data.get("areas"),
+                name=data.get("name", ""),
+            )
+        if kind == "instance":
+            return InstanceFlow(
+                origin=data["origin"],
+                target=data["target"],
+                rate_constant=data.get("rate_constant", 1.0),
+                rate=data.get("rate"),
+                name=data.get("name", ""),
+            )
+        if kind == "general":
+            return GeneralFlow(
+                origin=data["origin"],
+                name=data.get("name", ""),
+                description=data.get("description", ""),
+                body=data.get("body", ""),
+                moves=data.get("moves"),
+                rate_constant=data.get("rate_constant", 1.0),
+                rate=data.get("rate"),
+            )
+        raise ValueError(f"Unknown flow type: {kind!r}")
+
 
 class MembraneFlow(Flow):
     """Transport across parent-child membrane with stoichiometry.
@@ -121,7 +174,10 @@ class MembraneFlow(Flow):
     molecules moving together.
 
     The rate equation determines how many "events" occur per unit time.
-    Each event moves the specified stoichiometry of molecules.
+    Each event moves the specified stoichiometry of molecules. The rate is
+    rate_constant, scaled by an optional serializable rate law (an Expr over
+    the origin's molecules and the parent's as `parent_<name>`), or given by
+    a custom rate_fn.
 
     Direction convention:
     - Positive stoichiometry = molecules move INTO the origin (from parent)
@@ -147,7 +203,7 @@ class MembraneFlow(Flow):
         )
     """
 
-    __slots__ = ("_stoichiometry", "_rate_constant", "_rate_fn")
+    __slots__ = ("_stoichiometry", "_rate_constant", "_rate_fn", "_rate")
 
     def __init__(
         self,
@@ -156,6 +212,7 @@ class MembraneFlow(Flow):
         rate_constant: float = 1.0,
         rate_fn: Optional[Callable[..., float]] = None,
         name: str = "",
+        rate: Any = None,
     ) -> None:
         """Initialize a membrane flow.
 
@@ -166,6 +223,8 @@ class MembraneFlow(Flow):
             rate_constant: Base rate of events per unit time
             rate_fn: Optional custom rate function
             name: Human-readable name for this flow
+            rate: Optional rate law (Expr, Expr string or dict) scaling
+                  rate_constant; evaluated natively by alienbio_sim
         """
         if not name:
             molecules = "_".join(stoichiometry.keys())
@@ -175,6 +234,7 @@ class MembraneFlow(Flow):
         self._stoichiometry = stoichiometry.copy()
         self._rate_constant = rate_constant
         self._rate_fn = rate_fn
+        self._rate = rate
 
     @property
     def stoichiometry(self) -> Dict[str, float]:
@@ -186,6 +246,11 @@ class MembraneFlow(Flow):
         """Base rate of events per unit time."""
         return self._rate_constant
 
+    @property
+    def rate(self) -> Any:
+        """Rate law scaling rate_constant, or None."""
+        return self._rate
+
     @property
     def is_membrane_flow(self) -> bool:
         """True - this is a membrane flow."""
@@ -230,32 +295,51 @@ class MembraneFlow(Flow):
         tree: CompartmentTreeImpl,
         dt: float = 1.0,
     ) -> None:
-        """Apply this flow to the state (mutates in place).
+        """Apply one explicit Euler step across the origin's membrane.
 
-        Computes event rate, then applies stoichiometry to both
-        origin and parent compartments.
+        Computes the event rate, then moves the stoichiometry between the
+        origin and its parent, scaled down uniformly if either side would go
+        negative. The parent's change is scaled by the origin/parent
+        multiplicity ratio. This reference implementation assumes unit
+        volumes and needs molecule IDs as stoichiometry keys;
+        alienbio_sim.WorldSimulator resolves names, evaluates `rate` laws and
+        takes per-compartment `volumes`.
 
         Args:
             state: World state to modify
             tree: Compartment topology
             dt: Time step
         """
+        if any(not isinstance(m, int) for m in self._stoichiometry):
+            raise NotImplementedError(
+                f"MembraneFlow {self._name!r}: molecule names are resolved by alienbio_sim.WorldSimulator"
+            )
+        if self._rate is not None and self._rate_fn is None:
+            raise NotImplementedError(
+                f"MembraneFlow {self._name!r}: rate laws are evaluated by alienbio_sim.WorldSimulator"
+            )
         parent = tree.parent(self._origin)
         if parent is None:
             return
+        m_origin, m_parent = state.get_multiplicity(self._origin), state.get_multiplicity(parent)
+        if m_origin <= 0.0 or m_parent <= 0.0:
+            return
+        to_origin, to_parent = 1.0, m_origin / m_parent
 
-        event_rate = self.compute_flux(state, tree) * dt
-
-        # Apply stoichiometry
         # Positive stoich = into origin (from parent)
         # Negative stoich = out of origin (into parent)
-        for molecule_name, count in self._stoichiometry.items():
-            # TODO: Need molecule name -> ID mapping from chemistry
-            # For now, this is a placeholder showing the pattern
-            # molecules_transferred = event_rate * count
-            # origin gains: +molecules_transferred
-            # parent loses: -molecules_transferred
-            pass
+        events = self.compute_flux(state, tree) * dt
+        scale = 1.0
+        for mol, count in self._stoichiometry.items():
+            moved = events * count
+            for comp, delta in ((self._origin, moved * to_origin), (parent, -moved * to_parent)):
+                if delta < 0.0:
+                    scale = min(scale, max(state.get(comp, mol), 0.0) / -delta)
+        events *= scale
+        for mol, count in self._stoichiometry.items():
+            moved = events * count
+            state.set(self._origin, mol, state.get(self._origin, mol) + moved * to_origin)
+            state.set(parent, mol, state.get(parent, mol) - moved * to_parent)
 
     def attributes(self) -> Dict[str, Any]:
         """Semantic content for serialization."""
@@ -266,6 +350,8 @@ class MembraneFlow(Flow):
             "stoichiometry": self._stoichiometry.copy(),
             "rate_constant": self._rate_constant,
         }
+        if self._rate is not None:
+            result["rate"] = self._rate if isinstance(self._rate, (str, dict)) else str(self._rate)
         # Note: rate_fn cannot be serialized
         return result
 
@@ -279,42 +365,364 @@ class MembraneFlow(Flow):
         return f"MembraneFlow({self._name})"
 
 
+class DiffusionFlow(Flow):
+    """Passive diffusion down the concentration gradient across every membrane.
+
+    For each parent-child edge and each permeable molecule, the flux into one
+    child instance is `permeability × area × (parent_conc - child_conc)`, an
+    amount per unit time. The child's concentration changes by the amount
+    over its volume, the parent's by the amount times the child/parent
+    multiplicity ratio over the parent's volume (see MembraneFlow). A step
+    never moves more than the amount that equalizes both sides.
+
+    The flow is anchored at the root and covers the whole tree; `areas`
+    scales individual membranes (default 1.0, 0 seals a membrane).
+
+    Example:
+        # Oxygen and CO2 cross every membrane; the gut wall is twice as large
+        gas = DiffusionFlow(
+            permeability={"oxygen": 2.0, "co2": 1.5},
+            areas={"gut": 2.0},
+            name="gas_exchange",
+        )
+    """
+
+    __slots__ = ("_permeability", "_areas")
+
+    def __init__(
+        self,
+        permeability: Dict[Any, float],
+        areas: Optional[Dict[Any, float]] = None,
+        name: str = "",
+    ) -> None:
+        """Initialize a diffusion flow.
+
+        Args:
+            permeability: Permeability per molecule {molecule: value}, keyed
+                by name (resolved by alienbio_sim) or molecule ID
+            areas: Optional membrane area per compartment {compartment: area},
+                keyed by compartment ID or name; missing compartments use 1.0
+            name: Human-readable name for this flow
+        """
+        if not name:
+            name = "diffusion_" + "_".join(str(m) for m in permeability)
+        super().__init__(0, name)
+        self._permeability = dict(permeability)
+        self._areas = dict(areas) if areas is not None else None
+
+    @property
+    def permeability(self) -> Dict[Any, float]:
+        """Permeability per molecule."""
+        return self._permeability.copy()
+
+    @property
+    def areas(self) -> Optional[Dict[Any, float]]:
+        """Membrane area per compartment, or None for 1.0 everywhere."""
+        return None if self._areas is None else self._areas.copy()
+
+    @property
+    def is_membrane_flow(self) -> bool:
+        """False - not a single-membrane stoichiometric flow."""
+        return False
+
+    @property
+    def is_general_flow(self) -> bool:
+        """False - this is not a general flow."""
+        return False
+
+    @property
+    def is_diffusion_flow(self) -> bool:
+        """True - this is a diffusion flow."""
+        return True
+
+    def area(self, tree: CompartmentTreeImpl, compartment: CompartmentId) -> float:
+        """Membrane area of `compartment` (its membrane to the parent)."""
+        if self._areas is None:
+            return 1.0
+        if compartment in self._areas:
+            return self._areas[compartment]
+        return self._areas.get(tree.name(compartment), 1.0)
+
+    def compute_flux(
+        self,
+        state: WorldStateImpl,
+        tree: CompartmentTreeImpl,
+    ) -> float:
+        """Diffusion has one flux per membrane and molecule, not a single rate.
+
+        Returns 0.0; the work happens in apply().
+        """
+        return 0.0
+
+    def apply(
+        self,
+        state: WorldStateImpl,
+        tree: CompartmentTreeImpl,
+        dt: float = 1.0,
+    ) -> None:
+        """Apply one explicit Euler step, membrane by membrane in child order.
+
+        This reference implementation assumes unit volumes and needs molecule
+        IDs as permeability keys; alienbio_sim.WorldSimulator resolves names
+        and takes per-compartment `volumes`.
+
+        Args:
+            state: World state to modify
+            tree: Compartment topology
+            dt: Time step
+        """
+        if any(not isinstance(m, int) for m in self._permeability):
+            raise NotImplementedError(
+                f"DiffusionFlow {self._name!r}: molecule names are resolved by alienbio_sim.WorldSimulator"
+            )
+        for child in range(tree.num_compartments):
+            parent = tree.parent(child)
+            if parent is None:
+                continue
+            m_child, m_parent = state.get_multiplicity(child), state.get_multiplicity(parent)
+            conductance = self.area(tree, child) * dt
+            if m_child <= 0.0 or m_parent <= 0.0 or conductance == 0.0:
+                continue
+            to_child, to_parent = 1.0, m_child / m_parent
+            cap = 1.0 / (to_child + to_parent)
+            for mol, permeability in self._permeability.items():
+                c_child, c_parent = state.get(child, mol), state.get(parent, mol)
+                moved = (c_parent - c_child) * min(permeability * conductance, cap)
+                state.set(child, mol, c_child + moved * to_child)
+                state.set(parent, mol, c_parent - moved * to_parent)
+
+    def attributes(self) -> Dict[str, Any]:
+        """Semantic content for serialization."""
+        result: Dict[str, Any] = {
+            "type": "diffusion",
+            "name": self._name,
+            "permeability": self._permeability.copy(),
+        }
+        if self._areas is not None:
+            result["areas"] = self._areas.copy()
+        return result
+
+    def __repr__(self) -> str:
+        """Full representation."""
+        perm_str = ", ".join(f"{m}:{p}" for m, p in self._permeability.items())
+        return f"DiffusionFlow(permeability={{{perm_str}}})"
+
+    def __str__(self) -> str:
+        """Short representation."""
+        return f"DiffusionFlow({self._name})"
+
+
+class InstanceFlow(Flow):
+    """Migration of whole instances from `origin` to `target`.
+
+    Instances carry their per-instance concentrations with them: the origin's
+    concentrations are unchanged, and the target's become the amount-weighted
+    mix of resident and arriving instances. Each origin instance leaves at
+    `rate_constant` per unit time, scaled by an optional rate law that reads
+    `<compartment>.<molecule>` and the origin's bare molecule names (as for
+    GeneralFlow). A step of `dt` moves the fraction `1 - exp(-rate * dt)` of
+    the origin's instances, so it never moves more than are there.
+
+    Example:
+        # Red blood cells circulate from arteries to veins, faster under hypoxia
+        circulation = InstanceFlow(
+            origin="arterial_rbc",
+            target="venous_rbc",
+            rate_constant=0.5,
+            rate="div(1, add(0.1, blood.oxygen))",
+            name="circulation",
+        )
+    """
+
+    __slots__ = ("_target", "_rate_constant", "_rate")
+
+    def __init__(
+        self,
+        origin: Any,
+        target: Any,
+        rate_constant: float = 1.0,
+        rate: Any = None,
+        name: str = "",
+    ) -> None:
+        """Initialize an instance flow.
+
+        Args:
+            origin: Compartment the instances leave (ID or name)
+            target: Compartment the instances join (ID or name)
+            rate_constant: Departure rate per origin instance
+            rate: Optional rate law (Expr, Expr string or dict) scaling
+                  rate_constant; evaluated natively by alienbio_sim
+            name: Human-readable name for this flow
+        """
+        if not name:
+            name = f"instances_{origin}_to_{target}"
+        super().__init__(origin, name)
+        self._target = target
+        self._rate_constant = rate_constant
+        self._rate = rate
+
+    @property
+    def target(self) -> Any:
+        """Compartment the instances join."""
+        return self._target
+
+    @property
+    def rate_constant(self) -> float:
+        """Departure rate per origin instance."""
+        return self._rate_constant
+
+    @property
+    def rate(self) -> Any:
+        """Rate law scaling rate_constant, or None."""
+        return self._rate
+
+    @property
+    def is_membrane_flow(self) -> bool:
+        """False - instances move, not molecules across a membrane."""
+        return False
+
+    @property
+    def is_general_flow(self) -> bool:
+        """False - this is not a general flow."""
+        return False
+
+    @property
+    def is_instance_flow(self) -> bool:
+        """True - this flow moves instances."""
+        return True
+
+    def compute_flux(
+        self,
+        state: WorldStateImpl,
+        tree: CompartmentTreeImpl,
+    ) -> float:
+        """Instances leaving the origin per unit time (without a rate law)."""
+        return self._rate_constant * state.get_multiplicity(self._origin)
+
+    def apply(
+        self,
+        state: WorldStateImpl,
+        tree: CompartmentTreeImpl,
+        dt: float = 1.0,
+    ) -> None:
+        """Move instances for one step and mix their contents into the target.
+
+        This reference implementation assumes unit volumes and needs
+        compartment IDs and no rate law; alienbio_sim.WorldSimulator resolves
+        names, compiles `rate` and takes per-compartment `volumes`.
+
+        Args:
+            state: World state to modify
+            tree: Compartment topology
+            dt: Time step
+        """
+        if self._rate is not None or not all(
+            isinstance(c, int) for c in (self._origin, self._target)
+        ):
+            raise NotImplementedError(
+                f"InstanceFlow {self._name!r}: rate laws and compartment names are resolved by alienbio_sim.WorldSimulator"
+            )
+        available = state.get_multiplicity(self._origin)
+        if available <= 0.0 or self._rate_constant <= 0.0:
+            return
+        moved = available * -math.expm1(-self._rate_constant * dt)
+        resident = state.get_multiplicity(self._target)
+        after = resident + moved
+        for mol in range(state.num_molecules):
+            mixed = (
+                state.get(self._target, mol) * resident + state.get(self._origin, mol) * moved
+            ) / after
+            state.set(self._target, mol, mixed)
+        state.set_multiplicity(self._origin, available - moved)
+        state.set_multiplicity(self._target, after)
+
+    def attributes(self) -> Dict[str, Any]:
+        """Semantic content for serialization."""
+        result: Dict[str, Any] = {
+            "type": "instance",
+            "name": self._name,
+            "origin": self._origin,
+            "target": self._target,
+            "rate_constant": self._rate_constant,
+        }
+        if self._rate is not None:
+            result["rate"] = self._rate if isinstance(self._rate, (str, dict)) else str(self._rate)
+        return result
+
+    def __repr__(self) -> str:
+        """Full representation."""
+        return f"InstanceFlow(origin={self._origin}, target={self._target}, rate={self._rate_constant})"
+
+    def __str__(self) -> str:
+        """Short representation."""
+        return f"InstanceFlow({self._name})"
+
+
 class GeneralFlow(Flow):
-    """Arbitrary state modifications (placeholder).
+    """Transfers that don't fit the MembraneFlow pattern.
 
     GeneralFlow is a catch-all for flows that don't fit the MembraneFlow pattern.
     This includes:
     - Lateral flows between siblings
-    - Instance transfers (RBCs moving between compartments)
+    - Transfers between arbitrary compartments across the tree
+    - Multi-edge moves (several legs per event)
     - Any other arbitrary edits to the system
 
-    NOTE: This is currently a placeholder. Full implementation will require
-    a more general interpreter to handle arbitrary state modifications
-    specified via Expr or similar.
+    The serializable form is declarative: `moves` lists the legs of one event,
+    each moving molecule amounts from one compartment to another, and events
+    occur at `rate_constant` scaled by an optional `rate` Expr. The rate reads
+    `<compartment>.<molecule>` for any compartment and bare molecule names for
+    the origin. Events count the whole population, so an amount `a` changes a
+    compartment's per-instance concentration by `a / (volume * multiplicity)`.
+    Declarative flows run in the Rust WorldSimulator.
 
-    For now, GeneralFlow stores an apply_fn that takes state and tree
-    and performs arbitrary modifications.
+    Alternatively, a serializable `rhai:` body runs in the Rust WorldSimulator
+    against the origin compartment's concentrations (see
+    alienbio_sim.enable_rhai), or an apply_fn takes state and tree and performs
+    arbitrary modifications (not serializable).
+
+    Example:
+        # Lateral exchange: 2 glucose from liver to kidney per event, and the
+        # kidney returns 1 urea to the blood
+        shuttle = GeneralFlow(
+            origin="liver",
+            name="shuttle",
+            moves=[
+                {"from": "liver", "to": "kidney", "molecules": {"glucose": 2}},
+                {"from": "kidney", "to": "blood", "molecules": {"urea": 1}},
+            ],
+            rate_constant=0.5,
+            rate="div(glucose, add(1, kidney.glucose))",
+        )
     """
 
-    __slots__ = ("_apply_fn", "_description")
+    __slots__ = ("_apply_fn", "_description", "_body", "_moves", "_rate_constant", "_rate")
 
     def __init__(
         self,
-        origin: CompartmentId,
+        origin: Any,
         apply_fn: Optional[Callable[[WorldStateImpl, CompartmentTreeImpl, float], None]] = None,
         name: str = "",
         description: str = "",
+        body: str = "",
+        moves: Optional[List[Dict[str, Any]]] = None,
+        rate_constant: float = 1.0,
+        rate: Any = None,
     ) -> None:
         """Initialize a general flow.
 
         Args:
             origin: The compartment where this flow is conceptually anchored
+                (ID, or name for declarative flows)
             apply_fn: Function (state, tree, dt) -> None that modifies state
             name: Human-readable name for this flow
             description: Description of what this flow does
-
-        NOTE: This is a placeholder. Full implementation will need a more
-        general interpreter to support Expr-based specifications.
+            body: Optional "rhai:..." script run natively by the Rust
+                WorldSimulator; molecule variables it assigns are written back
+            moves: Legs of one event, each {"from": compartment, "to":
+                compartment, "molecules": {molecule: amount}}
+            rate_constant: Events per unit time (scaled by rate)
+            rate: Optional rate law (Expr, Expr string or dict)
         """
         if not name:
             name = f"general_flow_at_{origin}"
@@ -322,12 +730,36 @@ class GeneralFlow(Flow):
 
         self._apply_fn = apply_fn
         self._description = description
+        self._body = body
+        self._moves = [dict(m, molecules=dict(m["molecules"])) for m in moves or []]
+        self._rate_constant = rate_constant
+        self._rate = rate
 
     @property
     def description(self) -> str:
         """Description of what this flow does."""
         return self._description
 
+    @property
+    def body(self) -> str:
+        """Script body ("rhai:..."), or empty if the flow uses apply_fn."""
+        return self._body
+
+    @property
+    def moves(self) -> List[Dict[str, Any]]:
+        """Legs of one event: {"from", "to", "molecules"} dicts."""
+        return [dict(m, molecules=dict(m["molecules"])) for m in self._moves]
+
+    @property
+    def rate_constant(self) -> float:
+        """Events per unit time (declarative flows)."""
+        return self._rate_constant
+
+    @property
+    def rate(self) -> Any:
+        """Rate law scaling rate_constant, or None."""
+        return self._rate
+
     @property
     def is_membrane_flow(self) -> bool:
         """False - this is not a membrane flow."""
@@ -364,19 +796,30 @@ class GeneralFlow(Flow):
         """
         if self._apply_fn is not None:
             self._apply_fn(state, tree, dt)
+        elif self._body or self._moves:
+            raise NotImplementedError(
+                f"GeneralFlow {self._name!r}: script bodies and moves run in the Rust WorldSimulator"
+            )
 
     def attributes(self) -> Dict[str, Any]:
         """Semantic content for serialization.
 
-        NOTE: apply_fn cannot be serialized. Full implementation will
-        need Expr-based specification that can be serialized.
+        NOTE: apply_fn cannot be serialized; moves and rhai: bodies can.
         """
-        return {
+        result: Dict[str, Any] = {
             "type": "general",
             "name": self._name,
             "origin": self._origin,
             "description": self._description,
         }
+        if self._body:
+            result["body"] = self._body
+        if self._moves:
+            result["moves"] = self.moves
+            result["rate_constant"] = self._rate_constant
+            if self._rate is not None:
+                result["rate"] = self._rate if isinstance(self._rate, (str, dict)) else str(self._rate)
+        return result
 
     def __repr__(self) -> str:
         """Full representation."""
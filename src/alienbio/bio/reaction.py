@@ -25,6 +25,8 @@ class ReactionImpl(Entity, head="Reaction"):
     - reactants: molecules consumed (with stoichiometric coefficients)
     - products: molecules produced (with stoichiometric coefficients)
     - rate: constant or function determining reaction speed
+    - effectors: molecules that appear in the rate but are neither consumed
+      nor produced (with kinetic orders), e.g. catalysts and inhibitors
 
     Example:
         # A + 2B -> C with rate 0.1
@@ -37,7 +39,7 @@ class ReactionImpl(Entity, head="Reaction"):
         )
     """
 
-    __slots__ = ("_reactants", "_products", "_rate")
+    __slots__ = ("_reactants", "_products", "_rate", "_effectors")
 
     def __init__(
         self,
@@ -46,6 +48,7 @@ class ReactionImpl(Entity, head="Reaction"):
         reactants: Optional[Dict[Molecule, float]] = None,
         products: Optional[Dict[Molecule, float]] = None,
         rate: RateValue = 1.0,
+        effectors: Optional[Dict[Molecule, float]] = None,
         parent: Optional[Entity] = None,
         dat: Optional[Dat] = None,
         description: str = "",
@@ -57,6 +60,7 @@ class ReactionImpl(Entity, head="Reaction"):
             reactants: Dict mapping molecules to stoichiometric coefficients
             products: Dict mapping molecules to stoichiometric coefficients
             rate: Reaction rate (constant float or function of State)
+            effectors: Dict mapping rate-modifying molecules to kinetic orders
             parent: Link to containing entity
             dat: DAT anchor for root reactions
             description: Human-readable description
@@ -65,6 +69,7 @@ class ReactionImpl(Entity, head="Reaction"):
         self._reactants: Dict[Molecule, float] = reactants.copy() if reactants else {}
         self._products: Dict[Molecule, float] = products.copy() if products else {}
         self._rate: RateValue = rate
+        self._effectors: Dict[Molecule, float] = effectors.copy() if effectors else {}
 
     @classmethod
     def hydrate(
@@ -79,7 +84,8 @@ class ReactionImpl(Entity, head="Reaction"):
         """Create a Reaction from a dict.
 
         Args:
-            data: Dict with keys: reactants, products, rate, name, description
+            data: Dict with keys: reactants, products, effectors, rate, name,
+                description
             molecules: Dict mapping molecule names to MoleculeImpl instances
             dat: DAT anchor (if root entity)
             parent: Parent entity (if child)
@@ -122,6 +128,17 @@ class ReactionImpl(Entity, head="Reaction"):
                     if mol_name in molecules:
                         products[molecules[mol_name]] = coef
 
+        # Build effectors dict: {MoleculeImpl: order}
+        effectors: Dict[Molecule, float] = {}
+        for e in data.get("effectors", []):
+            if isinstance(e, str):
+                if e in molecules:
+                    effectors[molecules[e]] = 1
+            elif isinstance(e, dict):
+                for mol_name, order in e.items():
+                    if mol_name in molecules:
+                        effectors[molecules[mol_name]] = order
+
         # Get rate (function or constant)
         rate = data.get("rate", 1.0)
 
@@ -130,6 +147,7 @@ class ReactionImpl(Entity, head="Reaction"):
             reactants=reactants,
             products=products,
             rate=rate,
+            effectors=effectors,
             parent=parent,
             dat=dat,
             description=data.get("description", ""),
@@ -145,6 +163,11 @@ class ReactionImpl(Entity, head="Reaction"):
         """Product molecules and their stoichiometric coefficients."""
         return self._products.copy()
 
+    @property
+    def effectors(self) -> Dict[Molecule, float]:
+        """Rate-modifying molecules (neither consumed nor produced) and their orders."""
+        return self._effectors.copy()
+
     @property
     def rate(self) -> RateValue:
         """Reaction rate (constant or function)."""
@@ -193,6 +216,10 @@ class ReactionImpl(Entity, head="Reaction"):
         """Add a product to this reaction."""
         self._products[molecule] = coefficient
 
+    def add_effector(self, molecule: Molecule, order: float = 1.0) -> None:
+        """Add an effector (catalyst, activator or inhibitor) to this reaction."""
+        self._effectors[molecule] = order
+
     def attributes(self) -> Dict[str, Any]:
         """Semantic content of this reaction."""
         result = super().attributes()
@@ -206,6 +233,10 @@ class ReactionImpl(Entity, head="Reaction"):
             result["products"] = {
                 mol.local_name: coef for mol, coef in self._products.items()
             }
+        if self._effectors:
+            result["effectors"] = {
+                mol.local_name: order for mol, order in self._effectors.items()
+            }
 
         # Only serialize rate if it's a constant
         if not callable(self._rate):
@@ -0,0 +1,145 @@
+"""RustSimulator: registers the compiled alienbio_sim engine as a Simulator factory.
+
+The `alienbio_sim` extension (built from `rust/`) provides `ChemistrySimulator`,
+a native single-compartment simulator with the same `chemistry`, `dt`, `step`
+and `run` contract as `ReferenceSimulatorImpl`. When the extension is
+installed it is registered under these names, selectable with
+`sim.simulator: <name>` or via `bio._simulator_factory`:
+
+- `rust`: explicit Euler, same arithmetic as `ReferenceSimulatorImpl`
+- `rust_rk45`: adaptive Dormand–Prince on the same kinetics
+- `rust_rosenbrock`: implicit Rosenbrock with an analytic sparse Jacobian,
+  for stiff chemistries
+
+The adaptive simulators integrate the kinetics `rust` steps, with a constant
+`rate` as a zero-order flux, and sample the timeline at exact multiples of
+`dt`; reactions with callable rates are rejected.
+
+The stochastic simulators wrap `alienbio_sim.StochasticSimulator` on a single
+compartment of `volume` (default 1.0), so concentrations are rounded to
+`concentration × volume` whole molecules (callable rates are rejected here
+too):
+
+- `stochastic`: Gillespie direct method
+- `stochastic_next_reaction`: Gibson–Bruck next-reaction method
+- `stochastic_tau_leap`: adaptive tau-leaping
+
+They are `seeded`: `Bio.run` passes the scenario seed (`SimulationResult.seed`),
+so a scenario replays the same trajectory.
+
+If the extension is not built, the `Rust*SimulatorImpl` names are None and
+nothing is registered.
+"""
+
+from __future__ import annotations
+
+from typing import Any, List, Optional
+
+from alienbio.spec_lang.decorators import factory
+from alienbio.protocols.bio import Simulator
+
+RustSimulatorImpl: Optional[Any]
+RustRk45SimulatorImpl: Optional[Any]
+RustRosenbrockSimulatorImpl: Optional[Any]
+RustStochasticSimulatorImpl: Optional[Any]
+RustNextReactionSimulatorImpl: Optional[Any]
+RustTauLeapSimulatorImpl: Optional[Any]
+
+try:
+    from alienbio_sim import ChemistrySimulator as RustSimulatorImpl
+except ImportError:  # extension not built
+    RustSimulatorImpl = None
+    RustRk45SimulatorImpl = None
+    RustRosenbrockSimulatorImpl = None
+    RustStochasticSimulatorImpl = None
+    RustNextReactionSimulatorImpl = None
+    RustTauLeapSimulatorImpl = None
+else:
+    factory(name="rust", protocol=Simulator)(RustSimulatorImpl)
+
+    @factory(name="rust_rk45", protocol=Simulator)
+    class RustRk45SimulatorImpl(RustSimulatorImpl):  # type: ignore[no-redef]
+        """Native simulator using the adaptive RK45 integrator."""
+
+        def __new__(cls, chemistry: Any, dt: float = 1.0, **options: Any) -> Any:
+            return super().__new__(cls, chemistry, dt=dt, method="rk45", **options)
+
+    @factory(name="rust_rosenbrock", protocol=Simulator)
+    class RustRosenbrockSimulatorImpl(RustSimulatorImpl):  # type: ignore[no-redef]
+        """Native simulator using the implicit Rosenbrock integrator."""
+
+        def __new__(cls, chemistry: Any, dt: float = 1.0, **options: Any) -> Any:
+            return super().__new__(cls, chemistry, dt=dt, method="rosenbrock", **options)
+
+    from alienbio_sim import CompartmentTree, StochasticSimulator, WorldState
+
+    from alienbio.bio.simulator import SimulatorBase
+
+    @factory(name="stochastic", protocol=Simulator)
+    class RustStochasticSimulatorImpl(SimulatorBase):  # type: ignore[no-redef]
+        """Native stochastic simulator over a single well-mixed compartment."""
+
+        __slots__ = ("_engine", "_names", "_tree")
+
+        seeded = True
+        method = "direct"
+
+        def __init__(
+            self,
+            chemistry: Any,
+            dt: float = 1.0,
+            seed: int = 0,
+            volume: float = 1.0,
+            epsilon: Optional[float] = None,
+        ) -> None:
+            super().__init__(chemistry, dt)
+            self._tree = CompartmentTree()
+            self._tree.add_root("scenario")
+            self._names = list(chemistry.molecules)
+            self._engine = StochasticSimulator.from_chemistry(
+                chemistry,
+                self._tree,
+                dt=dt,
+                seed=seed,
+                volumes=[volume],
+                method=self.method,
+                epsilon=epsilon,
+            )
+
+        @property
+        def seed(self) -> int:
+            """Seed the random stream was started from."""
+            return self._engine.seed
+
+        def _to_world(self, state: Any) -> Any:
+            world = WorldState(self._tree, len(self._names))
+            for i, name in enumerate(self._names):
+                world.set(0, i, state.get(name))
+            return world
+
+        def _from_world(self, world: Any, like: Any) -> Any:
+            result = like.copy()
+            for i, name in enumerate(self._names):
+                result[name] = world.get(0, i)
+            return result
+
+        def step(self, state: Any) -> Any:
+            return self._from_world(self._engine.step(self._to_world(state)), state)
+
+        def run(self, state: Any, steps: int) -> List[Any]:
+            history = self._engine.run(self._to_world(state), steps)
+            return [self._from_world(world, state) for world in history]
+
+    @factory(name="stochastic_next_reaction", protocol=Simulator)
+    class RustNextReactionSimulatorImpl(RustStochasticSimulatorImpl):  # type: ignore[no-redef]
+        """Native stochastic simulator using the Gibson–Bruck next-reaction method."""
+
+        __slots__ = ()
+        method = "next_reaction"
+
+    @factory(name="stochastic_tau_leap", protocol=Simulator)
+    class RustTauLeapSimulatorImpl(RustStochasticSimulatorImpl):  # type: ignore[no-redef]
+        """Native stochastic simulator using adaptive tau-leaping."""
+
+        __slots__ = ()
+        method = "tau_leap"
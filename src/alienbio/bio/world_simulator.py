@@ -30,9 +30,13 @@ class ReactionSpec:
         products: Dict[MoleculeId, stoichiometry]
         rate_constant: Base reaction rate
         compartments: Which compartments this reaction occurs in (None = all)
+        effectors: Dict[MoleculeId, order] of species that scale the rate
+            but are neither consumed nor produced (None = no effectors)
     """
 
-    __slots__ = ("name", "reactants", "products", "rate_constant", "compartments")
+    __slots__ = (
+        "name", "reactants", "products", "rate_constant", "compartments", "effectors",
+    )
 
     def __init__(
         self,
@@ -41,12 +45,14 @@ class ReactionSpec:
         products: Dict[MoleculeId, float],
         rate_constant: float = 1.0,
         compartments: Optional[List[CompartmentId]] = None,
+        effectors: Optional[Dict[MoleculeId, float]] = None,
     ) -> None:
         self.name = name
         self.reactants = reactants
         self.products = products
         self.rate_constant = rate_constant
         self.compartments = compartments  # None means all compartments
+        self.effectors = effectors or {}
 
 
 class WorldSimulatorImpl:
@@ -179,6 +185,8 @@ class WorldSimulatorImpl:
         for mol_id, stoich in reaction.reactants.items():
             conc = state.get(compartment, mol_id)
             rate *= conc ** stoich
+        for mol_id, order in reaction.effectors.items():
+            rate *= state.get(compartment, mol_id) ** order
 
         rate *= self._dt
 
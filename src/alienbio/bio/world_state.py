@@ -21,7 +21,8 @@ class WorldStateImpl:
 
     Each WorldState holds a reference to its CompartmentTree. Multiple states
     share the same tree reference (immutable sharing) until topology changes.
-    When topology changes (e.g., cell division), a new tree is created.
+    When topology changes (e.g., cell division), a new tree is created; the
+    native alienbio_sim.WorldSimulator does this with divide/kill/fuse.
 
     Attributes:
         tree: The CompartmentTree this state belongs to (shared reference)
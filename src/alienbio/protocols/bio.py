@@ -152,7 +152,7 @@ class Flow(Protocol):
     Flow hierarchy:
     - Flow (base): common interface for all flows
     - MembraneFlow: transport across parent-child membrane with stoichiometry
-    - GeneralFlow: arbitrary state modifications (placeholder)
+    - GeneralFlow: moves between arbitrary compartments, or arbitrary edits
 
     Each flow is anchored to an origin compartment.
     """
@@ -220,13 +220,11 @@ class MembraneFlow(Flow, Protocol):
 
 @runtime_checkable
 class GeneralFlow(Flow, Protocol):
-    """Protocol for general flows (placeholder).
+    """Protocol for general flows.
 
     GeneralFlow is a catch-all for flows that don't fit the MembraneFlow pattern.
-    This includes lateral flows, instance transfers, and arbitrary state edits.
-
-    NOTE: This is a placeholder. Full implementation will require a more
-    general interpreter to handle arbitrary state modifications.
+    This includes lateral flows, cross-tree transfers, and arbitrary state edits.
+    The serializable form is a list of `moves` at an Expr-defined rate.
     """
 
     @property
@@ -234,6 +232,11 @@ class GeneralFlow(Flow, Protocol):
         """Description of what this flow does."""
         ...
 
+    @property
+    def moves(self) -> List[Dict[str, Any]]:
+        """Legs of one event: {"from", "to", "molecules"} dicts."""
+        ...
+
 
 # ═══════════════════════════════════════════════════════════════════════════════
 # Containers: Chemistry and CompartmentTree
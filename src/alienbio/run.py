@@ -133,8 +133,9 @@ def _run_scenario(scenario: Any, dat: "Dat") -> dict[str, Any]:
     initial_state_dict = scenario.get("initial_state", {})
     state = StateImpl(chemistry, initial=initial_state_dict)
 
-    # Create simulator via Bio pegboard
-    sim = bio._simulator_factory(chemistry, dt=dt)
+    # Create simulator via Bio pegboard (or the scenario's sim.simulator: name)
+    seed = scenario.get("_seed") if isinstance(scenario, dict) else getattr(scenario, "_seed", None)
+    sim = bio._make_simulator(sim_config, chemistry, dt, seed=seed)
 
     # Run simulation
     timeline_states = sim.run(state, steps=steps)
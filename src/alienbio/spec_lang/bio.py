@@ -648,7 +648,7 @@ class Bio:
         state = StateImpl(chemistry, initial=initial_concentrations)
 
         # Create simulator and run
-        sim = self._simulator_factory(chemistry, dt=effective_dt)  # type: ignore[call-arg]
+        sim = self._make_simulator(sim_config, chemistry, effective_dt, seed=scenario_seed)
         timeline = sim.run(state, steps=effective_steps)  # type: ignore[arg-type]
 
         return SimulationResult(
@@ -660,6 +660,34 @@ class Bio:
             scenario_name=scenario_name,
         )
 
+    def _simulator_for(self, sim_config: Any) -> Any:
+        """Simulator class for a scenario's `sim:` section.
+
+        A `simulator:` name (e.g. "reference", "rust") is resolved through the
+        Simulator factory registry; otherwise the pegboard default is used.
+        """
+        name = sim_config.get("simulator") if isinstance(sim_config, dict) else None
+        if not name:
+            return self._simulator_factory
+
+        import alienbio.bio  # noqa: F401  (registers the simulator factories)
+        from alienbio.protocols.bio import Simulator
+
+        return _resolve_factory(Simulator, name)
+
+    def _make_simulator(
+        self, sim_config: Any, chemistry: Any, dt: float, seed: Any = None
+    ) -> Any:
+        """Instantiate the scenario's simulator.
+
+        Simulators marked `seeded` (the stochastic ones) also receive the
+        scenario seed, so the run is reproducible from `SimulationResult.seed`.
+        """
+        simulator_class = self._simulator_for(sim_config)
+        if seed is not None and getattr(simulator_class, "seeded", False):
+            return simulator_class(chemistry, dt=dt, seed=seed)
+        return simulator_class(chemistry, dt=dt)
+
     def _extract_initial_state(
         self,
         regions: list,
@@ -0,0 +1 @@
+"""Parity tests: Python simulators vs the alienbio_sim Rust engine."""
@@ -0,0 +1,294 @@
+"""Parity runner: run a shared YAML fixture through Python and Rust and compare.
+
+Fixtures live in `tests/fixtures/systems/` and are read by both sides:
+`alienbio_sim.Fixture` on the Rust side, and `load_fixture` here on the Python
+side. Fixtures without a `tree` run through `ReferenceSimulatorImpl`; fixtures
+with a tree run through `WorldSimulatorImpl`. Trajectories are compared per
+sample, compartment and molecule with `|rust - python| <= atol + rtol * |python|`.
+
+Example:
+    report = run_parity("tests/fixtures/systems/two_compartment.yaml")
+    assert report.ok, report.summary()
+"""
+
+from __future__ import annotations
+
+from dataclasses import dataclass, field
+from pathlib import Path
+from typing import Any, Dict, List, Optional, Tuple
+
+import yaml
+
+from alienbio.bio import (
+    ChemistryImpl,
+    CompartmentTreeImpl,
+    Flow,
+    MoleculeImpl,
+    ReactionImpl,
+    ReactionSpec,
+    ReferenceSimulatorImpl,
+    StateImpl,
+    WorldSimulatorImpl,
+    WorldStateImpl,
+)
+from alienbio.infra.entity import _MockDat
+
+FIXTURES_DIR = Path(__file__).resolve().parent.parent / "fixtures" / "systems"
+
+DEFAULT_ATOL = 1e-12
+DEFAULT_RTOL = 1e-9
+
+# Trajectory indexed as [sample][compartment][molecule]
+Trajectory = List[List[List[float]]]
+
+
+def fixture_paths() -> List[Path]:
+    """All shared system fixtures, sorted by name."""
+    return sorted(FIXTURES_DIR.glob("*.yaml"))
+
+
+def load_fixture(path: Path | str) -> Dict[str, Any]:
+    """Read a fixture file as a plain dict."""
+    with open(path) as f:
+        return yaml.safe_load(f)
+
+
+def _sim_settings(spec: Dict[str, Any]) -> Tuple[int, float, int]:
+    sim = spec.get("sim", {})
+    return sim.get("steps", 100), sim.get("dt", 1.0), sim.get("sample_every", 1)
+
+
+def _run_reference(spec: Dict[str, Any]) -> Trajectory:
+    """Single-compartment fixture through ReferenceSimulatorImpl."""
+    names = spec["molecules"]
+    molecules = {name: MoleculeImpl(name, dat=_MockDat(f"mol/{name}")) for name in names}
+    reactions = {
+        name: ReactionImpl(
+            name,
+            reactants={molecules[m]: c for m, c in rxn.get("reactants", {}).items()},
+            products={molecules[m]: c for m, c in rxn.get("products", {}).items()},
+            rate=rxn.get("rate", 1.0),
+            dat=_MockDat(f"rxn/{name}"),
+        )
+        for name, rxn in spec.get("reactions", {}).items()
+    }
+    chemistry = ChemistryImpl(
+        spec["name"],
+        molecules=molecules,
+        reactions=reactions,
+        dat=_MockDat(f"chem/{spec['name']}"),
+    )
+    steps, dt, sample_every = _sim_settings(spec)
+    timeline = ReferenceSimulatorImpl(chemistry, dt=dt).run(
+        StateImpl(chemistry, initial=spec.get("initial", {})), steps=steps
+    )
+    # Same sampling rule as WorldSimulatorImpl.run: every Nth state, plus final
+    sampled = [timeline[i] for i in range(steps) if i % sample_every == 0]
+    sampled.append(timeline[-1])
+    return [[[state[name] for name in names]] for state in sampled]
+
+
+def _run_world(spec: Dict[str, Any]) -> Trajectory:
+    """Multi-compartment fixture through WorldSimulatorImpl."""
+    names = spec["molecules"]
+    mol_ids = {name: i for i, name in enumerate(names)}
+    tree = CompartmentTreeImpl.from_dict(
+        {"parents": list(spec["tree"]["parents"]), "names": list(spec["tree"]["names"])}
+    )
+    comp_ids = {tree.name(c): c for c in range(tree.num_compartments)}
+
+    reactions = []
+    for name, rxn in spec.get("reactions", {}).items():
+        compartments = rxn.get("compartments")
+        reactions.append(
+            ReactionSpec(
+                name,
+                {mol_ids[m]: c for m, c in rxn.get("reactants", {}).items()},
+                {mol_ids[m]: c for m, c in rxn.get("products", {}).items()},
+                rate_constant=rxn.get("rate", 1.0),
+                compartments=None if compartments is None else [comp_ids[c] for c in compartments],
+            )
+        )
+
+    state = WorldStateImpl(tree=tree, num_molecules=len(names))
+    for comp, values in spec.get("initial", {}).items():
+        for mol, value in values.items():
+            state.set(comp_ids[comp], mol_ids[mol], float(value))
+    for comp, value in spec.get("multiplicities", {}).items():
+        state.set_multiplicity(comp_ids[comp], float(value))
+
+    # Same YAML as Flow.attributes(), with names resolved to IDs.
+    flows = []
+    for flow in spec.get("flows", []):
+        data = dict(flow)
+        if flow["type"] == "diffusion":
+            data["permeability"] = {mol_ids[m]: p for m, p in flow["permeability"].items()}
+            if flow.get("areas") is not None:
+                data["areas"] = {comp_ids[c]: a for c, a in flow["areas"].items()}
+        elif flow["type"] == "membrane":
+            data["origin"] = comp_ids[flow["origin"]]
+            data["stoichiometry"] = {mol_ids[m]: c for m, c in flow["stoichiometry"].items()}
+        else:
+            raise ValueError(f"Unsupported fixture flow type: {flow['type']!r}")
+        flows.append(Flow.from_dict(data))
+
+    steps, dt, sample_every = _sim_settings(spec)
+    sim = WorldSimulatorImpl(
+        tree=tree, reactions=reactions, flows=flows, num_molecules=len(names), dt=dt
+    )
+    history = sim.run(state, steps=steps, sample_every=sample_every)
+    return [
+        [state.get_compartment(c) for c in range(tree.num_compartments)] for state in history
+    ]
+
+
+def run_python(spec: Dict[str, Any]) -> Trajectory:
+    """Run a fixture with the Python simulators."""
+    return _run_world(spec) if "tree" in spec else _run_reference(spec)
+
+
+def run_rust(path: Path | str) -> Trajectory:
+    """Run a fixture with the alienbio_sim engine."""
+    import alienbio_sim
+
+    return alienbio_sim.Fixture.load(str(path)).run()
+
+
+@dataclass
+class Divergence:
+    """Worst divergence for one (compartment, molecule) trajectory."""
+
+    compartment: str
+    molecule: str
+    max_abs: float = 0.0
+    max_rel: float = 0.0
+    worst_sample: int = 0
+    failures: int = 0
+
+
+@dataclass
+class ParityReport:
+    """Per-molecule trajectory divergence between Python and Rust."""
+
+    name: str
+    atol: float
+    rtol: float
+    num_samples: int
+    divergences: List[Divergence] = field(default_factory=list)
+    error: Optional[str] = None
+
+    @property
+    def ok(self) -> bool:
+        return self.error is None and all(d.failures == 0 for d in self.divergences)
+
+    @property
+    def worst(self) -> Optional[Divergence]:
+        if not self.divergences:
+            return None
+        return max(self.divergences, key=lambda d: d.max_abs)
+
+    def summary(self) -> str:
+        """Human-readable report, failing trajectories first."""
+        status = "OK" if self.ok else "FAIL"
+        lines = [
+            f"{self.name}: {status} ({self.num_samples} samples, "
+            f"atol={self.atol:g}, rtol={self.rtol:g})"
+        ]
+        if self.error:
+            lines.append(f"  error: {self.error}")
+        for d in sorted(self.divergences, key=lambda d: (d.failures == 0, -d.max_abs)):
+            lines.append(
+                f"  {d.compartment}/{d.molecule}: max_abs={d.max_abs:.3e} "
+                f"max_rel={d.max_rel:.3e} worst_sample={d.worst_sample} failures={d.failures}"
+            )
+        return "\n".join(lines)
+
+
+def compare(
+    name: str,
+    python: Trajectory,
+    rust: Trajectory,
+    compartments: List[str],
+    molecules: List[str],
+    atol: float = DEFAULT_ATOL,
+    rtol: float = DEFAULT_RTOL,
+) -> ParityReport:
+    """Compare two trajectories, treating Python as the expected values."""
+    report = ParityReport(name=name, atol=atol, rtol=rtol, num_samples=len(python))
+    if len(python) != len(rust):
+        report.error = f"sample count differs: python={len(python)} rust={len(rust)}"
+        return report
+    for s, (py_sample, rs_sample) in enumerate(zip(python, rust)):
+        py_shape = [len(row) for row in py_sample]
+        rs_shape = [len(row) for row in rs_sample]
+        expected_shape = [len(molecules)] * len(compartments)
+        if py_shape != expected_shape or rs_shape != expected_shape:
+            report.error = (
+                f"sample {s} shape differs: expected {len(compartments)}x{len(molecules)}, "
+                f"python={py_shape} rust={rs_shape}"
+            )
+            return report
+
+    for c, comp in enumerate(compartments):
+        for m, mol in enumerate(molecules):
+            d = Divergence(compartment=comp, molecule=mol)
+            for s, (py_sample, rs_sample) in enumerate(zip(python, rust)):
+                expected, actual = py_sample[c][m], rs_sample[c][m]
+                diff = abs(actual - expected)
+                rel = diff / abs(expected) if expected != 0.0 else (0.0 if diff == 0.0 else float("inf"))
+                # Written so a NaN difference fails and counts as the worst.
+                if not diff <= d.max_abs:
+                    d.max_abs, d.worst_sample = diff, s
+                d.max_rel = max(d.max_rel, rel)
+                if not diff <= atol + rtol * abs(expected):
+                    d.failures += 1
+            report.divergences.append(d)
+    return report
+
+
+def run_parity(
+    path: Path | str,
+    atol: Optional[float] = None,
+    rtol: Optional[float] = None,
+) -> ParityReport:
+    """Run one fixture through both engines and report divergence.
+
+    Tolerances default to the fixture's `tolerance` block, then to
+    DEFAULT_ATOL / DEFAULT_RTOL.
+    """
+    spec = load_fixture(path)
+    tolerance = spec.get("tolerance", {})
+    atol = tolerance.get("atol", DEFAULT_ATOL) if atol is None else atol
+    rtol = tolerance.get("rtol", DEFAULT_RTOL) if rtol is None else rtol
+    compartments = list(spec["tree"]["names"]) if "tree" in spec else ["root"]
+    return compare(
+        spec["name"],
+        run_python(spec),
+        run_rust(path),
+        compartments,
+        list(spec["molecules"]),
+        atol=atol,
+        rtol=rtol,
+    )
+
+
+def main(argv: Optional[List[str]] = None) -> int:
+    """Command-line entry: `python -m tests.parity.runner [fixture.yaml ...]`."""
+    import argparse
+
+    parser = argparse.ArgumentParser(description="Python/Rust simulator parity")
+    parser.add_argument("fixtures", nargs="*", type=Path, help="fixture files (default: all)")
+    parser.add_argument("--atol", type=float, default=None)
+    parser.add_argument("--rtol", type=float, default=None)
+    args = parser.parse_args(argv)
+
+    ok = True
+    for path in args.fixtures or fixture_paths():
+        report = run_parity(path, atol=args.atol, rtol=args.rtol)
+        print(report.summary())
+        ok = ok and report.ok
+    return 0 if ok else 1
+
+
+if __name__ == "__main__":
+    raise SystemExit(main())
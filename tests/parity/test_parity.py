@@ -0,0 +1,63 @@
+"""Python == Rust parity over the shared fixtures in tests/fixtures/systems/.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from .runner import compare, fixture_paths, load_fixture, run_parity
+
+
+@pytest.mark.parametrize("path", fixture_paths(), ids=lambda p: p.stem)
+def test_fixture_parity(path):
+    report = run_parity(path)
+    assert report.ok, report.summary()
+
+
+@pytest.mark.parametrize("path", fixture_paths(), ids=lambda p: p.stem)
+def test_fixture_metadata_matches(path):
+    spec = load_fixture(path)
+    fixture = alienbio_sim.Fixture.load(str(path))
+    assert fixture.name == spec["name"]
+    assert fixture.molecules == spec["molecules"]
+    if "tree" in spec:
+        assert fixture.compartments == spec["tree"]["names"]
+
+
+class TestCompare:
+    """The comparison itself, independent of either engine."""
+
+    def test_within_tolerance(self):
+        report = compare("t", [[[1.0, 2.0]]], [[[1.0 + 1e-12, 2.0]]], ["root"], ["A", "B"])
+        assert report.ok
+
+    def test_reports_worst_divergence(self):
+        python = [[[1.0, 2.0]], [[1.0, 2.0]]]
+        rust = [[[1.0, 2.0]], [[1.5, 2.0]]]
+        report = compare("t", python, rust, ["root"], ["A", "B"], atol=1e-6, rtol=0.0)
+        assert not report.ok
+        worst = report.worst
+        assert (worst.molecule, worst.worst_sample, worst.failures) == ("A", 1, 1)
+        assert worst.max_abs == pytest.approx(0.5)
+
+    def test_non_finite_values_fail(self):
+        python = [[[1.0, 2.0]], [[1.0, 2.0]]]
+        rust = [[[1.0, 2.0]], [[float("nan"), float("inf")]]]
+        report = compare("t", python, rust, ["root"], ["A", "B"], atol=1.0)
+        assert not report.ok
+        assert [d.failures for d in report.divergences] == [1, 1]
+        assert report.divergences[0].worst_sample == 1
+
+    def test_shape_mismatch(self):
+        report = compare("t", [[[1.0, 2.0]]], [[[1.0]]], ["root"], ["A", "B"])
+        assert not report.ok
+        assert "shape" in report.error
+        report = compare("t", [[[1.0]]], [[[1.0], [2.0]]], ["root"], ["A"])
+        assert "shape" in report.error
+
+    def test_sample_count_mismatch(self):
+        report = compare("t", [[[1.0]]], [[[1.0]], [[1.0]]], ["root"], ["A"])
+        assert not report.ok
+        assert "sample count" in report.summary()
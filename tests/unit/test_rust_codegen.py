@@ -0,0 +1,113 @@
+"""Tests for native code generation (alienbio_sim.to_code, chemistry_to_rust).
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import math
+import shutil
+import subprocess
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import ChemistryImpl, MoleculeImpl, ReactionImpl
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+EXPRESSIONS = [
+    "michaelis_menten(vmax=10, km=5, S=S)",
+    "competitive_inhibition(vmax=vmax, km=2, ki=0.5, S=S, I=I)",
+    "sub(S, sub(I, 1))",
+    "power(neg(S), 2)",
+    "neg(power(S, I))",
+    "min(S, mul(2, I), 3)",
+    "if(and(gt(S, 1), not(I)), exp(S), log(add(S, I)))",
+    "add(lt(S, I), or(eq(S, 2), ge(I, 1)))",
+]
+
+
+def make_chemistry():
+    mols = {name: MoleculeImpl(name, dat=MockDat(f"mol/{name}")) for name in ("S", "P", "I")}
+    reactions = {
+        "convert": ReactionImpl(
+            "convert",
+            reactants={mols["S"]: 1},
+            products={mols["P"]: 1},
+            effectors={mols["I"]: 1},
+            rate="competitive_inhibition(vmax=4, km=0.5, ki=0.2)",
+            dat=MockDat("rxn/convert"),
+        ),
+        "dimerize": ReactionImpl(
+            "dimerize", reactants={mols["P"]: 2}, products={mols["S"]: 1}, rate=0.3, dat=MockDat("rxn/dimerize")
+        ),
+    }
+    return ChemistryImpl("toy", molecules=mols, reactions=reactions, dat=MockDat("chem/toy"))
+
+
+class TestToCode:
+    """Generated snippets match the compiled rate."""
+
+    def test_interpreter_spec_examples(self):
+        mm = "michaelis_menten(vmax=10, km=5)"
+        assert alienbio_sim.to_code(mm) == "10.0 * S / (5.0 + S)"
+        assert alienbio_sim.to_code(mm, "lua") == "return 10.0 * S / (5.0 + S)"
+        assert alienbio_sim.to_code(mm, "rust") == "|s: f64| 10.0 * s / (5.0 + s)"
+        with pytest.raises(ValueError):
+            alienbio_sim.to_code(mm, "cobol")
+
+    @pytest.mark.parametrize("source", EXPRESSIONS)
+    def test_python_code_matches_compiled_rate(self, source):
+        params = {"vmax": 3.0}
+        rate = alienbio_sim.CompiledRate(source, ["S", "I"], params)
+        code = alienbio_sim.to_code(source, "python", molecules=["S", "I"], params=params)
+        assert rate.to_code() == code
+        for s, i in [(0.5, 0.0), (2.0, 0.0), (2.0, 1.5), (3.0, 0.25)]:
+            assert eval(code, {"math": math, "S": s, "I": i}) == pytest.approx(rate([s, i]))
+
+    def test_molecules_inferred_in_order(self):
+        assert alienbio_sim.to_code("mul(k, B, A)", "rust", params={"k": 2}) == "|b: f64, a: f64| 2.0 * b * a"
+
+
+class TestChemistryToRust:
+    """A chemistry exports as a self-contained Rust module."""
+
+    def test_module_functions(self):
+        module = alienbio_sim.chemistry_to_rust(make_chemistry())
+        assert module.startswith('//! Generated by alienbio_sim from "toy"')
+        assert 'pub const MOLECULES: [&str; NUM_MOLECULES] = ["S", "P", "I"];' in module
+        assert "r[1] = 0.3 * c[1].powi(2); // dimerize" in module
+        for fn in ("pub fn rates(", "pub fn rhs(", "pub fn jacobian("):
+            assert fn in module
+
+    def test_callable_rates_rejected(self):
+        chem = make_chemistry()
+        chem.reactions["dimerize"].set_rate(lambda state: 1.0)
+        with pytest.raises(ValueError):
+            alienbio_sim.chemistry_to_rust(chem)
+
+    @pytest.mark.skipif(shutil.which("rustc") is None, reason="rustc not available")
+    def test_module_compiles_and_matches_simulator(self, tmp_path):
+        chem = make_chemistry()
+        (tmp_path / "toy.rs").write_text(alienbio_sim.chemistry_to_rust(chem))
+        (tmp_path / "main.rs").write_text(
+            "#![allow(dead_code)]\nmod toy;\n"
+            "fn main() {\n"
+            "    let mut d = [0.0; toy::NUM_MOLECULES];\n"
+            "    toy::rhs(&[1.2, 0.7, 0.3], &mut d);\n"
+            '    println!("{} {} {}", d[0], d[1], d[2]);\n'
+            "}\n"
+        )
+        subprocess.run(["rustc", "--edition", "2021", "main.rs", "-o", "main"], cwd=tmp_path, check=True)
+        output = subprocess.run([str(tmp_path / "main")], capture_output=True, text=True, check=True).stdout
+        dcdt = [float(x) for x in output.split()]
+
+        r0 = 4 * 1.2 / (0.5 * (1 + 0.3 / 0.2) + 1.2)
+        r1 = 0.3 * 0.7**2
+        assert dcdt == pytest.approx([-r0 + r1, r0 - 2 * r1, 0.0])
@@ -0,0 +1,59 @@
+"""Tests for the native alienbio_sim.CompartmentTree.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio.compartment_tree import CompartmentTreeImpl
+
+
+def build(tree):
+    organism = tree.add_root("organism")
+    organ_a = tree.add_child(organism, "organ_a")
+    tree.add_child(organism, "organ_b")
+    tree.add_child(organ_a)
+    return tree
+
+
+class TestRustCompartmentTree:
+    """CompartmentTree mirrors CompartmentTreeImpl."""
+
+    def test_topology_queries_match_python(self):
+        rs = build(alienbio_sim.CompartmentTree())
+        py = build(CompartmentTreeImpl())
+        assert rs.num_compartments == py.num_compartments
+        for c in range(py.num_compartments):
+            assert rs.parent(c) == py.parent(c)
+            assert rs.children(c) == py.children(c)
+            assert rs.ancestors(c) == py.ancestors(c)
+            assert rs.descendants(c) == py.descendants(c)
+            assert rs.depth(c) == py.depth(c)
+            assert rs.name(c) == py.name(c)
+
+    def test_to_dict_matches_python_layout(self):
+        rs = build(alienbio_sim.CompartmentTree())
+        py = build(CompartmentTreeImpl())
+        assert rs.to_dict() == py.to_dict()
+
+    def test_from_dict_round_trip(self):
+        py = build(CompartmentTreeImpl())
+        rs = alienbio_sim.CompartmentTree.from_dict(py.to_dict())
+        assert CompartmentTreeImpl.from_dict(rs.to_dict()).to_dict() == py.to_dict()
+
+    def test_second_root_rejected(self):
+        tree = build(alienbio_sim.CompartmentTree())
+        with pytest.raises(ValueError):
+            tree.add_root("again")
+
+    def test_edges(self):
+        tree = build(alienbio_sim.CompartmentTree())
+        assert tree.edges() == [(0, 1), (0, 2), (1, 3)]
+
+    def test_states_share_tree(self):
+        tree = build(alienbio_sim.CompartmentTree())
+        state = alienbio_sim.WorldState(tree, num_molecules=2)
+        assert state.tree is tree
+        assert state.copy().tree is tree
@@ -0,0 +1,198 @@
+"""Tests for the native alienbio_sim.Expr parser and compiled rate laws.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import math
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import ChemistryImpl, CompartmentTreeImpl, MoleculeImpl, ReactionImpl, StateImpl
+
+Expr = alienbio_sim.Expr
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_chemistry(rate):
+    """S -> P with the given rate."""
+    s = MoleculeImpl("S", dat=MockDat("mol/S"))
+    p = MoleculeImpl("P", dat=MockDat("mol/P"))
+    r = ReactionImpl("convert", reactants={s: 1}, products={p: 1}, rate=rate, dat=MockDat("rxn/convert"))
+    return ChemistryImpl("test", molecules={"S": s, "P": p}, reactions={"convert": r}, dat=MockDat("chem/test"))
+
+
+class TestExpr:
+    """Parsing, printing and the structured dict form."""
+
+    def test_parse_and_print(self):
+        expr = Expr.parse("div(var(S), add(var(S), 0.5))")
+        assert expr.head == "div"
+        assert expr.args[0] == Expr("var", "S")
+        assert expr.print() == str(expr) == "div(var(S), add(var(S), 0.5))"
+        assert Expr.parse("constant") == Expr("constant")
+        assert Expr("react", "A", "B", rate=1.2).print() == "react(A, B, rate=1.2)"
+        # Numbers are stored as floats natively.
+        assert repr(Expr("add", 1, 2)) == "Expr('add', args=(1.0, 2.0), kwargs={})"
+
+    def test_structured_form_round_trips(self):
+        expr = Expr.parse("michaelis_menten(vmax=10.0, km=5.0)")
+        data = expr.to_dict()
+        assert data == {"head": "michaelis_menten", "kwargs": {"vmax": 10.0, "km": 5.0}}
+        assert Expr.from_dict(data) == expr
+        nested = {"head": "mul", "args": [2, {"head": "var", "args": ["S"]}]}
+        assert Expr.from_dict(nested) == Expr.parse("mul(2, var(S))")
+
+    @pytest.mark.parametrize("bad", ["f(", "f(a b)", "1 + 2", "f(k=1, 2)"])
+    def test_invalid_strings_rejected(self, bad):
+        with pytest.raises(ValueError):
+            Expr.parse(bad)
+
+    def test_deep_nesting_rejected(self):
+        with pytest.raises(ValueError, match="deep"):
+            Expr.parse("neg(" * 100_000 + "S" + ")" * 100_000)
+        deep = "S"
+        for _ in range(1_000):
+            deep = {"head": "neg", "args": [deep]}
+        with pytest.raises(ValueError, match="deep"):
+            Expr.from_dict(deep)
+        with pytest.raises(ValueError, match="deep"):
+            alienbio_sim.CompiledRate(deep, ["S"])
+        expr = Expr("var", "S")
+        with pytest.raises(ValueError, match="deep"):
+            for _ in range(1_000):
+                expr = Expr("neg", expr)
+        # Nesting within the limit still compiles.
+        rate = alienbio_sim.CompiledRate("neg(" * 200 + "S" + ")" * 200, ["S"])
+        assert rate([3.0]) == 3.0
+        # Structured calls around a parsed string share one budget.
+        mixed = Expr.parse("neg(" * 200 + "S" + ")" * 200)
+        for _ in range(100):
+            mixed = {"head": "neg", "args": [mixed]}
+        with pytest.raises(ValueError, match="deep"):
+            alienbio_sim.CompiledRate(mixed, ["S"])
+        with pytest.raises(ValueError, match="deep"):
+            alienbio_sim.CompiledRate("neg(" * 257 + "S" + ")" * 257, ["S"])
+
+
+class TestCompiledRate:
+    """Expr rate laws compiled to native closures."""
+
+    def test_michaelis_menten(self):
+        rate = Expr.parse("div(mul(vmax, S), add(km, S))").compile(["S", "P"], {"vmax": 10.0, "km": 5.0})
+        assert rate.variables == ["S"]
+        assert rate([5.0, 0.0]) == 5.0
+        assert rate([15.0, 0.0]) == 7.5
+
+    def test_hill_from_string_and_dict(self):
+        hill = "div(power(S, 2), add(power(K, 2), power(S, 2)))"
+        from_string = alienbio_sim.CompiledRate(hill, ["S"], {"K": 2.0})
+        from_dict = alienbio_sim.CompiledRate(Expr.parse(hill).to_dict(), ["S"], {"K": 2.0})
+        assert from_string([2.0]) == from_dict([2.0]) == 0.5
+
+    def test_unknown_names_rejected(self):
+        with pytest.raises(KeyError):
+            alienbio_sim.CompiledRate("mul(k, X)", ["S"])
+        with pytest.raises(ValueError):
+            alienbio_sim.CompiledRate("hill(S, n=2)", ["S"])
+
+
+class TestExprRatesInSimulators:
+    """Reactions with Expr rates run natively in the Rust engines."""
+
+    def test_chemistry_simulator_euler_step(self):
+        chem = make_chemistry("div(mul(10, S), add(5, S))")
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=0.1)
+        state = sim.step(StateImpl(chem, initial={"S": 5.0, "P": 0.0}))
+        assert state["S"] == pytest.approx(5.0 - 0.5)
+        assert state["P"] == pytest.approx(0.5)
+
+    @pytest.mark.parametrize("method", ["rk45", "rosenbrock"])
+    def test_first_order_limit_matches_analytic(self, method):
+        # Far below km, v = vmax * s / km is first order with k = 0.5.
+        chem = make_chemistry({"head": "div", "args": [{"head": "mul", "args": [1.0, "S"]}, 2.0]})
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=1.0, method=method, atol=1e-10, rtol=1e-10)
+        final = sim.run(StateImpl(chem, initial={"S": 1.0, "P": 0.0}), steps=2)[-1]
+        assert final["S"] == pytest.approx(math.exp(-1.0), rel=1e-6)
+        assert final["S"] + final["P"] == pytest.approx(1.0)
+
+    def test_world_simulator_from_chemistry(self):
+        chem = make_chemistry(Expr.parse("div(mul(10, S), add(5, S))"))
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("cell")
+        sim = alienbio_sim.WorldSimulator.from_chemistry(chem, tree, dt=0.1, method="rk45")
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 1000.0)
+        # Saturated: S falls at close to vmax.
+        final = sim.step(state)
+        assert 1000.0 - final.get(root, 0) == pytest.approx(10.0 * 0.1, rel=1e-2)
+
+
+class TestBytecode:
+    """Compiled rates run as bytecode across every compartment of a state."""
+
+    def test_eval_state_matches_scalar_calls(self):
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("organism")
+        cells = [tree.add_child(root, f"cell{i}") for i in range(4)]
+        state = alienbio_sim.WorldState(tree, 2)
+        for i, cell in enumerate(cells):
+            state.set(cell, 0, float(i))
+            state.set(cell, 1, 0.5 * i)
+        rate = alienbio_sim.CompiledRate("if(gt(S, I), div(mul(10, S), add(5, S, I)), 0)", ["S", "I"])
+        expected = [rate(state.get_compartment(c)) for c in range(5)]
+        assert rate.eval_state(state) == pytest.approx(expected)
+        assert rate.eval_state(state, compartments=[cells[3], root]) == pytest.approx([expected[cells[3]], expected[root]])
+        with pytest.raises(IndexError):
+            rate.eval_state(state, compartments=[9])
+
+    def test_folding_and_shared_subexpressions(self):
+        rate = alienbio_sim.CompiledRate("div(mul(power(k, 2), S), add(S, mul(k, k)))", ["S"], {"k": 2.0})
+        # S is loaded once and k² is folded to a single constant shared by both sides.
+        assert rate.bytecode.count("load") == 1
+        assert rate.bytecode.count("= 4.0") == 1
+        assert rate([4.0]) == 2.0
+
+
+def central(f, x, i, h=1e-6):
+    up, down = list(x), list(x)
+    up[i] += h
+    down[i] -= h
+    return (f(up) - f(down)) / (2 * h)
+
+
+class TestDerivatives:
+    """Symbolic derivatives agree with finite differences."""
+
+    def test_gradients_match_finite_differences(self):
+        params = {"vmax": 10.0, "km": 5.0, "ki": 0.5}
+        source = "competitive_inhibition(vmax=vmax, km=km, ki=ki, S=S, I=I)"
+        rate = alienbio_sim.CompiledRate(source, ["S", "I"], params)
+        conc = [3.0, 0.8]
+        gradient = rate.gradient(conc)
+        assert set(gradient) == {"S", "I"}
+        for i, name in enumerate(["S", "I"]):
+            assert gradient[name] == pytest.approx(central(rate, conc, i), rel=1e-6)
+
+        def with_params(values):
+            return alienbio_sim.CompiledRate(source, ["S", "I"], dict(zip(params, values)))(conc)
+
+        values = list(params.values())
+        param_gradient = rate.param_gradient(conc)
+        for i, name in enumerate(params):
+            assert param_gradient[name] == pytest.approx(central(with_params, values, i), rel=1e-6)
+
+    def test_derivative_is_a_compiled_rate(self):
+        rate = alienbio_sim.CompiledRate("mul(k, power(S, 3))", ["S"], {"k": 2.0})
+        d2 = rate.derivative("S").derivative("S")
+        assert d2([2.0]) == pytest.approx(2.0 * 6 * 2.0)
+        assert rate.derivative("k")([2.0]) == 8.0
+        with pytest.raises(KeyError):
+            rate.derivative("X")
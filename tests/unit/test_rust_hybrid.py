@@ -0,0 +1,110 @@
+"""Tests for the native alienbio_sim.HybridSimulator (ODE/SSA partitioning).
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import ChemistryImpl, CompartmentTreeImpl, MoleculeImpl, ReactionImpl, ReactionSpec
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_world():
+    """Bulk metabolism A -> B with a rare promoter switch P -> P* driven by B."""
+    tree = CompartmentTreeImpl()
+    root = tree.add_root("cell")
+    reactions = [
+        ReactionSpec("bulk", {0: 1}, {1: 1}, rate_constant=0.5),
+        ReactionSpec("switch", {2: 1, 1: 1}, {3: 1, 1: 1}, rate_constant=1e-4),
+    ]
+    state = alienbio_sim.WorldState(tree, 4)
+    state.set(root, 0, 5000.0)
+    state.set(root, 2, 20.0)
+    return tree, root, reactions, state
+
+
+class TestHybridSimulator:
+    """Fast reactions integrated, slow reactions fired as events."""
+
+    def test_partitions(self):
+        tree, root, reactions, state = make_world()
+        dynamic = alienbio_sim.HybridSimulator(tree, reactions, 4)
+        assert dynamic.partition == "dynamic"
+        # B starts at zero, so even the bulk reaction is slow at first.
+        assert dynamic.fast_reactions(state) == []
+        state.set(root, 1, 500.0)
+        assert dynamic.fast_reactions(state) == [(0, root)]
+
+        threshold = alienbio_sim.HybridSimulator(tree, reactions, 4, threshold=[100, 0, 1e9, 1e9])
+        assert threshold.partition == "threshold"
+        assert threshold.fast_reactions(state) == [(0, root)]
+        everything = alienbio_sim.HybridSimulator(tree, reactions, 4, threshold=0.0)
+        assert everything.fast_reactions(state) == [(0, root), (1, root)]
+
+    def test_run_is_seeded_and_conserving(self):
+        tree, root, reactions, state = make_world()
+
+        def history(seed):
+            sim = alienbio_sim.HybridSimulator(tree, reactions, 4, dt=0.5, seed=seed)
+            return sim.run(state, steps=10)
+
+        runs = history(12)
+        assert len(runs) == 11
+        assert [s.get_compartment(root) for s in runs] == [s.get_compartment(root) for s in history(12)]
+        final = runs[-1]
+        assert final.get(root, 0) + final.get(root, 1) == pytest.approx(5000.0, rel=1e-6)
+        assert final.get(root, 2) + final.get(root, 3) == pytest.approx(20.0)
+        # The promoter count stays a whole number while the bulk is continuous.
+        assert final.get(root, 3) == round(final.get(root, 3))
+        assert final.get(root, 3) > 0
+
+    def test_all_fast_matches_ode(self):
+        tree, root, reactions, state = make_world()
+        hybrid = alienbio_sim.HybridSimulator(tree, reactions, 4, dt=0.5, threshold=0.0, atol=1e-9, rtol=1e-9)
+        ode = alienbio_sim.WorldSimulator(tree, reactions, [], 4, dt=0.5, method="rk45", atol=1e-9, rtol=1e-9)
+        a = hybrid.run(state, steps=6)[-1]
+        b = ode.run(state, steps=6)[-1]
+        for mol in range(4):
+            assert a.get(root, mol) == pytest.approx(b.get(root, mol), rel=1e-6, abs=1e-6)
+
+    def test_from_chemistry_keeps_constant_rates_zero_order(self):
+        s = MoleculeImpl("S", dat=MockDat("mol/S"))
+        p = MoleculeImpl("P", dat=MockDat("mol/P"))
+        r = ReactionImpl("r", reactants={s: 1}, products={p: 1}, rate=0.1, dat=MockDat("rxn/r"))
+        chem = ChemistryImpl("flux", molecules={"S": s, "P": p}, reactions={"r": r}, dat=MockDat("chem/flux"))
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("cell")
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 10.0)
+        sim = alienbio_sim.HybridSimulator.from_chemistry(chem, tree, dt=0.01, threshold=0.0)
+        final = sim.run(state, steps=100)[-1]
+        assert final.get(root, 0) == pytest.approx(9.9, rel=1e-5)
+
+    def test_from_chemistry_rejects_callable_rates(self):
+        s = MoleculeImpl("S", dat=MockDat("mol/S"))
+        p = MoleculeImpl("P", dat=MockDat("mol/P"))
+        r = ReactionImpl("r", reactants={s: 1}, products={p: 1}, rate=lambda state: 0.0, dat=MockDat("rxn/r"))
+        chem = ChemistryImpl("flux", molecules={"S": s, "P": p}, reactions={"r": r}, dat=MockDat("chem/flux"))
+        tree = CompartmentTreeImpl()
+        tree.add_root("cell")
+        with pytest.raises(ValueError, match="callable rate"):
+            alienbio_sim.HybridSimulator.from_chemistry(chem, tree)
+
+    def test_invalid_configuration_rejected(self):
+        tree, _, reactions, _ = make_world()
+        with pytest.raises(ValueError):
+            alienbio_sim.HybridSimulator(tree, reactions, 4, threshold=[1.0, 2.0])
+        with pytest.raises(ValueError):
+            alienbio_sim.HybridSimulator(tree, reactions, 4, min_events=-1.0)
+        with pytest.raises(ValueError):
+            alienbio_sim.HybridSimulator(tree, reactions, 4, dt=0.0)
+        with pytest.raises(ValueError):
+            alienbio_sim.HybridSimulator(tree, [ReactionSpec("half", {0: 0.5}, {1: 1})], 4)
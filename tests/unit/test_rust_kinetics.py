@@ -0,0 +1,105 @@
+"""Tests for the native kinetic template registry (alienbio_sim.kinetic_templates).
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import ChemistryImpl, MoleculeImpl, ReactionImpl, StateImpl
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_chemistry(rate, effectors=()):
+    """S -> P, with an inhibitor molecule I that takes no part in the reaction."""
+    mols = {name: MoleculeImpl(name, dat=MockDat(f"mol/{name}")) for name in ("S", "P", "I")}
+    r = ReactionImpl(
+        "convert",
+        reactants={mols["S"]: 1},
+        products={mols["P"]: 1},
+        effectors={mols[name]: 1 for name in effectors},
+        rate=rate,
+        dat=MockDat("rxn/convert"),
+    )
+    return ChemistryImpl("test", molecules=mols, reactions={"convert": r}, dat=MockDat("chem/test"))
+
+
+class TestRegistry:
+    """Templates are listed with their parameters and roles."""
+
+    def test_builtins(self):
+        templates = alienbio_sim.kinetic_templates()
+        assert set(templates) >= {
+            "michaelis_menten",
+            "hill_activation",
+            "hill_repression",
+            "competitive_inhibition",
+            "uncompetitive_inhibition",
+            "noncompetitive_inhibition",
+            "reversible_mass_action",
+            "substrate_inhibition",
+        }
+        hill = templates["hill_activation"]
+        assert hill.params == {"vmax": None, "k": None, "n": 1.0}
+        assert hill.roles == {"A": "effector 0"}
+        assert templates["competitive_inhibition"].roles == {"S": "reactant 0", "I": "effector 0"}
+        with pytest.raises(KeyError):
+            alienbio_sim.kinetic_template("no_such_law")
+
+    def test_expand_binds_molecule_ids(self):
+        mm = alienbio_sim.kinetic_template("michaelis_menten")
+        expr = mm.expand(reactants={3: 1}, products=[4], vmax=10.0, km=5.0)
+        assert expr.print() == "div(mul(10.0, var(3.0)), add(5.0, var(3.0)))"
+        rate = expr.compile(["a", "b", "c", "S"])
+        assert rate([0, 0, 0, 5.0]) == 5.0
+
+        rev = alienbio_sim.kinetic_template("reversible_mass_action")
+        expr = rev.expand(reactants={0: 2}, products={1: 1}, kf=2.0, kr=0.5)
+        assert expr.compile(["A", "B"])([3.0, 4.0]) == 2.0 * 9.0 - 0.5 * 4.0
+
+    def test_missing_role_and_parameter_rejected(self):
+        ci = alienbio_sim.kinetic_template("competitive_inhibition")
+        with pytest.raises(ValueError):
+            ci.expand(reactants=[0], vmax=1.0, km=1.0, ki=1.0)
+        with pytest.raises(ValueError):
+            ci.expand(reactants=[0], effectors=[1], vmax=1.0, km=1.0)
+
+
+class TestTemplatesInChemistry:
+    """A reaction names its kinetic law instead of embedding a lambda."""
+
+    def test_michaelis_menten_reaction(self):
+        chem = make_chemistry("michaelis_menten(vmax=10, km=5)")
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=0.1)
+        state = sim.step(StateImpl(chem, initial={"S": 5.0, "P": 0.0, "I": 0.0}))
+        assert state["P"] == pytest.approx(0.5)
+
+    def test_inhibitor_named_by_keyword(self):
+        chem = make_chemistry({"head": "competitive_inhibition", "kwargs": {"vmax": 10, "km": 5, "ki": 1, "I": "I"}})
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=0.1)
+        free = sim.step(StateImpl(chem, initial={"S": 5.0, "P": 0.0, "I": 0.0}))
+        inhibited = sim.step(StateImpl(chem, initial={"S": 5.0, "P": 0.0, "I": 1.0}))
+        assert free["P"] == pytest.approx(0.5)
+        assert inhibited["P"] == pytest.approx(0.1 * 10 * 5 / (5 * 2 + 5))
+        assert inhibited["I"] == 1.0
+
+    def test_inhibitor_bound_from_reaction_effectors(self):
+        chem = make_chemistry("competitive_inhibition(vmax=10, km=5, ki=1)", effectors=["I"])
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=0.1)
+        inhibited = sim.step(StateImpl(chem, initial={"S": 5.0, "P": 0.0, "I": 1.0}))
+        assert inhibited["P"] == pytest.approx(0.1 * 10 * 5 / (5 * 2 + 5))
+        assert inhibited["I"] == 1.0
+
+    def test_adaptive_method_uses_template(self):
+        chem = make_chemistry("substrate_inhibition(vmax=1, km=1, ksi=10)")
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=1.0, method="rk45")
+        final = sim.run(StateImpl(chem, initial={"S": 2.0, "P": 0.0, "I": 0.0}), steps=3)[-1]
+        assert final["S"] + final["P"] == pytest.approx(2.0)
+        assert 0.0 < final["S"] < 2.0
@@ -0,0 +1,155 @@
+"""Tests for the sandboxed rhai: escape hatch (alienbio_sim.enable_rhai).
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import (
+    ChemistryImpl,
+    CompartmentTreeImpl,
+    GeneralFlow,
+    MoleculeImpl,
+    ReactionImpl,
+    StateImpl,
+)
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_chemistry(rate):
+    """S -> P with the given rate."""
+    s = MoleculeImpl("S", dat=MockDat("mol/S"))
+    p = MoleculeImpl("P", dat=MockDat("mol/P"))
+    r = ReactionImpl("convert", reactants={s: 1}, products={p: 1}, rate=rate, dat=MockDat("rxn/convert"))
+    return ChemistryImpl("test", molecules={"S": s, "P": p}, reactions={"convert": r}, dat=MockDat("chem/test"))
+
+
+def make_tree():
+    tree = CompartmentTreeImpl()
+    organism = tree.add_root("organism")
+    cell = tree.add_child(organism, "cell")
+    return tree, organism, cell
+
+
+class TestRhaiRates:
+    """rhai: rates run natively, sandboxed and bounded."""
+
+    def setup_method(self, method):
+        alienbio_sim.enable_rhai()
+
+    def teardown_method(self, method):
+        alienbio_sim.enable_rhai(False, max_operations=100_000)
+
+    def test_disabled_by_default_switch(self):
+        alienbio_sim.enable_rhai(False)
+        assert not alienbio_sim.rhai_enabled()
+        with pytest.raises(ValueError, match="enable_rhai"):
+            alienbio_sim.ChemistrySimulator(make_chemistry("rhai:0.5 * S"))
+
+    def test_matches_expr_rate(self):
+        mm = "michaelis_menten(vmax=10, km=5)"
+        script = "rhai:|S| " + alienbio_sim.to_code(mm, "rhai")
+        initial = {"S": 5.0, "P": 0.0}
+        expected = alienbio_sim.ChemistrySimulator(make_chemistry(mm), dt=0.1).run(
+            StateImpl(make_chemistry(mm), initial=initial), steps=20
+        )[-1]
+        chem = make_chemistry(script)
+        final = alienbio_sim.ChemistrySimulator(chem, dt=0.1).run(StateImpl(chem, initial=initial), steps=20)[-1]
+        assert final["S"] == pytest.approx(expected["S"])
+        assert final["P"] == pytest.approx(expected["P"])
+
+    def test_piecewise_rate_with_rosenbrock(self):
+        chem = make_chemistry("rhai:if S > 0.5 { 0.5 * S } else { 0.0 }")
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=1.0, method="rosenbrock")
+        final = sim.run(StateImpl(chem, initial={"S": 1.0, "P": 0.0}), steps=5)[-1]
+        assert final["S"] == pytest.approx(0.5, abs=1e-3)
+        assert final["S"] + final["P"] == pytest.approx(1.0)
+
+    def test_history_via_memory(self):
+        # Fires only on the third evaluation in each compartment.
+        rate = "rhai:let n = if \"n\" in memory { memory.n } else { 0 }; memory.n = n + 1; if n == 2 { 1.0 } else { 0.0 }"
+        tree, organism, cell = make_tree()
+        sim = alienbio_sim.WorldSimulator.from_chemistry(make_chemistry(rate), tree, dt=1.0)
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(organism, 0, 10.0)
+        state.set(cell, 0, 10.0)
+        history = sim.run(state, steps=4)
+        assert [s.get(cell, 1) for s in history] == [0.0, 0.0, 0.0, 1.0, 1.0]
+        assert history[-1].get(organism, 1) == 1.0
+        # Memory starts afresh with every run.
+        assert [s.get(cell, 1) for s in sim.run(state, steps=4)] == [0.0, 0.0, 0.0, 1.0, 1.0]
+
+    def test_memory_needs_a_fixed_step_method(self):
+        rate = "rhai:memory.seen = true; S"
+        tree, _, _ = make_tree()
+        for method in ("rk45", "rosenbrock"):
+            with pytest.raises(ValueError, match="memory"):
+                alienbio_sim.ChemistrySimulator(make_chemistry(rate), method=method)
+            with pytest.raises(ValueError, match="memory"):
+                alienbio_sim.WorldSimulator.from_chemistry(make_chemistry(rate), tree, method=method)
+        with pytest.raises(ValueError, match="memory"):
+            alienbio_sim.HybridSimulator.from_chemistry(make_chemistry(rate), tree)
+
+    def test_operation_limit(self):
+        alienbio_sim.enable_rhai(max_operations=1_000)
+        chem = make_chemistry("rhai:let x = 0; while x < 100000 { x += 1; } S")
+        sim = alienbio_sim.ChemistrySimulator(chem, dt=0.1)
+        with pytest.raises(ValueError, match="operations"):
+            sim.step(StateImpl(chem, initial={"S": 1.0, "P": 0.0}))
+
+    def test_no_escape_from_sandbox(self):
+        for source in ('rhai:eval("S")', 'rhai:import "std" as s; S', "rhai:undefined_name"):
+            with pytest.raises(ValueError):
+                alienbio_sim.ChemistrySimulator(make_chemistry(source))
+        # No clock either: unknown functions fail when the rate is evaluated.
+        chem = make_chemistry("rhai:timestamp()")
+        with pytest.raises(ValueError, match="timestamp"):
+            alienbio_sim.ChemistrySimulator(chem).step(StateImpl(chem, initial={"S": 1.0, "P": 0.0}))
+
+
+class TestRhaiFlows:
+    """GeneralFlow bodies run natively against the origin compartment."""
+
+    def setup_method(self, method):
+        alienbio_sim.enable_rhai()
+
+    def teardown_method(self, method):
+        alienbio_sim.enable_rhai(False)
+
+    def test_body_matches_python_flow(self):
+        tree, organism, cell = make_tree()
+
+        def decay(state, tree, dt):
+            state.set(cell, 0, state.get(cell, 0) * (1.0 - 0.2 * dt))
+
+        scripted = GeneralFlow(cell, name="decay", body="rhai:m0 *= 1.0 - 0.2 * dt;")
+        assert scripted.attributes()["body"] == "rhai:m0 *= 1.0 - 0.2 * dt;"
+        with pytest.raises(NotImplementedError):
+            scripted.apply(None, tree, 1.0)
+
+        results = []
+        for flow in (GeneralFlow(cell, apply_fn=decay), scripted):
+            sim = alienbio_sim.WorldSimulator(tree, [], [flow], num_molecules=2, dt=0.5)
+            state = alienbio_sim.WorldState(tree, 2)
+            state.set(cell, 0, 8.0)
+            state.set(organism, 0, 8.0)
+            results.append(sim.run(state, steps=3)[-1])
+        assert results[1].get(cell, 0) == pytest.approx(results[0].get(cell, 0))
+        assert results[1].get(organism, 0) == 8.0
+
+    def test_body_uses_chemistry_names(self):
+        tree, organism, cell = make_tree()
+        flow = GeneralFlow(organism, name="feed", body="rhai:if S < 1.0 { S += 5.0 }")
+        sim = alienbio_sim.WorldSimulator.from_chemistry(make_chemistry(0.0), tree, flows=[flow])
+        state = alienbio_sim.WorldState(tree, 2)
+        final = sim.step(state)
+        assert final.get(organism, 0) == 5.0
+        assert final.get(cell, 0) == 0.0
@@ -0,0 +1,159 @@
+"""Tests for the native `rust` Simulator factory (alienbio_sim.ChemistrySimulator).
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import math
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import (
+    ChemistryImpl,
+    MoleculeImpl,
+    ReactionImpl,
+    ReferenceSimulatorImpl,
+    RustRk45SimulatorImpl,
+    RustRosenbrockSimulatorImpl,
+    RustSimulatorImpl,
+    StateImpl,
+)
+from alienbio.protocols.bio import Simulator
+from alienbio.spec_lang.bio import Bio, _resolve_factory
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_chemistry():
+    a = MoleculeImpl("A", dat=MockDat("mol/A"))
+    b = MoleculeImpl("B", dat=MockDat("mol/B"))
+    c = MoleculeImpl("C", dat=MockDat("mol/C"))
+    r1 = ReactionImpl("r1", reactants={a: 1}, products={b: 1}, rate=0.5, dat=MockDat("rxn/r1"))
+    r2 = ReactionImpl(
+        "r2",
+        reactants={b: 1},
+        products={c: 2},
+        rate=lambda state: 0.1 * state["B"],
+        dat=MockDat("rxn/r2"),
+    )
+    return ChemistryImpl(
+        "test",
+        molecules={"A": a, "B": b, "C": c},
+        reactions={"r1": r1, "r2": r2},
+        dat=MockDat("chem/test"),
+    )
+
+
+class TestRustSimulatorFactory:
+    """The rust factory is registered and matches the reference simulator."""
+
+    def test_registered_as_rust(self):
+        assert RustSimulatorImpl is alienbio_sim.ChemistrySimulator
+        assert _resolve_factory(Simulator, "rust") is alienbio_sim.ChemistrySimulator
+
+    def test_selected_by_sim_config(self):
+        assert Bio()._simulator_for({"simulator": "rust"}) is alienbio_sim.ChemistrySimulator
+        assert Bio()._simulator_for({}) is ReferenceSimulatorImpl
+
+    def test_protocol_properties(self):
+        chem = make_chemistry()
+        sim = RustSimulatorImpl(chem, dt=0.5)
+        assert sim.chemistry is chem
+        assert sim.dt == 0.5
+
+    def test_run_matches_reference(self):
+        chem = make_chemistry()
+        state = StateImpl(chem, initial={"A": 10.0})
+        ref_timeline = ReferenceSimulatorImpl(chem, dt=0.5).run(state, steps=30)
+        rust_timeline = RustSimulatorImpl(chem, dt=0.5).run(state, steps=30)
+
+        assert len(rust_timeline) == len(ref_timeline) == 31
+        for ref_state, rust_state in zip(ref_timeline, rust_timeline):
+            assert isinstance(rust_state, StateImpl)
+            for name in ("A", "B", "C"):
+                assert rust_state[name] == pytest.approx(ref_state[name])
+
+    def test_step_does_not_modify_input(self):
+        chem = make_chemistry()
+        state = StateImpl(chem, initial={"A": 10.0})
+        new_state = RustSimulatorImpl(chem, dt=1.0).step(state)
+        assert state["A"] == 10.0
+        assert new_state["A"] == pytest.approx(9.5)
+
+
+def make_stiff_chemistry():
+    """Fast equilibrium A <-> B feeding a slow conversion B -> C."""
+    a = MoleculeImpl("A", dat=MockDat("mol/A"))
+    b = MoleculeImpl("B", dat=MockDat("mol/B"))
+    c = MoleculeImpl("C", dat=MockDat("mol/C"))
+    def mass_action(name, reactant, product, k):
+        rate = f"mass_action(k={k})"
+        return ReactionImpl(name, reactants={reactant: 1}, products={product: 1}, rate=rate, dat=MockDat(f"rxn/{name}"))
+
+    reactions = {
+        "fwd": mass_action("fwd", a, b, 1000),
+        "rev": mass_action("rev", b, a, 1000),
+        "slow": mass_action("slow", b, c, 0.01),
+    }
+    return ChemistryImpl(
+        "stiff",
+        molecules={"A": a, "B": b, "C": c},
+        reactions=reactions,
+        dat=MockDat("chem/stiff"),
+    )
+
+
+class TestRustAdaptiveSimulators:
+    """rust_rk45 / rust_rosenbrock: adaptive ODE integration."""
+
+    def test_registered_names(self):
+        assert _resolve_factory(Simulator, "rust_rk45") is RustRk45SimulatorImpl
+        assert _resolve_factory(Simulator, "rust_rosenbrock") is RustRosenbrockSimulatorImpl
+        assert Bio()._simulator_for({"simulator": "rust_rosenbrock"}) is RustRosenbrockSimulatorImpl
+
+    def test_factory_call_presets_method(self):
+        chem = make_stiff_chemistry()
+        assert RustRk45SimulatorImpl(chem, dt=0.5).method == "rk45"
+        sim = RustRosenbrockSimulatorImpl(chem, dt=0.5, lu="sparse")
+        assert (sim.method, sim.dt) == ("rosenbrock", 0.5)
+        assert isinstance(sim, RustSimulatorImpl)
+
+    def test_stiff_chemistry(self):
+        chem = make_stiff_chemistry()
+        state = StateImpl(chem, initial={"A": 1.0})
+        timeline = RustRosenbrockSimulatorImpl(chem, dt=10.0, rtol=1e-8).run(state, steps=10)
+        assert len(timeline) == 11
+        # A and B equilibrate instantly; C grows at 0.01 * B = 0.005 * (A + B).
+        for i, s in enumerate(timeline):
+            t = 10.0 * i
+            assert s["A"] + s["B"] + s["C"] == pytest.approx(1.0)
+            assert s["C"] == pytest.approx(1.0 - math.exp(-0.005 * t), rel=1e-3, abs=1e-6)
+            if i > 0:
+                assert s["A"] == pytest.approx(s["B"], rel=1e-4)
+        rk45 = RustRk45SimulatorImpl(chem, dt=10.0, rtol=1e-8).run(state, steps=10)
+        assert rk45[-1]["C"] == pytest.approx(timeline[-1]["C"], rel=1e-5)
+
+    def test_constant_rates_stay_zero_order(self):
+        a = MoleculeImpl("S", dat=MockDat("mol/S"))
+        b = MoleculeImpl("P", dat=MockDat("mol/P"))
+        r = ReactionImpl("r", reactants={a: 1}, products={b: 1}, rate=0.1, dat=MockDat("rxn/r"))
+        chem = ChemistryImpl("flux", molecules={"S": a, "P": b}, reactions={"r": r}, dat=MockDat("chem/flux"))
+        state = StateImpl(chem, initial={"S": 10.0})
+        for factory in (RustSimulatorImpl, RustRk45SimulatorImpl, RustRosenbrockSimulatorImpl):
+            final = factory(chem, dt=0.01).run(state, steps=100)[-1]
+            assert final["S"] == pytest.approx(9.9)
+            assert final["P"] == pytest.approx(0.1)
+
+    def test_callable_rates_rejected(self):
+        with pytest.raises(ValueError, match="callable rate"):
+            RustRosenbrockSimulatorImpl(make_chemistry(), dt=1.0)
+
+    def test_unknown_method_rejected(self):
+        with pytest.raises(ValueError):
+            RustSimulatorImpl(make_stiff_chemistry(), method="bdf")
@@ -0,0 +1,245 @@
+"""Tests for the native alienbio_sim.StochasticSimulator and its scenario factories.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import math
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import (
+    ChemistryImpl,
+    CompartmentTreeImpl,
+    MoleculeImpl,
+    ReactionImpl,
+    ReactionSpec,
+    RustNextReactionSimulatorImpl,
+    RustStochasticSimulatorImpl,
+    RustTauLeapSimulatorImpl,
+    StateImpl,
+)
+from alienbio.protocols.bio import Simulator
+from alienbio.spec_lang.bio import Bio, _resolve_factory
+
+
+def make_world():
+    tree = CompartmentTreeImpl()
+    organism = tree.add_root("organism")
+    cell = tree.add_child(organism, "cell")
+    reactions = [
+        ReactionSpec("decay", {0: 1}, {1: 1}, rate_constant=0.1),
+        ReactionSpec("dimer", {1: 2}, {2: 1}, rate_constant=0.01, compartments=[cell]),
+    ]
+    return tree, organism, cell, reactions
+
+
+def initial_state(tree, organism, cell):
+    state = alienbio_sim.WorldState(tree, 3)
+    state.set(organism, 0, 200.0)
+    state.set(cell, 0, 0.5)
+    state.set_multiplicity(cell, 100.0)
+    return state
+
+
+class TestStochasticSimulator:
+    """Direct-method SSA over WorldState histories."""
+
+    def test_history_shape_matches_world_simulator(self):
+        tree, organism, cell, reactions = make_world()
+        state = initial_state(tree, organism, cell)
+        ssa = alienbio_sim.StochasticSimulator(tree, reactions, 3, dt=0.5, seed=1)
+        ode = alienbio_sim.WorldSimulator(tree, reactions, [], 3, dt=0.5)
+
+        history = ssa.run(state, steps=40, sample_every=10)
+        assert len(history) == len(ode.run(state, steps=40, sample_every=10)) == 5
+        assert all(s.tree is history[0].tree for s in history)
+        assert history[0].get(organism, 0) == 200.0
+        assert state.get(organism, 0) == 200.0  # input not modified
+
+    def test_counts_are_whole_molecules(self):
+        tree, organism, cell, reactions = make_world()
+        state = initial_state(tree, organism, cell)
+        sim = alienbio_sim.StochasticSimulator(tree, reactions, 3, dt=1.0, seed=3, volumes=[2.0, 0.1])
+        final = sim.run(state, steps=20)[-1]
+        # cell: Ω = 0.1 volume × 100 instances = 10, so counts = 10 × concentration
+        for mol in range(3):
+            assert (final.get(cell, mol) * 10.0) == pytest.approx(round(final.get(cell, mol) * 10.0))
+            assert (final.get(organism, mol) * 2.0) == pytest.approx(round(final.get(organism, mol) * 2.0))
+        # decay conserves A + B + 2C within each compartment
+        total = final.get(cell, 0) + final.get(cell, 1) + 2 * final.get(cell, 2)
+        assert total == pytest.approx(0.5)
+
+    def test_seed_reproducibility(self):
+        tree, organism, cell, reactions = make_world()
+        state = initial_state(tree, organism, cell)
+
+        def final(seed):
+            sim = alienbio_sim.StochasticSimulator(tree, reactions, 3, seed=seed)
+            last = sim.run(state, steps=10)[-1]
+            return last.get_compartment(organism) + last.get_compartment(cell)
+
+        assert final(42) == final(42)
+        assert final(42) != final(43)
+
+        sim = alienbio_sim.StochasticSimulator(tree, reactions, 3, seed=42)
+        first = sim.run(state, steps=10)[-1].get_compartment(cell)
+        sim.reseed(42)
+        assert sim.run(state, steps=10)[-1].get_compartment(cell) == first
+        assert sim.seed == 42
+
+    def test_mean_follows_deterministic_decay(self):
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("root")
+        reactions = [ReactionSpec("decay", {0: 1}, {}, rate_constant=0.3)]
+        sim = alienbio_sim.StochasticSimulator(tree, reactions, 1, dt=2.0, seed=5)
+        state = alienbio_sim.WorldState(tree, 1)
+        state.set(root, 0, 100.0)
+        finals = [sim.step(state).get(root, 0) for _ in range(300)]
+        assert sum(finals) / len(finals) == pytest.approx(100.0 * math.exp(-0.6), abs=1.5)
+
+    def test_invalid_configuration_rejected(self):
+        tree, organism, cell, reactions = make_world()
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, [ReactionSpec("half", {0: 0.5}, {1: 1})], 3)
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, reactions, 3, volumes=[1.0])
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, reactions, 3, dt=0.0)
+        sim = alienbio_sim.StochasticSimulator(tree, reactions, 3)
+        with pytest.raises(ValueError):
+            sim.run(initial_state(tree, organism, cell), steps=5, sample_every=0)
+
+
+class TestStochasticMethods:
+    """next_reaction and tau_leap behind the same simulator interface."""
+
+    @pytest.mark.parametrize("method", ["direct", "next_reaction", "tau_leap"])
+    def test_seeded_and_conserving(self, method):
+        tree, organism, cell, reactions = make_world()
+        state = initial_state(tree, organism, cell)
+
+        def final(seed):
+            sim = alienbio_sim.StochasticSimulator(tree, reactions, 3, dt=0.5, seed=seed, method=method)
+            assert sim.method == method
+            return sim.run(state, steps=20)[-1]
+
+        last = final(9)
+        assert last.get_compartment(organism) == final(9).get_compartment(organism)
+        for comp in (organism, cell):
+            counts = last.get_compartment(comp)
+            assert all(c >= 0.0 for c in counts)
+            total = counts[0] + counts[1] + 2 * counts[2]
+            assert total == pytest.approx(state.get(comp, 0))
+
+    @pytest.mark.parametrize("method", ["next_reaction", "tau_leap"])
+    def test_mean_agrees_with_direct(self, method):
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("root")
+        reactions = [
+            ReactionSpec("decay", {0: 1}, {1: 1}, rate_constant=0.3),
+            ReactionSpec("source", {}, {0: 1}, rate_constant=20.0),
+        ]
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 500.0)
+
+        def mean(m):
+            sim = alienbio_sim.StochasticSimulator(tree, reactions, 2, dt=2.0, seed=4, method=m)
+            finals = [sim.step(state).get(root, 0) for _ in range(200)]
+            return sum(finals) / len(finals)
+
+        expected = 20.0 / 0.3 + (500.0 - 20.0 / 0.3) * math.exp(-0.6)
+        assert mean(method) == pytest.approx(expected, rel=0.02)
+        assert mean("direct") == pytest.approx(expected, rel=0.02)
+
+    def test_method_arguments_validated(self):
+        tree, _, _, reactions = make_world()
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, reactions, 3, method="gillespie")
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, reactions, 3, method="tau_leap", epsilon=2.0)
+        with pytest.raises(ValueError):
+            alienbio_sim.StochasticSimulator(tree, reactions, 3, method="direct", epsilon=0.05)
+        sim = alienbio_sim.StochasticSimulator(tree, reactions, 3, method="tau_leap", epsilon=0.05)
+        assert "tau_leap" in repr(sim)
+
+
+class MockDat:
+    """Mock DAT for testing."""
+
+    def __init__(self, path: str):
+        self.path = path
+
+
+def make_chemistry(rate=0.2):
+    a = MoleculeImpl("A", dat=MockDat("mol/A"))
+    b = MoleculeImpl("B", dat=MockDat("mol/B"))
+    r1 = ReactionImpl("r1", reactants={a: 1}, products={b: 1}, rate=rate, dat=MockDat("rxn/r1"))
+    return ChemistryImpl(
+        "test",
+        molecules={"A": a, "B": b},
+        reactions={"r1": r1},
+        dat=MockDat("chem/test"),
+    )
+
+
+class TestStochasticScenarioSimulators:
+    """The stochastic factories run StateImpl timelines seeded from the scenario."""
+
+    @pytest.mark.parametrize(
+        "name, cls",
+        [
+            ("stochastic", RustStochasticSimulatorImpl),
+            ("stochastic_next_reaction", RustNextReactionSimulatorImpl),
+            ("stochastic_tau_leap", RustTauLeapSimulatorImpl),
+        ],
+    )
+    def test_registered(self, name, cls):
+        assert _resolve_factory(Simulator, name) is cls
+        chem = make_chemistry()
+        sim = cls(chem, dt=0.5, seed=3, volume=10.0)
+        assert sim.chemistry is chem
+        assert sim.dt == 0.5
+        assert sim.seed == 3
+
+        state = StateImpl(chem, initial={"A": 10.0, "B": 0.0})
+        timeline = sim.run(state, steps=8)
+        assert len(timeline) == 9
+        assert timeline[0]["A"] == 10.0
+        for s in timeline:
+            assert s["A"] + s["B"] == pytest.approx(10.0)
+            assert s["A"] * 10.0 == pytest.approx(round(s["A"] * 10.0))
+        assert isinstance(sim.step(state), StateImpl)
+
+    @pytest.mark.parametrize("cls", [RustStochasticSimulatorImpl, RustTauLeapSimulatorImpl])
+    def test_constant_rates_stay_zero_order(self, cls):
+        chem = make_chemistry()
+        state = StateImpl(chem, initial={"A": 10.0, "B": 0.0})
+        final = cls(chem, dt=0.01, seed=5, volume=1000.0).run(state, steps=100)[-1]
+        # 0.2 per unit time for 1.0, as the reference simulator; mass action would leave 8.19.
+        assert final["A"] == pytest.approx(9.8, abs=0.05)
+
+    @pytest.mark.parametrize(
+        "cls", [RustStochasticSimulatorImpl, RustNextReactionSimulatorImpl, RustTauLeapSimulatorImpl]
+    )
+    def test_callable_rates_rejected(self, cls):
+        chem = make_chemistry(rate=lambda state: 0.0)
+        with pytest.raises(ValueError, match="callable rate"):
+            cls(chem, dt=0.01)
+
+    def test_bio_passes_scenario_seed(self):
+        bio = Bio()
+        config = {"simulator": "stochastic_next_reaction"}
+        chem = make_chemistry()
+        sim = bio._make_simulator(config, chem, 1.0, seed=17)
+        assert isinstance(sim, RustNextReactionSimulatorImpl)
+        assert sim.seed == 17
+
+        state = StateImpl(chem, initial={"A": 50.0, "B": 0.0})
+        again = bio._make_simulator(config, chem, 1.0, seed=17)
+        assert [s["A"] for s in sim.run(state, 10)] == [s["A"] for s in again.run(state, 10)]
+
+    def test_unseeded_simulators_ignore_seed(self):
+        sim = Bio()._make_simulator({"simulator": "reference"}, make_chemistry(), 1.0, seed=17)
+        assert not hasattr(sim, "seed")
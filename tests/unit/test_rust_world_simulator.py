@@ -0,0 +1,853 @@
+"""Tests for the native alienbio_sim.WorldSimulator.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import math
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+
+from alienbio.bio import (
+    CompartmentTreeImpl,
+    DiffusionFlow,
+    Flow,
+    GeneralFlow,
+    InstanceFlow,
+    MembraneFlow,
+    ReactionSpec,
+    WorldSimulatorImpl,
+    WorldStateImpl,
+)
+
+
+def make_world():
+    tree = CompartmentTreeImpl()
+    organism = tree.add_root("organism")
+    cell = tree.add_child(organism, "cell")
+    reactions = [
+        ReactionSpec("r1", {0: 1}, {1: 1}, rate_constant=0.1),
+        ReactionSpec("r2", {1: 2}, {2: 1}, rate_constant=0.05, compartments=[cell]),
+    ]
+    return tree, organism, cell, reactions
+
+
+def states(tree, values):
+    py_state = WorldStateImpl(tree, 3)
+    rs_state = alienbio_sim.WorldState(tree, 3)
+    for (comp, mol), value in values.items():
+        py_state.set(comp, mol, value)
+        rs_state.set(comp, mol, value)
+    return py_state, rs_state
+
+
+class TestRustWorldSimulator:
+    """WorldSimulator reproduces WorldSimulatorImpl step for step."""
+
+    def test_step_matches_python(self):
+        tree, organism, cell, reactions = make_world()
+        py_sim = WorldSimulatorImpl(tree, reactions, [], num_molecules=3, dt=0.1)
+        rs_sim = alienbio_sim.WorldSimulator(tree, reactions, [], num_molecules=3, dt=0.1)
+        py_state, rs_state = states(tree, {(organism, 0): 100.0, (cell, 1): 4.0})
+
+        py_next = py_sim.step(py_state)
+        rs_next = rs_sim.step(rs_state)
+        for comp in range(2):
+            assert rs_next.get_compartment(comp) == pytest.approx(py_next.get_compartment(comp))
+        assert rs_state.get(organism, 0) == 100.0  # input not modified
+
+    def test_run_sampling_matches_python(self):
+        tree, organism, cell, reactions = make_world()
+        py_sim = WorldSimulatorImpl(tree, reactions, [], num_molecules=3, dt=0.1)
+        rs_sim = alienbio_sim.WorldSimulator(tree, reactions, [], num_molecules=3, dt=0.1)
+        py_state, rs_state = states(tree, {(organism, 0): 100.0, (cell, 0): 10.0})
+
+        py_history = py_sim.run(py_state, steps=1000, sample_every=100)
+        rs_history = rs_sim.run(rs_state, steps=1000, sample_every=100)
+        assert len(rs_history) == len(py_history) == 11
+        for py_s, rs_s in zip(py_history, rs_history):
+            for comp in range(2):
+                assert rs_s.get_compartment(comp) == pytest.approx(py_s.get_compartment(comp))
+        assert rs_history[0].tree is rs_history[-1].tree
+
+    def test_python_flows_are_applied(self):
+        tree, organism, cell, reactions = make_world()
+
+        def leak(state, tree, dt):
+            inside = state.get(cell, 0)
+            state.set(cell, 0, inside + 0.5 * dt * (state.get(organism, 0) - inside))
+
+        flows = [GeneralFlow(cell, apply_fn=leak)]
+        py_sim = WorldSimulatorImpl(tree, reactions, flows, num_molecules=3, dt=0.1)
+        rs_sim = alienbio_sim.WorldSimulator(tree, reactions, flows, num_molecules=3, dt=0.1)
+        py_state, rs_state = states(tree, {(organism, 0): 100.0})
+
+        py_final = py_sim.run(py_state, steps=50)[-1]
+        rs_final = rs_sim.run(rs_state, steps=50)[-1]
+        assert rs_final.get(cell, 0) == pytest.approx(py_final.get(cell, 0))
+
+    def test_effectors_match_python(self):
+        tree, organism, cell, _ = make_world()
+        reactions = [ReactionSpec("cat", {0: 1}, {1: 1}, rate_constant=0.1, effectors={2: 2.0})]
+        py_sim = WorldSimulatorImpl(tree, reactions, [], num_molecules=3, dt=0.1)
+        rs_sim = alienbio_sim.WorldSimulator(tree, reactions, [], num_molecules=3, dt=0.1)
+        py_state, rs_state = states(tree, {(organism, 0): 10.0, (organism, 2): 1.5})
+
+        py_final = py_sim.run(py_state, steps=20)[-1]
+        rs_final = rs_sim.run(rs_state, steps=20)[-1]
+        assert rs_final.get_compartment(organism) == pytest.approx(py_final.get_compartment(organism))
+        assert rs_final.get(organism, 2) == 1.5
+        assert rs_final.get(cell, 1) == 0.0  # no effector in the cell, no reaction
+
+    def test_shape_mismatch_rejected(self):
+        tree, _, _, reactions = make_world()
+        sim = alienbio_sim.WorldSimulator(tree, reactions, [], num_molecules=3)
+        with pytest.raises(ValueError):
+            sim.step(alienbio_sim.WorldState(tree, 4))
+
+    def test_invalid_dt_rejected(self):
+        tree, _, _, reactions = make_world()
+        for dt in (0.0, -1.0, math.nan, math.inf):
+            with pytest.raises(ValueError, match="dt must be positive"):
+                alienbio_sim.WorldSimulator(tree, reactions, [], 3, dt=dt)
+
+
+class TestRustWorldSimulatorRk45:
+    """method="rk45": adaptive Dormand–Prince with exact sample times."""
+
+    def decay_world(self, k=0.5):
+        tree = CompartmentTreeImpl()
+        root = tree.add_root("organism")
+        reactions = [ReactionSpec("decay", {0: 1}, {1: 1}, rate_constant=k)]
+        return tree, root, reactions
+
+    def test_method_selection(self):
+        tree, _, reactions = self.decay_world()
+        assert alienbio_sim.WorldSimulator(tree, reactions, [], 2).method == "euler"
+        assert alienbio_sim.WorldSimulator(tree, reactions, [], 2, method="rk45").method == "rk45"
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, reactions, [], 2, method="leapfrog")
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, reactions, [], 2, method="rk45", atol=0.0, rtol=0.0)
+
+    def test_samples_at_exact_times(self):
+        k, dt = 0.5, 0.1
+        tree, root, reactions = self.decay_world(k)
+        sim = alienbio_sim.WorldSimulator(
+            tree, reactions, [], 2, dt=dt, method="rk45", atol=1e-12, rtol=1e-10
+        )
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 100.0)
+
+        history = sim.run(state, steps=95, sample_every=10)
+        times = [i * dt for i in range(0, 95, 10)] + [95 * dt]
+        assert len(history) == len(times)
+        for t, sample in zip(times, history):
+            assert sample.get(root, 0) == pytest.approx(100.0 * math.exp(-k * t), rel=1e-8)
+            assert sample.get(root, 0) + sample.get(root, 1) == pytest.approx(100.0)
+        assert history[-1].tree is history[0].tree
+
+    def test_stiff_rate_stays_bounded(self):
+        # Euler with dt=1 overshoots (clamped to zero); rk45 follows the decay.
+        tree, root, reactions = self.decay_world(k=50.0)
+        sim = alienbio_sim.WorldSimulator(tree, reactions, [], 2, dt=1.0, method="rk45")
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 1.0)
+        final = sim.run(state, steps=3)[-1]
+        assert abs(final.get(root, 0)) < 1e-6
+        assert final.get(root, 1) == pytest.approx(1.0)
+
+    def test_step_integrates_one_dt(self):
+        tree, root, reactions = self.decay_world(k=0.5)
+        sim = alienbio_sim.WorldSimulator(
+            tree, reactions, [], 2, dt=2.0, method="rk45", atol=1e-12, rtol=1e-10
+        )
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(root, 0, 10.0)
+        assert sim.step(state).get(root, 0) == pytest.approx(10.0 * math.exp(-1.0), rel=1e-8)
+
+
+class TestRustWorldSimulatorRosenbrock:
+    """method="rosenbrock": implicit integration with dense or sparse LU."""
+
+    def test_dense_and_sparse_lu_agree(self):
+        tree, organism, cell, reactions = make_world()
+        reactions.append(ReactionSpec("fast", {2: 1}, {0: 2}, rate_constant=500.0))
+        results = []
+        for lu in ("dense", "sparse"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, reactions, [], 3, dt=1.0, method="rosenbrock", lu=lu, rtol=1e-8
+            )
+            _, state = states(tree, {(organism, 0): 100.0, (cell, 1): 40.0})
+            results.append(sim.run(state, steps=20, sample_every=5))
+        assert sim.method == "rosenbrock"
+        for dense, sparse in zip(*results):
+            for comp in range(2):
+                assert sparse.get_compartment(comp) == pytest.approx(dense.get_compartment(comp))
+
+    def test_matches_rk45(self):
+        tree, organism, cell, reactions = make_world()
+        runs = {}
+        for method in ("rk45", "rosenbrock"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, reactions, [], 3, dt=0.5, method=method, atol=1e-10, rtol=1e-8
+            )
+            _, state = states(tree, {(organism, 0): 100.0, (cell, 0): 10.0})
+            runs[method] = sim.run(state, steps=40)[-1]
+        for comp in range(2):
+            assert runs["rosenbrock"].get_compartment(comp) == pytest.approx(
+                runs["rk45"].get_compartment(comp), rel=1e-5
+            )
+
+    def test_unknown_lu_rejected(self):
+        tree, _, _, reactions = make_world()
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, reactions, [], 3, method="rosenbrock", lu="qr")
+
+
+class TestRustMembraneFlow:
+    """MembraneFlow transport runs natively across the origin's membrane."""
+
+    NAMES = ["Na", "K", "glucose"]
+
+    def test_pump_sign_convention_and_clamping(self):
+        tree, organism, cell, _ = make_world()
+        # 3 Na out of the cell and 2 K in per event.
+        pump = MembraneFlow(cell, {"Na": -3, "K": 2}, rate_constant=1.0, name="pump")
+        sim = alienbio_sim.WorldSimulator(tree, [], [pump], 3, dt=0.5, molecules=self.NAMES)
+        _, state = states(tree, {(organism, 1): 10.0, (cell, 0): 6.0})
+
+        state = sim.step(state)
+        assert state.get_compartment(cell) == pytest.approx([4.5, 1.0, 0.0])
+        assert state.get_compartment(organism) == pytest.approx([1.5, 9.0, 0.0])
+
+        # Only 4.5 Na left inside: the transfer stops when the cell runs out.
+        history = sim.run(state, steps=10)
+        assert history[-1].get(cell, 0) == pytest.approx(0.0)
+        assert history[-1].get(cell, 1) == pytest.approx(4.0)
+        assert all(s.get(comp, m) >= 0.0 for s in history for comp in (organism, cell) for m in range(3))
+
+    def test_gradient_rate_law_reaches_equilibrium(self):
+        tree, organism, cell, _ = make_world()
+        glut = MembraneFlow(cell, {"glucose": 1}, rate_constant=0.5, rate="sub(parent_glucose, glucose)")
+        assert glut.attributes()["rate"] == "sub(parent_glucose, glucose)"
+        finals = {}
+        for method in ("euler", "rk45", "rosenbrock"):
+            sim = alienbio_sim.WorldSimulator(tree, [], [glut], 3, dt=0.1, method=method, molecules=self.NAMES)
+            _, state = states(tree, {(organism, 2): 10.0})
+            finals[method] = sim.run(state, steps=200)[-1]
+        for final in finals.values():
+            assert final.get(cell, 2) == pytest.approx(5.0, rel=1e-3)
+            assert final.get(cell, 2) + final.get(organism, 2) == pytest.approx(10.0)
+        # 0.5 * (10 - 0) * 0.1 after one euler step.
+        sim = alienbio_sim.WorldSimulator(tree, [], [glut], 3, dt=0.1, molecules=self.NAMES)
+        _, state = states(tree, {(organism, 2): 10.0})
+        assert sim.step(state).get(cell, 2) == pytest.approx(0.5)
+
+    def test_rate_fn_sets_event_rate(self):
+        tree, organism, cell, _ = make_world()
+        flow = MembraneFlow(cell, {"glucose": -1}, rate_fn=lambda state, origin, parent: state.get(origin, 2))
+        sim = alienbio_sim.WorldSimulator(tree, [], [flow], 3, dt=0.25, molecules=self.NAMES)
+        _, state = states(tree, {(cell, 2): 4.0})
+        state = sim.step(state)
+        assert state.get(cell, 2) == pytest.approx(3.0)
+        assert state.get(organism, 2) == pytest.approx(1.0)
+
+    def test_unknown_molecule_rejected(self):
+        tree, _, cell, _ = make_world()
+        flow = MembraneFlow(cell, {"ATP": 1})
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, [], [], 3, molecules=["Na"])
+
+
+class TestRustMembraneAmounts:
+    """Membrane transport moves amounts through volumes and multiplicities."""
+
+    NAMES = ["Na", "K", "glucose"]
+
+    def rbc_world(self):
+        tree = CompartmentTreeImpl()
+        plasma = tree.add_root("plasma")
+        rbc = tree.add_child(plasma, "rbc")
+        state = alienbio_sim.WorldState(tree, 3)
+        state.set_multiplicity(rbc, 1e6)
+        state.set(rbc, 2, 5.0)
+        return tree, plasma, rbc, state
+
+    def test_rbc_export_scales_with_count_and_volume(self):
+        tree, plasma, rbc, state = self.rbc_world()
+        # Each cell exports 1e-7 glucose per unit time.
+        export = MembraneFlow(rbc, {"glucose": -1}, rate_constant=1e-7, name="export")
+        sim = alienbio_sim.WorldSimulator(
+            tree, [], [export], 3, dt=1.0, molecules=self.NAMES, volumes=[3.0, 1e-7], check_conservation=1e-12
+        )
+        assert sim.volumes == [3.0, 1e-7]
+        before = sim.total_amounts(state)
+        state = sim.step(state)
+        # Each cell loses 1e-7 / 1e-7 = 1.0; plasma gains 1e6 * 1e-7 / 3.
+        assert state.get(rbc, 2) == pytest.approx(4.0)
+        assert state.get(plasma, 2) == pytest.approx(0.1 / 3)
+        assert sim.total_amounts(state) == pytest.approx(before)
+
+    def test_adaptive_methods_agree(self):
+        tree, plasma, rbc, state = self.rbc_world()
+        glut = MembraneFlow(rbc, {"glucose": 1}, rate_constant=1e-7, rate="sub(parent_glucose, glucose)")
+        finals = []
+        for method in ("euler", "rk45", "rosenbrock"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, [], [glut], 3, dt=0.01, method=method, molecules=self.NAMES, volumes=[1.0, 1e-7]
+            )
+            finals.append(sim.run(state, steps=1000)[-1])
+        for final in finals:
+            # Equilibrium: both sides equal, total amount 5e6 * 1e-7 conserved.
+            assert final.get(rbc, 2) == pytest.approx(final.get(plasma, 2), rel=1e-3)
+            assert final.get(plasma, 2) + 0.1 * final.get(rbc, 2) == pytest.approx(0.5)
+
+    def test_conservation_check_catches_leaky_flow(self):
+        tree, plasma, rbc, state = self.rbc_world()
+
+        def leak(state, tree, dt):
+            state.set(plasma, 0, state.get(plasma, 0) + dt)
+
+        sim = alienbio_sim.WorldSimulator(tree, [], [GeneralFlow(plasma, apply_fn=leak)], 3, check_conservation=1e-9)
+        with pytest.raises(ValueError, match="m0"):
+            sim.step(state)
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, [], [], 3, volumes=[1.0, 0.0])
+
+
+class TestRustDiffusionFlow:
+    """DiffusionFlow exchanges every permeable molecule across every membrane."""
+
+    NAMES = ["Na", "K", "glucose"]
+
+    def test_matches_python_reference(self):
+        tree, organism, cell, reactions = make_world()
+        py_state, rs_state = states(tree, {(organism, 0): 50.0, (cell, 1): 20.0})
+        py_state.set_multiplicity(cell, 10.0)
+        rs_state.set_multiplicity(cell, 10.0)
+        flow = DiffusionFlow({0: 0.02, 1: 0.05}, areas={cell: 2.0})
+        py_hist = WorldSimulatorImpl(tree, reactions, [flow], 3, dt=0.1).run(py_state, steps=50)
+        rs_hist = alienbio_sim.WorldSimulator(tree, reactions, [flow], 3, dt=0.1).run(rs_state, steps=50)
+        for py, rs in zip(py_hist, rs_hist):
+            for comp in (organism, cell):
+                assert rs.get_compartment(comp) == pytest.approx(py.get_compartment(comp), rel=1e-12)
+
+    def test_equilibrium_across_methods(self):
+        tree, organism, cell, _ = make_world()
+        flow = DiffusionFlow({"glucose": 0.5, "Na": 0.1}, areas={"cell": 2.0})
+        for method in ("euler", "rk45", "rosenbrock"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, [], [flow], 3, dt=0.1, method=method, molecules=self.NAMES, volumes=[4.0, 1.0]
+            )
+            _, state = states(tree, {(organism, 2): 10.0, (cell, 0): 5.0})
+            final = sim.run(state, steps=500)[-1]
+            # Equal concentrations; total amounts 40 glucose and 5 Na conserved.
+            assert final.get(cell, 2) == pytest.approx(final.get(organism, 2), rel=1e-4)
+            assert 4.0 * final.get(organism, 2) + final.get(cell, 2) == pytest.approx(40.0)
+            assert 4.0 * final.get(organism, 0) + final.get(cell, 0) == pytest.approx(5.0)
+            assert final.get(cell, 1) == 0.0
+
+    def test_zero_area_seals_membrane(self):
+        tree, organism, cell, _ = make_world()
+        flow = DiffusionFlow({"glucose": 1.0}, areas={cell: 0.0})
+        sim = alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+        _, state = states(tree, {(organism, 2): 10.0})
+        assert sim.step(state).get(cell, 2) == 0.0
+
+    def test_yaml_round_trip(self):
+        yaml = pytest.importorskip("yaml")
+        flow = DiffusionFlow({"glucose": 0.5}, areas={"cell": 2.0}, name="glut")
+        loaded = Flow.from_dict(yaml.safe_load(yaml.safe_dump(flow.attributes())))
+        assert isinstance(loaded, DiffusionFlow)
+        assert loaded.attributes() == flow.attributes()
+        assert loaded.is_diffusion_flow and not loaded.is_membrane_flow
+
+    def test_unknown_names_rejected(self):
+        tree, _, _, _ = make_world()
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(tree, [], [DiffusionFlow({"ATP": 1.0})], 3, molecules=self.NAMES)
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(
+                tree, [], [DiffusionFlow({"K": 1.0}, areas={"liver": 1.0})], 3, molecules=self.NAMES
+            )
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, [], [DiffusionFlow({"K": -1.0})], 3, molecules=self.NAMES)
+
+
+class TestRustGeneralFlow:
+    """Declarative GeneralFlow moves run natively between any compartments."""
+
+    NAMES = ["Na", "K", "glucose"]
+
+    def make_tree(self):
+        # organism -> {liver -> hepatocyte, kidney}
+        tree = CompartmentTreeImpl()
+        organism = tree.add_root("organism")
+        liver = tree.add_child(organism, "liver")
+        kidney = tree.add_child(organism, "kidney")
+        hepatocyte = tree.add_child(liver, "hepatocyte")
+        return tree, organism, liver, kidney, hepatocyte
+
+    def test_lateral_transfer_between_siblings(self):
+        tree, organism, liver, kidney, _ = self.make_tree()
+        flow = GeneralFlow(
+            "liver", name="lateral", moves=[{"from": "liver", "to": "kidney", "molecules": {"glucose": 1}}]
+        )
+        sim = alienbio_sim.WorldSimulator(
+            tree, [], [flow], 3, dt=0.5, molecules=self.NAMES, volumes=[1.0, 2.0, 4.0, 1.0], check_conservation=1e-12
+        )
+        state = alienbio_sim.WorldState(tree, 3)
+        state.set(liver, 2, 1.0)
+        state = sim.step(state)
+        # 0.5 amount leaves 2 units of liver and enters 4 units of kidney.
+        assert state.get(liver, 2) == pytest.approx(0.75)
+        assert state.get(kidney, 2) == pytest.approx(0.125)
+        assert state.get(organism, 2) == 0.0
+        # The liver only holds 1.5 more: the transfer stops there.
+        final = sim.run(state, steps=10)[-1]
+        assert final.get(liver, 2) == pytest.approx(0.0)
+        assert final.get(kidney, 2) == pytest.approx(0.5)
+
+    def test_multi_edge_expr_rate_across_methods(self):
+        tree, organism, liver, kidney, hepatocyte = self.make_tree()
+        # Hepatocytes export Na to the kidney while K returns to the organism.
+        flow = GeneralFlow(
+            hepatocyte,
+            name="relay",
+            moves=[
+                {"from": hepatocyte, "to": kidney, "molecules": {"Na": 2}},
+                {"from": kidney, "to": organism, "molecules": {"K": 1}},
+            ],
+            rate_constant=0.1,
+            rate="mul(Na, kidney.K)",
+        )
+        finals = {}
+        for method in ("euler", "rk45", "rosenbrock"):
+            sim = alienbio_sim.WorldSimulator(tree, [], [flow], 3, dt=0.01, method=method, molecules=self.NAMES)
+            state = alienbio_sim.WorldState(tree, 3)
+            state.set(hepatocyte, 0, 4.0)
+            state.set(kidney, 1, 3.0)
+            state.set_multiplicity(hepatocyte, 2.0)
+            finals[method] = sim.run(state, steps=200)[-1]
+        for final in finals.values():
+            assert final.get(hepatocyte, 0) == pytest.approx(finals["rk45"].get(hepatocyte, 0), rel=1e-2)
+            # 2 hepatocytes: 2 Na per event out of 8 in total, 1 K per event.
+            assert 2.0 * final.get(hepatocyte, 0) + final.get(kidney, 0) == pytest.approx(8.0)
+            assert final.get(kidney, 0) == pytest.approx(2.0 * final.get(organism, 1))
+
+    def test_yaml_round_trip_runs(self):
+        yaml = pytest.importorskip("yaml")
+        tree, organism, liver, kidney, _ = self.make_tree()
+        flow = GeneralFlow(
+            "kidney",
+            name="filter",
+            moves=[{"from": "organism", "to": "kidney", "molecules": {"glucose": 1}}],
+            rate_constant=0.5,
+            rate="organism.glucose",
+        )
+        data = yaml.safe_load(yaml.safe_dump(flow.attributes()))
+        assert data["moves"] == [{"from": "organism", "to": "kidney", "molecules": {"glucose": 1}}]
+        loaded = Flow.from_dict(data)
+        assert loaded.attributes() == flow.attributes()
+        with pytest.raises(NotImplementedError):
+            loaded.apply(None, tree, 1.0)
+
+        finals = []
+        for f in (flow, loaded):
+            sim = alienbio_sim.WorldSimulator(tree, [], [f], 3, dt=0.1, molecules=self.NAMES)
+            state = alienbio_sim.WorldState(tree, 3)
+            state.set(organism, 2, 10.0)
+            finals.append(sim.run(state, steps=5)[-1])
+        assert finals[0].get(kidney, 2) == pytest.approx(10.0 * (1 - 0.95**5))
+        assert finals[1].get(kidney, 2) == finals[0].get(kidney, 2)
+
+    def test_unknown_compartment_rejected(self):
+        tree, *_ = self.make_tree()
+        flow = GeneralFlow("liver", moves=[{"from": "liver", "to": "spleen", "molecules": {"Na": 1}}])
+        with pytest.raises(KeyError, match="spleen"):
+            alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+        flow = GeneralFlow("liver", moves=[{"from": "liver", "to": "kidney", "molecules": {"Na": 1}}], rate="spleen.Na")
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+
+    def test_ambiguous_compartment_name_rejected(self):
+        tree, organism, liver, kidney, _ = self.make_tree()
+        cell = tree.add_child(liver, "cell")
+        other = tree.add_child(kidney, "cell")
+        move = {"from": "cell", "to": "organism", "molecules": {"Na": 1}}
+        for flow in (GeneralFlow(cell, moves=[move]), GeneralFlow("cell", moves=[{**move, "from": cell}])):
+            with pytest.raises(ValueError, match="compartment ID"):
+                alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+        flow = GeneralFlow(cell, name="leak", moves=[{**move, "from": cell}], rate="cell.Na")
+        with pytest.raises(ValueError, match="leak: rate reads cell.Na, but 2 compartments"):
+            alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+        with pytest.raises(ValueError, match="compartment ID"):
+            alienbio_sim.WorldSimulator(tree, [], [InstanceFlow("cell", organism)], 3, molecules=self.NAMES)
+        # IDs and unshared names still resolve.
+        flow = GeneralFlow(other, moves=[{"from": other, "to": "kidney", "molecules": {"Na": 1}}], rate="Na")
+        alienbio_sim.WorldSimulator(tree, [], [flow], 3, molecules=self.NAMES)
+
+
+class TestRustInstanceFlow:
+    """InstanceFlow moves multiplicity between compartments with its contents."""
+
+    NAMES = ["O2", "CO2"]
+
+    def make_tree(self):
+        # body -> {lung, artery, vein}
+        tree = CompartmentTreeImpl()
+        body = tree.add_root("body")
+        lung = tree.add_child(body, "lung")
+        artery = tree.add_child(body, "artery")
+        vein = tree.add_child(body, "vein")
+        return tree, body, lung, artery, vein
+
+    def test_matches_python_reference(self):
+        tree, _, _, artery, vein = self.make_tree()
+        flow = InstanceFlow(artery, vein, rate_constant=0.3)
+        reactions = [ReactionSpec("burn", {0: 1}, {1: 1}, rate_constant=0.2, compartments=[vein])]
+        py_state = WorldStateImpl(tree=tree, num_molecules=2)
+        py_state.set(artery, 0, 4.0)
+        py_state.set(vein, 1, 1.0)
+        py_state.set_multiplicity(artery, 1000.0)
+        py_state.set_multiplicity(vein, 10.0)
+        expected = WorldSimulatorImpl(tree=tree, reactions=reactions, flows=[flow], num_molecules=2, dt=0.1).run(
+            py_state, steps=20
+        )
+
+        sim = alienbio_sim.WorldSimulator(tree, reactions, [flow], 2, dt=0.1)
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(artery, 0, 4.0)
+        state.set(vein, 1, 1.0)
+        state.set_multiplicity(artery, 1000.0)
+        state.set_multiplicity(vein, 10.0)
+        actual = sim.run(state, steps=20)
+        for py, rs in zip(expected, actual):
+            for c in (artery, vein):
+                assert rs.get_multiplicity(c) == pytest.approx(py.get_multiplicity(c), rel=1e-12)
+                assert rs.get_compartment(c) == pytest.approx(py.get_compartment(c), rel=1e-12)
+        # Arterial cells keep their contents; the veins fill with oxygenated cells.
+        assert actual[-1].get(artery, 0) == 4.0
+        assert actual[-1].get_multiplicity(artery) == pytest.approx(1000.0 * math.exp(-0.6))
+
+    def test_rate_law_volumes_and_conservation(self):
+        tree, body, lung, artery, vein = self.make_tree()
+        # Cells leave the lung faster the more oxygen they carry.
+        flow = InstanceFlow("lung", "artery", rate_constant=0.5, rate="O2", name="oxygenated")
+        for method in ("euler", "rk45"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, [], [flow], 2, dt=0.1, method=method, molecules=self.NAMES,
+                volumes=[1.0, 2.0, 1.0, 1.0], check_conservation=1e-12,
+            )
+            state = alienbio_sim.WorldState(tree, 2)
+            state.set(lung, 0, 2.0)
+            state.set_multiplicity(lung, 100.0)
+            state.set_multiplicity(artery, 0.0)
+            before = sim.total_amounts(state)
+            state = sim.step(state)
+            # rate 1.0 per cell: 100 * (1 - e^-0.1) cells leave, each carrying 2.0 * 2 volume.
+            moved = 100.0 * (1 - math.exp(-0.1))
+            assert state.get_multiplicity(lung) == pytest.approx(100.0 - moved)
+            assert state.get_multiplicity(artery) == pytest.approx(moved)
+            assert state.get(artery, 0) == pytest.approx(4.0)
+            assert sim.total_amounts(state) == pytest.approx(before)
+            final = sim.run(state, steps=100)[-1]
+            assert final.get_multiplicity(lung) + final.get_multiplicity(artery) == pytest.approx(100.0)
+
+    def test_yaml_round_trip(self):
+        yaml = pytest.importorskip("yaml")
+        tree, *_ = self.make_tree()
+        flow = InstanceFlow("artery", "vein", rate_constant=0.2, rate="div(1, add(1, body.O2))")
+        data = yaml.safe_load(yaml.safe_dump(flow.attributes()))
+        assert data["type"] == "instance"
+        loaded = Flow.from_dict(data)
+        assert loaded.attributes() == flow.attributes()
+        assert loaded.is_instance_flow
+        with pytest.raises(NotImplementedError):
+            loaded.apply(None, tree, 1.0)
+        alienbio_sim.WorldSimulator(tree, [], [loaded], 2, molecules=self.NAMES)
+
+    def test_invalid_transfer_rejected(self):
+        tree, *_ = self.make_tree()
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldSimulator(tree, [], [InstanceFlow("vein", "vein")], 2, molecules=self.NAMES)
+        with pytest.raises(KeyError, match="spleen"):
+            alienbio_sim.WorldSimulator(tree, [], [InstanceFlow("vein", "spleen")], 2, molecules=self.NAMES)
+
+
+class TestRustTopologyChanges:
+    """Division, death and fusion remap the state onto a new tree."""
+
+    NAMES = ["glucose", "waste"]
+
+    def make_sim(self, flows=()):
+        # body -> {cell -> mito, neighbor}
+        tree = CompartmentTreeImpl()
+        body = tree.add_root("body")
+        cell = tree.add_child(body, "cell")
+        tree.add_child(cell, "mito")
+        tree.add_child(body, "neighbor")
+        reactions = [ReactionSpec("burn", {0: 1}, {1: 1}, rate_constant=0.1, compartments=[cell])]
+        sim = alienbio_sim.WorldSimulator(
+            tree, reactions, list(flows), 2, dt=0.1, molecules=self.NAMES, volumes=[10.0, 2.0, 0.5, 2.0]
+        )
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(body, 0, 1.0)
+        state.set(cell, 0, 4.0)
+        state.set(2, 1, 2.0)
+        state.set_multiplicity(2, 50.0)
+        return sim, state
+
+    def test_division_copies_subtree_and_history_tracks_trees(self):
+        export = MembraneFlow(1, {"waste": -1}, rate_constant=0.2)
+        sim, state = self.make_sim([export])
+        before = sim.run(state, steps=5)
+        totals = sim.total_amounts(before[-1])
+        divided = sim.divide(before[-1], "cell", ratio=0.5)
+        tree = divided.tree
+        assert tree is sim.tree and tree is not state.tree
+        assert [tree.name(c) for c in range(tree.num_compartments)] == [
+            "body", "cell", "mito", "neighbor", "cell_2", "mito_2",
+        ]
+        assert tree.parent(5) == 4 and tree.parent(4) == 0
+        # Volumes split with the contents, so concentrations carry over.
+        assert sim.volumes == pytest.approx([10.0, 1.0, 0.25, 2.0, 1.0, 0.25])
+        assert divided.get_compartment(4) == pytest.approx(before[-1].get_compartment(1))
+        assert divided.get_multiplicity(5) == 50.0
+        assert sim.total_amounts(divided) == pytest.approx(totals)
+
+        after = sim.run(divided, steps=5)
+        assert all(s.tree is state.tree for s in before)
+        assert all(s.tree is tree for s in after)
+        # The daughter burns glucose and exports waste like its mother.
+        assert after[-1].get(4, 0) == pytest.approx(after[-1].get(1, 0))
+        assert after[-1].get(4, 1) == pytest.approx(after[-1].get(1, 1))
+        with pytest.raises(ValueError):
+            sim.step(state)
+
+    def test_stochastic_division_is_seeded(self):
+        sim, state = self.make_sim()
+        state.set(1, 0, 1000.0)
+        first = sim.divide(state, 1, stochastic=True, seed=3)
+        sim, _ = self.make_sim()
+        second = sim.divide(state, 1, stochastic=True, seed=3)
+        assert first.get_compartment(4) == second.get_compartment(4)
+        assert first.get(1, 0) != first.get(4, 0)
+        assert first.get(1, 0) + first.get(4, 0) == pytest.approx(2000.0)
+
+    def test_death_releases_contents(self):
+        sim, state = self.make_sim()
+        released = sim.kill(state, "cell")
+        tree = released.tree
+        assert [tree.name(c) for c in range(tree.num_compartments)] == ["body", "neighbor"]
+        # 8 glucose from the cell and 50 mitochondria x 0.5 x 2.0 waste into a body of 10.
+        assert released.get_compartment(0) == pytest.approx([1.8, 5.0])
+        final = sim.run(released, steps=3)[-1]
+        assert final.get_compartment(0) == pytest.approx([1.8, 5.0])
+
+    def test_fusion_pools_siblings(self):
+        sim, state = self.make_sim()
+        fused = sim.fuse(state, "cell", "neighbor")
+        tree = fused.tree
+        assert tree.num_compartments == 3 and tree.children(1) == [2]
+        assert sim.volumes == pytest.approx([10.0, 4.0, 0.5])
+        assert fused.get(1, 0) == pytest.approx(2.0)
+        with pytest.raises(ValueError, match="siblings"):
+            sim.fuse(fused, "cell", "mito")
+
+    def test_python_flows_block_topology_changes(self):
+        flow = GeneralFlow(1, apply_fn=lambda state, tree, dt: None, name="custom")
+        sim, state = self.make_sim([flow])
+        with pytest.raises(ValueError, match="custom"):
+            sim.divide(state, "cell")
+        with pytest.raises(ValueError, match="root"):
+            self.make_sim()[0].kill(state, "body")
+
+
+class TestRustGrowth:
+    """Multiplicities follow birth/death laws, diluting contents on births."""
+
+    def make_world(self):
+        tree = CompartmentTreeImpl()
+        medium = tree.add_root("medium")
+        cells = tree.add_child(medium, "cells")
+        return tree, medium, cells
+
+    def test_constant_growth_dilutes_and_conserves(self):
+        tree, _, cells = self.make_world()
+        sim = alienbio_sim.WorldSimulator(tree, [], [], 2, dt=0.1, molecules=["atp", "dna"], growth={"cells": 0.2})
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(cells, 0, 5.0)
+        state.set_multiplicity(cells, 10.0)
+        final = sim.run(state, steps=50)[-1]
+        assert final.get_multiplicity(cells) == pytest.approx(10.0 * math.exp(1.0))
+        assert final.get(cells, 0) == pytest.approx(5.0 * math.exp(-1.0))
+        assert sim.total_amounts(final) == pytest.approx(sim.total_amounts(state))
+
+    def test_structured_expr_is_a_net_law(self):
+        tree, _, cells = self.make_world()
+        law = {"head": "mul", "args": [0.4, 0.5]}
+        sim = alienbio_sim.WorldSimulator(tree, [], [], 1, dt=0.1, growth={"cells": law})
+        state = alienbio_sim.WorldState(tree, 1)
+        state.set_multiplicity(cells, 10.0)
+        final = sim.run(state, steps=50)[-1]
+        assert final.get_multiplicity(cells) == pytest.approx(10.0 * math.exp(1.0))
+
+    def test_energy_limited_boom_and_bust(self):
+        tree, _, cells = self.make_world()
+        # Cells burn ATP; births need ATP, deaths happen at a fixed rate.
+        reactions = [ReactionSpec("burn", {0: 1}, {1: 1}, rate_constant=0.1, compartments=[cells])]
+        growth = {cells: {"birth": "mul(0.5, atp)", "death": 0.1}}
+        finals = {}
+        for method in ("euler", "rk45"):
+            sim = alienbio_sim.WorldSimulator(
+                tree, reactions, [], 2, dt=0.05, method=method, molecules=["atp", "waste"], growth=growth
+            )
+            state = alienbio_sim.WorldState(tree, 2)
+            state.set(cells, 0, 2.0)
+            state.set_multiplicity(cells, 100.0)
+            history = sim.run(state, steps=400, sample_every=20)
+            sizes = [s.get_multiplicity(cells) for s in history]
+            peak = max(range(len(sizes)), key=sizes.__getitem__)
+            assert 0 < peak < len(sizes) - 1
+            assert sizes[-1] < sizes[peak]
+            finals[method] = history[-1]
+        assert finals["rk45"].get_multiplicity(cells) == pytest.approx(finals["euler"].get_multiplicity(cells), rel=1e-2)
+
+    def test_division_copies_growth_law(self):
+        tree, _, cells = self.make_world()
+        sim = alienbio_sim.WorldSimulator(tree, [], [], 1, dt=0.1, growth={"cells": {"death": 0.5}})
+        state = alienbio_sim.WorldState(tree, 1)
+        state.set(cells, 0, 1.0)
+        state.set_multiplicity(cells, 8.0)
+        state = sim.step(sim.divide(state, "cells"))
+        expected = 8.0 * math.exp(-0.05)
+        assert [state.get_multiplicity(c) for c in (1, 2)] == pytest.approx([expected, expected])
+        # Deaths take their contents with them.
+        assert state.get(2, 0) == pytest.approx(1.0)
+
+    def test_invalid_laws_rejected(self):
+        tree, *_ = self.make_world()
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(tree, [], [], 1, growth={"cells": {"births": 1.0}})
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(tree, [], [], 1, growth={"tissue": 1.0})
+        with pytest.raises(KeyError):
+            alienbio_sim.WorldSimulator(tree, [], [], 1, growth={"cells": "glucose"})
+
+
+class TestRustEvents:
+    """Scheduled interventions land at their exact times during run()."""
+
+    def make_sim(self, method="euler", growth=None):
+        tree = CompartmentTreeImpl()
+        body = tree.add_root("body")
+        cell = tree.add_child(body, "cell")
+        reactions = [ReactionSpec("decay", {0: 1}, {1: 1}, rate_constant=0.1, compartments=[cell])]
+        sim = alienbio_sim.WorldSimulator(tree, reactions, [], 2, dt=1.0, method=method, molecules=["A", "B"])
+        state = alienbio_sim.WorldState(tree, 2)
+        state.set(cell, 0, 10.0)
+        return sim, state, cell
+
+    def test_bolus_splits_the_step(self):
+        sim, state, cell = self.make_sim()
+        event = {"time": 1.5, "type": "bolus", "compartment": "cell", "molecule": "A", "amount": 10.0}
+        history = sim.run(state, steps=3, events=[event])
+        a1 = 10.0 * 0.9
+        assert history[1].get(cell, 0) == pytest.approx(a1)
+        assert history[2].get(cell, 0) == pytest.approx((a1 * 0.95 + 10.0) * 0.95)
+        assert sim.event_log == [(1.5, "bolus 10 A into cell")]
+
+    def test_adaptive_methods_stop_at_events(self):
+        sim, state, cell = self.make_sim(method="rk45")
+        event = {"time": 0.7, "type": "bolus", "compartment": cell, "molecule": 0, "amount": 10.0}
+        final = sim.run(state, steps=2, events=[event])[-1]
+        expected = (10.0 * math.exp(-0.07) + 10.0) * math.exp(-0.13)
+        assert final.get(cell, 0) == pytest.approx(expected, rel=1e-6)
+
+    def test_sample_at_event_time_precedes_it(self):
+        sim, state, cell = self.make_sim()
+        event = {"time": 1.0, "type": "bolus", "compartment": "cell", "molecule": "A", "amount": 5.0}
+        history = sim.run(state, steps=2, events=[event])
+        assert history[1].get(cell, 0) == pytest.approx(9.0)
+        assert history[2].get(cell, 0) == pytest.approx(14.0 * 0.9)
+
+    def test_knockdown_and_knockout_last_for_the_run(self):
+        sim, state, cell = self.make_sim()
+        events = [
+            {"time": 1.0, "type": "knockdown", "reaction": "decay", "factor": 0.5},
+            {"time": 2.0, "type": "knockout", "reaction": 0},
+        ]
+        history = sim.run(state, steps=4, events=events)
+        a = [s.get(cell, 0) for s in history]
+        assert a[2] == pytest.approx(a[1] * 0.95)
+        assert a[4] == pytest.approx(a[2])
+        assert [d for _, d in sim.event_log] == ["knockdown decay x0.5", "knockout decay"]
+        # A later run starts from the unperturbed chemistry.
+        assert sim.run(state, steps=1)[-1].get(cell, 0) == pytest.approx(9.0)
+        assert sim.event_log == []
+
+    @pytest.mark.parametrize("method", ["euler", "rosenbrock"])
+    def test_clamp_holds_until_released(self, method):
+        sim, state, cell = self.make_sim(method=method)
+        events = [
+            {"time": 0.0, "type": "clamp", "compartment": "cell", "molecule": "A", "value": 5.0},
+            {"time": 2.0, "type": "release", "compartment": "cell", "molecule": "A"},
+        ]
+        history = sim.run(state, steps=4, events=events)
+        assert history[0].get(cell, 0) == 10.0
+        assert [s.get(cell, 0) for s in history[1:3]] == pytest.approx([5.0, 5.0])
+        # A clamped reactant feeds the product at a constant rate.
+        assert history[2].get(cell, 1) == pytest.approx(1.0, rel=1e-6)
+        assert history[4].get(cell, 0) < 5.0
+
+    def test_remove_kills_the_compartment(self):
+        sim, state, cell = self.make_sim()
+        tree = sim.tree
+        history = sim.run(state, steps=2, events=[{"time": 0.5, "type": "remove", "compartment": "cell"}])
+        assert history[0].tree.num_compartments == 2
+        final = history[-1]
+        assert final.tree.num_compartments == 1
+        # Contents at removal are released into the body, which has no reactions.
+        assert final.get(0, 0) + final.get(0, 1) == pytest.approx(10.0)
+        assert final.get(0, 0) == pytest.approx(10.0 * 0.95)
+        assert sim.event_log == [(0.5, "remove cell")]
+        # The removal only lasts for the run.
+        assert sim.tree is tree
+        assert sim.run(state, steps=1)[-1].get(cell, 0) == pytest.approx(9.0)
+
+    def test_failed_run_restores_the_tree(self):
+        sim, state, cell = self.make_sim()
+        events = [
+            {"time": 0.5, "type": "remove", "compartment": "cell"},
+            {"time": 1.0, "type": "bolus", "compartment": "cell", "molecule": "A", "amount": 1.0},
+        ]
+        tree = sim.tree
+        with pytest.raises(KeyError):
+            sim.run(state, steps=2, events=events)
+        assert sim.tree is tree
+        assert len(sim.run(state, steps=2)) == 3
+
+    def test_events_at_the_end_apply_to_the_final_state(self):
+        sim, state, cell = self.make_sim()
+        event = {"time": 3.0, "type": "bolus", "compartment": "cell", "molecule": "B", "amount": 2.0}
+        final = sim.run(state, steps=3, events=[event])[-1]
+        assert final.get(cell, 1) == pytest.approx(10.0 - 10.0 * 0.9**3 + 2.0)
+        assert sim.event_log == [(3.0, "bolus 2 B into cell")]
+        with pytest.raises(ValueError, match="after the end"):
+            sim.run(state, steps=3, events=[dict(event, time=50.0)])
+
+    def test_invalid_events_rejected(self):
+        sim, state, _ = self.make_sim()
+        with pytest.raises(ValueError):
+            sim.run(state, steps=1, events=[{"time": 0.5, "type": "poison", "compartment": "cell"}])
+        with pytest.raises(ValueError):
+            sim.run(state, steps=1, events=[{"time": -1.0, "type": "knockout", "reaction": 0}])
+        with pytest.raises(KeyError):
+            sim.run(state, steps=1, events=[{"time": 0.5, "type": "knockout", "reaction": "grow"}])
+        with pytest.raises(KeyError):
+            sim.run(state, steps=1, events=[{"time": 0.5, "type": "bolus", "compartment": "cell", "molecule": "A"}])
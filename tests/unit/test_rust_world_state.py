@@ -0,0 +1,81 @@
+"""Tests for the native alienbio_sim.WorldState.
+
+Skipped unless the Rust extension has been built (maturin develop in rust/).
+"""
+
+import pytest
+
+alienbio_sim = pytest.importorskip("alienbio_sim")
+np = pytest.importorskip("numpy")
+
+from alienbio.bio.compartment_tree import CompartmentTreeImpl
+from alienbio.bio.world_state import WorldStateImpl
+
+
+def make_tree():
+    tree = CompartmentTreeImpl()
+    root = tree.add_root("organism")
+    tree.add_child(root, "cell")
+    return tree
+
+
+class TestRustWorldState:
+    """WorldState mirrors WorldStateImpl."""
+
+    def test_get_set(self):
+        state = alienbio_sim.WorldState(make_tree(), num_molecules=3)
+        state.set(1, 2, 5.0)
+        assert state.get(1, 2) == 5.0
+        assert state.get_compartment(1) == [0.0, 0.0, 5.0]
+
+    def test_matches_python_impl(self):
+        tree = make_tree()
+        conc = [1.0, 2.0, 3.0, 4.0, 5.0, 6.0]
+        py_state = WorldStateImpl(tree, 3, initial_concentrations=conc)
+        rs_state = alienbio_sim.WorldState(tree, 3, initial_concentrations=conc)
+        py_state.set_multiplicity(1, 1000.0)
+        rs_state.set_multiplicity(1, 1000.0)
+        for c in range(2):
+            assert rs_state.get_compartment(c) == py_state.get_compartment(c)
+            assert rs_state.get_multiplicity(c) == py_state.get_multiplicity(c)
+        assert rs_state.total_molecules(1, 0) == py_state.total_molecules(1, 0)
+
+    def test_size_validation(self):
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldState(make_tree(), 3, initial_concentrations=[1.0])
+        with pytest.raises(ValueError):
+            alienbio_sim.WorldState(make_tree(), 3, initial_multiplicities=[1.0])
+
+    def test_copy_shares_tree(self):
+        state = alienbio_sim.WorldState(make_tree(), 2)
+        copy = state.copy()
+        copy.set(0, 0, 9.0)
+        assert state.get(0, 0) == 0.0
+        assert copy.tree is state.tree
+
+    def test_growing_the_shared_tree_invalidates_the_state(self):
+        state = alienbio_sim.WorldState(make_tree(), 2)
+        state.tree.add_child(1, "nucleus")
+        assert state.tree.num_compartments == 3
+        with pytest.raises(ValueError, match="new WorldState"):
+            state.get(0, 0)
+        with pytest.raises(ValueError, match="new WorldState"):
+            state.set_multiplicity(1, 2.0)
+        assert alienbio_sim.WorldState(state.tree, 2).get(2, 0) == 0.0
+
+    def test_as_array_is_writable_view(self):
+        state = alienbio_sim.WorldState(make_tree(), 3)
+        arr = state.as_array()
+        assert arr.shape == (2, 3)
+        assert arr.dtype == np.float64
+        arr[1, 0] = 4.0
+        assert state.get(1, 0) == 4.0
+        state.set(0, 2, 8.0)
+        assert arr[0, 2] == 8.0
+
+    def test_from_array(self):
+        state = alienbio_sim.WorldState(make_tree(), 2)
+        state.from_array([[1.0, 2.0], [3.0, 4.0]])
+        assert state.get_compartment(1) == [3.0, 4.0]
+        with pytest.raises(ValueError):
+            state.from_array([1.0, 2.0])